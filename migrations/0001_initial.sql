@@ -0,0 +1,58 @@
+-- Początkowy schemat: produkty, klienci, zamówienia i wiadomości z formularza kontaktowego.
+
+CREATE TABLE products (
+    id INTEGER PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    category TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    original_price INTEGER,
+    images TEXT NOT NULL DEFAULT '[]',
+    condition TEXT NOT NULL,
+    size TEXT NOT NULL,
+    brand TEXT,
+    description TEXT NOT NULL DEFAULT '',
+    measurements TEXT NOT NULL DEFAULT '[]',
+    listed_on TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'available',
+    featured INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX products_category ON products (category, listed_on);
+
+CREATE TABLE customers (
+    id INTEGER PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE orders (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER REFERENCES customers (id),
+    email TEXT NOT NULL,
+    status TEXT NOT NULL,
+    total INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX orders_customer ON orders (customer_id);
+
+CREATE TABLE order_lines (
+    id INTEGER PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products (id),
+    title TEXT NOT NULL,
+    price INTEGER NOT NULL
+);
+
+CREATE INDEX order_lines_order ON order_lines (order_id);
+
+CREATE TABLE contact_messages (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    subject TEXT NOT NULL DEFAULT '',
+    message TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
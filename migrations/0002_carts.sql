@@ -0,0 +1,7 @@
+-- Koszyki zalogowanych klientów; koszyki gości trzymane są w przeglądarce.
+
+CREATE TABLE carts (
+    customer_id INTEGER PRIMARY KEY REFERENCES customers (id) ON DELETE CASCADE,
+    items TEXT NOT NULL DEFAULT '[]',
+    updated_at TEXT NOT NULL
+);
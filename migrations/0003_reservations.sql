@@ -0,0 +1,15 @@
+-- Czasowe rezerwacje pojedynczych sztuk trzymanych w koszykach.
+-- Klucz główny na product_id gwarantuje, że sztukę trzyma co najwyżej jeden koszyk.
+
+CREATE TABLE reservations (
+    product_id INTEGER PRIMARY KEY REFERENCES products (id) ON DELETE CASCADE,
+    cart_id TEXT NOT NULL,
+    -- sekundy od epoki Unix (UTC)
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX reservations_cart ON reservations (cart_id);
+CREATE INDEX reservations_expiry ON reservations (expires_at);
+
+-- Koszyk zalogowanego klienta pamięta identyfikator swoich rezerwacji.
+ALTER TABLE carts ADD COLUMN cart_id TEXT;
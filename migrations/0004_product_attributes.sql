@@ -0,0 +1,4 @@
+-- Kolor i materiał, potrzebne do filtrowania list produktów.
+
+ALTER TABLE products ADD COLUMN color TEXT;
+ALTER TABLE products ADD COLUMN material TEXT;
@@ -0,0 +1,13 @@
+-- Dane z kasy: kontakt, adres dostawy, sposób dostawy i płatności.
+-- Zamówienia sprzed tej migracji dostają puste wartości domyślne.
+
+ALTER TABLE orders ADD COLUMN name TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN phone TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN street TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN postcode TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN city TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN delivery_method TEXT NOT NULL DEFAULT 'courier';
+ALTER TABLE orders ADD COLUMN payment_method TEXT NOT NULL DEFAULT 'bank_transfer';
+-- koszt dostawy w groszach, wliczony już w total
+ALTER TABLE orders ADD COLUMN shipping INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE orders ADD COLUMN terms_accepted_at TEXT;
@@ -0,0 +1,17 @@
+-- Lokalna kopia listy paczkomatów InPost, importowana z pliku przy starcie serwera,
+-- żeby wybór i walidacja paczkomatu działały bez dostępu do API InPost.
+
+CREATE TABLE lockers (
+    code TEXT PRIMARY KEY,
+    city TEXT NOT NULL,
+    postcode TEXT NOT NULL,
+    street TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    -- miasto małymi literami i bez polskich znaków, do wyszukiwania
+    city_search TEXT NOT NULL
+);
+
+CREATE INDEX lockers_postcode ON lockers (postcode);
+CREATE INDEX lockers_city_search ON lockers (city_search);
+
+ALTER TABLE orders ADD COLUMN locker_code TEXT;
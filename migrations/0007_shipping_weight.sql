@@ -0,0 +1,3 @@
+-- Waga przesyłki dla cennika dostaw (w gramach, razem z opakowaniem).
+-- Produkty bez wagi wyceniamy według domyślnej wagi z cennika.
+ALTER TABLE products ADD COLUMN weight_grams INTEGER;
@@ -0,0 +1,20 @@
+-- Płatności za zamówienia. Identyfikator płatności przekazujemy operatorowi jako
+-- identyfikator sesji, a on odsyła go w powiadomieniu (webhooku).
+
+CREATE TABLE payments (
+    id TEXT PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders (id),
+    -- nazwa operatora, np. przelewy24, bank_transfer, mock
+    provider TEXT NOT NULL,
+    method TEXT NOT NULL,
+    -- kwota w groszach
+    amount INTEGER NOT NULL,
+    -- pending, paid albo failed
+    status TEXT NOT NULL DEFAULT 'pending',
+    -- identyfikator transakcji po stronie operatora
+    external_id TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX payments_order ON payments (order_id);
@@ -0,0 +1,18 @@
+-- Historia zmian statusu zamówień: kto, kiedy i z jakiego statusu na jaki.
+
+CREATE TABLE order_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id INTEGER NOT NULL REFERENCES orders (id),
+    -- NULL dla zdarzenia utworzenia zamówienia
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+    -- np. system, customer:12, staff:ania, payment:przelewy24
+    actor TEXT NOT NULL,
+    note TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX order_events_order ON order_events (order_id, id);
+
+-- koniec 14-dniowego terminu na zwrot, liczony od doręczenia
+ALTER TABLE orders ADD COLUMN return_window_ends_at TEXT;
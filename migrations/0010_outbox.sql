@@ -0,0 +1,21 @@
+-- Skrzynka nadawcza. E-maile trafiają tu w tej samej chwili, w której dzieje się to,
+-- o czym informują, a wysyła je w tle osobne zadanie, ponawiając próby po błędach.
+
+CREATE TABLE outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recipient TEXT NOT NULL,
+    -- nazwa szablonu, np. order_confirmation; przydaje się przy przeglądaniu kolejki
+    template TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    html TEXT NOT NULL,
+    text TEXT NOT NULL,
+    -- pending, sent albo failed (po wyczerpaniu prób)
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    created_at TEXT NOT NULL,
+    sent_at TEXT
+);
+
+CREATE INDEX outbox_due ON outbox (status, next_attempt_at);
@@ -0,0 +1,9 @@
+-- Formularz kontaktowy: adres nadawcy pozwala ograniczać liczbę wiadomości,
+-- a powiadomienia dla sklepu mają adres odpowiedzi ustawiony na klienta.
+
+ALTER TABLE contact_messages ADD COLUMN ip TEXT;
+
+CREATE INDEX contact_messages_email ON contact_messages (email, created_at);
+CREATE INDEX contact_messages_ip ON contact_messages (ip, created_at);
+
+ALTER TABLE outbox ADD COLUMN reply_to TEXT;
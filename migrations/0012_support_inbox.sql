@@ -0,0 +1,46 @@
+-- Skrzynka obsługi klienta. Wiadomości z formularza od tego samego adresu e-mail
+-- tworzą jeden wątek; odpowiedzi sklepu zapisujemy obok nich.
+
+CREATE TABLE support_threads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    -- imię z ostatniej wiadomości
+    name TEXT NOT NULL,
+    -- new, answered albo closed
+    status TEXT NOT NULL DEFAULT 'new',
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX support_threads_status ON support_threads (status, updated_at);
+
+CREATE TABLE support_replies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id INTEGER NOT NULL REFERENCES support_threads (id),
+    body TEXT NOT NULL,
+    -- kto odpowiedział, np. staff:klucz
+    sent_by TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX support_replies_thread ON support_replies (thread_id);
+
+ALTER TABLE contact_messages ADD COLUMN thread_id INTEGER REFERENCES support_threads (id);
+
+CREATE INDEX contact_messages_thread ON contact_messages (thread_id);
+
+-- Wątki dla wiadomości zapisanych przed powstaniem skrzynki.
+INSERT INTO support_threads (email, name, status, created_at, updated_at)
+SELECT
+    m.email,
+    (SELECT latest.name FROM contact_messages latest
+     WHERE latest.email = m.email COLLATE NOCASE
+     ORDER BY latest.created_at DESC LIMIT 1),
+    'new',
+    MIN(m.created_at),
+    MAX(m.created_at)
+FROM contact_messages m
+GROUP BY m.email COLLATE NOCASE;
+
+UPDATE contact_messages SET thread_id =
+    (SELECT t.id FROM support_threads t WHERE t.email = contact_messages.email);
@@ -0,0 +1,42 @@
+-- Konta klientów: hasło, potwierdzony adres e-mail, sesje logowania, jednorazowe linki
+-- z e-maili i zapisane adresy.
+
+ALTER TABLE customers ADD COLUMN password_hash TEXT;
+ALTER TABLE customers ADD COLUMN phone TEXT NOT NULL DEFAULT '';
+ALTER TABLE customers ADD COLUMN email_verified_at TEXT;
+ALTER TABLE customers ADD COLUMN last_login_at TEXT;
+
+CREATE TABLE sessions (
+    -- skrót SHA-256 tokenu z ciasteczka; sam token zna tylko przeglądarka
+    token_hash TEXT PRIMARY KEY,
+    customer_id INTEGER NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX sessions_customer ON sessions (customer_id);
+
+CREATE TABLE account_tokens (
+    token_hash TEXT PRIMARY KEY,
+    customer_id INTEGER NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+    -- verify_email albo reset_password
+    purpose TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT
+);
+
+CREATE INDEX account_tokens_customer ON account_tokens (customer_id);
+
+CREATE TABLE addresses (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    phone TEXT NOT NULL,
+    street TEXT NOT NULL,
+    postcode TEXT NOT NULL,
+    city TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX addresses_customer ON addresses (customer_id);
@@ -0,0 +1,12 @@
+-- Faktury dla firm: dane nabywcy przy zamówieniu i zapisane adresy do faktur.
+
+ALTER TABLE orders ADD COLUMN invoice_company TEXT;
+ALTER TABLE orders ADD COLUMN invoice_nip TEXT;
+ALTER TABLE orders ADD COLUMN invoice_street TEXT;
+ALTER TABLE orders ADD COLUMN invoice_postcode TEXT;
+ALTER TABLE orders ADD COLUMN invoice_city TEXT;
+
+-- shipping albo billing; adresy do faktur mają firmę i NIP zamiast osoby i telefonu
+ALTER TABLE addresses ADD COLUMN kind TEXT NOT NULL DEFAULT 'shipping';
+ALTER TABLE addresses ADD COLUMN company TEXT NOT NULL DEFAULT '';
+ALTER TABLE addresses ADD COLUMN nip TEXT NOT NULL DEFAULT '';
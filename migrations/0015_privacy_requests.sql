@@ -0,0 +1,20 @@
+-- Wnioski z RODO: eksport danych i usunięcie konta. Wpis musi przetrwać usunięcie konta,
+-- dlatego zamiast adresu e-mail zapisujemy jego skrót SHA-256, a identyfikator klienta
+-- nie jest kluczem obcym.
+
+CREATE TABLE privacy_requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- export albo erasure
+    kind TEXT NOT NULL,
+    customer_id INTEGER NOT NULL,
+    email_hash TEXT NOT NULL,
+    requested_at TEXT NOT NULL,
+    fulfilled_at TEXT NOT NULL,
+    -- co zostało usunięte lub zanonimizowane, jako JSON
+    summary TEXT NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX privacy_requests_email ON privacy_requests (email_hash);
+
+-- Zamówienia zostają dla księgowości, ale bez danych osobowych kupującego.
+ALTER TABLE orders ADD COLUMN anonymised_at TEXT;
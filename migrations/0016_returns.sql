@@ -0,0 +1,29 @@
+-- Zwroty bez podania przyczyny: klient wybiera rzeczy z doręczonego zamówienia, drukuje
+-- formularz odstąpienia od umowy, a my odnotowujemy przyjęcie paczki, sprawdzenie rzeczy
+-- i zwrot pieniędzy.
+
+CREATE TABLE returns (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id INTEGER NOT NULL REFERENCES orders (id),
+    -- losowy identyfikator z linku do formularza, znany tylko klientowi
+    token TEXT NOT NULL UNIQUE,
+    -- requested, received, inspected albo refunded
+    status TEXT NOT NULL DEFAULT 'requested',
+    reason TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL,
+    received_at TEXT,
+    inspected_at TEXT,
+    refunded_at TEXT
+);
+
+CREATE INDEX returns_order ON returns (order_id);
+CREATE INDEX returns_status ON returns (status, created_at);
+
+CREATE TABLE return_lines (
+    return_id INTEGER NOT NULL REFERENCES returns (id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL,
+    -- tytuł i cena skopiowane z zamówienia
+    title TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    PRIMARY KEY (return_id, product_id)
+);
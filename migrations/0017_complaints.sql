@@ -0,0 +1,38 @@
+-- Reklamacje. Każda dotyczy jednej rzeczy z doręczonego zamówienia. Na odpowiedź mamy
+-- 14 dni od zgłoszenia, inaczej reklamację uważa się za uznaną.
+
+CREATE TABLE complaints (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id INTEGER NOT NULL REFERENCES orders (id),
+    product_id INTEGER NOT NULL,
+    -- tytuł i cena skopiowane z zamówienia
+    title TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    -- losowy identyfikator z linku do zgłoszenia, znany tylko klientowi
+    token TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL,
+    -- repair, replacement, price_reduction albo withdrawal
+    demand TEXT NOT NULL,
+    -- submitted, in_review, accepted, rejected albo resolved
+    status TEXT NOT NULL DEFAULT 'submitted',
+    -- nasza odpowiedź, którą widzi klient
+    response TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL,
+    -- ustawowy termin na odpowiedź
+    respond_by TEXT NOT NULL,
+    responded_at TEXT,
+    resolved_at TEXT
+);
+
+CREATE INDEX complaints_order ON complaints (order_id, product_id);
+CREATE INDEX complaints_status ON complaints (status, respond_by);
+
+CREATE TABLE complaint_photos (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    complaint_id INTEGER NOT NULL REFERENCES complaints (id) ON DELETE CASCADE,
+    -- image/jpeg, image/png albo image/webp, rozpoznane po zawartości pliku
+    content_type TEXT NOT NULL,
+    data BLOB NOT NULL
+);
+
+CREATE INDEX complaint_photos_complaint ON complaint_photos (complaint_id);
@@ -0,0 +1,52 @@
+-- Zwroty pieniędzy: tą samą drogą, którą klient zapłacił, przelewem na podany numer
+-- konta albo jako środki w sklepie. Każdy zwrot jest zapisany przy zamówieniu razem
+-- z pozycjami i częścią kosztu dostawy, których dotyczy.
+
+CREATE TABLE refunds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id INTEGER NOT NULL REFERENCES orders (id),
+    -- zwrot rzeczy, za który oddajemy pieniądze, jeśli jest
+    return_id INTEGER REFERENCES returns (id),
+    -- original_payment, bank_transfer albo store_credit
+    method TEXT NOT NULL,
+    -- pending, completed albo failed
+    status TEXT NOT NULL DEFAULT 'pending',
+    -- razem z kosztem dostawy, w groszach
+    amount INTEGER NOT NULL,
+    shipping INTEGER NOT NULL DEFAULT 0,
+    -- zwracana płatność, przy zwrocie tą samą drogą
+    payment_id TEXT REFERENCES payments (id),
+    -- numer konta klienta przy zwrocie przelewem
+    iban TEXT,
+    -- identyfikator zwrotu u operatora płatności
+    external_id TEXT,
+    -- przyczyna niepowodzenia
+    note TEXT NOT NULL DEFAULT '',
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    completed_at TEXT
+);
+
+CREATE INDEX refunds_order ON refunds (order_id);
+CREATE INDEX refunds_status ON refunds (status, created_at);
+
+CREATE TABLE refund_lines (
+    refund_id INTEGER NOT NULL REFERENCES refunds (id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL,
+    -- tytuł i cena skopiowane z zamówienia
+    title TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    PRIMARY KEY (refund_id, product_id)
+);
+
+-- Środki w sklepie: zwroty na plus, wykorzystanie na minus.
+CREATE TABLE store_credit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    customer_id INTEGER NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+    -- w groszach
+    amount INTEGER NOT NULL,
+    refund_id INTEGER REFERENCES refunds (id),
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX store_credit_customer ON store_credit (customer_id);
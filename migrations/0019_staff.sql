@@ -0,0 +1,33 @@
+-- Konta pracowników z rolami (owner, packer, editor), ich sesje logowania do panelu
+-- i ustawienia sklepu edytowane w panelu zamiast w kodzie.
+
+CREATE TABLE staff (
+    id INTEGER PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    name TEXT NOT NULL,
+    -- owner, packer albo editor
+    role TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    -- wyłączone konto nie może się zalogować, ale zostaje w historii zmian
+    active INTEGER NOT NULL DEFAULT 1,
+    last_login_at TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE staff_sessions (
+    -- skrót SHA-256 tokenu z ciasteczka, jak w sesjach klientów
+    token_hash TEXT PRIMARY KEY,
+    staff_id INTEGER NOT NULL REFERENCES staff (id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX staff_sessions_staff ON staff_sessions (staff_id);
+
+-- Jeden wiersz z ustawieniami zapisanymi jako JSON; brak wiersza oznacza domyślne.
+CREATE TABLE shop_settings (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    value TEXT NOT NULL,
+    updated_by TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Etap przygotowania używanej rzeczy do sprzedaży: przyjęcie, kontrola jakości, mierzenie,
+-- zdjęcia, wycena, wystawienie. Dotychczasowe produkty są już wystawione. Sprzedaż
+-- wynika ze statusu, więc "sold" nigdy nie trafia do tej kolumny.
+ALTER TABLE products ADD COLUMN stage TEXT NOT NULL DEFAULT 'listed';
+
+CREATE INDEX products_stage ON products (stage);
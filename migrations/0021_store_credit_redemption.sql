@@ -0,0 +1,25 @@
+-- Środki w sklepie można wydać przy zamówieniu. Wykorzystana kwota jest zapisana przy
+-- zamówieniu, a w historii środków jako wpis na minus; anulowanie nieopłaconego
+-- zamówienia oddaje ją wpisem na plus.
+ALTER TABLE orders ADD COLUMN store_credit INTEGER NOT NULL DEFAULT 0;
+
+-- Historia środków zostaje w rozliczeniach także po usunięciu konta; konta z dodatnim
+-- saldem nie da się usunąć, dopóki środki nie zostaną wykorzystane.
+CREATE TABLE store_credit_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    customer_id INTEGER REFERENCES customers (id) ON DELETE SET NULL,
+    -- w groszach
+    amount INTEGER NOT NULL,
+    refund_id INTEGER REFERENCES refunds (id),
+    -- zamówienie opłacone tymi środkami albo anulowane przed zapłatą
+    order_id INTEGER REFERENCES orders (id),
+    created_at TEXT NOT NULL
+);
+
+INSERT INTO store_credit_new (id, customer_id, amount, refund_id, created_at)
+SELECT id, customer_id, amount, refund_id, created_at FROM store_credit;
+
+DROP TABLE store_credit;
+ALTER TABLE store_credit_new RENAME TO store_credit;
+
+CREATE INDEX store_credit_customer ON store_credit (customer_id);
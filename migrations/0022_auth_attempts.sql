@@ -0,0 +1,14 @@
+-- Próby logowania i formularze wysyłające e-maile, liczone na adres sieciowy i na konto,
+-- żeby ograniczyć zgadywanie haseł i zasypywanie skrzynek linkami. Adres e-mail jest
+-- zapisany jako skrót SHA-256, a wpisy starsze niż doba są usuwane przy kolejnych próbach.
+CREATE TABLE auth_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- log_in, password_reset albo register
+    kind TEXT NOT NULL,
+    email_hash TEXT NOT NULL,
+    ip TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX auth_attempts_email ON auth_attempts (kind, email_hash, created_at);
+CREATE INDEX auth_attempts_ip ON auth_attempts (kind, ip, created_at);
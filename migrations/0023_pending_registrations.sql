@@ -0,0 +1,4 @@
+-- Ponowna rejestracja niepotwierdzonego adresu nie zmienia danych konta: nowe imię i
+-- skrót hasła czekają przy linku aktywacyjnym i trafiają do konta dopiero po jego użyciu.
+ALTER TABLE account_tokens ADD COLUMN name TEXT;
+ALTER TABLE account_tokens ADD COLUMN password_hash TEXT;
@@ -0,0 +1,1121 @@
+//! Customer accounts at `/account`: registration confirmed by e-mail, signing in and
+//! out, password reset, and a dashboard with the customer's orders, saved addresses and
+//! profile. Like the contact form, every form also works without JavaScript.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::catalog::Price;
+use crate::checkout::{Address, FieldError, InvoiceDetails, is_phone};
+use crate::complaints::{Complaint, ComplaintHistory};
+use crate::orders::OrderSummary;
+use leptos::prelude::*;
+use leptos::server_fn::ServerFn;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+pub mod auth;
+pub mod privacy;
+
+pub const MIN_PASSWORD_LEN: usize = 8;
+#[cfg(feature = "ssr")]
+const MAX_PASSWORD_LEN: usize = 128;
+/// Query parameter set after a form that sends an e-mail was submitted without JavaScript.
+const SENT_PARAM: &str = "wyslano";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressKind {
+    /// Where parcels go; has a recipient and a phone number.
+    #[default]
+    Shipping,
+    /// Company details for invoices; has a company name and NIP.
+    Billing,
+}
+
+/// An address kept in the customer's account.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedAddress {
+    pub id: i64,
+    pub kind: AddressKind,
+    /// Recipient's name, for shipping addresses.
+    pub name: String,
+    pub phone: String,
+    /// Company name, for billing addresses.
+    pub company: String,
+    /// The NIP's 10 digits, for billing addresses.
+    pub nip: String,
+    pub address: Address,
+}
+
+impl SavedAddress {
+    /// The billing address as invoice details for checkout.
+    pub fn invoice(&self) -> InvoiceDetails {
+        InvoiceDetails {
+            company: self.company.clone(),
+            nip: self.nip.clone(),
+            address: self.address.clone(),
+        }
+    }
+
+    /// Problems with the fields this kind of address needs.
+    pub fn errors(&self) -> Vec<FieldError> {
+        match self.kind {
+            AddressKind::Shipping => {
+                let mut errors = Vec::new();
+                check_name(&self.name, &mut errors);
+                check_phone(&self.phone, true, &mut errors);
+                errors.extend(self.address.errors(""));
+                errors
+            }
+            // Te same zasady i nazwy pól co przy fakturze w kasie.
+            AddressKind::Billing => self.invoice().errors(),
+        }
+    }
+}
+
+/// What checkout fills in for a signed-in customer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheckoutProfile {
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub addresses: Vec<SavedAddress>,
+    /// Store credit the customer can pay with.
+    pub store_credit: Price,
+}
+
+/// Everything the account dashboard shows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountOverview {
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    /// Newest first.
+    pub orders: Vec<OrderSummary>,
+    /// Newest first.
+    pub complaints: Vec<Complaint>,
+    pub addresses: Vec<SavedAddress>,
+    /// Refunds paid out as store credit.
+    pub store_credit: Price,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AccountError {
+    Invalid(Vec<FieldError>),
+    EmailTaken,
+    WrongCredentials,
+    /// The account exists but its address is not verified yet; a new link was sent.
+    NotVerified,
+    /// The link from the e-mail expired or was already used.
+    InvalidLink,
+    NotSignedIn,
+    /// The account cannot be deleted while its orders are being fulfilled.
+    OrdersInProgress,
+    /// The account cannot be deleted while it holds store credit, which would be lost.
+    StoreCreditLeft,
+    /// Too many sign-in attempts or e-mails requested recently.
+    TooManyAttempts,
+    Unavailable,
+}
+
+impl AccountError {
+    fn field(&self, field: &str) -> Option<String> {
+        match self {
+            AccountError::Invalid(errors) => errors
+                .iter()
+                .find(|error| error.field == field)
+                .map(|error| error.message.clone()),
+            _ => None,
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            AccountError::Invalid(_) => "Popraw zaznaczone pola.",
+            AccountError::EmailTaken => {
+                "Konto z tym adresem e-mail już istnieje. Zaloguj się albo ustaw nowe hasło."
+            }
+            AccountError::WrongCredentials => "Nieprawidłowy e-mail lub hasło.",
+            AccountError::NotVerified => {
+                "Najpierw potwierdź adres e-mail. Wysłaliśmy Ci nowy link aktywacyjny."
+            }
+            AccountError::InvalidLink => "Link wygasł albo został już użyty. Poproś o nowy.",
+            AccountError::NotSignedIn => "Zaloguj się, aby kontynuować.",
+            AccountError::OrdersInProgress => {
+                "Realizujemy jeszcze Twoje zamówienie. Konto możesz usunąć, gdy do Ciebie dotrze."
+            }
+            AccountError::StoreCreditLeft => {
+                "Masz jeszcze środki w sklepie. Wykorzystaj je przy zamówieniu, zanim usuniesz konto."
+            }
+            AccountError::TooManyAttempts => {
+                "Zbyt wiele prób w krótkim czasie. Spróbuj ponownie za kwadrans."
+            }
+            AccountError::Unavailable => "Coś poszło nie tak. Spróbuj ponownie za chwilę.",
+        }
+    }
+}
+
+// Jak w formularzu kontaktowym: błąd musi przejść przez adres URL przy wysyłce bez
+// JavaScriptu, więc zapisujemy go jako JSON.
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl FromStr for AccountError {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn fail(error: AccountError) -> ServerFnError<AccountError> {
+    ServerFnError::WrappedServerError(error)
+}
+
+#[cfg(feature = "ssr")]
+fn unavailable(e: impl fmt::Display) -> ServerFnError<AccountError> {
+    leptos::logging::log!("błąd obsługi konta: {e}");
+    fail(AccountError::Unavailable)
+}
+
+/// Refuses the request once too many like it were made, and counts it otherwise. For
+/// forms that send an e-mail, so they cannot be used to flood someone's inbox.
+#[cfg(feature = "ssr")]
+fn throttle(
+    db: &crate::db::Db,
+    kind: crate::db::AttemptKind,
+    email: &str,
+) -> Result<(), ServerFnError<AccountError>> {
+    if !auth::attempt_allowed(db, kind, email).map_err(unavailable)? {
+        return Err(fail(AccountError::TooManyAttempts));
+    }
+    auth::record_attempt(db, kind, email).map_err(unavailable)
+}
+
+#[cfg(feature = "ssr")]
+fn invalid(errors: Vec<FieldError>) -> Result<(), ServerFnError<AccountError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(fail(AccountError::Invalid(errors)))
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn check_password(field: &str, password: &str, errors: &mut Vec<FieldError>) {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LEN {
+        errors.push(FieldError::new(
+            field,
+            "Hasło musi mieć co najmniej 8 znaków.",
+        ));
+    } else if length > MAX_PASSWORD_LEN {
+        errors.push(FieldError::new(
+            field,
+            "Hasło może mieć najwyżej 128 znaków.",
+        ));
+    }
+}
+
+fn check_name(name: &str, errors: &mut Vec<FieldError>) {
+    if name.trim().is_empty() {
+        errors.push(FieldError::new("name", "Podaj imię i nazwisko."));
+    } else if name.trim().chars().count() > 100 {
+        errors.push(FieldError::new(
+            "name",
+            "Imię i nazwisko może mieć najwyżej 100 znaków.",
+        ));
+    }
+}
+
+fn check_phone(phone: &str, required: bool, errors: &mut Vec<FieldError>) {
+    if phone.trim().is_empty() {
+        if required {
+            errors.push(FieldError::new("phone", "Podaj numer telefonu."));
+        }
+    } else if !is_phone(phone) {
+        errors.push(FieldError::new(
+            "phone",
+            "Podaj poprawny numer telefonu, np. 600 100 200.",
+        ));
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn hash(password: &str) -> Result<String, ServerFnError<AccountError>> {
+    auth::hash_password(password).map_err(unavailable)
+}
+
+/// The signed-in customer, or [`AccountError::NotSignedIn`].
+#[cfg(feature = "ssr")]
+fn signed_in(db: &crate::db::Db) -> Result<crate::db::Customer, ServerFnError<AccountError>> {
+    auth::current_customer(db)
+        .map_err(unavailable)?
+        .ok_or_else(|| fail(AccountError::NotSignedIn))
+}
+
+/// Creates the account and sends the verification link. Registering again with an
+/// address that was never verified leaves the account as it is: the new name and
+/// password travel with the new link and are applied only when it is used, and the
+/// links sent earlier stop working.
+#[server]
+pub async fn register_account(
+    name: String,
+    email: String,
+    password: String,
+) -> Result<(), ServerFnError<AccountError>> {
+    use crate::contact::is_form_post;
+    use crate::db::{
+        AttemptKind, CustomerRepository, PendingRegistration, SessionRepository, TokenPurpose,
+        use_db,
+    };
+
+    let mut errors = Vec::new();
+    check_name(&name, &mut errors);
+    if !crate::checkout::is_email(&email) {
+        errors.push(FieldError::new("email", "Podaj poprawny adres e-mail."));
+    }
+    check_password("password", &password, &mut errors);
+    invalid(errors)?;
+
+    let db = use_db().map_err(unavailable)?;
+    let (name, email) = (name.trim(), email.trim());
+    throttle(&db, AttemptKind::Register, email)?;
+    match db.find_customer_by_email(email).map_err(unavailable)? {
+        Some(customer) if customer.is_verified() => return Err(fail(AccountError::EmailTaken)),
+        Some(customer) => {
+            db.delete_customer_tokens(customer.id)
+                .map_err(unavailable)?;
+            let pending = PendingRegistration {
+                name: name.to_string(),
+                password_hash: hash(&password)?,
+            };
+            auth::send_registration_link(&db, &customer, &pending).map_err(unavailable)?;
+        }
+        None => {
+            let customer = db
+                .create_customer(email, name, &hash(&password)?)
+                .map_err(unavailable)?;
+            auth::send_link(&db, &customer, TokenPurpose::VerifyEmail).map_err(unavailable)?;
+        }
+    }
+
+    if is_form_post() {
+        leptos_axum::redirect(&format!("/account/register?{SENT_PARAM}=1"));
+    }
+    Ok(())
+}
+
+/// Confirms the address from the verification link and signs the customer in.
+#[server]
+pub async fn verify_email(token: String) -> Result<(), ServerFnError<AccountError>> {
+    use crate::db::{CustomerRepository, SessionRepository, TokenPurpose, use_db};
+    use chrono::Utc;
+
+    let db = use_db().map_err(unavailable)?;
+    let customer = auth::redeem_link(&db, &token, TokenPurpose::VerifyEmail)
+        .map_err(unavailable)?
+        .ok_or_else(|| fail(AccountError::InvalidLink))?;
+    db.mark_email_verified(customer.id, Utc::now())
+        .map_err(unavailable)?;
+    // Niewykorzystane linki mogłyby jeszcze zmienić hasło potwierdzonego konta.
+    db.delete_customer_tokens(customer.id)
+        .map_err(unavailable)?;
+    auth::start_session(&db, customer.id).map_err(unavailable)?;
+    leptos_axum::redirect("/account");
+    Ok(())
+}
+
+#[server]
+pub async fn log_in(email: String, password: String) -> Result<(), ServerFnError<AccountError>> {
+    use crate::db::{AttemptKind, TokenPurpose, use_db};
+
+    let db = use_db().map_err(unavailable)?;
+    let email = email.trim();
+    if !auth::attempt_allowed(&db, AttemptKind::LogIn, email).map_err(unavailable)? {
+        return Err(fail(AccountError::TooManyAttempts));
+    }
+    let Some(customer) = auth::check_credentials(&db, email, &password).map_err(unavailable)?
+    else {
+        auth::record_attempt(&db, AttemptKind::LogIn, email).map_err(unavailable)?;
+        return Err(fail(AccountError::WrongCredentials));
+    };
+    auth::clear_attempts(&db, AttemptKind::LogIn, email).map_err(unavailable)?;
+    if !customer.is_verified() {
+        auth::send_link(&db, &customer, TokenPurpose::VerifyEmail).map_err(unavailable)?;
+        return Err(fail(AccountError::NotVerified));
+    }
+    auth::start_session(&db, customer.id).map_err(unavailable)?;
+    Ok(())
+}
+
+#[server]
+pub async fn log_out() -> Result<(), ServerFnError> {
+    let db = crate::db::use_db()?;
+    auth::end_session(&db)
+}
+
+/// Sends a link for setting a new password. The answer is the same whether or not the
+/// account exists, so the form cannot be used to find out who shops here.
+#[server]
+pub async fn request_password_reset(email: String) -> Result<(), ServerFnError<AccountError>> {
+    use crate::contact::is_form_post;
+    use crate::db::{AttemptKind, CustomerRepository, TokenPurpose, use_db};
+
+    if !crate::checkout::is_email(&email) {
+        return invalid(vec![FieldError::new(
+            "email",
+            "Podaj poprawny adres e-mail.",
+        )]);
+    }
+    let db = use_db().map_err(unavailable)?;
+    let email = email.trim();
+    throttle(&db, AttemptKind::PasswordReset, email)?;
+    if let Some(customer) = db.find_customer_by_email(email).map_err(unavailable)? {
+        auth::send_link(&db, &customer, TokenPurpose::ResetPassword).map_err(unavailable)?;
+    }
+
+    if is_form_post() {
+        leptos_axum::redirect(&format!("/account/forgot-password?{SENT_PARAM}=1"));
+    }
+    Ok(())
+}
+
+/// Sets the password from a reset link, signs out every other device and signs the
+/// customer in. The link also proves the address, so it counts as verification.
+#[server]
+pub async fn set_new_password(
+    token: String,
+    password: String,
+) -> Result<(), ServerFnError<AccountError>> {
+    use crate::db::{CustomerRepository, SessionRepository, TokenPurpose, use_db};
+    use chrono::Utc;
+
+    let mut errors = Vec::new();
+    check_password("password", &password, &mut errors);
+    invalid(errors)?;
+
+    let db = use_db().map_err(unavailable)?;
+    let customer = auth::redeem_link(&db, &token, TokenPurpose::ResetPassword)
+        .map_err(unavailable)?
+        .ok_or_else(|| fail(AccountError::InvalidLink))?;
+    db.set_password_hash(customer.id, &hash(&password)?)
+        .map_err(unavailable)?;
+    db.delete_customer_sessions(customer.id)
+        .map_err(unavailable)?;
+    db.mark_email_verified(customer.id, Utc::now())
+        .map_err(unavailable)?;
+    // Niewykorzystane linki mogłyby jeszcze zmienić hasło potwierdzonego konta.
+    db.delete_customer_tokens(customer.id)
+        .map_err(unavailable)?;
+    auth::start_session(&db, customer.id).map_err(unavailable)?;
+    leptos_axum::redirect("/account");
+    Ok(())
+}
+
+/// The signed-in customer's contact details and saved addresses, or `None` when nobody is
+/// signed in.
+#[server]
+pub async fn get_checkout_profile() -> Result<Option<CheckoutProfile>, ServerFnError> {
+    use crate::db::{AddressRepository, RefundRepository, use_db};
+
+    let db = use_db()?;
+    let Some(customer) = auth::current_customer(&db)? else {
+        return Ok(None);
+    };
+    Ok(Some(CheckoutProfile {
+        addresses: db.list_addresses(customer.id)?,
+        store_credit: db.store_credit_balance(customer.id)?,
+        email: customer.email,
+        name: customer.name,
+        phone: customer.phone,
+    }))
+}
+
+/// The signed-in customer's dashboard, or `None` when nobody is signed in.
+#[server]
+pub async fn get_account() -> Result<Option<AccountOverview>, ServerFnError> {
+    use crate::db::{
+        AddressRepository, ComplaintRepository, OrderRepository, RefundRepository, use_db,
+    };
+
+    let db = use_db()?;
+    let Some(customer) = auth::current_customer(&db)? else {
+        return Ok(None);
+    };
+    Ok(Some(AccountOverview {
+        orders: db
+            .orders_for_customer(customer.id)?
+            .iter()
+            .map(OrderSummary::from)
+            .collect(),
+        complaints: db.complaints_for_customer(customer.id)?,
+        addresses: db.list_addresses(customer.id)?,
+        store_credit: db.store_credit_balance(customer.id)?,
+        name: customer.name,
+        email: customer.email,
+        phone: customer.phone,
+    }))
+}
+
+#[server]
+pub async fn update_profile(
+    name: String,
+    phone: String,
+) -> Result<(), ServerFnError<AccountError>> {
+    use crate::db::{CustomerRepository, use_db};
+
+    let mut errors = Vec::new();
+    check_name(&name, &mut errors);
+    check_phone(&phone, false, &mut errors);
+    invalid(errors)?;
+    let db = use_db().map_err(unavailable)?;
+    let customer = signed_in(&db)?;
+    db.update_profile(customer.id, name.trim(), phone.trim())
+        .map_err(unavailable)?;
+    Ok(())
+}
+
+/// Changes the password and signs out every other device.
+#[server]
+pub async fn change_password(
+    current_password: String,
+    new_password: String,
+) -> Result<(), ServerFnError<AccountError>> {
+    use crate::db::{CustomerRepository, SessionRepository, use_db};
+
+    let mut errors = Vec::new();
+    check_password("new_password", &new_password, &mut errors);
+    invalid(errors)?;
+    let db = use_db().map_err(unavailable)?;
+    let customer = signed_in(&db)?;
+    let current = db.password_hash(customer.id).map_err(unavailable)?;
+    if !current.is_some_and(|hash| auth::verify_password(&current_password, &hash)) {
+        return invalid(vec![FieldError::new(
+            "current_password",
+            "Obecne hasło jest nieprawidłowe.",
+        )]);
+    }
+    db.set_password_hash(customer.id, &hash(&new_password)?)
+        .map_err(unavailable)?;
+    db.delete_customer_sessions(customer.id)
+        .map_err(unavailable)?;
+    auth::start_session(&db, customer.id).map_err(unavailable)?;
+    Ok(())
+}
+
+/// Saves a shipping address, or with `kind` set to billing, company details for invoices.
+#[server]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_address(
+    kind: AddressKind,
+    #[server(default)] name: String,
+    #[server(default)] phone: String,
+    #[server(default)] company: String,
+    #[server(default)] nip: String,
+    street: String,
+    postcode: String,
+    city: String,
+) -> Result<(), ServerFnError<AccountError>> {
+    use crate::checkout::normalize_nip;
+    use crate::db::{AddressRepository, use_db};
+
+    let address = match kind {
+        AddressKind::Shipping => SavedAddress {
+            name: name.trim().to_string(),
+            phone: phone.trim().to_string(),
+            ..SavedAddress::default()
+        },
+        AddressKind::Billing => SavedAddress {
+            kind,
+            company: company.trim().to_string(),
+            nip: normalize_nip(&nip).unwrap_or_else(|| nip.trim().to_string()),
+            ..SavedAddress::default()
+        },
+    };
+    let address = SavedAddress {
+        address: Address {
+            street,
+            postcode,
+            city,
+        }
+        .trimmed(),
+        ..address
+    };
+    invalid(address.errors())?;
+    let db = use_db().map_err(unavailable)?;
+    let customer = signed_in(&db)?;
+    db.add_address(customer.id, &address).map_err(unavailable)?;
+    Ok(())
+}
+
+#[server]
+pub async fn delete_address(id: i64) -> Result<(), ServerFnError<AccountError>> {
+    use crate::db::{AddressRepository, use_db};
+
+    let db = use_db().map_err(unavailable)?;
+    let customer = signed_in(&db)?;
+    db.delete_address(customer.id, id).map_err(unavailable)?;
+    Ok(())
+}
+
+/// What went wrong with the last submission of `action`'s form.
+fn failure<S>(action: ServerAction<S>) -> Option<AccountError>
+where
+    S: ServerFn<Output = (), Error = AccountError> + Clone + Send + Sync + 'static,
+{
+    match action.value().get() {
+        Some(Err(ServerFnError::WrappedServerError(error))) => Some(error),
+        Some(Err(_)) => Some(AccountError::Unavailable),
+        _ => None,
+    }
+}
+
+fn succeeded<S>(action: ServerAction<S>) -> bool
+where
+    S: ServerFn<Output = (), Error = AccountError> + Clone + Send + Sync + 'static,
+{
+    matches!(action.value().get(), Some(Ok(())))
+}
+
+/// Brings in the customer's saved cart once `action` signs them in.
+fn merge_cart_after<S>(action: ServerAction<S>)
+where
+    S: ServerFn<Output = (), Error = AccountError> + Clone + Send + Sync + 'static,
+{
+    let cart = crate::cart::use_cart();
+    Effect::new(move |_| {
+        if succeeded(action) {
+            cart.merge_saved();
+        }
+    });
+}
+
+fn field_message(error: Option<AccountError>, field: &str) -> Option<impl IntoView + use<>> {
+    error
+        .and_then(|error| error.field(field))
+        .map(|message| view! { <p class="field-error">{message}</p> })
+}
+
+fn error_summary(error: Option<AccountError>) -> Option<impl IntoView + use<>> {
+    error.map(|error| view! { <p class="account-error" role="alert">{error.summary()}</p> })
+}
+
+/// Every form of the dashboard; the account is reloaded after any of them is used.
+#[derive(Clone, Copy)]
+struct AccountActions {
+    log_in: ServerAction<LogIn>,
+    log_out: ServerAction<LogOut>,
+    update_profile: ServerAction<UpdateProfile>,
+    change_password: ServerAction<ChangePassword>,
+    add_address: ServerAction<AddAddress>,
+    delete_address: ServerAction<DeleteAddress>,
+    erase_account: ServerAction<privacy::EraseAccount>,
+}
+
+impl AccountActions {
+    fn new() -> Self {
+        AccountActions {
+            log_in: ServerAction::new(),
+            log_out: ServerAction::new(),
+            update_profile: ServerAction::new(),
+            change_password: ServerAction::new(),
+            add_address: ServerAction::new(),
+            delete_address: ServerAction::new(),
+            erase_account: ServerAction::new(),
+        }
+    }
+
+    fn versions(&self) -> [usize; 7] {
+        [
+            self.log_in.version().get(),
+            self.log_out.version().get(),
+            self.update_profile.version().get(),
+            self.change_password.version().get(),
+            self.add_address.version().get(),
+            self.delete_address.version().get(),
+            self.erase_account.version().get(),
+        ]
+    }
+}
+
+#[component]
+pub fn AccountPage() -> impl IntoView {
+    let actions = AccountActions::new();
+    let account = Resource::new(move || actions.versions(), |_| get_account());
+    let query = use_query_map();
+    let erased = move || {
+        succeeded(actions.erase_account) || query.read().get(privacy::ERASED_PARAM).is_some()
+    };
+    merge_cart_after(actions.log_in);
+    let cart = crate::cart::use_cart();
+    Effect::new(move |_| {
+        let logged_out = matches!(actions.log_out.value().get(), Some(Ok(())));
+        if logged_out || succeeded(actions.erase_account) {
+            cart.sign_out();
+        }
+    });
+
+    view! {
+        <Title text="Moje konto - Meg Joni" />
+        <main>
+            <section class="account-section">
+                <Show when=erased>
+                    <p class="account-success" role="status">
+                        "Twoje konto zostało usunięte. Dziękujemy, że byłaś/eś z nami."
+                    </p>
+                </Show>
+                <Suspense fallback=|| view! { <p>"Ładowanie konta..."</p> }>
+                    {move || Suspend::new(async move {
+                        match account.await {
+                            Ok(Some(account)) => {
+                                view! { <AccountDashboard account actions /> }.into_any()
+                            }
+                            Ok(None) => view! { <LoginForm action=actions.log_in /> }.into_any(),
+                            Err(_) => {
+                                view! { <p>{AccountError::Unavailable.summary()}</p> }.into_any()
+                            }
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn LoginForm(action: ServerAction<LogIn>) -> impl IntoView {
+    let error = move || failure(action);
+
+    view! {
+        <h2>"Zaloguj się"</h2>
+        <ActionForm action attr:class="account-form">
+            <label for="login-email">"E-mail"</label>
+            <input type="email" id="login-email" name="email" autocomplete="email" required />
+            <label for="login-password">"Hasło"</label>
+            <input
+                type="password"
+                id="login-password"
+                name="password"
+                autocomplete="current-password"
+                required
+            />
+            {move || error_summary(error())}
+            <button type="submit" disabled=move || action.pending().get()>"Zaloguj się"</button>
+        </ActionForm>
+        <p>
+            <a href="/account/forgot-password">"Nie pamiętasz hasła?"</a>
+        </p>
+        <p>
+            "Nie masz jeszcze konta? " <a href="/account/register">"Załóż konto"</a>
+            " – zobaczysz w nim historię zamówień i zapiszesz adresy do wysyłki."
+        </p>
+    }
+}
+
+#[component]
+fn AccountDashboard(account: AccountOverview, actions: AccountActions) -> impl IntoView {
+    let AccountOverview {
+        name,
+        email,
+        phone,
+        orders,
+        complaints,
+        addresses,
+        store_credit,
+    } = account;
+
+    view! {
+        <div class="account-header">
+            <h2>{format!("Witaj, {name}!")}</h2>
+            <ActionForm action=actions.log_out>
+                <button type="submit">"Wyloguj się"</button>
+            </ActionForm>
+        </div>
+        <h3>"Zamówienia"</h3>
+        <OrderHistory orders />
+        {(store_credit.grosze() > 0)
+            .then(|| view! {
+                <p class="account-store-credit">
+                    {format!(
+                        "Środki w sklepie ze zwrotów: {store_credit}. Możesz za nie zapłacić przy następnym zamówieniu."
+                    )}
+                </p>
+            })}
+        <p>
+            <a href="/returns">"Zwróć rzeczy z zamówienia"</a>
+        </p>
+        <h3>"Reklamacje"</h3>
+        <ComplaintHistory complaints />
+        <p>
+            <a href="/complaints">"Zgłoś reklamację"</a>
+        </p>
+        <h3>"Adresy"</h3>
+        <AddressBook addresses add=actions.add_address delete=actions.delete_address />
+        <h3>"Dane konta"</h3>
+        <ProfileForm name phone email action=actions.update_profile />
+        <PasswordForm action=actions.change_password />
+        <h3>"Twoje dane"</h3>
+        <privacy::PrivacySection erase=actions.erase_account />
+    }
+}
+
+#[component]
+fn OrderHistory(orders: Vec<OrderSummary>) -> impl IntoView {
+    if orders.is_empty() {
+        return view! { <p>"Nie masz jeszcze żadnych zamówień."</p> }.into_any();
+    }
+    view! {
+        <table class="account-orders">
+            <thead>
+                <tr>
+                    <th>"Nr"</th>
+                    <th>"Data"</th>
+                    <th>"Produkty"</th>
+                    <th>"Status"</th>
+                    <th>"Kwota"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {orders
+                    .into_iter()
+                    .map(|order| {
+                        view! {
+                            <tr>
+                                <td>{order.id}</td>
+                                <td>{order.created_at.format("%d.%m.%Y").to_string()}</td>
+                                <td>{order.items.join(", ")}</td>
+                                <td>{order.status.label()}</td>
+                                <td>
+                                    {order.total.to_string()}
+                                    {(order.refunded.grosze() > 0)
+                                        .then(|| view! {
+                                            <br />
+                                            <small>{format!("zwrócono {}", order.refunded)}</small>
+                                        })}
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+    .into_any()
+}
+
+#[component]
+fn AddressBook(
+    addresses: Vec<SavedAddress>,
+    add: ServerAction<AddAddress>,
+    delete: ServerAction<DeleteAddress>,
+) -> impl IntoView {
+    let error = move || failure(add);
+    let (shipping, billing): (Vec<_>, Vec<_>) = addresses
+        .into_iter()
+        .partition(|saved| saved.kind == AddressKind::Shipping);
+    let list = move |addresses: Vec<SavedAddress>, empty: &'static str| {
+        if addresses.is_empty() {
+            return view! { <p>{empty}</p> }.into_any();
+        }
+        let items = addresses
+            .into_iter()
+            .map(|saved| {
+                let first_line = match saved.kind {
+                    AddressKind::Shipping => saved.name,
+                    AddressKind::Billing => saved.company,
+                };
+                let last_line = match saved.kind {
+                    AddressKind::Shipping => format!("tel. {}", saved.phone),
+                    AddressKind::Billing => format!("NIP {}", saved.nip),
+                };
+                view! {
+                    <li>
+                        <address>
+                            {first_line}
+                            <br />
+                            {saved.address.street}
+                            <br />
+                            {format!("{} {}", saved.address.postcode, saved.address.city)}
+                            <br />
+                            {last_line}
+                        </address>
+                        <ActionForm action=delete>
+                            <input type="hidden" name="id" value=saved.id />
+                            <button type="submit">"Usuń"</button>
+                        </ActionForm>
+                    </li>
+                }
+            })
+            .collect_view();
+        view! { <ul class="account-addresses">{items}</ul> }.into_any()
+    };
+    // Błędy danych do faktury przychodzą z przedrostkiem `invoice_`, tak jak w kasie, więc
+    // nie trafiają do pól formularza adresu dostawy.
+    let field = move |kind: AddressKind,
+                      name: &'static str,
+                      label: &'static str,
+                      autocomplete: &'static str| {
+        let (id, error_field) = match kind {
+            AddressKind::Shipping => (format!("address-{name}"), name.to_string()),
+            AddressKind::Billing => (format!("billing-{name}"), format!("invoice_{name}")),
+        };
+        view! {
+            <label for=id.clone()>{label}</label>
+            <input type="text" id=id name=name autocomplete=autocomplete required />
+            {move || field_message(error(), &error_field)}
+        }
+    };
+
+    view! {
+        <h4>"Adresy dostawy"</h4>
+        {list(shipping, "Nie masz zapisanych adresów dostawy.")}
+        <details class="account-new-address">
+            <summary>"Dodaj adres dostawy"</summary>
+            <ActionForm action=add attr:class="account-form">
+                <input type="hidden" name="kind" value="shipping" />
+                {field(AddressKind::Shipping, "name", "Imię i nazwisko", "name")}
+                {field(AddressKind::Shipping, "phone", "Telefon", "tel")}
+                {field(AddressKind::Shipping, "street", "Ulica i numer", "street-address")}
+                {field(AddressKind::Shipping, "postcode", "Kod pocztowy", "postal-code")}
+                {field(AddressKind::Shipping, "city", "Miejscowość", "address-level2")}
+                <button type="submit">"Zapisz adres"</button>
+            </ActionForm>
+        </details>
+        <h4>"Dane do faktury"</h4>
+        {list(billing, "Nie masz zapisanych danych do faktury.")}
+        <details class="account-new-address">
+            <summary>"Dodaj dane do faktury"</summary>
+            <ActionForm action=add attr:class="account-form">
+                <input type="hidden" name="kind" value="billing" />
+                {field(AddressKind::Billing, "company", "Nazwa firmy", "organization")}
+                {field(AddressKind::Billing, "nip", "NIP", "off")}
+                {field(AddressKind::Billing, "street", "Ulica i numer", "street-address")}
+                {field(AddressKind::Billing, "postcode", "Kod pocztowy", "postal-code")}
+                {field(AddressKind::Billing, "city", "Miejscowość", "address-level2")}
+                <button type="submit">"Zapisz dane"</button>
+            </ActionForm>
+        </details>
+        {move || error_summary(error())}
+    }
+}
+
+#[component]
+fn ProfileForm(
+    name: String,
+    phone: String,
+    email: String,
+    action: ServerAction<UpdateProfile>,
+) -> impl IntoView {
+    let error = move || failure(action);
+
+    view! {
+        <ActionForm action attr:class="account-form">
+            <p>"E-mail: " <strong>{email}</strong></p>
+            <label for="profile-name">"Imię i nazwisko"</label>
+            <input type="text" id="profile-name" name="name" value=name autocomplete="name" required />
+            {move || field_message(error(), "name")}
+            <label for="profile-phone">"Telefon"</label>
+            <input type="tel" id="profile-phone" name="phone" value=phone autocomplete="tel" />
+            {move || error_summary(error())}
+            {move || {
+                succeeded(action)
+                    .then(|| view! { <p class="account-success" role="status">"Zapisano."</p> })
+            }}
+            <button type="submit">"Zapisz dane"</button>
+        </ActionForm>
+    }
+}
+
+#[component]
+fn PasswordForm(action: ServerAction<ChangePassword>) -> impl IntoView {
+    let error = move || failure(action);
+
+    view! {
+        <ActionForm action attr:class="account-form">
+            <label for="current-password">"Obecne hasło"</label>
+            <input
+                type="password"
+                id="current-password"
+                name="current_password"
+                autocomplete="current-password"
+                required
+            />
+            {move || field_message(error(), "current_password")}
+            <label for="new-password">"Nowe hasło"</label>
+            <input
+                type="password"
+                id="new-password"
+                name="new_password"
+                autocomplete="new-password"
+                minlength=MIN_PASSWORD_LEN
+                required
+            />
+            {move || field_message(error(), "new_password")}
+            {move || {
+                succeeded(action)
+                    .then(|| {
+                        view! {
+                            <p class="account-success" role="status">
+                                "Hasło zostało zmienione. Wylogowaliśmy pozostałe urządzenia."
+                            </p>
+                        }
+                    })
+            }}
+            <button type="submit">"Zmień hasło"</button>
+        </ActionForm>
+    }
+}
+
+#[component]
+pub fn RegisterPage() -> impl IntoView {
+    let register = ServerAction::<RegisterAccount>::new();
+    let query = use_query_map();
+    let sent = move || succeeded(register) || query.read().get(SENT_PARAM).is_some();
+    let error = move || failure(register);
+
+    view! {
+        <Title text="Załóż konto - Meg Joni" />
+        <main>
+            <section class="account-section">
+                <h2>"Załóż konto"</h2>
+                <Show
+                    when=sent
+                    fallback=move || view! {
+                        <ActionForm action=register attr:class="account-form">
+                            <label for="register-name">"Imię i nazwisko"</label>
+                            <input type="text" id="register-name" name="name" autocomplete="name" required />
+                            {move || field_message(error(), "name")}
+                            <label for="register-email">"E-mail"</label>
+                            <input type="email" id="register-email" name="email" autocomplete="email" required />
+                            {move || field_message(error(), "email")}
+                            <label for="register-password">"Hasło (co najmniej 8 znaków)"</label>
+                            <input
+                                type="password"
+                                id="register-password"
+                                name="password"
+                                autocomplete="new-password"
+                                minlength=MIN_PASSWORD_LEN
+                                required
+                            />
+                            {move || field_message(error(), "password")}
+                            {move || error_summary(error())}
+                            <button type="submit" disabled=move || register.pending().get()>
+                                "Załóż konto"
+                            </button>
+                        </ActionForm>
+                        <p>"Masz już konto? " <a href="/account">"Zaloguj się"</a></p>
+                    }
+                >
+                    <p class="account-success" role="status">
+                        "Prawie gotowe! Wysłaliśmy Ci e-mail z linkiem – kliknij go, aby potwierdzić adres i zalogować się."
+                    </p>
+                </Show>
+            </section>
+        </main>
+    }
+}
+
+/// Opened from the verification e-mail. Confirming takes a click, so that mail scanners
+/// following links do not use the token up.
+#[component]
+pub fn VerifyEmailPage() -> impl IntoView {
+    let verify = ServerAction::<VerifyEmail>::new();
+    merge_cart_after(verify);
+    let query = use_query_map();
+    let token = move || query.read().get("token").unwrap_or_default();
+    let error = move || failure(verify);
+
+    view! {
+        <Title text="Potwierdź adres e-mail - Meg Joni" />
+        <main>
+            <section class="account-section">
+                <h2>"Potwierdź adres e-mail"</h2>
+                <ActionForm action=verify attr:class="account-form">
+                    <input type="hidden" name="token" value=token />
+                    {move || error_summary(error())}
+                    <button type="submit">"Potwierdzam i przechodzę do konta"</button>
+                </ActionForm>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+pub fn ForgotPasswordPage() -> impl IntoView {
+    let request = ServerAction::<RequestPasswordReset>::new();
+    let query = use_query_map();
+    let sent = move || succeeded(request) || query.read().get(SENT_PARAM).is_some();
+    let error = move || failure(request);
+
+    view! {
+        <Title text="Nie pamiętasz hasła? - Meg Joni" />
+        <main>
+            <section class="account-section">
+                <h2>"Ustaw nowe hasło"</h2>
+                <Show
+                    when=sent
+                    fallback=move || view! {
+                        <p>"Podaj adres e-mail konta, a wyślemy Ci link do ustawienia nowego hasła."</p>
+                        <ActionForm action=request attr:class="account-form">
+                            <label for="reset-email">"E-mail"</label>
+                            <input type="email" id="reset-email" name="email" autocomplete="email" required />
+                            {move || field_message(error(), "email")}
+                            {move || error_summary(error())}
+                            <button type="submit">"Wyślij link"</button>
+                        </ActionForm>
+                    }
+                >
+                    <p class="account-success" role="status">
+                        "Jeśli mamy konto z tym adresem, za chwilę dostaniesz e-mail z linkiem do ustawienia nowego hasła."
+                    </p>
+                </Show>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+pub fn NewPasswordPage() -> impl IntoView {
+    let set_password = ServerAction::<SetNewPassword>::new();
+    merge_cart_after(set_password);
+    let query = use_query_map();
+    let token = move || query.read().get("token").unwrap_or_default();
+    let error = move || failure(set_password);
+
+    view! {
+        <Title text="Nowe hasło - Meg Joni" />
+        <main>
+            <section class="account-section">
+                <h2>"Ustaw nowe hasło"</h2>
+                <ActionForm action=set_password attr:class="account-form">
+                    <input type="hidden" name="token" value=token />
+                    <label for="new-password">"Nowe hasło (co najmniej 8 znaków)"</label>
+                    <input
+                        type="password"
+                        id="new-password"
+                        name="password"
+                        autocomplete="new-password"
+                        minlength=MIN_PASSWORD_LEN
+                        required
+                    />
+                    {move || field_message(error(), "password")}
+                    {move || error_summary(error())}
+                    {move || {
+                        (error() == Some(AccountError::InvalidLink))
+                            .then(|| view! { <a href="/account/forgot-password">"Wyślij nowy link"</a> })
+                    }}
+                    <button type="submit">"Zapisz hasło"</button>
+                </ActionForm>
+            </section>
+        </main>
+    }
+}
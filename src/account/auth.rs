@@ -0,0 +1,306 @@
+//! Passwords, sign-in sessions and the one-time links sent by e-mail.
+//!
+//! A session is a random token kept by the browser in an `HttpOnly` cookie; the
+//! database stores only its SHA-256 hash. Passwords are hashed with Argon2id.
+
+use std::sync::LazyLock;
+
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use axum::http::{HeaderMap, HeaderValue, header};
+use chrono::{Duration, Utc};
+use leptos::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::account::privacy::email_hash;
+use crate::db::{
+    self, AttemptKind, AttemptRepository, Customer, CustomerRepository, Db, PendingRegistration,
+    SessionRepository, TokenPurpose,
+};
+use crate::mail::{self, Email};
+use crate::payments::gateway::Payments;
+
+pub const SESSION_COOKIE: &str = "megjoni_session";
+/// How long a sign-in lasts.
+pub const SESSION_DAYS: i64 = 30;
+pub const VERIFY_LINK_HOURS: u32 = 48;
+pub const RESET_LINK_HOURS: u32 = 2;
+
+/// Compared against when the e-mail is unknown, so a failed sign-in takes as long
+/// whether or not the account exists.
+static DUMMY_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password("nie-ma-takiego-konta").expect("hasło da się zahashować"));
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Returns `account` if `password` matches its stored hash. Used for both customer and
+/// staff sign-in; an unknown account is checked against a dummy hash all the same.
+pub fn check_account_password<A>(
+    account: Option<A>,
+    password: &str,
+    stored_hash: impl FnOnce(&A) -> db::Result<Option<String>>,
+) -> db::Result<Option<A>> {
+    let hash = match &account {
+        Some(account) => stored_hash(account)?,
+        None => None,
+    };
+    let valid = verify_password(password, hash.as_deref().unwrap_or(&DUMMY_HASH));
+    Ok(account.filter(|_| valid && hash.is_some()))
+}
+
+/// Checks the password of the account with this e-mail address.
+pub fn check_credentials(db: &Db, email: &str, password: &str) -> db::Result<Option<Customer>> {
+    check_account_password(db.find_customer_by_email(email)?, password, |customer| {
+        db.password_hash(customer.id)
+    })
+}
+
+/// How many attempts of one kind are allowed within `minutes`, from one network address
+/// and for one e-mail address.
+struct AttemptLimit {
+    per_ip: usize,
+    per_email: usize,
+    minutes: i64,
+}
+
+fn attempt_limit(kind: AttemptKind) -> AttemptLimit {
+    match kind {
+        // Liczą się tylko nieudane logowania.
+        AttemptKind::LogIn | AttemptKind::StaffLogIn => AttemptLimit {
+            per_ip: 20,
+            per_email: 5,
+            minutes: 15,
+        },
+        AttemptKind::PasswordReset | AttemptKind::Register => AttemptLimit {
+            per_ip: 10,
+            per_email: 3,
+            minutes: 60,
+        },
+    }
+}
+
+/// Whether the current client may make another attempt of `kind` for `email`. Like the
+/// contact form, limited per network address and per e-mail address.
+pub fn attempt_allowed(db: &Db, kind: AttemptKind, email: &str) -> db::Result<bool> {
+    let limit = attempt_limit(kind);
+    let since = Utc::now() - Duration::minutes(limit.minutes);
+    let ip = crate::contact::client_ip();
+    let counts = db.count_attempts(kind, &email_hash(email), ip.as_deref(), since)?;
+    Ok(counts.by_ip < limit.per_ip && counts.by_email < limit.per_email)
+}
+
+pub fn record_attempt(db: &Db, kind: AttemptKind, email: &str) -> db::Result<()> {
+    let ip = crate::contact::client_ip();
+    db.record_attempt(kind, &email_hash(email), ip.as_deref(), Utc::now())
+}
+
+/// Forgets the attempts of `kind` for `email`, e.g. once the right password was given.
+pub fn clear_attempts(db: &Db, kind: AttemptKind, email: &str) -> db::Result<()> {
+    db.clear_attempts(kind, &email_hash(email))
+}
+
+/// 256 random bits, hex-encoded.
+pub(crate) fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+pub(crate) fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub(crate) fn public_url() -> Result<String, ServerFnError> {
+    use_context::<Payments>()
+        .map(|payments| payments.public_url().to_string())
+        .ok_or_else(|| ServerFnError::new("adres sklepu nie jest skonfigurowany"))
+}
+
+/// The session token sent with the current request.
+fn session_token() -> Option<String> {
+    let parts = use_context::<axum::http::request::Parts>()?;
+    session_token_in(&parts.headers)
+}
+
+fn session_token_in(headers: &HeaderMap) -> Option<String> {
+    cookie_in(headers, SESSION_COOKIE)
+}
+
+/// The value of the cookie called `name`.
+pub(crate) fn cookie_in(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| {
+            let (cookie, value) = cookie.trim().split_once('=')?;
+            (cookie == name).then(|| value.to_string())
+        })
+}
+
+fn set_session_cookie(value: &str, max_age_secs: i64) -> Result<(), ServerFnError> {
+    set_cookie(SESSION_COOKIE, value, max_age_secs)
+}
+
+/// Sets an `HttpOnly` cookie for the whole shop on the response.
+pub(crate) fn set_cookie(name: &str, value: &str, max_age_secs: i64) -> Result<(), ServerFnError> {
+    // Na zwykłym http (np. lokalnie) przeglądarka nie odesłałaby ciasteczka Secure.
+    let secure = if public_url()?.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
+    };
+    let cookie =
+        format!("{name}={value}; Path=/; Max-Age={max_age_secs}; HttpOnly; SameSite=Lax{secure}");
+    let response = expect_context::<leptos_axum::ResponseOptions>();
+    response.append_header(header::SET_COOKIE, HeaderValue::from_str(&cookie)?);
+    Ok(())
+}
+
+/// The customer signed in with the current request, if any.
+pub fn current_customer(db: &Db) -> db::Result<Option<Customer>> {
+    match session_token() {
+        Some(token) => db.session_customer(&token_hash(&token), Utc::now()),
+        None => Ok(None),
+    }
+}
+
+/// The customer signed in with a request handled outside Leptos, such as a file upload.
+pub fn customer_from_headers(db: &Db, headers: &HeaderMap) -> db::Result<Option<Customer>> {
+    match session_token_in(headers) {
+        Some(token) => db.session_customer(&token_hash(&token), Utc::now()),
+        None => Ok(None),
+    }
+}
+
+/// Signs the customer in on this browser.
+pub fn start_session(db: &Db, customer_id: i64) -> Result<(), ServerFnError> {
+    let token = new_token();
+    let now = Utc::now();
+    db.create_session(
+        &token_hash(&token),
+        customer_id,
+        now,
+        now + Duration::days(SESSION_DAYS),
+    )?;
+    db.record_login(customer_id, now)?;
+    set_session_cookie(&token, SESSION_DAYS * 24 * 60 * 60)
+}
+
+/// Signs out the current browser.
+pub fn end_session(db: &Db) -> Result<(), ServerFnError> {
+    if let Some(token) = session_token() {
+        db.delete_session(&token_hash(&token))?;
+    }
+    set_session_cookie("", 0)
+}
+
+/// Queues an e-mail with a one-time link for `purpose`.
+pub fn send_link(db: &Db, customer: &Customer, purpose: TokenPurpose) -> Result<(), ServerFnError> {
+    queue_link(db, customer, purpose, None)
+}
+
+/// Queues a verification link that applies `pending` to the account when it is used.
+pub fn send_registration_link(
+    db: &Db,
+    customer: &Customer,
+    pending: &PendingRegistration,
+) -> Result<(), ServerFnError> {
+    queue_link(db, customer, TokenPurpose::VerifyEmail, Some(pending))
+}
+
+fn queue_link(
+    db: &Db,
+    customer: &Customer,
+    purpose: TokenPurpose,
+    pending: Option<&PendingRegistration>,
+) -> Result<(), ServerFnError> {
+    let token = new_token();
+    let (path, hours) = match purpose {
+        TokenPurpose::VerifyEmail => ("/account/verify", VERIFY_LINK_HOURS),
+        TokenPurpose::ResetPassword => ("/account/new-password", RESET_LINK_HOURS),
+    };
+    let now = Utc::now();
+    db.create_account_token(
+        &token_hash(&token),
+        customer.id,
+        purpose,
+        pending,
+        now,
+        now + Duration::hours(hours.into()),
+    )?;
+    let link = format!("{}{path}?token={token}", public_url()?);
+    let name = pending
+        .map_or(&customer.name, |pending| &pending.name)
+        .clone();
+    let email = match purpose {
+        TokenPurpose::VerifyEmail => Email::VerifyEmail {
+            name,
+            link,
+            valid_for_hours: hours,
+        },
+        TokenPurpose::ResetPassword => Email::PasswordReset {
+            name,
+            link,
+            valid_for_hours: hours,
+        },
+    };
+    mail::queue(db, &customer.email, &email)?;
+    Ok(())
+}
+
+/// Uses up a link's token and returns the customer it was sent to, with the name and
+/// password of a repeated registration applied.
+pub fn redeem_link(db: &Db, token: &str, purpose: TokenPurpose) -> db::Result<Option<Customer>> {
+    let Some(used) = db.use_account_token(&token_hash(token.trim()), purpose, Utc::now())? else {
+        return Ok(None);
+    };
+    if let Some(pending) = used.pending {
+        if let Some(customer) = db.find_customer(used.customer_id)? {
+            db.update_profile(customer.id, &pending.name, &customer.phone)?;
+            db.set_password_hash(customer.id, &pending.password_hash)?;
+        }
+    }
+    db.find_customer(used.customer_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::db;
+
+    #[test]
+    fn sign_in_failures_are_limited_per_email_and_per_address() {
+        let db = db();
+        for _ in 0..5 {
+            assert!(attempt_allowed(&db, AttemptKind::LogIn, "anna@example.com").unwrap());
+            record_attempt(&db, AttemptKind::LogIn, "anna@example.com").unwrap();
+        }
+        assert!(!attempt_allowed(&db, AttemptKind::LogIn, "anna@example.com").unwrap());
+        assert!(attempt_allowed(&db, AttemptKind::StaffLogIn, "anna@example.com").unwrap());
+
+        // Bez adresu klienta wszystkie próby liczą się razem.
+        for n in 0..15 {
+            let email = format!("klient{n}@example.com");
+            assert!(attempt_allowed(&db, AttemptKind::LogIn, &email).unwrap());
+            record_attempt(&db, AttemptKind::LogIn, &email).unwrap();
+        }
+        assert!(!attempt_allowed(&db, AttemptKind::LogIn, "nowa@example.com").unwrap());
+    }
+}
@@ -0,0 +1,390 @@
+//! The customer's rights from section "7. Twoje prawa" of the privacy policy: a JSON
+//! copy of everything we hold about them, and deleting the account. Orders are kept for
+//! accounting but lose the buyer's personal details. Both requests are recorded in an
+//! audit trail.
+
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use leptos::server_fn::ServerFn;
+use leptos::server_fn::codec::GetUrl;
+use serde::{Deserialize, Serialize};
+
+use super::{AccountError, SavedAddress, error_summary, failure, field_message};
+#[cfg(feature = "ssr")]
+use super::{auth, fail, invalid, signed_in, unavailable};
+use crate::catalog::Price;
+#[cfg(feature = "ssr")]
+use crate::checkout::FieldError;
+use crate::checkout::{Address, DeliveryMethod, InvoiceDetails, PaymentMethod};
+use crate::complaints::Complaint;
+use crate::inbox::ThreadEntry;
+use crate::orders::{OrderEvent, OrderStatus};
+use crate::refunds::{RefundMethod, RefundStatus, StoreCreditEntry};
+use crate::returns::ReturnRequest;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedAccount {
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub created_at: DateTime<Utc>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedOrderLine {
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedPayment {
+    pub provider: String,
+    pub method: PaymentMethod,
+    pub amount: Price,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedRefund {
+    pub method: RefundMethod,
+    pub status: RefundStatus,
+    pub amount: Price,
+    /// The part of `amount` that pays back delivery.
+    pub shipping: Price,
+    /// Products the refund pays back.
+    pub product_ids: Vec<u32>,
+    /// The account the money went to, for refunds by bank transfer.
+    pub iban: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedOrder {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub status: OrderStatus,
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub address: Address,
+    pub delivery: DeliveryMethod,
+    pub locker_code: Option<String>,
+    pub payment: PaymentMethod,
+    pub invoice: Option<InvoiceDetails>,
+    pub shipping: Price,
+    pub total: Price,
+    /// Part of `total` paid with store credit.
+    pub store_credit: Price,
+    pub refunded: Price,
+    pub terms_accepted_at: Option<DateTime<Utc>>,
+    pub lines: Vec<ExportedOrderLine>,
+    pub history: Vec<OrderEvent>,
+    pub payments: Vec<ExportedPayment>,
+    pub returns: Vec<ReturnRequest>,
+    pub complaints: Vec<Complaint>,
+    pub refunds: Vec<ExportedRefund>,
+}
+
+/// A message sent through the contact form.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    pub name: String,
+    pub subject: String,
+    pub message: String,
+    /// Network address it was sent from.
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An e-mail the shop sent (or is about to send) to the customer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedEmail {
+    pub subject: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+/// Everything we hold about a customer, as handed to them on request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DataExport {
+    pub generated_at: DateTime<Utc>,
+    pub account: ExportedAccount,
+    pub addresses: Vec<SavedAddress>,
+    /// Including orders placed as a guest with the same e-mail address, oldest first.
+    pub orders: Vec<ExportedOrder>,
+    pub messages: Vec<ExportedMessage>,
+    /// Our replies to the customer's messages.
+    pub replies: Vec<ThreadEntry>,
+    pub emails: Vec<ExportedEmail>,
+    /// Store credit added by refunds and spent on orders, oldest first.
+    pub store_credit: Vec<StoreCreditEntry>,
+}
+
+const EXPORT_FILE_NAME: &str = "megjoni-moje-dane.json";
+
+/// SHA-256 of the lowercased address, which identifies the customer in the audit trail
+/// without keeping their e-mail.
+#[cfg(feature = "ssr")]
+pub(crate) fn email_hash(email: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    hex::encode(Sha256::digest(email.to_lowercase().as_bytes()))
+}
+
+/// The signed-in customer's data. Downloaded by the browser as a file through a plain
+/// link, hence `GET`.
+#[server(input = GetUrl)]
+pub async fn export_account_data() -> Result<DataExport, ServerFnError<AccountError>> {
+    use crate::db::{
+        AddressRepository, ComplaintRepository, MessageRepository, OrderRepository,
+        OutboxRepository, PaymentRepository, PrivacyRepository, RefundRepository, ReturnRepository,
+        SupportRepository, use_db,
+    };
+    use crate::inbox::EntryAuthor;
+    use axum::http::{HeaderValue, header};
+
+    let db = use_db().map_err(unavailable)?;
+    let customer = match signed_in(&db) {
+        Ok(customer) => customer,
+        Err(error) => {
+            leptos_axum::redirect("/account");
+            return Err(error);
+        }
+    };
+    let mut orders = db.orders_for_email(&customer.email).map_err(unavailable)?;
+    for order in db.orders_for_customer(customer.id).map_err(unavailable)? {
+        if !orders.iter().any(|known| known.id == order.id) {
+            orders.push(order);
+        }
+    }
+    orders.sort_by_key(|order| (order.created_at, order.id));
+    let orders = orders
+        .into_iter()
+        .map(|order| {
+            let history = db.order_events(order.id)?;
+            let payments = db
+                .payments_for_order(order.id)?
+                .into_iter()
+                .map(|payment| ExportedPayment {
+                    provider: payment.provider,
+                    method: payment.method,
+                    amount: payment.amount,
+                    status: crate::db::enum_to_sql(&payment.status),
+                    created_at: payment.created_at,
+                })
+                .collect();
+            let refunds = db
+                .refunds_for_order(order.id)?
+                .into_iter()
+                .map(|refund| ExportedRefund {
+                    method: refund.method,
+                    status: refund.status,
+                    amount: refund.amount,
+                    shipping: refund.shipping,
+                    product_ids: refund.lines.iter().map(|line| line.product_id).collect(),
+                    iban: refund.iban,
+                    created_at: refund.created_at,
+                    completed_at: refund.completed_at,
+                })
+                .collect();
+            Ok(ExportedOrder {
+                id: order.id,
+                created_at: order.created_at,
+                status: order.status,
+                email: order.email,
+                name: order.name,
+                phone: order.phone,
+                address: order.address,
+                delivery: order.delivery,
+                locker_code: order.locker_code,
+                payment: order.payment,
+                invoice: order.invoice,
+                shipping: order.shipping,
+                total: order.total,
+                store_credit: order.store_credit,
+                refunded: order.refunded,
+                terms_accepted_at: order.terms_accepted_at,
+                lines: order
+                    .lines
+                    .into_iter()
+                    .map(|line| ExportedOrderLine {
+                        product_id: line.product_id,
+                        title: line.title,
+                        price: line.price,
+                    })
+                    .collect(),
+                history,
+                payments,
+                returns: db.returns_for_order(order.id)?,
+                complaints: db.complaints_for_order(order.id)?,
+                refunds,
+            })
+        })
+        .collect::<crate::db::Result<_>>()
+        .map_err(unavailable)?;
+    let messages = db
+        .messages_from_email(&customer.email)
+        .map_err(unavailable)?
+        .into_iter()
+        .map(|message| ExportedMessage {
+            name: message.name,
+            subject: message.subject,
+            message: message.message,
+            ip: message.ip,
+            created_at: message.created_at,
+        })
+        .collect();
+    let replies = db
+        .thread_for_email(&customer.email)
+        .map_err(unavailable)?
+        .map(|thread| thread.entries)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| entry.author == EntryAuthor::Shop)
+        .collect();
+    let emails = db
+        .emails_to(&customer.email)
+        .map_err(unavailable)?
+        .into_iter()
+        .map(|email| ExportedEmail {
+            subject: email.subject,
+            text: email.text,
+            created_at: email.created_at,
+            sent_at: email.sent_at,
+        })
+        .collect();
+    let now = Utc::now();
+    let export = DataExport {
+        generated_at: now,
+        addresses: db.list_addresses(customer.id).map_err(unavailable)?,
+        orders,
+        messages,
+        replies,
+        emails,
+        store_credit: db.store_credit_history(customer.id).map_err(unavailable)?,
+        account: ExportedAccount {
+            email: customer.email.clone(),
+            name: customer.name,
+            phone: customer.phone,
+            created_at: customer.created_at,
+            email_verified_at: customer.email_verified_at,
+            last_login_at: customer.last_login_at,
+        },
+    };
+    db.record_export(customer.id, &email_hash(&customer.email), now)
+        .map_err(unavailable)?;
+    leptos::logging::log!("wydano eksport danych klienta {}", customer.id);
+
+    let response = expect_context::<leptos_axum::ResponseOptions>();
+    let disposition = format!("attachment; filename=\"{EXPORT_FILE_NAME}\"");
+    response.insert_header(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).map_err(unavailable)?,
+    );
+    Ok(export)
+}
+
+/// Deletes the account after checking the password again. Refused while an order is
+/// still on its way, since we need the address to deliver it.
+#[server]
+pub async fn erase_account(
+    password: String,
+    #[server(default)] confirm: Option<String>,
+) -> Result<(), ServerFnError<AccountError>> {
+    use crate::contact::is_form_post;
+    use crate::db::{CustomerRepository, Erasure, PrivacyRepository, PrivacyRequestKind, use_db};
+
+    let db = use_db().map_err(unavailable)?;
+    let customer = signed_in(&db)?;
+    let mut errors = Vec::new();
+    if confirm.is_none() {
+        errors.push(FieldError::new(
+            "confirm",
+            "Potwierdź, że chcesz usunąć konto.",
+        ));
+    }
+    let hash = db.password_hash(customer.id).map_err(unavailable)?;
+    if !hash.is_some_and(|hash| auth::verify_password(&password, &hash)) {
+        errors.push(FieldError::new(
+            "erase_password",
+            "Hasło jest nieprawidłowe.",
+        ));
+    }
+    invalid(errors)?;
+
+    let erasure = db
+        .erase_customer(
+            customer.id,
+            &email_hash(&customer.email),
+            PrivacyRequestKind::Erasure,
+            Utc::now(),
+        )
+        .map_err(unavailable)?;
+    match erasure {
+        Erasure::Erased(summary) => {
+            leptos::logging::log!("usunięto konto klienta {}: {summary:?}", customer.id);
+        }
+        Erasure::OrdersInProgress(_) => return Err(fail(AccountError::OrdersInProgress)),
+        Erasure::StoreCreditLeft(_) => return Err(fail(AccountError::StoreCreditLeft)),
+    }
+    auth::end_session(&db).map_err(unavailable)?;
+    if is_form_post() {
+        leptos_axum::redirect(&format!("/account?{ERASED_PARAM}=1"));
+    }
+    Ok(())
+}
+
+/// Query parameter set after the account was deleted without JavaScript.
+pub(super) const ERASED_PARAM: &str = "usunieto";
+
+#[component]
+pub(super) fn PrivacySection(erase: ServerAction<EraseAccount>) -> impl IntoView {
+    let error = move || failure(erase);
+    let export_url = <ExportAccountData as ServerFn>::PATH;
+
+    view! {
+        <p>
+            "Możesz pobrać kopię wszystkich danych, które o Tobie przechowujemy, w formacie "
+            "JSON: konto, adresy, zamówienia, wiadomości i wysłane do Ciebie e-maile."
+        </p>
+        <p>
+            <a href=export_url download=EXPORT_FILE_NAME rel="external">
+                "Pobierz moje dane"
+            </a>
+        </p>
+        <details class="account-erase">
+            <summary>"Usuń konto"</summary>
+            <p>
+                "Usuniemy Twoje konto, adresy i wiadomości. Zamówienia musimy przechowywać "
+                "ze względów księgowych, ale usuniemy z nich Twoje imię, adres, telefon i "
+                "e-mail. Tej operacji nie da się cofnąć."
+            </p>
+            <ActionForm action=erase attr:class="account-form">
+                <label for="erase-password">"Hasło"</label>
+                <input
+                    type="password"
+                    id="erase-password"
+                    name="password"
+                    autocomplete="current-password"
+                    required
+                />
+                {move || field_message(error(), "erase_password")}
+                <label class="account-checkbox">
+                    <input type="checkbox" name="confirm" value="tak" />
+                    "Rozumiem, że konta nie da się przywrócić."
+                </label>
+                {move || field_message(error(), "confirm")}
+                {move || error_summary(error())}
+                <button type="submit" disabled=move || erase.pending().get()>
+                    "Usuń konto na zawsze"
+                </button>
+            </ActionForm>
+        </details>
+    }
+}
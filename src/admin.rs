@@ -0,0 +1,235 @@
+//! The staff panel at `/admin`. [`AdminLayout`] asks for a staff sign-in and shows the
+//! sections the signed-in role may use; the pages themselves live next to what they
+//! manage (e.g. [`crate::catalog::admin`], [`crate::orders::admin`]). Hiding a link is only
+//! a convenience: every server function behind a page checks the permission itself.
+
+use crate::staff::{Permission, StaffLogIn, StaffLogOut, StaffMember, get_current_staff};
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::components::Outlet;
+use serde::{Deserialize, Serialize};
+
+/// Sections of the panel: address, name in the menu and who may open them.
+const SECTIONS: [(&str, &str, Permission); 9] = [
+    ("/admin/orders", "Zamówienia", Permission::HandleOrders),
+    ("/admin/returns", "Zwroty", Permission::HandleOrders),
+    ("/admin/intake", "Przygotowanie", Permission::EditProducts),
+    ("/admin/products", "Produkty", Permission::EditProducts),
+    ("/admin/inbox", "Wiadomości", Permission::AnswerCustomers),
+    (
+        "/admin/complaints",
+        "Reklamacje",
+        Permission::AnswerCustomers,
+    ),
+    (
+        "/admin/refunds",
+        "Zwroty pieniędzy",
+        Permission::IssueRefunds,
+    ),
+    ("/admin/settings", "Ustawienia", Permission::EditSettings),
+    ("/admin/staff", "Pracownicy", Permission::ManageStaff),
+];
+
+/// Something waiting for staff, shown on the panel's start page.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminTask {
+    pub label: String,
+    pub count: usize,
+    pub href: String,
+}
+
+/// What waits for the signed-in staff member, limited to what their role may handle.
+#[server]
+pub async fn get_admin_tasks() -> Result<Vec<AdminTask>, ServerFnError> {
+    use crate::complaints::ComplaintStatus;
+    use crate::db::{
+        ComplaintRepository, OrderRepository, ProductRepository, RefundRepository,
+        ReturnRepository, SupportRepository, use_db,
+    };
+    use crate::inbox::ThreadStatus;
+    use crate::orders::OrderStatus;
+    use crate::refunds::RefundStatus;
+    use crate::returns::ReturnStatus;
+
+    let db = use_db()?;
+    let Some(staff) = crate::staff::auth::current_staff(&db)? else {
+        return Err(ServerFnError::new("zaloguj się do panelu"));
+    };
+    let task = |label: &str, count: usize, href: &str| AdminTask {
+        label: label.to_string(),
+        count,
+        href: href.to_string(),
+    };
+    let mut tasks = Vec::new();
+    if staff.can(Permission::HandleOrders) {
+        tasks.push(task(
+            "Opłacone zamówienia do spakowania",
+            db.list_orders(Some(OrderStatus::Paid))?.len(),
+            "/admin/orders?status=paid",
+        ));
+        tasks.push(task(
+            "Zwroty w drodze do nas",
+            db.list_returns(Some(ReturnStatus::Requested))?.len(),
+            "/admin/returns?status=requested",
+        ));
+    }
+    if staff.can(Permission::EditProducts) {
+        let preparing = db
+            .count_by_stage()?
+            .into_iter()
+            .filter(|(stage, _)| stage.in_preparation())
+            .map(|(_, count)| count)
+            .sum();
+        tasks.push(task("Rzeczy w przygotowaniu", preparing, "/admin/intake"));
+    }
+    if staff.can(Permission::AnswerCustomers) {
+        tasks.push(task(
+            "Nowe wiadomości",
+            db.list_threads(Some(ThreadStatus::New))?.len(),
+            "/admin/inbox?status=new",
+        ));
+        tasks.push(task(
+            "Nowe reklamacje",
+            db.list_complaints(Some(ComplaintStatus::Submitted))?.len(),
+            "/admin/complaints?filter=submitted",
+        ));
+    }
+    if staff.can(Permission::IssueRefunds) {
+        tasks.push(task(
+            "Przelewy zwrotów do wysłania",
+            db.list_refunds(Some(RefundStatus::Pending))?.len(),
+            "/admin/refunds?status=pending",
+        ));
+    }
+    Ok(tasks)
+}
+
+#[component]
+pub fn AdminLayout() -> impl IntoView {
+    let log_in = ServerAction::<StaffLogIn>::new();
+    let log_out = ServerAction::<StaffLogOut>::new();
+    let staff = Resource::new(
+        move || (log_in.version().get(), log_out.version().get()),
+        |_| get_current_staff(),
+    );
+
+    view! {
+        <Title text="Panel sklepu - Meg Joni" />
+        <Suspense fallback=|| view! { <p>"Ładowanie panelu..."</p> }>
+            {move || Suspend::new(async move {
+                match staff.await {
+                    Ok(Some(staff)) => view! {
+                        <AdminNav staff log_out />
+                        <Outlet />
+                    }
+                    .into_any(),
+                    Ok(None) => view! { <StaffLoginForm action=log_in /> }.into_any(),
+                    Err(_) => view! { <p>"Panel jest chwilowo niedostępny."</p> }.into_any(),
+                }
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn AdminNav(staff: StaffMember, log_out: ServerAction<StaffLogOut>) -> impl IntoView {
+    let links = SECTIONS
+        .into_iter()
+        .filter(|&(_, _, permission)| staff.can(permission))
+        .map(|(href, label, _)| view! { <a href=href>{label}</a> })
+        .collect_view();
+
+    view! {
+        <nav class="admin-nav">
+            <a href="/admin">"Start"</a>
+            {links}
+            <span class="admin-nav-user">
+                {format!("{} ({})", staff.name, staff.role.label())}
+                <ActionForm action=log_out>
+                    <button type="submit">"Wyloguj się"</button>
+                </ActionForm>
+            </span>
+        </nav>
+    }
+}
+
+#[component]
+fn StaffLoginForm(action: ServerAction<StaffLogIn>) -> impl IntoView {
+    let error = move || match action.value().get() {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się zalogować, spróbuj ponownie.".to_string()),
+        _ => None,
+    };
+
+    view! {
+        <main>
+            <section class="account-section">
+                <h2>"Panel sklepu"</h2>
+                <ActionForm action attr:class="account-form">
+                    <label for="staff-login-email">"E-mail"</label>
+                    <input
+                        type="email"
+                        id="staff-login-email"
+                        name="email"
+                        autocomplete="username"
+                        required
+                    />
+                    <label for="staff-login-password">"Hasło"</label>
+                    <input
+                        type="password"
+                        id="staff-login-password"
+                        name="password"
+                        autocomplete="current-password"
+                        required
+                    />
+                    {move || error().map(|e| view! { <p class="account-error" role="alert">{e}</p> })}
+                    <button type="submit" disabled=move || action.pending().get()>
+                        "Zaloguj się"
+                    </button>
+                </ActionForm>
+                <p>"Hasło zmienia właściciel sklepu w zakładce „Pracownicy”."</p>
+            </section>
+        </main>
+    }
+}
+
+/// The panel's start page at `/admin`.
+#[component]
+pub fn AdminDashboard() -> impl IntoView {
+    let tasks = Resource::new(|| (), |_| get_admin_tasks());
+
+    view! {
+        <main>
+            <section class="inbox">
+                <h2>"Do zrobienia"</h2>
+                <Suspense fallback=|| view! { <p>"Ładowanie..."</p> }>
+                    {move || Suspend::new(async move {
+                        match tasks.await {
+                            Ok(tasks) if tasks.is_empty() => {
+                                view! { <p>"Nic nie czeka."</p> }.into_any()
+                            }
+                            Ok(tasks) => view! {
+                                <ul class="admin-tasks">
+                                    {tasks
+                                        .into_iter()
+                                        .map(|task| {
+                                            view! {
+                                                <li class:admin-task-waiting=(task.count > 0)>
+                                                    <a href=task.href>{task.label}</a>
+                                                    ": "
+                                                    <strong>{task.count}</strong>
+                                                </li>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </ul>
+                            }
+                            .into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do panelu."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
@@ -1,8 +1,35 @@
+use crate::account::{
+    AccountPage, ForgotPasswordPage, NewPasswordPage, RegisterPage, VerifyEmailPage,
+};
+use crate::admin::{AdminDashboard, AdminLayout};
+use crate::cart::{Cart, CartPage, use_cart};
+use crate::catalog::admin::{ProductEditPage, ProductsAdminPage};
+use crate::catalog::intake::IntakePage;
+use crate::catalog::{Category, ProductGrid, ProductQuery};
+use crate::checkout::CheckoutPage;
+use crate::complaints::admin::ComplaintsAdminPage;
+use crate::complaints::{ComplaintPage, ComplaintsPage, RESPONSE_DAYS};
+use crate::contact::ContactPage;
+use crate::filters::FilteredProductGrid;
+use crate::inbox::{InboxPage, ThreadPage};
+use crate::orders::RETURN_WINDOW_DAYS;
+use crate::orders::admin::OrdersAdminPage;
+use crate::packing_slip::PackingSlipPage;
+use crate::payments::PaymentReturnPage;
+use crate::product_page::ProductPage;
+use crate::refunds::REFUND_DAYS;
+use crate::refunds::admin::RefundsAdminPage;
+use crate::returns::admin::ReturnsAdminPage;
+use crate::returns::{ReturnAddress, ReturnPage, ReturnsPage};
+use crate::search::SearchPage;
+use crate::settings::{Announcement, SettingsAdminPage};
+use crate::shipping::{DeliveryMethodList, ShippingPriceList};
+use crate::staff::admin::StaffAdminPage;
 use leptos::prelude::*;
 use leptos_meta::{MetaTags, Stylesheet, Title, provide_meta_context};
 use leptos_router::{
-    StaticSegment,
-    components::{Route, Router, Routes},
+    ParamSegment, SsrMode, StaticSegment,
+    components::{ParentRoute, Route, Router, Routes},
 };
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
@@ -44,12 +71,14 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 #[component]
 pub fn App() -> impl IntoView {
     provide_meta_context();
+    Cart::provide();
 
     view! {
         <Stylesheet id="leptos" href="/style.css"/>
         <Title text="Meg Joni - Odzież Używana Online | Najlepsze Second Hand Odkrycia"/>
 
         <Router>
+            <Announcement />
             <Header />
             <Navbar />
 
@@ -60,11 +89,47 @@ pub fn App() -> impl IntoView {
                     <Route path=StaticSegment("man") view=MenPage/>
                     <Route path=StaticSegment("new-arrivals") view=NewsPage/>
                     <Route path=StaticSegment("sale") view=SalePage/>
+                    // Async, żeby nieistniejący produkt zwrócił prawdziwy status 404
+                    <Route path=(StaticSegment("product"), ParamSegment("slug")) view=ProductPage ssr=SsrMode::Async/>
+                    <Route path=StaticSegment("cart") view=CartPage/>
+                    <Route path=StaticSegment("checkout") view=CheckoutPage/>
+                    <Route path=StaticSegment("search") view=SearchPage/>
+                    <Route
+                        path=(StaticSegment("payments"), StaticSegment("return"), ParamSegment("provider"))
+                        view=PaymentReturnPage
+                    />
+                    <ParentRoute path=StaticSegment("admin") view=AdminLayout ssr=SsrMode::Async>
+                        <Route path=StaticSegment("") view=AdminDashboard/>
+                        <Route path=StaticSegment("orders") view=OrdersAdminPage/>
+                        <Route
+                            path=(StaticSegment("orders"), ParamSegment("id"), StaticSegment("packing-slip"))
+                            view=PackingSlipPage
+                        />
+                        <Route path=StaticSegment("intake") view=IntakePage/>
+                        <Route path=StaticSegment("products") view=ProductsAdminPage/>
+                        <Route path=(StaticSegment("products"), ParamSegment("id")) view=ProductEditPage/>
+                        <Route path=StaticSegment("inbox") view=InboxPage/>
+                        <Route path=(StaticSegment("inbox"), ParamSegment("id")) view=ThreadPage/>
+                        <Route path=StaticSegment("returns") view=ReturnsAdminPage/>
+                        <Route path=StaticSegment("complaints") view=ComplaintsAdminPage/>
+                        <Route path=StaticSegment("refunds") view=RefundsAdminPage/>
+                        <Route path=StaticSegment("settings") view=SettingsAdminPage/>
+                        <Route path=StaticSegment("staff") view=StaffAdminPage/>
+                    </ParentRoute>
+                    <Route path=StaticSegment("account") view=AccountPage ssr=SsrMode::Async/>
+                    <Route path=(StaticSegment("account"), StaticSegment("register")) view=RegisterPage/>
+                    <Route path=(StaticSegment("account"), StaticSegment("verify")) view=VerifyEmailPage/>
+                    <Route path=(StaticSegment("account"), StaticSegment("forgot-password")) view=ForgotPasswordPage/>
+                    <Route path=(StaticSegment("account"), StaticSegment("new-password")) view=NewPasswordPage/>
                     <Route path=StaticSegment("about") view=AboutPage/>
                     <Route path=StaticSegment("contact") view=ContactPage/>
+                    <Route path=StaticSegment("returns") view=ReturnsPage/>
+                    <Route path=(StaticSegment("returns"), ParamSegment("token")) view=ReturnPage ssr=SsrMode::Async/>
+                    <Route path=StaticSegment("complaints") view=ComplaintsPage/>
+                    <Route path=(StaticSegment("complaints"), ParamSegment("token")) view=ComplaintPage ssr=SsrMode::Async/>
 
                     <Route path=StaticSegment("privacy") view=PrivacyPage/>
-                    <Route path=StaticSegment("shipping") view=ShippingReturnsPage/>
+                    <Route path=StaticSegment("shipping") view=ShippingReturnsPage ssr=SsrMode::Async/>
                     <Route path=StaticSegment("terms") view=TermsAndConditionsPage/>
                 </Routes>
             </main>
@@ -76,6 +141,8 @@ pub fn App() -> impl IntoView {
 
 #[component]
 fn Header() -> impl IntoView {
+    let cart = use_cart();
+
     view! {
       <header>
         <div class="logo-title">
@@ -109,7 +176,7 @@ fn Header() -> impl IntoView {
           </a>
           <a href="/cart" aria-label="Mój koszyk">
             <img src="/shopping-cart.svg" width="32" height="32" />
-            <span class="cart-count">"0"</span>
+            <span class="cart-count">{move || cart.count()}</span>
           </a>
         </div>
     </header>
@@ -152,17 +219,11 @@ fn HomePage() -> impl IntoView {
 
         <section class="featured-products">
             <h2>Polecane produkty</h2>
-             <div class="product-grid">
-                 <article class="product-item">
-                     <a href="product/id-produktu">
-                         <figure>
-                             <img src=r"/spodnie-vintage.jpg" alt="Opis produktu. Np. Czerwona sukienka" width="300" height="400" />
-                         </figure>
-                         <h3>Spodnie Vintage</h3>
-                         <p class="product-price">49.99 PLN</p>
-                     </a>
-                 </article>
-             </div>
+             <ProductGrid query=ProductQuery {
+                 featured: true,
+                 limit: Some(4),
+                 ..Default::default()
+             } />
              <div class="view-all-link">
                  <a href="/woman">Zobacz wszystkie produkty</a>
              </div>
@@ -189,6 +250,8 @@ fn Footer() -> impl IntoView {
                 <ul>
                     <li><a href="/contact">Kontakt</a></li>
                     <li><a href="/shipping">Wysyłka i zwroty</a></li>
+                    <li><a href="/returns">Zwroty</a></li>
+                    <li><a href="/complaints">Reklamacje</a></li>
                     <li><a href="/privacy">Polityka Prywatności</a></li>
                     <li><a href="/terms">Regulamin sklepu</a></li>
                 </ul>
@@ -249,46 +312,6 @@ pub fn AboutPage() -> impl IntoView {
     }
 }
 
-#[component]
-pub fn ContactPage() -> impl IntoView {
-    view! {
-        <main>
-            <section class="contact-section" style="max-width: 600px; margin: var(--space-md) auto; background-color: var(--color-surface); padding: var(--space-md); border-radius: 8px; box-shadow: 0 2px 8px rgba(0, 0, 0, 0.08);"> // Proste style inline dla przykładu, możesz przenieść do CSS
-                <h2>Skontaktuj się z Nami</h2>
-                <p>
-                    Masz pytania dotyczące produktów, zamówień, czy współpracy? Chętnie pomożemy!
-                    Skontaktuj się z nami poprzez formularz poniżej lub bezpośrednio.
-                </p>
-
-                <div class="contact-info" style="margin-bottom: var(--space-md);">
-                    <p><strong>Email: </strong> <a href="mailto:kontakt@megjoni.pl">kontakt@megjoni.pl</a></p>
-                    // Dodaj opcjonalnie telefon, adres itp.
-                </div>
-
-                <form action="/submit-contact-form" method="post"> // To jest tylko placeholder, rzeczywista obsługa wymaga backendu
-                    <div style="margin-bottom: var(--space-sm);">
-                        <label for="name" class="visually-hidden">Twoje imię:</label>
-                        <input type="text" id="name" name="name" placeholder="Twoje imię" required style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;" />
-                    </div>
-                    <div style="margin-bottom: var(--space-sm);">
-                        <label for="email" class="visually-hidden">Twój email:</label>
-                        <input type="email" id="email" name="email" placeholder="Twój email" required style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;" />
-                    </div>
-                     <div style="margin-bottom: var(--space-sm);">
-                        <label for="subject" class="visually-hidden">Temat:</label>
-                        <input type="text" id="subject" name="subject" placeholder="Temat wiadomości" style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;" />
-                    </div>
-                    <div style="margin-bottom: var(--space-sm);">
-                        <label for="message" class="visually-hidden">Twoja wiadomość:</label>
-                        <textarea id="message" name="message" placeholder="Twoja wiadomość" rows="6" required style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;"></textarea>
-                    </div>
-                    <button type="submit">Wyślij wiadomość</button>
-                </form>
-            </section>
-        </main>
-    }
-}
-
 #[component]
 pub fn WomanPage() -> impl IntoView {
     view! {
@@ -297,30 +320,7 @@ pub fn WomanPage() -> impl IntoView {
                 <h2>Kategoria: Damska</h2>
                 <p>"Odkryj naszą kolekcję odzieży damskiej z drugiej ręki. Eleganckie sukienki, wygodne spodnie, stylowe bluzki i wiele więcej!"</p>
 
-                // Placeholder dla siatki produktów damskich
-                <div class="product-grid">
-                     // Skopiuj i wklej przykładowe product-itemy z HomePage lub stwórz nowe
-                     // W rzeczywistości tutaj będzie pętla iterująca po danych produktów
-                    <article class="product-item">
-                        <a href="/product/damskie-001">
-                            <figure>
-                                <img src="/czerwona-sukienka.jpg" alt="Przykładowy produkt damski" width="300" height="400" />
-                            </figure>
-                            <h3>Czerwona sukienka</h3>
-                            <p class="product-price">75.00 PLN</p>
-                        </a>
-                    </article>
-                     <article class="product-item">
-                        <a href="/product/damskie-002">
-                            <figure>
-                                <img src="/elegancka-sukienka.jpg" alt="Przykładowy produkt damski" width="300" height="400" />
-                            </figure>
-                            <h3>Elegancka sukienka</h3>
-                            <p class="product-price">55.00 PLN</p>
-                        </a>
-                    </article>
-                    // Dodaj więcej placeholderów produktów damskich
-                </div>
+                <FilteredProductGrid query=ProductQuery::category(Category::Woman) />
             </section>
         </main>
     }
@@ -334,30 +334,7 @@ pub fn MenPage() -> impl IntoView {
                 <h2>Kategoria: Męska</h2>
                 <p>"Przeglądaj naszą ofertę męskiej odzieży używanej. Znajdź koszule, spodnie, marynarki i inne elementy garderoby w świetnych cenach."</p>
 
-                 // Placeholder dla siatki produktów męskich
-                <div class="product-grid">
-                     // Skopiuj i wklej przykładowe product-itemy z HomePage lub stwórz nowe
-                     // W rzeczywistości tutaj będzie pętla iterująca po danych produktów
-                    <article class="product-item">
-                        <a href="/product/meskie-001">
-                            <figure>
-                                <img src="/black-tshirt.jpg" alt="Czarny T-Shirt Męski" width="300" height="400" />
-                            </figure>
-                            <h3>Czarny T-Shirt Męski</h3>
-                            <p class="product-price">39.50 PLN</p>
-                        </a>
-                    </article>
-                     <article class="product-item">
-                        <a href="/product/meskie-002">
-                            <figure>
-                                <img src="/niebieska-bluza.jpg" alt="Przykładowy produkt męski" width="300" height="400" />
-                            </figure>
-                            <h3>Niebieska Bluza</h3>
-                            <p class="product-price">85.00 PLN</p>
-                        </a>
-                    </article>
-                    // Dodaj więcej placeholderów produktów męskich
-                </div>
+                <FilteredProductGrid query=ProductQuery::category(Category::Man) />
             </section>
         </main>
     }
@@ -371,21 +348,10 @@ pub fn NewsPage() -> impl IntoView {
                 <h2>Nowości u Meg Joni</h2>
                 <p>"Zobacz nasze najnowsze dostawy! Świeże i unikalne ubrania dodane do sklepu."</p>
 
-                 // Placeholder dla siatki najnowszych produktów
-                <div class="product-grid">
-                     // Skopiuj i wklej przykładowe product-itemy z HomePage lub stwórz nowe
-                     // Upewnij się, że używasz zdjęć pasujących do "nowości"
-                    <article class="product-item">
-                        <a href="/product/nowosc-001">
-                            <figure>
-                                <img src="/bluza-oversize.jpg" alt="Najnowszy produkt" width="300" height="400" />
-                            </figure>
-                            <h3>Bluza Oversize</h3>
-                            <p class="product-price">65.00 PLN</p>
-                        </a>
-                    </article>
-                    // Dodaj więcej placeholderów nowości
-                </div>
+                <FilteredProductGrid query=ProductQuery {
+                    newest_first: true,
+                    ..Default::default()
+                } />
             </section>
         </main>
     }
@@ -399,21 +365,10 @@ pub fn SalePage() -> impl IntoView {
                 <h2>Wyprzedaż</h2>
                 <p>"Super okazje czekają! Ostatnie sztuki w niższych cenach."</p>
 
-                // Placeholder dla siatki produktów na wyprzedaży
-                <div class="product-grid">
-                     // Skopiuj i wklej przykładowe product-itemy z HomePage lub stwórz nowe
-                     // Możesz dodać stylizację do pokazania starej/nowej ceny w przyszłości
-                    <article class="product-item">
-                        <a href="/product/sale-001">
-                            <figure>
-                                <img src="letnia-sukienka.jpg" alt="Produkt na wyprzedaży" width="300" height="400" />
-                            </figure>
-                            <h3>Letnia Sukienka</h3>
-                            <p class="product-price"><del style="color: var(--color-text-light);">50.00 PLN</del> 30.00 PLN</p> // Przykład ceny wyprzedażowej
-                        </a>
-                    </article>
-                    // Dodaj więcej placeholderów produktów na wyprzedaży
-                </div>
+                <FilteredProductGrid query=ProductQuery {
+                    on_sale: true,
+                    ..Default::default()
+                } />
             </section>
         </main>
     }
@@ -479,6 +434,11 @@ pub fn PrivacyPage() -> impl IntoView {
                     <li>"cofnięcie zgody"</li>
                     <li>"skarga do Prezesa UODO"</li>
                 </ul>
+                <p>
+                    "Kopię swoich danych pobierzesz, a konto usuniesz samodzielnie w zakładce "
+                    <a href="/account">"Moje konto"</a>
+                    ". Zamówienia przechowujemy dla celów księgowych, ale bez Twoich danych osobowych."
+                </p>
             </section>
 
             <section class="mb-6">
@@ -503,43 +463,60 @@ pub fn ShippingReturnsPage() -> impl IntoView {
             <section class="mb-6">
                 <h2 class="text-xl font-semibold mb-2">"1. Koszt i czas wysyłki"</h2>
                 <ul class="list-disc list-inside">
-                    <li>"Koszt dostawy na terenie Polski: 14,99 zł"</li>
+                    <ShippingPriceList />
                     <li>"Czas realizacji zamówienia: 1–3 dni robocze"</li>
                     <li>"Czas dostawy: 1–2 dni robocze od momentu nadania"</li>
-                    <li>"Darmowa dostawa dla zamówień powyżej [np. 200 zł]"</li>
                 </ul>
             </section>
 
             <section class="mb-6">
                 <h2 class="text-xl font-semibold mb-2">"2. Formy dostawy"</h2>
                 <ul class="list-disc list-inside">
-                    <li>"Kurier (np. InPost, DPD, DHL)"</li>
-                    <li>"Paczkomaty InPost"</li>
+                    <DeliveryMethodList />
                 </ul>
             </section>
 
             <section class="mb-6">
                 <h2 class="text-xl font-semibold mb-2">"3. Zwroty i reklamacje"</h2>
-                <p>"Zgodnie z prawem konsumenta masz prawo do zwrotu towaru w ciągu 14 dni od jego otrzymania – bez podania przyczyny."</p>
+                <p>
+                    "Zgodnie z prawem konsumenta masz prawo do zwrotu towaru w ciągu "
+                    {RETURN_WINDOW_DAYS}
+                    " dni od jego otrzymania – bez podania przyczyny."
+                </p>
                 <ul class="list-disc list-inside mt-2">
                     <li>"Produkt nie może nosić śladów użytkowania i musi być odesłany w oryginalnym stanie"</li>
                     <li>"Zwrotu dokonujesz na własny koszt"</li>
-                    <li>"Zwrot środków nastąpi do 14 dni od otrzymania przesyłki"</li>
+                    <li>
+                        "Zwrot środków nastąpi do " {REFUND_DAYS}
+                        " dni od otrzymania przesyłki – tą samą drogą, którą zapłacono, przelewem na wskazany przez Ciebie numer konta albo, jeśli wolisz, jako środki na Twoim koncie w sklepie"
+                    </li>
                 </ul>
             </section>
 
             <section class="mb-6">
                 <h2 class="text-xl font-semibold mb-2">"4. Jak dokonać zwrotu?"</h2>
                 <ol class="list-decimal list-inside">
-                    <li>"Wypełnij formularz zwrotu (dostępny w zakładce Zwroty lub dołączony do przesyłki)"</li>
-                    <li>"Zapakuj produkt i odeślij na adres:"<br/>"[Adres do zwrotu]"</li>
+                    <li>
+                        "Wypełnij formularz zwrotu (dostępny w zakładce "
+                        <a href="/returns">"Zwroty"</a>
+                        " lub dołączony do przesyłki)"
+                    </li>
+                    <li>"Zapakuj produkt i odeślij na adres:"<ReturnAddress /></li>
                     <li>"Po otrzymaniu i sprawdzeniu przesyłki dokonamy zwrotu pieniędzy"</li>
                 </ol>
             </section>
 
             <section class="mb-6">
                 <h2 class="text-xl font-semibold mb-2">"5. Reklamacje"</h2>
-                <p>"Jeśli produkt jest uszkodzony lub niezgodny z opisem, skontaktuj się z nami pod adresem e-mail: kontakt@megjoni.pl. Do reklamacji dołącz zdjęcia oraz numer zamówienia."</p>
+                <p>
+                    "Jeśli produkt jest uszkodzony lub niezgodny z opisem, wypełnij "
+                    <a href="/complaints">"formularz reklamacji"</a>
+                    ". Wybierz rzecz z zamówienia, opisz wadę i dołącz jej zdjęcia."
+                </p>
+                <p>
+                    "Reklamacja otrzyma numer, a jej status sprawdzisz w linku z e-maila lub na swoim koncie. "
+                    {format!("Odpowiemy w ciągu {RESPONSE_DAYS} dni od zgłoszenia.")}
+                </p>
             </section>
 
             <section class="mb-6">
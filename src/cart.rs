@@ -0,0 +1,528 @@
+use crate::catalog::{Price, Product};
+use crate::inventory::{
+    Availability, ReserveOutcome, check_availability, release_cart, release_item, reserve_item,
+};
+use crate::shipping::{ShippingRates, get_shipping_rates};
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
+
+/// Snapshot of a product taken when it was added, so the cart renders without a server call.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CartItem {
+    pub product_id: u32,
+    pub slug: String,
+    pub title: String,
+    pub price: Price,
+    pub image: String,
+    pub size: String,
+    #[serde(default)]
+    pub weight_grams: Option<u32>,
+    /// End of the server-side hold on this item, as last reported by the server.
+    #[serde(default)]
+    pub reserved_until: Option<DateTime<Utc>>,
+}
+
+impl From<&Product> for CartItem {
+    fn from(product: &Product) -> Self {
+        CartItem {
+            product_id: product.id,
+            slug: product.slug.clone(),
+            title: product.title.clone(),
+            price: product.price,
+            image: product.cover_image().to_string(),
+            size: product.size.clone(),
+            weight_grams: product.weight_grams,
+            reserved_until: None,
+        }
+    }
+}
+
+/// Every item is one of a kind, so a cart holds each product at most once.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CartContents {
+    /// Identifies this cart's holds on the server; assigned with the first reservation.
+    #[serde(default)]
+    pub cart_id: Option<String>,
+    pub items: Vec<CartItem>,
+}
+
+impl CartContents {
+    pub fn contains(&self, product_id: u32) -> bool {
+        self.items.iter().any(|item| item.product_id == product_id)
+    }
+
+    /// Adds the item, or refreshes its hold if it is already in the cart.
+    pub fn add(&mut self, item: CartItem) {
+        match self
+            .items
+            .iter_mut()
+            .find(|existing| existing.product_id == item.product_id)
+        {
+            Some(existing) => existing.reserved_until = item.reserved_until,
+            None => self.items.push(item),
+        }
+    }
+
+    pub fn remove(&mut self, product_id: u32) {
+        self.items.retain(|item| item.product_id != product_id);
+    }
+
+    pub fn subtotal(&self) -> Price {
+        Price(self.items.iter().map(|item| item.price.grosze()).sum())
+    }
+}
+
+/// Reactive cart shared by the whole app; get it with [`use_cart`].
+#[derive(Clone, Copy)]
+pub struct Cart {
+    contents: RwSignal<CartContents>,
+    /// Message for the last add that failed, e.g. because someone else holds the item.
+    error: RwSignal<Option<String>>,
+    /// Whether a customer is signed in, so changes are also saved on the server.
+    signed_in: RwSignal<bool>,
+}
+
+impl Cart {
+    /// Creates the cart, restores it from browser storage, merges in the signed-in
+    /// customer's saved cart and provides it as context.
+    pub fn provide() -> Self {
+        let cart = Cart {
+            contents: RwSignal::new(CartContents::default()),
+            error: RwSignal::new(None),
+            signed_in: RwSignal::new(false),
+        };
+        let contents = cart.contents;
+        // Effects run only in the browser, after hydration, so the server-rendered
+        // badge ("0") always matches and the saved cart appears right after.
+        Effect::new(move |restored: Option<()>| {
+            if restored.is_none() {
+                if let Some(saved) = storage::load() {
+                    contents.set(saved);
+                }
+                cart.merge_saved();
+            } else {
+                contents.with_untracked(storage::save);
+                if cart.signed_in.get_untracked() {
+                    let saved = contents.get_untracked();
+                    spawn_local(async move {
+                        let _ = update_saved_cart(saved).await;
+                    });
+                }
+            }
+            contents.track();
+        });
+        provide_context(cart);
+        cart
+    }
+
+    /// Combines this browser's cart with the one saved for the signed-in customer; call
+    /// after signing in. Does nothing for guests.
+    pub fn merge_saved(&self) {
+        let cart = *self;
+        let local = cart.contents.get_untracked();
+        spawn_local(async move {
+            if let Ok(Some(merged)) = merge_saved_cart(local).await {
+                cart.signed_in.set(true);
+                cart.contents.set(merged);
+            }
+        });
+    }
+
+    /// Forgets the cart on this browser after signing out; it stays saved, holds
+    /// included, for the customer's next sign-in.
+    pub fn sign_out(&self) {
+        self.signed_in.set(false);
+        self.contents.set(CartContents::default());
+    }
+
+    pub fn count(&self) -> usize {
+        self.contents.with(|contents| contents.items.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.with(|contents| contents.items.is_empty())
+    }
+
+    pub fn items(&self) -> Vec<CartItem> {
+        self.contents.with(|contents| contents.items.clone())
+    }
+
+    pub fn product_ids(&self) -> Vec<u32> {
+        self.contents
+            .with(|contents| contents.items.iter().map(|item| item.product_id).collect())
+    }
+
+    pub fn contains(&self, product_id: u32) -> bool {
+        self.contents.with(|contents| contents.contains(product_id))
+    }
+
+    pub fn subtotal(&self) -> Price {
+        self.contents.with(CartContents::subtotal)
+    }
+
+    pub fn cart_id(&self) -> Option<String> {
+        self.contents.with(|contents| contents.cart_id.clone())
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.get()
+    }
+
+    /// Reserves the item on the server and adds it once the hold is granted.
+    pub fn add(&self, product: &Product) {
+        self.reserve(CartItem::from(product));
+    }
+
+    /// Renews an expired hold on an item that is already in the cart.
+    pub fn renew(&self, product_id: u32) {
+        let item = self.contents.with_untracked(|contents| {
+            contents
+                .items
+                .iter()
+                .find(|item| item.product_id == product_id)
+                .cloned()
+        });
+        if let Some(item) = item {
+            self.reserve(item);
+        }
+    }
+
+    fn reserve(&self, item: CartItem) {
+        let cart = *self;
+        cart.error.set(None);
+        spawn_local(async move {
+            let cart_id = cart.contents.with_untracked(|c| c.cart_id.clone());
+            match reserve_item(cart_id, item.product_id).await {
+                Ok(ReserveOutcome::Reserved {
+                    cart_id,
+                    expires_at,
+                }) => cart.contents.update(|contents| {
+                    contents.cart_id = Some(cart_id);
+                    contents.add(CartItem {
+                        reserved_until: Some(expires_at),
+                        ..item
+                    });
+                }),
+                Ok(ReserveOutcome::Unavailable) => cart.error.set(Some(
+                    "Ta sztuka została właśnie zarezerwowana lub kupiona przez innego klienta."
+                        .to_string(),
+                )),
+                Err(_) => cart.error.set(Some(
+                    "Nie udało się zarezerwować produktu. Spróbuj ponownie.".to_string(),
+                )),
+            }
+        });
+    }
+
+    /// Removes the item and gives up its hold so other shoppers can buy it.
+    pub fn remove(&self, product_id: u32) {
+        self.contents.update(|contents| contents.remove(product_id));
+        if let Some(cart_id) = self.contents.with_untracked(|c| c.cart_id.clone()) {
+            spawn_local(async move {
+                let _ = release_item(cart_id, product_id).await;
+            });
+        }
+    }
+
+    /// Empties the cart and gives up whatever holds it still has.
+    pub fn clear(&self) {
+        let cart_id = self.contents.with_untracked(|c| c.cart_id.clone());
+        self.contents.set(CartContents::default());
+        if let Some(cart_id) = cart_id {
+            spawn_local(async move {
+                let _ = release_cart(cart_id).await;
+            });
+        }
+    }
+}
+
+pub fn use_cart() -> Cart {
+    expect_context::<Cart>()
+}
+
+/// Merges `local` into the signed-in customer's saved cart and saves the result, which
+/// keeps the saved cart's id so its holds stay valid; the local cart's holds move to it.
+/// Returns `None` for guests.
+#[server]
+pub async fn merge_saved_cart(local: CartContents) -> Result<Option<CartContents>, ServerFnError> {
+    use crate::db::{CartRepository, ReservationRepository, use_db};
+
+    let db = use_db()?;
+    let Some(customer) = crate::account::auth::current_customer(&db)? else {
+        return Ok(None);
+    };
+    let mut merged = db.load_cart(customer.id)?.unwrap_or_default();
+    let local_id = local.cart_id.filter(|id| uuid::Uuid::parse_str(id).is_ok());
+    match (&merged.cart_id, local_id) {
+        (Some(saved_id), Some(local_id)) if *saved_id != local_id => {
+            db.move_holds(&local_id, saved_id)?;
+        }
+        (None, local_id) => merged.cart_id = local_id,
+        _ => {}
+    }
+    for item in local.items {
+        merged.add(item);
+    }
+    db.save_cart(customer.id, &merged)?;
+    Ok(Some(merged))
+}
+
+/// Saves the signed-in customer's cart after a change; does nothing for guests.
+#[server]
+pub async fn update_saved_cart(contents: CartContents) -> Result<(), ServerFnError> {
+    use crate::db::{CartRepository, use_db};
+
+    let db = use_db()?;
+    if let Some(customer) = crate::account::auth::current_customer(&db)? {
+        db.save_cart(customer.id, &contents)?;
+    }
+    Ok(())
+}
+
+/// Carts also live in `localStorage`, so guests keep theirs; there is nothing to persist
+/// during SSR.
+mod storage {
+    use super::CartContents;
+
+    #[cfg(feature = "hydrate")]
+    const STORAGE_KEY: &str = "megjoni_cart";
+
+    #[cfg(feature = "hydrate")]
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok().flatten()
+    }
+
+    #[cfg(feature = "hydrate")]
+    pub fn load() -> Option<CartContents> {
+        let json = local_storage()?.get_item(STORAGE_KEY).ok().flatten()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    #[cfg(feature = "hydrate")]
+    pub fn save(contents: &CartContents) {
+        if let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(contents)) {
+            let _ = storage.set_item(STORAGE_KEY, &json);
+        }
+    }
+
+    #[cfg(not(feature = "hydrate"))]
+    pub fn load() -> Option<CartContents> {
+        None
+    }
+
+    #[cfg(not(feature = "hydrate"))]
+    pub fn save(_contents: &CartContents) {}
+}
+
+/// Add/remove toggle used on the product page.
+#[component]
+pub fn AddToCartButton(product: Product, available: bool) -> impl IntoView {
+    let cart = use_cart();
+    let product_id = product.id;
+    let in_cart = move || cart.contains(product_id);
+
+    view! {
+        <Show
+            when=in_cart
+            fallback=move || {
+                let product = product.clone();
+                view! {
+                    <button
+                        class="add-to-cart"
+                        disabled=!available
+                        on:click=move |_| cart.add(&product)
+                    >
+                        "Dodaj do koszyka"
+                    </button>
+                }
+            }
+        >
+            <div class="in-cart">
+                <a href="/cart">
+                    <button class="add-to-cart">"W koszyku - przejdź do koszyka"</button>
+                </a>
+                <button class="remove-from-cart" on:click=move |_| cart.remove(product_id)>
+                    "Usuń z koszyka"
+                </button>
+            </div>
+        </Show>
+        <CartError />
+    }
+}
+
+#[component]
+fn CartError() -> impl IntoView {
+    let cart = use_cart();
+
+    move || {
+        cart.error()
+            .map(|message| view! { <p class="cart-error">{message}</p> })
+    }
+}
+
+#[component]
+pub fn CartPage() -> impl IntoView {
+    let cart = use_cart();
+    let rates = Resource::new(|| (), |_| get_shipping_rates());
+
+    // Stan rezerwacji sprawdzamy na serwerze przy każdej zmianie koszyka.
+    let availability = RwSignal::new(Vec::<(u32, Availability)>::new());
+    Effect::new(move |_| {
+        let cart_id = cart.cart_id();
+        let product_ids = cart.product_ids();
+        spawn_local(async move {
+            if let Ok(statuses) = check_availability(cart_id, product_ids).await {
+                availability.set(statuses);
+            }
+        });
+    });
+
+    view! {
+        <Title text="Koszyk - Meg Joni" />
+        <main>
+            <section class="cart-page">
+                <h2>"Twój koszyk"</h2>
+                <Show
+                    when=move || !cart.is_empty()
+                    fallback=|| {
+                        view! {
+                            <p>"Twój koszyk jest pusty."</p>
+                            <a href="/woman">
+                                <button>"Przejdź do sklepu"</button>
+                            </a>
+                        }
+                    }
+                >
+                    <CartError />
+                    <ul class="cart-items">
+                        <For
+                            each=move || cart.items()
+                            key=|item| item.product_id
+                            let:item
+                        >
+                            <CartLine item availability />
+                        </For>
+                    </ul>
+
+                    <Suspense fallback=|| ()>
+                        {move || Suspend::new(async move {
+                            rates.await.ok().map(|rates| view! { <CartSummary rates /> })
+                        })}
+                    </Suspense>
+
+                    <div class="cart-actions">
+                        <a href="/checkout">
+                            <button>"Przejdź do kasy"</button>
+                        </a>
+                    </div>
+                </Show>
+            </section>
+        </main>
+    }
+}
+
+/// Totals with the cheapest delivery for the cart's parcel and how far it is from free
+/// shipping.
+#[component]
+fn CartSummary(rates: ShippingRates) -> impl IntoView {
+    let cart = use_cart();
+    let rates = StoredValue::new(rates);
+    let parcel = Memo::new(move |_| rates.with_value(|r| r.cart_parcel(&cart.items())));
+    let shipping = Memo::new(move |_| rates.with_value(|r| r.cheapest_delivery(&parcel.get())));
+    let total = move || {
+        let shipping = shipping.get().map_or(0, |quote| quote.price.grosze());
+        Price(cart.subtotal().grosze() + shipping)
+    };
+    let free_shipping_note = move || {
+        let value = parcel.get().value;
+        rates.with_value(|r| match r.missing_for_free_shipping(value) {
+            Some(missing) => Some(format!("Do darmowej dostawy brakuje {missing}.")),
+            None if r.qualifies_for_free_shipping(value) => {
+                Some("Twoje zamówienie kwalifikuje się do darmowej dostawy.".to_string())
+            }
+            None => None,
+        })
+    };
+
+    view! {
+        <dl class="cart-summary">
+            <dt>"Wartość produktów"</dt>
+            <dd>{move || cart.subtotal().to_string()}</dd>
+            <dt>"Dostawa (od)"</dt>
+            <dd>
+                {move || {
+                    shipping
+                        .get()
+                        .map_or_else(|| "-".to_string(), |quote| quote.price.to_string())
+                }}
+            </dd>
+            <dt class="cart-total">"Razem"</dt>
+            <dd class="cart-total">{move || total().to_string()}</dd>
+        </dl>
+        {move || free_shipping_note().map(|note| view! { <p class="cart-shipping-note">{note}</p> })}
+    }
+}
+
+#[component]
+fn CartLine(item: CartItem, availability: RwSignal<Vec<(u32, Availability)>>) -> impl IntoView {
+    let cart = use_cart();
+    let href = format!("/product/{}", item.slug);
+    let product_id = item.product_id;
+    let status = move || {
+        availability.with(|statuses| {
+            statuses
+                .iter()
+                .find(|(id, _)| *id == product_id)
+                .map(|(_, status)| *status)
+        })
+    };
+    let hold = move || match status() {
+        Some(Availability::HeldByYou { expires_at }) => {
+            let minutes = (expires_at - Utc::now()).num_minutes().max(1);
+            view! {
+                <p class="cart-item-hold">
+                    {format!("Zarezerwowane dla Ciebie jeszcze ok. {minutes} min")}
+                </p>
+            }
+            .into_any()
+        }
+        Some(Availability::Available) => view! {
+            <p class="cart-item-hold expired">
+                "Rezerwacja wygasła. "
+                <button class="cart-item-renew" on:click=move |_| cart.renew(product_id)>
+                    "Zarezerwuj ponownie"
+                </button>
+            </p>
+        }
+        .into_any(),
+        Some(Availability::Reserved | Availability::Sold) => view! {
+            <p class="cart-item-hold unavailable">
+                "Niedostępny - ta sztuka trafiła już do innego klienta."
+            </p>
+        }
+        .into_any(),
+        None => ().into_any(),
+    };
+
+    view! {
+        <li class="cart-item">
+            <a href=href.clone()>
+                <img src=item.image alt=item.title.clone() width="75" height="100" />
+            </a>
+            <div class="cart-item-info">
+                <a href=href>
+                    <h3>{item.title}</h3>
+                </a>
+                <p>"Rozmiar: "{item.size}</p>
+                {hold}
+            </div>
+            <p class="cart-item-price">{item.price.to_string()}</p>
+            <button class="cart-item-remove" on:click=move |_| cart.remove(product_id)>
+                "Usuń"
+            </button>
+        </li>
+    }
+}
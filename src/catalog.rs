@@ -0,0 +1,418 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod admin;
+pub mod intake;
+
+/// Image shown when a product has no photos yet.
+pub const PLACEHOLDER_IMAGE: &str = "/placeholder300x400.svg";
+
+/// Price in grosze, so that amounts never go through floating point.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Price(pub u32);
+
+impl Price {
+    pub fn from_zloty(zloty: u32, grosze: u32) -> Self {
+        Price(zloty * 100 + grosze)
+    }
+
+    pub fn grosze(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02} PLN", self.0 / 100, self.0 % 100)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Woman,
+    Man,
+}
+
+impl Category {
+    pub const ALL: [Category; 2] = [Category::Woman, Category::Man];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Woman => "Damska",
+            Category::Man => "Męska",
+        }
+    }
+
+    /// The value used in forms, e.g. `woman`.
+    pub fn param(self) -> &'static str {
+        match self {
+            Category::Woman => "woman",
+            Category::Man => "man",
+        }
+    }
+}
+
+/// Condition grade of a second-hand item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    NewWithTags,
+    VeryGood,
+    Good,
+    Fair,
+}
+
+impl Condition {
+    /// From best to worst.
+    pub const ALL: [Condition; 4] = [
+        Condition::NewWithTags,
+        Condition::VeryGood,
+        Condition::Good,
+        Condition::Fair,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Condition::NewWithTags => "Nowy z metką",
+            Condition::VeryGood => "Bardzo dobry",
+            Condition::Good => "Dobry",
+            Condition::Fair => "Dostateczny",
+        }
+    }
+
+    /// The value used in forms, e.g. `very_good`.
+    pub fn param(self) -> &'static str {
+        match self {
+            Condition::NewWithTags => "new_with_tags",
+            Condition::VeryGood => "very_good",
+            Condition::Good => "good",
+            Condition::Fair => "fair",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductStatus {
+    #[default]
+    Available,
+    Reserved,
+    Sold,
+}
+
+impl ProductStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            ProductStatus::Available => "Dostępny",
+            ProductStatus::Reserved => "Zarezerwowany",
+            ProductStatus::Sold => "Sprzedany",
+        }
+    }
+}
+
+/// Where a used item is on its way from being bought in to being sold. Items are taken
+/// in, checked, measured, photographed and priced before they are listed; only listed
+/// items are shown to customers (see [`intake`]).
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingStage {
+    Intake,
+    QualityCheck,
+    Measuring,
+    Photographing,
+    Pricing,
+    /// On sale. The default, so products from the seed catalog are shown right away.
+    #[default]
+    Listed,
+    /// Bought by a customer; follows from [`ProductStatus::Sold`] rather than being set.
+    Sold,
+}
+
+impl ListingStage {
+    pub const ALL: [ListingStage; 7] = [
+        ListingStage::Intake,
+        ListingStage::QualityCheck,
+        ListingStage::Measuring,
+        ListingStage::Photographing,
+        ListingStage::Pricing,
+        ListingStage::Listed,
+        ListingStage::Sold,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ListingStage::Intake => "Przyjęcie",
+            ListingStage::QualityCheck => "Kontrola jakości",
+            ListingStage::Measuring => "Mierzenie",
+            ListingStage::Photographing => "Zdjęcia",
+            ListingStage::Pricing => "Wycena",
+            ListingStage::Listed => "Wystawiony",
+            ListingStage::Sold => "Sprzedany",
+        }
+    }
+
+    /// The value used in forms and links, e.g. `quality_check`.
+    pub fn param(self) -> &'static str {
+        match self {
+            ListingStage::Intake => "intake",
+            ListingStage::QualityCheck => "quality_check",
+            ListingStage::Measuring => "measuring",
+            ListingStage::Photographing => "photographing",
+            ListingStage::Pricing => "pricing",
+            ListingStage::Listed => "listed",
+            ListingStage::Sold => "sold",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|stage| stage.param() == param)
+    }
+
+    /// The step that follows this one in preparation; listed items leave only by being
+    /// sold.
+    pub fn next(self) -> Option<ListingStage> {
+        match self {
+            ListingStage::Intake => Some(ListingStage::QualityCheck),
+            ListingStage::QualityCheck => Some(ListingStage::Measuring),
+            ListingStage::Measuring => Some(ListingStage::Photographing),
+            ListingStage::Photographing => Some(ListingStage::Pricing),
+            ListingStage::Pricing => Some(ListingStage::Listed),
+            ListingStage::Listed | ListingStage::Sold => None,
+        }
+    }
+
+    /// Whether the item is still being prepared and hidden from customers.
+    pub fn in_preparation(self) -> bool {
+        self < ListingStage::Listed
+    }
+}
+
+/// A single garment measurement taken flat, e.g. "Długość": 92 cm.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub name: String,
+    pub cm: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Product {
+    pub id: u32,
+    pub slug: String,
+    pub title: String,
+    pub category: Category,
+    pub price: Price,
+    /// Price before a markdown; set only for items on sale.
+    #[serde(default)]
+    pub original_price: Option<Price>,
+    #[serde(default)]
+    pub images: Vec<String>,
+    pub condition: Condition,
+    pub size: String,
+    #[serde(default)]
+    pub brand: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub measurements: Vec<Measurement>,
+    pub listed_on: NaiveDate,
+    #[serde(default)]
+    pub status: ProductStatus,
+    #[serde(default)]
+    pub featured: bool,
+    /// Packed weight, used to price delivery; the rate card's default applies when unknown.
+    #[serde(default)]
+    pub weight_grams: Option<u32>,
+    /// Only listed items appear in the shop; older data without a stage counts as listed.
+    #[serde(default)]
+    pub stage: ListingStage,
+}
+
+impl Product {
+    pub fn href(&self) -> String {
+        format!("/product/{}", self.slug)
+    }
+
+    pub fn cover_image(&self) -> &str {
+        self.images
+            .first()
+            .map(String::as_str)
+            .unwrap_or(PLACEHOLDER_IMAGE)
+    }
+
+    pub fn is_on_sale(&self) -> bool {
+        self.original_price
+            .is_some_and(|original| original > self.price)
+    }
+}
+
+/// Which products a listing page wants to show.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProductQuery {
+    pub category: Option<Category>,
+    pub on_sale: bool,
+    pub featured: bool,
+    /// Sort the most recently listed items first.
+    pub newest_first: bool,
+    pub limit: Option<usize>,
+}
+
+impl ProductQuery {
+    pub fn category(category: Category) -> Self {
+        ProductQuery {
+            category: Some(category),
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, product: &Product) -> bool {
+        product.stage == ListingStage::Listed
+            && self
+                .category
+                .is_none_or(|category| product.category == category)
+            && (!self.on_sale || product.is_on_sale())
+            && (!self.featured || product.featured)
+    }
+
+    pub fn apply(&self, products: Vec<Product>) -> Vec<Product> {
+        let mut products: Vec<_> = products.into_iter().filter(|p| self.matches(p)).collect();
+        if self.newest_first {
+            products.sort_by(|a, b| b.listed_on.cmp(&a.listed_on).then(b.id.cmp(&a.id)));
+        }
+        if let Some(limit) = self.limit {
+            products.truncate(limit);
+        }
+        products
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod source {
+    use super::Product;
+    use crate::db::{Db, ProductRepository};
+
+    /// Location of the seed catalog, relative to the directory the server is started from.
+    pub const DEFAULT_CATALOG_FILE: &str = "data/products.json";
+
+    /// Reads the seed product list from disk.
+    pub fn load_products() -> Result<Vec<Product>, String> {
+        let path = std::env::var("MEGJONI_CATALOG_FILE")
+            .unwrap_or_else(|_| DEFAULT_CATALOG_FILE.to_string());
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("nie można odczytać {path}: {e}"))?;
+        serde_json::from_str(&contents).map_err(|e| format!("błędny format {path}: {e}"))
+    }
+
+    /// Fills an empty database with the seed catalog. Returns how many products were added.
+    pub fn seed_database(db: &Db) -> Result<usize, String> {
+        if db.count_products().map_err(|e| e.to_string())? > 0 {
+            return Ok(0);
+        }
+        let products = load_products()?;
+        for product in &products {
+            db.save_product(product).map_err(|e| e.to_string())?;
+        }
+        Ok(products.len())
+    }
+}
+
+#[server]
+pub async fn get_products(query: ProductQuery) -> Result<Vec<Product>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+
+    let products = use_db()?.list_products()?;
+    Ok(query.apply(products))
+}
+
+#[server]
+pub async fn get_product(slug: String) -> Result<Option<Product>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+
+    // Rzeczy w przygotowaniu nie istnieją dla klientów; sprzedane zostają pod starym adresem.
+    Ok(use_db()?
+        .find_product_by_slug(&slug)?
+        .filter(|product| !product.stage.in_preparation()))
+}
+
+/// Current price, with the pre-sale price struck through for discounted items.
+#[component]
+pub fn PriceTag(price: Price, original_price: Option<Price>) -> impl IntoView {
+    match original_price.filter(|original| *original > price) {
+        Some(original) => view! {
+            <p class="product-price">
+                <del class="product-price-original">{original.to_string()}</del>
+                " "
+                {price.to_string()}
+            </p>
+        }
+        .into_any(),
+        None => view! { <p class="product-price">{price.to_string()}</p> }.into_any(),
+    }
+}
+
+#[component]
+pub fn ProductCard(product: Product) -> impl IntoView {
+    let href = product.href();
+    let cover = product.cover_image().to_string();
+    let badge = (product.status != ProductStatus::Available).then(|| {
+        view! { <span class="product-badge">{product.status.label()}</span> }
+    });
+
+    view! {
+        <article class="product-item">
+            <a href=href>
+                <figure>
+                    <img src=cover alt=product.title.clone() width="300" height="400" />
+                    {badge}
+                </figure>
+                <h3>{product.title}</h3>
+                <PriceTag price=product.price original_price=product.original_price />
+            </a>
+        </article>
+    }
+}
+
+/// Cards for a `.product-grid`, shared by listing pages and search results.
+pub fn product_cards(products: Vec<Product>) -> impl IntoView {
+    products
+        .into_iter()
+        .map(|product| view! { <ProductCard product /> })
+        .collect_view()
+}
+
+/// Loads the products matching `query` and renders them as a `product-grid`.
+#[component]
+pub fn ProductGrid(query: ProductQuery) -> impl IntoView {
+    let products = Resource::new(move || query.clone(), get_products);
+
+    view! {
+        <div class="product-grid">
+            <Suspense fallback=|| view! { <p>"Ładowanie produktów..."</p> }>
+                {move || Suspend::new(async move {
+                    match products.await {
+                        Ok(products) if products.is_empty() => {
+                            view! { <p>"Brak produktów w tej kategorii."</p> }.into_any()
+                        }
+                        Ok(products) => product_cards(products).into_any(),
+                        Err(_) => view! {
+                            <p>"Nie udało się wczytać produktów. Spróbuj ponownie później."</p>
+                        }
+                        .into_any(),
+                    }
+                })}
+            </Suspense>
+        </div>
+    }
+}
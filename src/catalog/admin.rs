@@ -0,0 +1,571 @@
+//! Products as staff edit them at `/admin/products`: every item, sold ones included, with
+//! a form for adding a new one or changing its details, photos and measurements. Items
+//! that were ever ordered cannot be deleted, only marked as sold, so order history keeps
+//! pointing at them.
+//!
+//! Items still in preparation (see [`super::intake`]) may be saved without a price or
+//! size; those are filled in at their stages.
+
+use super::{Category, Condition, ListingStage, Measurement, Price, Product, ProductStatus};
+use crate::refunds::{amount_value, parse_amount};
+use crate::staff::Permission;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_params_map;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ProductFormError {
+    #[error("podaj nazwę produktu")]
+    MissingTitle,
+    #[error("adres może zawierać tylko małe litery bez polskich znaków, cyfry i myślniki")]
+    InvalidSlug,
+    #[error("inny produkt ma już adres „{0}”")]
+    SlugTaken(String),
+    #[error("podaj cenę w złotych, np. 49,99")]
+    InvalidPrice,
+    #[error("cena przed obniżką musi być wyższa od ceny")]
+    InvalidOriginalPrice,
+    #[error("podaj rozmiar")]
+    MissingSize,
+    #[error("nie rozumiem wymiaru „{0}”; wpisz np. „Długość: 92”")]
+    InvalidMeasurement(String),
+    #[error("wagę podaj w gramach")]
+    InvalidWeight,
+    #[error("nie ma takiego produktu")]
+    NotFound,
+    #[error("produkt był już zamówiony, więc można go tylko oznaczyć jako sprzedany")]
+    Ordered,
+}
+
+/// A URL-friendly form of `title`, e.g. `zolta-kurtka-zimowa` for "Żółta kurtka zimowa".
+pub fn slugify(title: &str) -> String {
+    crate::search::fold(title)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(feature = "ssr")]
+fn is_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Measurements typed one per line, e.g. `Długość: 92` or `Długość: 92 cm`.
+pub fn parse_measurements(text: &str) -> Result<Vec<Measurement>, ProductFormError> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let invalid = || ProductFormError::InvalidMeasurement(line.to_string());
+            let (name, cm) = line.rsplit_once(':').ok_or_else(invalid)?;
+            let cm = cm.trim().trim_end_matches("cm").trim();
+            Ok(Measurement {
+                name: name.trim().to_string(),
+                cm: cm.parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
+
+fn measurements_text(measurements: &[Measurement]) -> String {
+    measurements
+        .iter()
+        .map(|measurement| format!("{}: {}", measurement.name, measurement.cm))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(feature = "ssr")]
+fn optional(text: &str) -> Option<String> {
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) fn rebuild_search_index(db: &crate::db::Db) -> Result<(), ServerFnError> {
+    if let Some(index) = use_context::<crate::search::index::SharedSearchIndex>() {
+        index.rebuild(db)?;
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn list_admin_products() -> Result<Vec<Product>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    let mut products = use_db()?.list_products()?;
+    products.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(products)
+}
+
+#[server]
+pub async fn get_admin_product(id: u32) -> Result<Option<Product>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    Ok(use_db()?.find_product(id)?)
+}
+
+/// Adds a product, or changes the one with `id`, and goes back to the product list.
+/// The outer error means no access or a storage failure; the inner one a field to fix.
+#[server]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_product_details(
+    id: Option<u32>,
+    title: String,
+    #[server(default)] slug: String,
+    category: Category,
+    price: String,
+    #[server(default)] original_price: String,
+    condition: Condition,
+    size: String,
+    #[server(default)] brand: String,
+    #[server(default)] color: String,
+    #[server(default)] material: String,
+    #[server(default)] description: String,
+    #[server(default)] measurements: String,
+    #[server(default)] images: String,
+    #[server(default)] weight_grams: String,
+    #[server(default)] featured: bool,
+    #[server(default)] sold: bool,
+) -> Result<Result<u32, ProductFormError>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+    use chrono::Utc;
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    let db = use_db()?;
+    let existing = match id {
+        Some(id) => match db.find_product(id)? {
+            Some(product) => Some(product),
+            None => return Ok(Err(ProductFormError::NotFound)),
+        },
+        None => None,
+    };
+
+    let title = title.trim();
+    if title.is_empty() {
+        return Ok(Err(ProductFormError::MissingTitle));
+    }
+    let slug = match slug.trim() {
+        "" => slugify(title),
+        slug => slug.to_string(),
+    };
+    if !is_slug(&slug) {
+        return Ok(Err(ProductFormError::InvalidSlug));
+    }
+    if db
+        .find_product_by_slug(&slug)?
+        .is_some_and(|other| Some(other.id) != id)
+    {
+        return Ok(Err(ProductFormError::SlugTaken(slug)));
+    }
+    // Nowe produkty z tego formularza od razu trafiają do sprzedaży.
+    let stage = existing
+        .as_ref()
+        .map_or(ListingStage::Listed, |product| product.stage);
+    let preparing = stage.in_preparation();
+    let price = match parse_amount(&price) {
+        Some(price) if price.grosze() > 0 || preparing => price,
+        _ => return Ok(Err(ProductFormError::InvalidPrice)),
+    };
+    let original_price = match parse_amount(&original_price) {
+        Some(Price(0)) => None,
+        Some(original) if original > price => Some(original),
+        _ => return Ok(Err(ProductFormError::InvalidOriginalPrice)),
+    };
+    if size.trim().is_empty() && !preparing {
+        return Ok(Err(ProductFormError::MissingSize));
+    }
+    let measurements = match parse_measurements(&measurements) {
+        Ok(measurements) => measurements,
+        Err(e) => return Ok(Err(e)),
+    };
+    let weight_grams = match weight_grams.trim() {
+        "" => None,
+        grams => match grams.parse() {
+            Ok(grams) => Some(grams),
+            Err(_) => return Ok(Err(ProductFormError::InvalidWeight)),
+        },
+    };
+
+    let product = Product {
+        id: id.unwrap_or_default(),
+        slug,
+        title: title.to_string(),
+        category,
+        price,
+        original_price,
+        images: images
+            .lines()
+            .map(str::trim)
+            .filter(|image| !image.is_empty())
+            .map(str::to_string)
+            .collect(),
+        condition,
+        size: size.trim().to_string(),
+        brand: optional(&brand),
+        color: optional(&color),
+        material: optional(&material),
+        description: description.trim().to_string(),
+        measurements,
+        listed_on: existing
+            .as_ref()
+            .map(|product| product.listed_on)
+            .unwrap_or_else(|| Utc::now().date_naive()),
+        // Rezerwacje z koszyków zapisują się osobno, więc ten wybór ich nie zdejmuje.
+        status: if sold {
+            ProductStatus::Sold
+        } else {
+            ProductStatus::Available
+        },
+        featured,
+        weight_grams,
+        stage,
+    };
+    let id = match existing {
+        Some(_) => {
+            db.save_product(&product)?;
+            product.id
+        }
+        None => db.insert_product(&product)?,
+    };
+    rebuild_search_index(&db)?;
+    if preparing {
+        leptos_axum::redirect(&format!("/admin/intake?stage={}", stage.param()));
+    } else {
+        leptos_axum::redirect("/admin/products");
+    }
+    Ok(Ok(id))
+}
+
+#[server]
+pub async fn delete_admin_product(id: u32) -> Result<Result<(), ProductFormError>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    let db = use_db()?;
+    if db.find_product(id)?.is_none() {
+        return Ok(Err(ProductFormError::NotFound));
+    }
+    if !db.delete_product(id)? {
+        return Ok(Err(ProductFormError::Ordered));
+    }
+    rebuild_search_index(&db)?;
+    Ok(Ok(()))
+}
+
+#[component]
+pub fn ProductsAdminPage() -> impl IntoView {
+    let delete = ServerAction::<DeleteAdminProduct>::new();
+    let products = Resource::new(move || delete.version().get(), |_| list_admin_products());
+    let error = move || match delete.value().get() {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się usunąć produktu.".to_string()),
+        _ => None,
+    };
+
+    view! {
+        <Title text="Produkty - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Produkty"</h2>
+                <p>
+                    <a href="/admin/products/new">"Dodaj produkt"</a>
+                </p>
+                {move || error().map(|e| view! { <p class="inbox-error">{e}</p> })}
+                <Suspense fallback=|| view! { <p>"Ładowanie produktów..."</p> }>
+                    {move || Suspend::new(async move {
+                        match products.await {
+                            Ok(products) if products.is_empty() => {
+                                view! { <p>"Brak produktów."</p> }.into_any()
+                            }
+                            Ok(products) => view! { <ProductList products delete /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do produktów."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ProductList(products: Vec<Product>, delete: ServerAction<DeleteAdminProduct>) -> impl IntoView {
+    view! {
+        <table class="inbox-threads products-admin">
+            <thead>
+                <tr>
+                    <th>"Zdjęcie"</th>
+                    <th>"Produkt"</th>
+                    <th>"Cena"</th>
+                    <th>"Status"</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {products
+                    .into_iter()
+                    .map(|product| {
+                        let edit = format!("/admin/products/{}", product.id);
+                        view! {
+                            <tr>
+                                <td>
+                                    <img
+                                        src=product.cover_image().to_string()
+                                        alt=""
+                                        width="60"
+                                        height="80"
+                                    />
+                                </td>
+                                <td>
+                                    <a href=edit>{product.title.clone()}</a>
+                                    <br />
+                                    <small>
+                                        {format!(
+                                            "nr {} · {} · {} · dodany {}",
+                                            product.id,
+                                            product.category.label(),
+                                            product.size,
+                                            product.listed_on,
+                                        )}
+                                    </small>
+                                </td>
+                                <td>
+                                    {product.price.to_string()}
+                                    {product
+                                        .original_price
+                                        .map(|original| view! { <br /> <small><del>{original.to_string()}</del></small> })}
+                                </td>
+                                <td>
+                                    {if product.stage.in_preparation() {
+                                        product.stage.label()
+                                    } else {
+                                        product.status.label()
+                                    }}
+                                    {product.featured.then_some(" · polecany")}
+                                </td>
+                                <td>
+                                    <a href=product.href() target="_blank">"Podgląd"</a>
+                                    <ActionForm action=delete>
+                                        <input type="hidden" name="id" value=product.id />
+                                        <button
+                                            type="submit"
+                                            onclick="return confirm('Usunąć ten produkt?')"
+                                        >
+                                            "Usuń"
+                                        </button>
+                                    </ActionForm>
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
+
+/// `/admin/products/new` for a new product, `/admin/products/<id>` to edit one.
+#[component]
+pub fn ProductEditPage() -> impl IntoView {
+    let params = use_params_map();
+    let save = ServerAction::<SaveProductDetails>::new();
+    let product = Resource::new(
+        move || params.read().get("id"),
+        |id| async move {
+            match id.as_deref() {
+                Some("new") => Ok(Some(None)),
+                Some(id) => match id.parse::<u32>() {
+                    Ok(id) => get_admin_product(id).await.map(|product| product.map(Some)),
+                    Err(_) => Ok(None),
+                },
+                None => Ok(None),
+            }
+        },
+    );
+
+    view! {
+        <Title text="Produkt - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <p>
+                    <a href="/admin/products">"← Wszystkie produkty"</a>
+                </p>
+                <Suspense fallback=|| view! { <p>"Ładowanie produktu..."</p> }>
+                    {move || Suspend::new(async move {
+                        match product.await {
+                            Ok(Some(product)) => view! { <ProductForm product save /> }.into_any(),
+                            Ok(None) => view! { <p>"Nie ma takiego produktu."</p> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do produktów."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ProductForm(product: Option<Product>, save: ServerAction<SaveProductDetails>) -> impl IntoView {
+    let heading = match &product {
+        Some(product) => format!("Produkt nr {}", product.id),
+        None => "Nowy produkt".to_string(),
+    };
+    let error = move || match save.value().get() {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się zapisać produktu.".to_string()),
+        _ => None,
+    };
+    let id = product.as_ref().map(|product| product.id);
+    let category = product.as_ref().map(|product| product.category);
+    let condition = product.as_ref().map(|product| product.condition);
+    let preparing = product
+        .as_ref()
+        .is_some_and(|product| product.stage.in_preparation());
+    let text = |field: fn(&Product) -> String| product.as_ref().map(field).unwrap_or_default();
+    let categories = Category::ALL
+        .into_iter()
+        .map(|option| {
+            view! {
+                <option value=option.param() selected=(category == Some(option))>
+                    {option.label()}
+                </option>
+            }
+        })
+        .collect_view();
+    let conditions = Condition::ALL
+        .into_iter()
+        .map(|option| {
+            view! {
+                <option value=option.param() selected=(condition == Some(option))>
+                    {option.label()}
+                </option>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <h2>{heading}</h2>
+        {product
+            .as_ref()
+            .filter(|product| product.stage != ListingStage::Listed)
+            .map(|product| {
+                let queue = format!("/admin/intake?stage={}", product.stage.param());
+                view! {
+                    <p>
+                        "Etap: " <a href=queue>{product.stage.label()}</a>
+                    </p>
+                }
+            })}
+        <ActionForm action=save attr:class="account-form product-form">
+            {id.map(|id| view! { <input type="hidden" name="id" value=id /> })}
+            <label for="product-title">"Nazwa"</label>
+            <input
+                type="text"
+                id="product-title"
+                name="title"
+                value=text(|p| p.title.clone())
+                required
+            />
+            <label for="product-slug">"Adres (puste: z nazwy)"</label>
+            <input type="text" id="product-slug" name="slug" value=text(|p| p.slug.clone()) />
+            <label for="product-category">"Kategoria"</label>
+            <select id="product-category" name="category">{categories}</select>
+            <label for="product-price">"Cena, zł"</label>
+            <input
+                type="text"
+                id="product-price"
+                name="price"
+                inputmode="decimal"
+                value=text(|p| (p.price.grosze() > 0).then(|| amount_value(p.price)).unwrap_or_default())
+                required=!preparing
+            />
+            <label for="product-original-price">"Cena przed obniżką, zł (tylko na wyprzedaży)"</label>
+            <input
+                type="text"
+                id="product-original-price"
+                name="original_price"
+                inputmode="decimal"
+                value=text(|p| p.original_price.map(amount_value).unwrap_or_default())
+            />
+            <label for="product-condition">"Stan"</label>
+            <select id="product-condition" name="condition">{conditions}</select>
+            <label for="product-size">"Rozmiar"</label>
+            <input
+                type="text"
+                id="product-size"
+                name="size"
+                value=text(|p| p.size.clone())
+                required=!preparing
+            />
+            <label for="product-brand">"Marka"</label>
+            <input
+                type="text"
+                id="product-brand"
+                name="brand"
+                value=text(|p| p.brand.clone().unwrap_or_default())
+            />
+            <label for="product-color">"Kolor"</label>
+            <input
+                type="text"
+                id="product-color"
+                name="color"
+                value=text(|p| p.color.clone().unwrap_or_default())
+            />
+            <label for="product-material">"Materiał"</label>
+            <input
+                type="text"
+                id="product-material"
+                name="material"
+                value=text(|p| p.material.clone().unwrap_or_default())
+            />
+            <label for="product-description">"Opis"</label>
+            <textarea id="product-description" name="description" rows="5">
+                {text(|p| p.description.clone())}
+            </textarea>
+            <label for="product-measurements">"Wymiary w cm, jeden w wierszu (np. „Długość: 92”)"</label>
+            <textarea id="product-measurements" name="measurements" rows="4">
+                {text(|p| measurements_text(&p.measurements))}
+            </textarea>
+            <label for="product-images">"Zdjęcia, jeden adres w wierszu (pierwsze jest okładką)"</label>
+            <textarea id="product-images" name="images" rows="4">
+                {text(|p| p.images.join("\n"))}
+            </textarea>
+            <label for="product-weight">"Waga z opakowaniem, g"</label>
+            <input
+                type="text"
+                id="product-weight"
+                name="weight_grams"
+                inputmode="numeric"
+                value=text(|p| p.weight_grams.map(|grams| grams.to_string()).unwrap_or_default())
+            />
+            <label>
+                <input
+                    type="checkbox"
+                    name="featured"
+                    value="true"
+                    checked=product.as_ref().is_some_and(|p| p.featured)
+                />
+                " Polecany na stronie głównej"
+            </label>
+            <label>
+                <input
+                    type="checkbox"
+                    name="sold"
+                    value="true"
+                    checked=product.as_ref().is_some_and(|p| p.status == ProductStatus::Sold)
+                />
+                " Sprzedany"
+            </label>
+            {move || error().map(|e| view! { <p class="account-error" role="alert">{e}</p> })}
+            <button type="submit" disabled=move || save.pending().get()>"Zapisz"</button>
+        </ActionForm>
+    }
+}
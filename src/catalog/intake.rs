@@ -0,0 +1,476 @@
+//! Preparing used items for sale at `/admin/intake`. A newly bought batch is taken in
+//! with just a title per item; the items then go through quality check, measuring,
+//! photographing and pricing, each stage with its own queue, until they are listed.
+//! Staff move whole batches at once, one stage at a time or straight to listed, and items
+//! still missing what the shop needs (a size, photos, a price) are held back.
+//!
+//! Items in preparation are hidden from customers: listings, search and product pages
+//! show only listed items, and they cannot be put in a cart.
+
+use super::{Category, Condition, ListingStage, Product, ProductStatus};
+use crate::staff::Permission;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+use serde::{Deserialize, Serialize};
+
+/// Most items taken in at once, so one pasted list cannot flood the catalog.
+pub const MAX_BATCH: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum IntakeError {
+    #[error("wpisz nazwy rzeczy, jedną w wierszu")]
+    NoTitles,
+    #[error("naraz można przyjąć najwyżej {MAX_BATCH} rzeczy")]
+    TooMany,
+    #[error("zaznacz rzeczy do przeniesienia")]
+    NothingSelected,
+    #[error("sprzedaż oznacza się przez zamówienie albo w formularzu produktu")]
+    CannotSell,
+}
+
+/// Why one item of a batch stayed where it was.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum StageError {
+    #[error("nie ma takiego produktu")]
+    NotFound,
+    #[error("jest już sprzedany")]
+    Sold,
+    #[error("brakuje rozmiaru")]
+    NoSize,
+    #[error("brakuje zdjęć")]
+    NoPhotos,
+    #[error("brakuje ceny")]
+    NoPrice,
+    #[error("ktoś inny właśnie go przeniósł")]
+    Moved,
+}
+
+/// What a batch move did.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageReport {
+    pub to: ListingStage,
+    pub moved: usize,
+    /// Titles of the items that stayed behind, with the reason.
+    pub held: Vec<(String, StageError)>,
+}
+
+/// What the item still needs before it can move forward to `to`; moving back needs
+/// nothing. Measuring ends with a size, photographing with photos, pricing with a price.
+pub fn missing_for(product: &Product, to: ListingStage) -> Option<StageError> {
+    if to > ListingStage::Measuring && product.size.trim().is_empty() {
+        Some(StageError::NoSize)
+    } else if to > ListingStage::Photographing && product.images.is_empty() {
+        Some(StageError::NoPhotos)
+    } else if to > ListingStage::Pricing && product.price.grosze() == 0 {
+        Some(StageError::NoPrice)
+    } else {
+        None
+    }
+}
+
+/// The queue of one stage, with how many items wait at every stage.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StageQueue {
+    pub counts: Vec<(ListingStage, usize)>,
+    pub products: Vec<Product>,
+}
+
+impl StageQueue {
+    pub fn count(&self, stage: ListingStage) -> usize {
+        self.counts
+            .iter()
+            .find(|(counted, _)| *counted == stage)
+            .map_or(0, |(_, count)| *count)
+    }
+}
+
+#[server]
+pub async fn get_stage_queue(stage: ListingStage) -> Result<StageQueue, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    let db = use_db()?;
+    Ok(StageQueue {
+        counts: db.count_by_stage()?,
+        products: db.products_in_stage(stage)?,
+    })
+}
+
+/// Adds a newly bought batch at the intake stage, one item per line of `titles`. Price,
+/// size and the rest are filled in at the later stages.
+#[server]
+pub async fn take_in_items(
+    category: Category,
+    condition: Condition,
+    #[server(default)] titles: String,
+) -> Result<Result<usize, IntakeError>, ServerFnError> {
+    use crate::catalog::Price;
+    use crate::catalog::admin::slugify;
+    use crate::db::{ProductRepository, use_db};
+    use chrono::Utc;
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    let titles: Vec<&str> = titles
+        .lines()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .collect();
+    if titles.is_empty() {
+        return Ok(Err(IntakeError::NoTitles));
+    }
+    if titles.len() > MAX_BATCH {
+        return Ok(Err(IntakeError::TooMany));
+    }
+    let db = use_db()?;
+    let today = Utc::now().date_naive();
+    for title in &titles {
+        let base = match slugify(title) {
+            slug if slug.is_empty() => "rzecz".to_string(),
+            slug => slug,
+        };
+        let mut slug = base.clone();
+        let mut suffix = 1;
+        while db.find_product_by_slug(&slug)?.is_some() {
+            suffix += 1;
+            slug = format!("{base}-{suffix}");
+        }
+        db.insert_product(&Product {
+            id: 0,
+            slug,
+            title: title.to_string(),
+            category,
+            price: Price(0),
+            original_price: None,
+            images: Vec::new(),
+            condition,
+            size: String::new(),
+            brand: None,
+            color: None,
+            material: None,
+            description: String::new(),
+            measurements: Vec::new(),
+            listed_on: today,
+            status: ProductStatus::Available,
+            featured: false,
+            weight_grams: None,
+            stage: ListingStage::Intake,
+        })?;
+    }
+    crate::catalog::admin::rebuild_search_index(&db)?;
+    Ok(Ok(titles.len()))
+}
+
+/// Moves the chosen items to `to`, forward or back. Items that lack what a later stage
+/// needs stay where they are and are reported; the rest move. Listing an item makes it
+/// new in the shop from today.
+#[server]
+pub async fn move_products(
+    #[server(default)] product_ids: Vec<u32>,
+    to: ListingStage,
+) -> Result<Result<StageReport, IntakeError>, ServerFnError> {
+    use crate::db::{ProductRepository, use_db};
+    use chrono::Utc;
+
+    crate::staff::require_staff(Permission::EditProducts)?;
+    if to == ListingStage::Sold {
+        return Ok(Err(IntakeError::CannotSell));
+    }
+    if product_ids.is_empty() {
+        return Ok(Err(IntakeError::NothingSelected));
+    }
+    let db = use_db()?;
+    let today = Utc::now().date_naive();
+    let mut report = StageReport {
+        to,
+        moved: 0,
+        held: Vec::new(),
+    };
+    for id in product_ids {
+        let Some(product) = db.find_product(id)? else {
+            report.held.push((format!("nr {id}"), StageError::NotFound));
+            continue;
+        };
+        let from = product.stage;
+        if from == to {
+            continue;
+        }
+        let problem = match from {
+            ListingStage::Sold => Some(StageError::Sold),
+            from if to > from => missing_for(&product, to),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            report.held.push((product.title, problem));
+        } else if db.change_product_stage(id, from, to, today)? {
+            report.moved += 1;
+        } else {
+            report.held.push((product.title, StageError::Moved));
+        }
+    }
+    Ok(Ok(report))
+}
+
+#[component]
+pub fn IntakePage() -> impl IntoView {
+    let query = use_query_map();
+    let take_in = ServerAction::<TakeInItems>::new();
+    let move_action = ServerAction::<MoveProducts>::new();
+    let stage = move || {
+        query
+            .read()
+            .get("stage")
+            .and_then(|stage| ListingStage::from_param(&stage))
+            .unwrap_or(ListingStage::Intake)
+    };
+    let queue = Resource::new(
+        move || {
+            (
+                stage(),
+                take_in.version().get(),
+                move_action.version().get(),
+            )
+        },
+        |(stage, ..)| get_stage_queue(stage),
+    );
+
+    let take_in_message = move || match take_in.value().get() {
+        Some(Ok(Ok(count))) => Some(
+            view! {
+                <p class="account-success" role="status">{format!("Przyjęto rzeczy: {count}.")}</p>
+            }
+            .into_any(),
+        ),
+        Some(Ok(Err(e))) => Some(view! { <p class="inbox-error">{e.to_string()}</p> }.into_any()),
+        Some(Err(_)) => {
+            Some(view! { <p class="inbox-error">"Nie udało się przyjąć rzeczy."</p> }.into_any())
+        }
+        None => None,
+    };
+    let move_message = move || match move_action.value().get() {
+        Some(Ok(Ok(report))) => Some(view! { <StageReportView report /> }.into_any()),
+        Some(Ok(Err(e))) => Some(view! { <p class="inbox-error">{e.to_string()}</p> }.into_any()),
+        Some(Err(_)) => {
+            Some(view! { <p class="inbox-error">"Nie udało się przenieść rzeczy."</p> }.into_any())
+        }
+        None => None,
+    };
+
+    view! {
+        <Title text="Przygotowanie rzeczy - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Przygotowanie rzeczy do sprzedaży"</h2>
+                {move_message}
+                <Suspense fallback=|| view! { <p>"Ładowanie kolejki..."</p> }>
+                    {move || Suspend::new(async move {
+                        match queue.await {
+                            Ok(queue) => {
+                                let stage = stage();
+                                view! {
+                                    <StageNav queue=queue.clone() current=stage />
+                                    <StageQueueView
+                                        stage
+                                        products=queue.products
+                                        action=move_action
+                                    />
+                                }
+                                .into_any()
+                            }
+                            Err(_) => view! { <p>"Brak dostępu do produktów."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+                <Show when=move || stage() == ListingStage::Intake>
+                    <h3>"Przyjmij nową partię"</h3>
+                    {take_in_message}
+                    <TakeInForm action=take_in />
+                </Show>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn StageNav(queue: StageQueue, current: ListingStage) -> impl IntoView {
+    let links = ListingStage::ALL
+        .into_iter()
+        .map(|stage| {
+            let href = format!("/admin/intake?stage={}", stage.param());
+            view! {
+                <a href=href class:active=stage == current>
+                    {format!("{} ({})", stage.label(), queue.count(stage))}
+                </a>
+            }
+        })
+        .collect_view();
+    view! { <nav class="inbox-filters intake-stages">{links}</nav> }
+}
+
+#[component]
+fn StageReportView(report: StageReport) -> impl IntoView {
+    let moved = format!(
+        "Przeniesiono do etapu „{}”: {}.",
+        report.to.label(),
+        report.moved
+    );
+    let held = (!report.held.is_empty()).then(|| {
+        let items = report
+            .held
+            .into_iter()
+            .map(|(title, reason)| view! { <li>{format!("{title}: {reason}")}</li> })
+            .collect_view();
+        view! {
+            <p class="inbox-error">"Zostały na miejscu:"</p>
+            <ul class="intake-held">{items}</ul>
+        }
+    });
+
+    view! {
+        <p class="account-success" role="status">{moved}</p>
+        {held}
+    }
+}
+
+#[component]
+fn StageQueueView(
+    stage: ListingStage,
+    products: Vec<Product>,
+    action: ServerAction<MoveProducts>,
+) -> impl IntoView {
+    if products.is_empty() {
+        return view! { <p>"Na tym etapie nic nie czeka."</p> }.into_any();
+    }
+    let next = stage.next();
+    let mut buttons = Vec::new();
+    if let Some(next) = next {
+        buttons.push((next, format!("Dalej: {}", next.label())));
+    }
+    if stage.in_preparation() && next != Some(ListingStage::Listed) {
+        buttons.push((ListingStage::Listed, "Od razu wystaw".to_string()));
+    }
+    if stage == ListingStage::Listed {
+        buttons.push((
+            ListingStage::Pricing,
+            "Zdejmij ze sprzedaży do wyceny".to_string(),
+        ));
+    }
+    let buttons = buttons
+        .into_iter()
+        .map(|(to, label)| {
+            view! {
+                <button type="submit" name="to" value=to.param()>
+                    {label}
+                </button>
+            }
+        })
+        .collect_view();
+    let movable = stage != ListingStage::Sold;
+    let rows = products
+        .into_iter()
+        .enumerate()
+        .map(|(i, product)| {
+            let missing = next
+                .and_then(|next| missing_for(&product, next))
+                .map(|problem| view! { <small class="inbox-error">{problem.to_string()}</small> });
+            let details = [
+                (!product.size.is_empty()).then(|| format!("rozmiar {}", product.size)),
+                (!product.measurements.is_empty())
+                    .then(|| format!("wymiary: {}", product.measurements.len())),
+                (!product.images.is_empty()).then(|| format!("zdjęcia: {}", product.images.len())),
+                (product.price.grosze() > 0).then(|| product.price.to_string()),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" · ");
+            view! {
+                <tr>
+                    <td>
+                        {movable
+                            .then(|| {
+                                view! {
+                                    <input
+                                        type="checkbox"
+                                        name=format!("product_ids[{i}]")
+                                        value=product.id
+                                        checked=true
+                                    />
+                                }
+                            })}
+                    </td>
+                    <td>
+                        <img src=product.cover_image().to_string() alt="" width="60" height="80" />
+                    </td>
+                    <td>
+                        <a href=format!("/admin/products/{}", product.id)>{product.title.clone()}</a>
+                        <br />
+                        <small>
+                            {format!(
+                                "nr {} · {} · {}",
+                                product.id,
+                                product.category.label(),
+                                product.condition.label(),
+                            )}
+                        </small>
+                    </td>
+                    <td>{details} <br /> {missing}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <ActionForm action attr:class="intake-queue">
+            <table class="inbox-threads">
+                <thead>
+                    <tr>
+                        <th></th>
+                        <th>"Zdjęcie"</th>
+                        <th>"Rzecz"</th>
+                        <th>"Gotowe"</th>
+                    </tr>
+                </thead>
+                <tbody>{rows}</tbody>
+            </table>
+            {movable
+                .then(|| view! { <div class="inbox-status-actions">"Zaznaczone: " {buttons}</div> })}
+        </ActionForm>
+    }
+    .into_any()
+}
+
+#[component]
+fn TakeInForm(action: ServerAction<TakeInItems>) -> impl IntoView {
+    let categories = Category::ALL
+        .into_iter()
+        .map(|category| view! { <option value=category.param()>{category.label()}</option> })
+        .collect_view();
+    let conditions = Condition::ALL
+        .into_iter()
+        .map(|condition| {
+            view! {
+                <option value=condition.param() selected=(condition == Condition::Good)>
+                    {condition.label()}
+                </option>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <ActionForm action attr:class="account-form product-form">
+            <label for="intake-category">"Kategoria"</label>
+            <select id="intake-category" name="category">{categories}</select>
+            <label for="intake-condition">"Stan (do poprawienia przy kontroli jakości)"</label>
+            <select id="intake-condition" name="condition">{conditions}</select>
+            <label for="intake-titles">"Rzeczy, jedna nazwa w wierszu"</label>
+            <textarea
+                id="intake-titles"
+                name="titles"
+                rows="8"
+                placeholder="Wełniany sweter w warkocze\nJeansy z wysokim stanem"
+            ></textarea>
+            <button type="submit" disabled=move || action.pending().get()>"Przyjmij"</button>
+        </ActionForm>
+    }
+}
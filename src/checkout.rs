@@ -0,0 +1,1257 @@
+//! Multi-step checkout at `/checkout`: contact details, shipping address, delivery,
+//! payment and a final review. Placing the order claims the cart's items and creates an
+//! order waiting for payment; guests and signed-in customers go through the same steps,
+//! but signed-in customers can pick one of their saved addresses.
+
+use crate::account::{AddressKind, CheckoutProfile, SavedAddress, get_checkout_profile};
+use crate::cart::{CartItem, use_cart};
+use crate::catalog::Price;
+use crate::lockers::{Locker, LockerPicker};
+use crate::payments::{PaymentStart, TransferDetails};
+use crate::shipping::{ShippingQuote, ShippingRates, get_shipping_rates};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMethod {
+    #[default]
+    Courier,
+    InpostLocker,
+    PersonalPickup,
+}
+
+impl DeliveryMethod {
+    pub const ALL: [DeliveryMethod; 3] = [
+        DeliveryMethod::Courier,
+        DeliveryMethod::InpostLocker,
+        DeliveryMethod::PersonalPickup,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DeliveryMethod::Courier => "Kurier",
+            DeliveryMethod::InpostLocker => "Paczkomat InPost",
+            DeliveryMethod::PersonalPickup => "Odbiór osobisty",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    #[default]
+    OnlineTransfer,
+    Blik,
+    BankTransfer,
+}
+
+impl PaymentMethod {
+    pub const ALL: [PaymentMethod; 3] = [
+        PaymentMethod::OnlineTransfer,
+        PaymentMethod::Blik,
+        PaymentMethod::BankTransfer,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaymentMethod::OnlineTransfer => "Szybka płatność online",
+            PaymentMethod::Blik => "BLIK",
+            PaymentMethod::BankTransfer => "Tradycyjny przelew bankowy",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Address {
+    pub street: String,
+    pub postcode: String,
+    pub city: String,
+}
+
+impl Address {
+    pub fn trimmed(&self) -> Address {
+        Address {
+            street: self.street.trim().to_string(),
+            postcode: self.postcode.trim().to_string(),
+            city: self.city.trim().to_string(),
+        }
+    }
+
+    /// Problems with the address, reported for fields named `{prefix}street` and so on.
+    pub fn errors(&self, prefix: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let field = |name: &str| format!("{prefix}{name}");
+        if self.street.trim().is_empty() {
+            errors.push(FieldError::new(&field("street"), "Podaj ulicę i numer."));
+        }
+        if self.postcode.trim().is_empty() {
+            errors.push(FieldError::new(&field("postcode"), "Podaj kod pocztowy."));
+        } else if !is_postcode(&self.postcode) {
+            errors.push(FieldError::new(
+                &field("postcode"),
+                "Kod pocztowy ma postać 00-000.",
+            ));
+        }
+        if self.city.trim().is_empty() {
+            errors.push(FieldError::new(&field("city"), "Podaj miejscowość."));
+        }
+        errors
+    }
+}
+
+/// Company details for a VAT invoice.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InvoiceDetails {
+    pub company: String,
+    /// Tax number, kept as its 10 digits.
+    pub nip: String,
+    pub address: Address,
+}
+
+impl InvoiceDetails {
+    /// Trimmed, with the NIP reduced to its digits when it is valid.
+    pub fn trimmed(&self) -> InvoiceDetails {
+        InvoiceDetails {
+            company: self.company.trim().to_string(),
+            nip: normalize_nip(&self.nip).unwrap_or_else(|| self.nip.trim().to_string()),
+            address: self.address.trimmed(),
+        }
+    }
+
+    /// Problems with the fields, named `invoice_company`, `invoice_nip` and so on.
+    pub fn errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.company.trim().is_empty() {
+            errors.push(FieldError::new("invoice_company", "Podaj nazwę firmy."));
+        }
+        if self.nip.trim().is_empty() {
+            errors.push(FieldError::new("invoice_nip", "Podaj NIP."));
+        } else if normalize_nip(&self.nip).is_none() {
+            errors.push(FieldError::new(
+                "invoice_nip",
+                "Ten NIP jest nieprawidłowy. Sprawdź, czy nie ma w nim literówki.",
+            ));
+        }
+        errors.extend(self.address.errors("invoice_"));
+        errors
+    }
+}
+
+/// Everything the customer fills in at checkout.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheckoutDetails {
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub address: Address,
+    pub delivery: DeliveryMethod,
+    /// Code of the chosen InPost locker, used only for locker deliveries.
+    #[serde(default)]
+    pub locker_code: Option<String>,
+    pub payment: PaymentMethod,
+    /// The customer wants a VAT invoice for a company.
+    #[serde(default)]
+    pub wants_invoice: bool,
+    #[serde(default)]
+    pub invoice: InvoiceDetails,
+    /// Pay with the signed-in customer's store credit first.
+    #[serde(default)]
+    pub use_store_credit: bool,
+    pub accept_terms: bool,
+}
+
+/// Validation problem with one form field, identified by the field's `name`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub(crate) fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckoutStep {
+    Contact,
+    Address,
+    Delivery,
+    Payment,
+    Review,
+}
+
+impl CheckoutStep {
+    pub const ALL: [CheckoutStep; 5] = [
+        CheckoutStep::Contact,
+        CheckoutStep::Address,
+        CheckoutStep::Delivery,
+        CheckoutStep::Payment,
+        CheckoutStep::Review,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CheckoutStep::Contact => "Dane kontaktowe",
+            CheckoutStep::Address => "Adres",
+            CheckoutStep::Delivery => "Dostawa",
+            CheckoutStep::Payment => "Płatność",
+            CheckoutStep::Review => "Podsumowanie",
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = CheckoutStep::ALL
+            .iter()
+            .position(|s| *s == self)
+            .unwrap_or(0);
+        CheckoutStep::ALL[(index + 1).min(CheckoutStep::ALL.len() - 1)]
+    }
+
+    fn previous(self) -> Self {
+        let index = CheckoutStep::ALL
+            .iter()
+            .position(|s| *s == self)
+            .unwrap_or(0);
+        CheckoutStep::ALL[index.saturating_sub(1)]
+    }
+
+    /// Step on which `field` is filled in.
+    fn of_field(field: &str) -> Self {
+        match field {
+            "email" | "name" | "phone" => CheckoutStep::Contact,
+            "street" | "postcode" | "city" => CheckoutStep::Address,
+            field if field.starts_with("invoice_") => CheckoutStep::Address,
+            "delivery" | "locker" => CheckoutStep::Delivery,
+            "payment" => CheckoutStep::Payment,
+            _ => CheckoutStep::Review,
+        }
+    }
+}
+
+impl CheckoutDetails {
+    /// Problems with the fields filled in on `step`.
+    pub fn errors(&self, step: CheckoutStep) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut require = |field: &str, value: &str, message: &str| {
+            if value.trim().is_empty() {
+                errors.push(FieldError::new(field, message));
+            }
+        };
+        match step {
+            CheckoutStep::Contact => {
+                require("name", &self.name, "Podaj imię i nazwisko.");
+                require("phone", &self.phone, "Podaj numer telefonu.");
+                if !self.phone.trim().is_empty() && !is_phone(&self.phone) {
+                    errors.push(FieldError::new(
+                        "phone",
+                        "Podaj poprawny numer telefonu, np. 600 100 200.",
+                    ));
+                }
+                if !is_email(&self.email) {
+                    errors.push(FieldError::new("email", "Podaj poprawny adres e-mail."));
+                }
+            }
+            CheckoutStep::Address => {
+                errors.extend(self.address.errors(""));
+                if self.wants_invoice {
+                    errors.extend(self.invoice.errors());
+                }
+            }
+            CheckoutStep::Delivery => {
+                let has_locker = self
+                    .locker_code
+                    .as_ref()
+                    .is_some_and(|code| !code.trim().is_empty());
+                if self.delivery == DeliveryMethod::InpostLocker && !has_locker {
+                    errors.push(FieldError::new("locker", "Wybierz paczkomat."));
+                }
+            }
+            CheckoutStep::Payment => {}
+            CheckoutStep::Review => {
+                if !self.accept_terms {
+                    errors.push(FieldError::new(
+                        "accept_terms",
+                        "Aby złożyć zamówienie, zaakceptuj regulamin.",
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    pub fn all_errors(&self) -> Vec<FieldError> {
+        CheckoutStep::ALL
+            .into_iter()
+            .flat_map(|step| self.errors(step))
+            .collect()
+    }
+}
+
+/// Polish postcode, e.g. `00-950`.
+pub fn is_postcode(postcode: &str) -> bool {
+    let postcode = postcode.trim().as_bytes();
+    postcode.len() == 6
+        && postcode[2] == b'-'
+        && postcode
+            .iter()
+            .enumerate()
+            .all(|(i, c)| i == 2 || c.is_ascii_digit())
+}
+
+/// A Polish number of 9 digits, optionally with the `+48` prefix, or any international
+/// number in the `+` form. Spaces, dashes and brackets are allowed.
+pub fn is_phone(phone: &str) -> bool {
+    let phone: String = phone
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')'))
+        .collect();
+    let all_digits = |digits: &str| digits.chars().all(|c| c.is_ascii_digit());
+    if let Some(number) = phone
+        .strip_prefix("+48")
+        .or_else(|| phone.strip_prefix("0048"))
+    {
+        number.len() == 9 && all_digits(number)
+    } else if let Some(number) = phone.strip_prefix('+') {
+        (8..=15).contains(&number.len()) && all_digits(number)
+    } else {
+        phone.len() == 9 && all_digits(&phone)
+    }
+}
+
+/// The 10 digits of a valid NIP, which may be written with dashes, spaces or the `PL`
+/// prefix. The last digit is a checksum of the others.
+pub fn normalize_nip(nip: &str) -> Option<String> {
+    const WEIGHTS: [u32; 9] = [6, 5, 7, 2, 3, 4, 5, 6, 7];
+
+    let nip = nip.trim();
+    let nip = nip
+        .strip_prefix("PL")
+        .or_else(|| nip.strip_prefix("pl"))
+        .unwrap_or(nip);
+    let digits: Vec<u32> = nip
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-'))
+        .map(|c| c.to_digit(10))
+        .collect::<Option<_>>()?;
+    if digits.len() != 10 {
+        return None;
+    }
+    let checksum = WEIGHTS
+        .iter()
+        .zip(&digits)
+        .map(|(weight, digit)| weight * digit)
+        .sum::<u32>()
+        % 11;
+    // Suma kontrolna 10 nie jest przydzielana, więc taki numer nie istnieje.
+    (checksum == digits[9]).then(|| digits.iter().map(|d| d.to_string()).collect())
+}
+
+pub(crate) fn is_email(email: &str) -> bool {
+    let email = email.trim();
+    match email.split_once('@') {
+        Some((user, domain)) => {
+            !user.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CheckoutOutcome {
+    Placed {
+        order_id: i64,
+        /// What is left to pay after store credit.
+        total: Price,
+        /// How to pay, or `None` if the payment could not be started.
+        payment: Option<PaymentStart>,
+    },
+    Invalid(Vec<FieldError>),
+    /// Some items were sold or are held by another cart in the meantime.
+    Unavailable(Vec<u32>),
+    EmptyCart,
+}
+
+#[server]
+pub async fn place_order(
+    cart_id: Option<String>,
+    product_ids: Vec<u32>,
+    details: CheckoutDetails,
+) -> Result<CheckoutOutcome, ServerFnError> {
+    use crate::db::{
+        LockerRepository, NewOrder, OrderRepository, PlaceOrder, ProductRepository, use_db,
+    };
+    use crate::mail::{self, Email};
+    use crate::orders::{Actor, OrderStatus, lifecycle};
+    use crate::payments::{gateway::Payments, start_payment};
+    use crate::shipping::use_shipping_rates;
+    use chrono::Utc;
+
+    let errors = details.all_errors();
+    if !errors.is_empty() {
+        return Ok(CheckoutOutcome::Invalid(errors));
+    }
+    let mut product_ids = product_ids;
+    product_ids.sort_unstable();
+    product_ids.dedup();
+    if product_ids.is_empty() {
+        return Ok(CheckoutOutcome::EmptyCart);
+    }
+
+    let db = use_db()?;
+    let locker_code = match (details.delivery, &details.locker_code) {
+        (DeliveryMethod::InpostLocker, Some(code)) => match db.find_locker(code)? {
+            Some(locker) => Some(locker.code),
+            None => {
+                return Ok(CheckoutOutcome::Invalid(vec![FieldError::new(
+                    "locker",
+                    "Nie znaleźliśmy takiego paczkomatu. Wybierz inny.",
+                )]));
+            }
+        },
+        _ => None,
+    };
+
+    // Koszt dostawy liczymy od cen i wag z katalogu, nie z koszyka.
+    let rates = use_shipping_rates()?;
+    let mut items = Vec::with_capacity(product_ids.len());
+    for &product_id in &product_ids {
+        match db.find_product(product_id)? {
+            Some(product) => items.push((product.price, product.weight_grams)),
+            None => return Ok(CheckoutOutcome::Unavailable(vec![product_id])),
+        }
+    }
+    let Some(quote) = rates.quote(details.delivery, &rates.parcel(items)) else {
+        return Ok(CheckoutOutcome::Invalid(vec![FieldError::new(
+            "delivery",
+            "Wybrana forma dostawy nie jest dostępna dla tego zamówienia.",
+        )]));
+    };
+
+    let now = Utc::now();
+    let order = NewOrder {
+        customer_id: crate::account::auth::current_customer(&db)?.map(|customer| customer.id),
+        email: details.email.trim().to_string(),
+        name: details.name.trim().to_string(),
+        phone: details.phone.trim().to_string(),
+        address: details.address.trimmed(),
+        delivery: details.delivery,
+        locker_code,
+        payment: details.payment,
+        shipping: quote.price,
+        invoice: details.wants_invoice.then(|| details.invoice.trimmed()),
+        terms_accepted_at: Some(now),
+        use_store_credit: details.use_store_credit,
+    };
+    let cart_id = cart_id.unwrap_or_default();
+    match db.place_order(&order, &cart_id, &product_ids, now)? {
+        PlaceOrder::Placed(order) => {
+            let confirmation = Email::OrderConfirmation((*order).clone());
+            if let Err(e) = mail::queue(&db, &order.email, &confirmation) {
+                leptos::logging::log!("nie udało się zakolejkować potwierdzenia: {e}");
+            }
+            let payment = if order.amount_due() == Price(0) {
+                let note = "opłacone środkami w sklepie";
+                lifecycle::transition(&db, order.id, OrderStatus::Paid, &Actor::System, Some(note))
+                    .map_err(ServerFnError::new)?;
+                Some(PaymentStart::StoreCredit)
+            } else {
+                let payments = use_context::<Payments>()
+                    .ok_or_else(|| ServerFnError::new("płatności nie są dostępne"))?;
+                // Zamówienie już istnieje, więc błąd płatności go nie cofa.
+                match start_payment(&db, &payments, &order).await {
+                    Ok(start) => Some(start),
+                    Err(e) => {
+                        leptos::logging::log!("nie udało się rozpocząć płatności: {e}");
+                        None
+                    }
+                }
+            };
+            Ok(CheckoutOutcome::Placed {
+                order_id: order.id,
+                total: order.amount_due(),
+                payment,
+            })
+        }
+        PlaceOrder::Unavailable(ids) => Ok(CheckoutOutcome::Unavailable(ids)),
+    }
+}
+
+#[component]
+pub fn CheckoutPage() -> impl IntoView {
+    let cart = use_cart();
+    let details = RwSignal::new(CheckoutDetails::default());
+    let locker = RwSignal::new(None::<Locker>);
+    let rates = RwSignal::new(None::<ShippingRates>);
+    Effect::new(move |_| {
+        spawn_local(async move {
+            if let Ok(loaded) = get_shipping_rates().await {
+                rates.set(Some(loaded));
+            }
+        });
+    });
+    let profile = RwSignal::new(None::<CheckoutProfile>);
+    Effect::new(move |_| {
+        spawn_local(async move {
+            if let Ok(Some(loaded)) = get_checkout_profile().await {
+                details.update(|d| {
+                    for (field, value) in [
+                        (&mut d.email, &loaded.email),
+                        (&mut d.name, &loaded.name),
+                        (&mut d.phone, &loaded.phone),
+                    ] {
+                        if field.is_empty() {
+                            field.clone_from(value);
+                        }
+                    }
+                });
+                profile.set(Some(loaded));
+            }
+        });
+    });
+    Effect::new(move || {
+        let code = locker.with(|l| l.as_ref().map(|l| l.code.clone()));
+        details.update(|d| d.locker_code = code);
+    });
+    let step = RwSignal::new(CheckoutStep::Contact);
+    let errors = RwSignal::new(Vec::<FieldError>::new());
+    let problem = RwSignal::new(None::<String>);
+    let placing = RwSignal::new(false);
+    let placed = RwSignal::new(None::<(i64, Price, Option<PaymentStart>)>);
+
+    let next = move |_| {
+        let found = details.with(|d| d.errors(step.get_untracked()));
+        if found.is_empty() {
+            step.update(|step| *step = step.next());
+        }
+        errors.set(found);
+    };
+    let back = move |_| {
+        errors.set(Vec::new());
+        step.update(|step| *step = step.previous());
+    };
+    let submit = move |_| {
+        let checked = details.with(|d| d.errors(CheckoutStep::Review));
+        if !checked.is_empty() {
+            errors.set(checked);
+            return;
+        }
+        errors.set(Vec::new());
+        problem.set(None);
+        placing.set(true);
+        let (cart_id, product_ids) = (cart.cart_id(), cart.product_ids());
+        let details = details.get_untracked();
+        spawn_local(async move {
+            match place_order(cart_id, product_ids, details).await {
+                Ok(CheckoutOutcome::Placed {
+                    order_id,
+                    total,
+                    payment,
+                }) => {
+                    cart.clear();
+                    if let Some(PaymentStart::Redirect { url }) = &payment {
+                        let _ = window().location().set_href(url);
+                    }
+                    placed.set(Some((order_id, total, payment)));
+                }
+                Ok(CheckoutOutcome::Invalid(found)) => {
+                    if let Some(first) = found.first() {
+                        step.set(CheckoutStep::of_field(&first.field));
+                    }
+                    errors.set(found);
+                }
+                Ok(CheckoutOutcome::Unavailable(ids)) => {
+                    let titles: Vec<String> = cart
+                        .items()
+                        .into_iter()
+                        .filter(|item| ids.contains(&item.product_id))
+                        .map(|item| item.title)
+                        .collect();
+                    problem.set(Some(format!(
+                        "Niektóre produkty nie są już dostępne: {}. Usuń je z koszyka i spróbuj ponownie.",
+                        titles.join(", ")
+                    )));
+                }
+                Ok(CheckoutOutcome::EmptyCart) => {
+                    problem.set(Some("Twój koszyk jest pusty.".to_string()));
+                }
+                Err(_) => problem.set(Some(
+                    "Nie udało się złożyć zamówienia. Spróbuj ponownie za chwilę.".to_string(),
+                )),
+            }
+            placing.set(false);
+        });
+    };
+
+    let current_step = move || match step.get() {
+        CheckoutStep::Contact => view! { <ContactStep details errors /> }.into_any(),
+        CheckoutStep::Address => view! { <AddressStep details errors profile /> }.into_any(),
+        CheckoutStep::Delivery => view! { <DeliveryStep details errors locker rates /> }.into_any(),
+        CheckoutStep::Payment => view! { <PaymentStep details profile /> }.into_any(),
+        CheckoutStep::Review => {
+            view! { <ReviewStep details errors locker rates profile /> }.into_any()
+        }
+    };
+
+    view! {
+        <Title text="Zamówienie - Meg Joni" />
+        <main>
+            <section class="checkout-page">
+                <h2>"Zamówienie"</h2>
+                {move || match placed.get() {
+                    Some((order_id, total, payment)) => view! {
+                        <div class="checkout-confirmation">
+                            <h3>"Dziękujemy za zamówienie!"</h3>
+                            <p>{format!("Numer zamówienia: {order_id}")}</p>
+                            <p>{format!("Do zapłaty: {total}")}</p>
+                            <PaymentNextStep payment />
+                            <p>"Potwierdzenie wyślemy na podany adres e-mail."</p>
+                        </div>
+                    }
+                    .into_any(),
+                    None if cart.is_empty() => view! {
+                        <p>"Twój koszyk jest pusty."</p>
+                        <a href="/woman">
+                            <button>"Przejdź do sklepu"</button>
+                        </a>
+                    }
+                    .into_any(),
+                    None => view! {
+                        <ol class="checkout-steps">
+                            {CheckoutStep::ALL
+                                .into_iter()
+                                .map(|s| {
+                                    view! {
+                                        <li
+                                            class:current=move || step.get() == s
+                                            class:done=move || { step.get() > s }
+                                        >
+                                            {s.label()}
+                                        </li>
+                                    }
+                                })
+                                .collect_view()}
+                        </ol>
+                        <div class="checkout-step">{current_step}</div>
+                        {move || problem.get().map(|message| view! {
+                            <p class="checkout-problem">
+                                {message} " " <a href="/cart">"Przejdź do koszyka"</a>
+                            </p>
+                        })}
+                        <div class="checkout-actions">
+                            <Show when=move || step.get() != CheckoutStep::Contact>
+                                <button class="checkout-back" on:click=back>"Wstecz"</button>
+                            </Show>
+                            <Show
+                                when=move || step.get() == CheckoutStep::Review
+                                fallback=move || view! { <button on:click=next>"Dalej"</button> }
+                            >
+                                <button on:click=submit disabled=move || placing.get()>
+                                    {move || {
+                                        if placing.get() {
+                                            "Składanie zamówienia..."
+                                        } else {
+                                            "Zamawiam z obowiązkiem zapłaty"
+                                        }
+                                    }}
+                                </button>
+                            </Show>
+                        </div>
+                    }
+                    .into_any(),
+                }}
+            </section>
+        </main>
+    }
+}
+
+/// What to do after the order is placed: go to the payment page or make a transfer.
+#[component]
+fn PaymentNextStep(payment: Option<PaymentStart>) -> impl IntoView {
+    match payment {
+        Some(PaymentStart::Redirect { url }) => view! {
+            <p>
+                "Przekierowujemy do płatności... "
+                <a href=url rel="external">"Przejdź do płatności"</a>
+            </p>
+        }
+        .into_any(),
+        Some(PaymentStart::BankTransfer(instructions)) => view! {
+            <p>"Prosimy o przelew na poniższe dane:"</p>
+            <TransferDetails instructions />
+        }
+        .into_any(),
+        Some(PaymentStart::StoreCredit) => {
+            view! { <p>"Zamówienie w całości opłaciłeś(-aś) środkami w sklepie."</p> }.into_any()
+        }
+        None => view! {
+            <p>
+                "Nie udało się rozpocząć płatności. Skontaktujemy się z Tobą w sprawie "
+                "dokończenia zamówienia."
+            </p>
+        }
+        .into_any(),
+    }
+}
+
+/// Message for `field`, if the last validation found a problem with it.
+fn field_error(errors: RwSignal<Vec<FieldError>>, field: &'static str) -> impl IntoView {
+    move || {
+        errors.with(|errors| {
+            errors
+                .iter()
+                .find(|error| error.field == field)
+                .map(|error| view! { <p class="field-error">{error.message.clone()}</p> })
+        })
+    }
+}
+
+/// Labelled text input bound to one string in the checkout details.
+#[component]
+fn TextField(
+    details: RwSignal<CheckoutDetails>,
+    errors: RwSignal<Vec<FieldError>>,
+    label: &'static str,
+    name: &'static str,
+    autocomplete: &'static str,
+    #[prop(default = "text")] kind: &'static str,
+    get: fn(&CheckoutDetails) -> &String,
+    set: fn(&mut CheckoutDetails) -> &mut String,
+) -> impl IntoView {
+    let id = format!("checkout-{name}");
+
+    view! {
+        <div class="form-field">
+            <label for=id.clone()>{label}</label>
+            <input
+                id=id
+                type=kind
+                name=name
+                autocomplete=autocomplete
+                prop:value=move || details.with(|d| get(d).clone())
+                on:input=move |ev| details.update(|d| *set(d) = event_target_value(&ev))
+            />
+            {field_error(errors, name)}
+        </div>
+    }
+}
+
+#[component]
+fn ContactStep(
+    details: RwSignal<CheckoutDetails>,
+    errors: RwSignal<Vec<FieldError>>,
+) -> impl IntoView {
+    view! {
+        <h3>"Dane kontaktowe"</h3>
+        <TextField
+            details
+            errors
+            label="Adres e-mail"
+            name="email"
+            kind="email"
+            autocomplete="email"
+            get=|d| &d.email
+            set=|d| &mut d.email
+        />
+        <TextField
+            details
+            errors
+            label="Imię i nazwisko"
+            name="name"
+            autocomplete="name"
+            get=|d| &d.name
+            set=|d| &mut d.name
+        />
+        <TextField
+            details
+            errors
+            label="Telefon"
+            name="phone"
+            kind="tel"
+            autocomplete="tel"
+            get=|d| &d.phone
+            set=|d| &mut d.phone
+        />
+    }
+}
+
+#[component]
+fn AddressStep(
+    details: RwSignal<CheckoutDetails>,
+    errors: RwSignal<Vec<FieldError>>,
+    profile: RwSignal<Option<CheckoutProfile>>,
+) -> impl IntoView {
+    let saved = move |kind: AddressKind| {
+        profile.with(|profile| {
+            profile.as_ref().map_or_else(Vec::new, |profile| {
+                profile
+                    .addresses
+                    .iter()
+                    .filter(|saved| saved.kind == kind)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+        })
+    };
+
+    view! {
+        <h3>"Adres dostawy"</h3>
+        <SavedAddressPicker
+            addresses=Signal::derive(move || saved(AddressKind::Shipping))
+            label="Zapisany adres"
+            name="saved_address"
+            choose=move |saved: SavedAddress| {
+                details.update(|d| {
+                    d.name = saved.name;
+                    d.phone = saved.phone;
+                    d.address = saved.address;
+                })
+            }
+        />
+        <TextField
+            details
+            errors
+            label="Ulica i numer"
+            name="street"
+            autocomplete="street-address"
+            get=|d| &d.address.street
+            set=|d| &mut d.address.street
+        />
+        <TextField
+            details
+            errors
+            label="Kod pocztowy"
+            name="postcode"
+            autocomplete="postal-code"
+            get=|d| &d.address.postcode
+            set=|d| &mut d.address.postcode
+        />
+        <TextField
+            details
+            errors
+            label="Miejscowość"
+            name="city"
+            autocomplete="address-level2"
+            get=|d| &d.address.city
+            set=|d| &mut d.address.city
+        />
+        <label class="checkout-terms">
+            <input
+                type="checkbox"
+                name="wants_invoice"
+                prop:checked=move || details.with(|d| d.wants_invoice)
+                on:change=move |ev| details.update(|d| d.wants_invoice = event_target_checked(&ev))
+            />
+            "Chcę otrzymać fakturę na firmę"
+        </label>
+        <Show when=move || details.with(|d| d.wants_invoice)>
+            <SavedAddressPicker
+                addresses=Signal::derive(move || saved(AddressKind::Billing))
+                label="Zapisane dane do faktury"
+                name="saved_invoice"
+                choose=move |saved: SavedAddress| details.update(|d| d.invoice = saved.invoice())
+            />
+            <TextField
+                details
+                errors
+                label="Nazwa firmy"
+                name="invoice_company"
+                autocomplete="organization"
+                get=|d| &d.invoice.company
+                set=|d| &mut d.invoice.company
+            />
+            <TextField
+                details
+                errors
+                label="NIP"
+                name="invoice_nip"
+                autocomplete="off"
+                get=|d| &d.invoice.nip
+                set=|d| &mut d.invoice.nip
+            />
+            <TextField
+                details
+                errors
+                label="Ulica i numer"
+                name="invoice_street"
+                autocomplete="off"
+                get=|d| &d.invoice.address.street
+                set=|d| &mut d.invoice.address.street
+            />
+            <TextField
+                details
+                errors
+                label="Kod pocztowy"
+                name="invoice_postcode"
+                autocomplete="off"
+                get=|d| &d.invoice.address.postcode
+                set=|d| &mut d.invoice.address.postcode
+            />
+            <TextField
+                details
+                errors
+                label="Miejscowość"
+                name="invoice_city"
+                autocomplete="off"
+                get=|d| &d.invoice.address.city
+                set=|d| &mut d.invoice.address.city
+            />
+        </Show>
+    }
+}
+
+/// Fills in the form from one of the customer's saved addresses; hidden when they have
+/// none.
+#[component]
+fn SavedAddressPicker(
+    #[prop(into)] addresses: Signal<Vec<SavedAddress>>,
+    label: &'static str,
+    name: &'static str,
+    choose: impl Fn(SavedAddress) + Copy + Send + Sync + 'static,
+) -> impl IntoView {
+    let id = format!("checkout-{name}");
+    let on_change = move |ev| {
+        let chosen = event_target_value(&ev).parse::<i64>().ok();
+        let saved = addresses.with(|addresses| {
+            addresses
+                .iter()
+                .find(|saved| Some(saved.id) == chosen)
+                .cloned()
+        });
+        if let Some(saved) = saved {
+            choose(saved);
+        }
+    };
+    let options = move || {
+        addresses
+            .get()
+            .into_iter()
+            .map(|saved| {
+                let title = match saved.kind {
+                    AddressKind::Shipping => saved.name,
+                    AddressKind::Billing => saved.company,
+                };
+                let text = format!(
+                    "{title}, {}, {} {}",
+                    saved.address.street, saved.address.postcode, saved.address.city
+                );
+                view! { <option value=saved.id>{text}</option> }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <Show when=move || addresses.with(|addresses| !addresses.is_empty())>
+            <div class="form-field">
+                <label for=id.clone()>{label}</label>
+                <select id=id.clone() name=name on:change=on_change>
+                    <option value="">"Wpisz nowe dane"</option>
+                    {options}
+                </select>
+            </div>
+        </Show>
+    }
+}
+
+#[component]
+fn DeliveryStep(
+    details: RwSignal<CheckoutDetails>,
+    errors: RwSignal<Vec<FieldError>>,
+    locker: RwSignal<Option<Locker>>,
+    rates: RwSignal<Option<ShippingRates>>,
+) -> impl IntoView {
+    let cart = use_cart();
+    let options = move || {
+        rates.with(|rates| {
+            let Some(rates) = rates else {
+                return view! { <p>"Wczytywanie cennika dostaw..."</p> }.into_any();
+            };
+            let parcel = rates.cart_parcel(&cart.items());
+            rates
+                .available()
+                .map(|carrier| {
+                    let method = carrier.method;
+                    let quote = rates.quote(method, &parcel);
+                    view! { <DeliveryOption details method quote /> }
+                })
+                .collect_view()
+                .into_any()
+        })
+    };
+
+    view! {
+        <h3>"Sposób dostawy"</h3>
+        {options}
+        {field_error(errors, "delivery")}
+        <Show when=move || details.with(|d| d.delivery == DeliveryMethod::InpostLocker)>
+            <LockerPicker selected=locker />
+            {field_error(errors, "locker")}
+        </Show>
+    }
+}
+
+#[component]
+fn DeliveryOption(
+    details: RwSignal<CheckoutDetails>,
+    method: DeliveryMethod,
+    quote: Option<ShippingQuote>,
+) -> impl IntoView {
+    let available = quote.is_some();
+    let price = match &quote {
+        Some(quote) if quote.free => "Gratis".to_string(),
+        Some(quote) => quote.price.to_string(),
+        None => "Niedostępne dla tej przesyłki".to_string(),
+    };
+    let bracket = quote.map(|quote| quote.bracket);
+
+    view! {
+        <label class="checkout-option" class:unavailable=!available>
+            <input
+                type="radio"
+                name="delivery"
+                disabled=!available
+                prop:checked=move || details.with(|d| d.delivery == method)
+                on:change=move |_| details.update(|d| d.delivery = method)
+            />
+            {method.label()}
+            {bracket.map(|bracket| view! { <span class="checkout-option-note">{bracket}</span> })}
+            <span class="checkout-option-price">{price}</span>
+        </label>
+    }
+}
+
+/// Store credit that will pay for an order of `total`, if the customer chose to use it.
+fn store_credit_applied(
+    details: RwSignal<CheckoutDetails>,
+    profile: RwSignal<Option<CheckoutProfile>>,
+    total: Price,
+) -> Price {
+    let balance = profile.with(|p| p.as_ref().map_or(Price(0), |p| p.store_credit));
+    if details.with(|d| d.use_store_credit) {
+        Price(balance.grosze().min(total.grosze()))
+    } else {
+        Price(0)
+    }
+}
+
+#[component]
+fn PaymentStep(
+    details: RwSignal<CheckoutDetails>,
+    profile: RwSignal<Option<CheckoutProfile>>,
+) -> impl IntoView {
+    let store_credit = move || {
+        profile
+            .with(|p| p.as_ref().map(|p| p.store_credit))
+            .filter(|credit| credit.grosze() > 0)
+            .map(|credit| {
+                view! {
+                    <label class="checkout-option">
+                        <input
+                            type="checkbox"
+                            name="use_store_credit"
+                            prop:checked=move || details.with(|d| d.use_store_credit)
+                            on:change=move |ev| {
+                                details.update(|d| d.use_store_credit = event_target_checked(&ev))
+                            }
+                        />
+                        {format!("Zapłać najpierw środkami w sklepie (dostępne: {credit})")}
+                    </label>
+                }
+            })
+    };
+    let options = PaymentMethod::ALL
+        .into_iter()
+        .map(|method| {
+            view! {
+                <label class="checkout-option">
+                    <input
+                        type="radio"
+                        name="payment"
+                        prop:checked=move || details.with(|d| d.payment == method)
+                        on:change=move |_| details.update(|d| d.payment = method)
+                    />
+                    {method.label()}
+                </label>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <h3>"Sposób płatności"</h3>
+        {store_credit}
+        {options}
+    }
+}
+
+#[component]
+fn ReviewStep(
+    details: RwSignal<CheckoutDetails>,
+    errors: RwSignal<Vec<FieldError>>,
+    locker: RwSignal<Option<Locker>>,
+    rates: RwSignal<Option<ShippingRates>>,
+    profile: RwSignal<Option<CheckoutProfile>>,
+) -> impl IntoView {
+    let cart = use_cart();
+    let d = details.get_untracked();
+    let quote = Memo::new(move |_| {
+        rates.with(|rates| {
+            rates.as_ref().and_then(|rates| {
+                let delivery = details.with(|d| d.delivery);
+                rates.quote(delivery, &rates.cart_parcel(&cart.items()))
+            })
+        })
+    });
+    let shipping = move || quote.get().map_or(Price(0), |quote| quote.price);
+    let total = move || Price(cart.subtotal().grosze() + shipping().grosze());
+    let store_credit = move || store_credit_applied(details, profile, total());
+
+    view! {
+        <h3>"Podsumowanie"</h3>
+        <ul class="checkout-review-items">
+            <For each=move || cart.items() key=|item| item.product_id let:item>
+                <ReviewLine item />
+            </For>
+        </ul>
+        <dl class="cart-summary">
+            <dt>"Wartość produktów"</dt>
+            <dd>{move || cart.subtotal().to_string()}</dd>
+            <dt>{format!("Dostawa: {}", d.delivery.label())}</dt>
+            <dd>{move || shipping().to_string()}</dd>
+            <dt class="cart-total">"Razem"</dt>
+            <dd class="cart-total">{move || total().to_string()}</dd>
+            {move || {
+                let credit = store_credit();
+                (credit.grosze() > 0).then(|| view! {
+                    <dt>"Środki w sklepie"</dt>
+                    <dd>{format!("-{credit}")}</dd>
+                    <dt class="cart-total">"Do zapłaty"</dt>
+                    <dd class="cart-total">
+                        {Price(total().grosze() - credit.grosze()).to_string()}
+                    </dd>
+                })
+            }}
+        </dl>
+        <dl class="checkout-review-details">
+            <dt>"Kontakt"</dt>
+            <dd>{format!("{}, {}, {}", d.name, d.email, d.phone)}</dd>
+            <dt>"Adres"</dt>
+            <dd>{format!("{}, {} {}", d.address.street, d.address.postcode, d.address.city)}</dd>
+            {(d.delivery == DeliveryMethod::InpostLocker)
+                .then(|| locker.get_untracked())
+                .flatten()
+                .map(|locker| view! {
+                    <dt>"Paczkomat"</dt>
+                    <dd>{format!("{} - {}", locker.code, locker.address())}</dd>
+                })}
+            {d.wants_invoice.then(|| view! {
+                <dt>"Faktura"</dt>
+                <dd>
+                    {format!(
+                        "{}, NIP {}, {}, {} {}",
+                        d.invoice.company,
+                        d.invoice.nip,
+                        d.invoice.address.street,
+                        d.invoice.address.postcode,
+                        d.invoice.address.city,
+                    )}
+                </dd>
+            })}
+            <dt>"Płatność"</dt>
+            <dd>{d.payment.label()}</dd>
+        </dl>
+        <label class="checkout-terms">
+            <input
+                type="checkbox"
+                name="accept_terms"
+                prop:checked=move || details.with(|d| d.accept_terms)
+                on:change=move |ev| details.update(|d| d.accept_terms = event_target_checked(&ev))
+            />
+            "Akceptuję " <a href="/terms" target="_blank">"regulamin sklepu"</a>
+            " i zapoznałem(-am) się z "
+            <a href="/privacy" target="_blank">"polityką prywatności"</a>
+            "."
+        </label>
+        {field_error(errors, "accept_terms")}
+    }
+}
+
+#[component]
+fn ReviewLine(item: CartItem) -> impl IntoView {
+    view! {
+        <li>
+            <span>{format!("{} (rozm. {})", item.title, item.size)}</span>
+            <span>{item.price.to_string()}</span>
+        </li>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nip_is_normalised_when_its_checksum_is_right() {
+        for (input, expected) in [
+            ("5260250995", Some("5260250995")),
+            ("526-025-09-95", Some("5260250995")),
+            (" PL 526 025 09 95 ", Some("5260250995")),
+            ("pl1234563218", Some("1234563218")),
+            ("5260250994", None),
+            ("1234567890", None),
+            ("526025099", None),
+            ("52602509950", None),
+            ("52602509a5", None),
+            ("", None),
+        ] {
+            assert_eq!(normalize_nip(input).as_deref(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn postcodes_have_two_digits_a_dash_and_three_digits() {
+        for (input, expected) in [
+            ("00-950", true),
+            (" 31-042 ", true),
+            ("00950", false),
+            ("0-0950", false),
+            ("00-95a", false),
+            ("00-9500", false),
+            ("", false),
+        ] {
+            assert_eq!(is_postcode(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn phones_are_polish_or_international() {
+        for (input, expected) in [
+            ("600100200", true),
+            ("600 100 200", true),
+            ("600-100-200", true),
+            ("+48 600 100 200", true),
+            ("0048600100200", true),
+            ("(22) 628 12 34 5", false),
+            ("+49 30 1234567", true),
+            ("60010020", false),
+            ("6001002000", false),
+            ("+48 60010020", false),
+            ("+1234567", false),
+            ("+1234567890123456", false),
+            ("600 100 20x", false),
+            ("", false),
+        ] {
+            assert_eq!(is_phone(input), expected, "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,1022 @@
+//! Complaints about faulty items at `/complaints`. A customer picks an item from a
+//! delivered order, describes the fault, says what they want done and attaches photos;
+//! the case gets a number and a page at `/complaints/:token` where its status shows.
+//!
+//! By law we must answer within [`RESPONSE_DAYS`] days, otherwise the complaint counts
+//! as accepted, so staff see the deadline and overdue cases (see [`admin`]). The form
+//! carries files, so it is posted to a plain axum route rather than a server function.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::catalog::Price;
+use crate::checkout::FieldError;
+use chrono::{DateTime, Datelike, Utc};
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::{use_params_map, use_query_map};
+use serde::{Deserialize, Serialize};
+
+pub mod admin;
+
+/// Days we have to accept or reject a complaint.
+pub const RESPONSE_DAYS: i64 = 14;
+/// How long after delivery faults can be reported.
+pub const LIABILITY_YEARS: u32 = 2;
+pub const MAX_PHOTOS: usize = 5;
+pub const MAX_PHOTO_BYTES: usize = 5 * 1024 * 1024;
+/// Largest form the server accepts: every photo at its largest and room for the text.
+pub const MAX_UPLOAD_BYTES: usize = MAX_PHOTOS * MAX_PHOTO_BYTES + 64 * 1024;
+const MIN_DESCRIPTION_LEN: usize = 20;
+const MAX_DESCRIPTION_LEN: usize = 3000;
+/// Formats accepted in the photo field.
+const PHOTO_TYPES: &str = "image/jpeg,image/png,image/webp";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplaintStatus {
+    #[default]
+    Submitted,
+    InReview,
+    Accepted,
+    Rejected,
+    /// We did what the accepted complaint asked for.
+    Resolved,
+}
+
+impl ComplaintStatus {
+    pub const ALL: [ComplaintStatus; 5] = [
+        ComplaintStatus::Submitted,
+        ComplaintStatus::InReview,
+        ComplaintStatus::Accepted,
+        ComplaintStatus::Rejected,
+        ComplaintStatus::Resolved,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ComplaintStatus::Submitted => "Zgłoszona",
+            ComplaintStatus::InReview => "Rozpatrywana",
+            ComplaintStatus::Accepted => "Uznana",
+            ComplaintStatus::Rejected => "Odrzucona",
+            ComplaintStatus::Resolved => "Zakończona",
+        }
+    }
+
+    /// The value used in links and forms, e.g. `in_review`.
+    pub fn param(self) -> &'static str {
+        match self {
+            ComplaintStatus::Submitted => "submitted",
+            ComplaintStatus::InReview => "in_review",
+            ComplaintStatus::Accepted => "accepted",
+            ComplaintStatus::Rejected => "rejected",
+            ComplaintStatus::Resolved => "resolved",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.param() == param)
+    }
+
+    /// Statuses a complaint in this one may move to.
+    pub fn next(self) -> &'static [ComplaintStatus] {
+        match self {
+            ComplaintStatus::Submitted => &[
+                ComplaintStatus::InReview,
+                ComplaintStatus::Accepted,
+                ComplaintStatus::Rejected,
+            ],
+            ComplaintStatus::InReview => &[ComplaintStatus::Accepted, ComplaintStatus::Rejected],
+            ComplaintStatus::Accepted => &[ComplaintStatus::Resolved],
+            ComplaintStatus::Rejected | ComplaintStatus::Resolved => &[],
+        }
+    }
+
+    /// Whether moving to this status is our answer to the complaint, which must come
+    /// with an explanation for the customer.
+    pub fn is_decision(self) -> bool {
+        matches!(self, ComplaintStatus::Accepted | ComplaintStatus::Rejected)
+    }
+}
+
+/// What the customer wants done about the fault.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Demand {
+    #[default]
+    Repair,
+    Replacement,
+    PriceReduction,
+    Withdrawal,
+}
+
+impl Demand {
+    pub const ALL: [Demand; 4] = [
+        Demand::Repair,
+        Demand::Replacement,
+        Demand::PriceReduction,
+        Demand::Withdrawal,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Demand::Repair => "Naprawa",
+            Demand::Replacement => "Wymiana na rzecz wolną od wad",
+            Demand::PriceReduction => "Obniżenie ceny",
+            Demand::Withdrawal => "Odstąpienie od umowy i zwrot pieniędzy",
+        }
+    }
+
+    pub fn param(self) -> &'static str {
+        match self {
+            Demand::Repair => "repair",
+            Demand::Replacement => "replacement",
+            Demand::PriceReduction => "price_reduction",
+            Demand::Withdrawal => "withdrawal",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|demand| demand.param() == param)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Complaint {
+    pub id: i64,
+    pub order_id: i64,
+    pub product_id: u32,
+    /// Title and price of the item from the order.
+    pub title: String,
+    pub price: Price,
+    /// Identifies the complaint in the customer's link; nobody else knows it.
+    pub token: String,
+    pub description: String,
+    pub demand: Demand,
+    pub status: ComplaintStatus,
+    /// Our answer to the customer; empty until we give one.
+    pub response: String,
+    /// Ids of the attached photos.
+    pub photos: Vec<i64>,
+    pub created_at: DateTime<Utc>,
+    /// When our answer is due by law.
+    pub respond_by: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Complaint {
+    /// The case number customers and staff refer to, e.g. `REK/2026/0042`.
+    pub fn case_number(&self) -> String {
+        format!("REK/{}/{:04}", self.created_at.year(), self.id)
+    }
+
+    /// Whether the deadline passed without an answer.
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.responded_at.is_none() && now > self.respond_by
+    }
+
+    /// Where the photo can be viewed.
+    pub fn photo_href(&self, photo_id: i64) -> String {
+        format!("/complaints/{}/photos/{photo_id}", self.token)
+    }
+}
+
+/// An item of an order as offered on the complaint form.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ComplaintItem {
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+}
+
+/// A delivered order whose items can still be complained about.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ComplaintOrder {
+    pub order_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub items: Vec<ComplaintItem>,
+    /// Complaints already made about this order, newest first.
+    pub complaints: Vec<Complaint>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ComplaintError {
+    Invalid(Vec<FieldError>),
+    /// No order with this number was placed with this e-mail address.
+    NotFound,
+    /// The order has not reached the customer yet, or its items came back.
+    NotDelivered,
+    /// More than [`LIABILITY_YEARS`] passed since delivery.
+    Expired,
+    /// The item already has a complaint that is not closed.
+    AlreadyReported,
+    Unavailable,
+}
+
+impl ComplaintError {
+    fn field(&self, field: &str) -> Option<String> {
+        match self {
+            ComplaintError::Invalid(errors) => errors
+                .iter()
+                .find(|error| error.field == field)
+                .map(|error| error.message.clone()),
+            _ => None,
+        }
+    }
+
+    fn summary(&self) -> String {
+        match self {
+            ComplaintError::Invalid(_) => "Popraw zaznaczone pola.".to_string(),
+            ComplaintError::NotFound => {
+                "Nie znaleźliśmy zamówienia o tym numerze złożonego z tego adresu e-mail."
+                    .to_string()
+            }
+            ComplaintError::NotDelivered => {
+                "Reklamację możesz złożyć, gdy zamówienie do Ciebie dotrze.".to_string()
+            }
+            ComplaintError::Expired => format!(
+                "Od doręczenia tego zamówienia minęło ponad {LIABILITY_YEARS} lata. Napisz do \
+                 nas na kontakt@megjoni.pl, a sprawdzimy, co możemy zrobić."
+            ),
+            ComplaintError::AlreadyReported => {
+                "Ta rzecz ma już otwartą reklamację. Jej status znajdziesz w linku z e-maila."
+                    .to_string()
+            }
+            ComplaintError::Unavailable => {
+                "Coś poszło nie tak. Spróbuj ponownie za chwilę.".to_string()
+            }
+        }
+    }
+}
+
+// Jak w formularzu kontaktowym: błąd musi przejść przez adres URL, więc zapisujemy go
+// jako JSON.
+impl fmt::Display for ComplaintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl FromStr for ComplaintError {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn unavailable(e: impl fmt::Display) -> ComplaintError {
+    leptos::logging::log!("błąd obsługi reklamacji: {e}");
+    ComplaintError::Unavailable
+}
+
+/// The order if it belongs to `customer` or was placed with `email`.
+#[cfg(feature = "ssr")]
+fn find_own_order(
+    db: &crate::db::Db,
+    order_id: i64,
+    email: &str,
+    customer: Option<&crate::db::Customer>,
+) -> Result<crate::db::Order, ComplaintError> {
+    use crate::db::OrderRepository;
+
+    let order = db
+        .find_order(order_id)
+        .map_err(unavailable)?
+        .ok_or(ComplaintError::NotFound)?;
+    let email = email.trim();
+    let by_email = !email.is_empty() && order.email.eq_ignore_ascii_case(email);
+    let by_account = order.customer_id.is_some() && order.customer_id == customer.map(|c| c.id);
+    if by_email || by_account {
+        Ok(order)
+    } else {
+        Err(ComplaintError::NotFound)
+    }
+}
+
+/// The order's items that can be complained about, or why none can.
+#[cfg(feature = "ssr")]
+fn complaint_order(
+    db: &crate::db::Db,
+    order: &crate::db::Order,
+    now: DateTime<Utc>,
+) -> Result<ComplaintOrder, ComplaintError> {
+    use crate::db::ComplaintRepository;
+    use crate::orders::{OrderStatus, RETURN_WINDOW_DAYS};
+
+    if order.status != OrderStatus::Delivered {
+        return Err(ComplaintError::NotDelivered);
+    }
+    // Data doręczenia to początek okna na zwrot.
+    let delivered_at = order
+        .return_window_ends_at
+        .map(|end| end - chrono::Duration::days(RETURN_WINDOW_DAYS))
+        .ok_or(ComplaintError::NotDelivered)?;
+    let liable_until = delivered_at
+        .checked_add_months(chrono::Months::new(12 * LIABILITY_YEARS))
+        .ok_or(ComplaintError::Expired)?;
+    if now > liable_until {
+        return Err(ComplaintError::Expired);
+    }
+    let complaints = db.complaints_for_order(order.id).map_err(unavailable)?;
+    Ok(ComplaintOrder {
+        order_id: order.id,
+        created_at: order.created_at,
+        items: order
+            .lines
+            .iter()
+            .map(|line| ComplaintItem {
+                product_id: line.product_id,
+                title: line.title.clone(),
+                price: line.price,
+            })
+            .collect(),
+        complaints,
+    })
+}
+
+/// The order's items that can be complained about. The signed-in customer's own orders
+/// do not need the e-mail address.
+#[server]
+pub async fn get_complaint_order(
+    order_id: i64,
+    #[server(default)] email: String,
+) -> Result<ComplaintOrder, ServerFnError<ComplaintError>> {
+    use crate::db::use_db;
+
+    let fail = ServerFnError::WrappedServerError;
+    let db = use_db().map_err(|e| fail(unavailable(e)))?;
+    let customer = crate::account::auth::current_customer(&db).map_err(|e| fail(unavailable(e)))?;
+    let order = find_own_order(&db, order_id, &email, customer.as_ref()).map_err(fail)?;
+    complaint_order(&db, &order, Utc::now()).map_err(fail)
+}
+
+/// The signed-in customer's orders that can be complained about.
+#[server]
+pub async fn my_complaint_orders() -> Result<Vec<ComplaintOrder>, ServerFnError> {
+    use crate::db::{OrderRepository, use_db};
+
+    let db = use_db()?;
+    let Some(customer) = crate::account::auth::current_customer(&db)? else {
+        return Ok(Vec::new());
+    };
+    let now = Utc::now();
+    let mut orders = Vec::new();
+    for order in db.orders_for_customer(customer.id)? {
+        match complaint_order(&db, &order, now) {
+            Ok(order) => orders.push(order),
+            Err(ComplaintError::Unavailable) => {
+                return Err(ServerFnError::new("nie udało się wczytać zamówień"));
+            }
+            Err(_) => {}
+        }
+    }
+    Ok(orders)
+}
+
+#[server]
+pub async fn get_complaint(token: String) -> Result<Option<Complaint>, ServerFnError> {
+    use crate::db::{ComplaintRepository, use_db};
+
+    Ok(use_db()?.find_complaint_by_token(token.trim())?)
+}
+
+/// The complaint form as posted, before validation.
+#[cfg(feature = "ssr")]
+#[derive(Default)]
+struct PostedComplaint {
+    order_id: Option<i64>,
+    email: String,
+    product_id: Option<u32>,
+    demand: Option<Demand>,
+    description: String,
+    photos: Vec<crate::db::ComplaintPhoto>,
+    /// Problems found while reading the form, e.g. a file that is not a photo.
+    errors: Vec<FieldError>,
+}
+
+#[cfg(feature = "ssr")]
+impl PostedComplaint {
+    async fn read(multipart: &mut axum::extract::Multipart) -> Self {
+        let mut form = PostedComplaint::default();
+        let too_large = || {
+            FieldError::new(
+                "photos",
+                "Zdjęcia są za duże. Każde może mieć najwyżej 5 MB.",
+            )
+        };
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) => {
+                    leptos::logging::log!("nie udało się odczytać formularza reklamacji: {e}");
+                    form.errors.push(too_large());
+                    break;
+                }
+            };
+            let name = field.name().unwrap_or_default().to_string();
+            if name == "photos" {
+                let Ok(data) = field.bytes().await else {
+                    form.errors.push(too_large());
+                    break;
+                };
+                // Pole bez wybranego pliku przychodzi puste.
+                if !data.is_empty() {
+                    form.add_photo(data.to_vec());
+                }
+                continue;
+            }
+            let Ok(value) = field.text().await else {
+                form.errors.push(too_large());
+                break;
+            };
+            match name.as_str() {
+                "order_id" => form.order_id = value.trim().parse().ok(),
+                "email" => form.email = value,
+                "product_id" => form.product_id = value.trim().parse().ok(),
+                "demand" => form.demand = Demand::from_param(value.trim()),
+                "description" => form.description = value,
+                _ => {}
+            }
+        }
+        form
+    }
+
+    fn add_photo(&mut self, data: Vec<u8>) {
+        if data.len() > MAX_PHOTO_BYTES {
+            self.errors.push(FieldError::new(
+                "photos",
+                "Każde zdjęcie może mieć najwyżej 5 MB.",
+            ));
+            return;
+        }
+        match image_type(&data) {
+            Some(content_type) => self.photos.push(crate::db::ComplaintPhoto {
+                content_type: content_type.to_string(),
+                data,
+            }),
+            None => self.errors.push(FieldError::new(
+                "photos",
+                "Dołącz zdjęcia w formacie JPG, PNG albo WebP.",
+            )),
+        }
+    }
+
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = self.errors.clone();
+        if self.product_id.is_none() {
+            errors.push(FieldError::new(
+                "product_id",
+                "Wybierz rzecz, której dotyczy reklamacja.",
+            ));
+        }
+        if self.demand.is_none() {
+            errors.push(FieldError::new("demand", "Wybierz, czego oczekujesz."));
+        }
+        let length = self.description.trim().chars().count();
+        if length < MIN_DESCRIPTION_LEN {
+            errors.push(FieldError::new(
+                "description",
+                "Opisz wadę (co najmniej 20 znaków).",
+            ));
+        } else if length > MAX_DESCRIPTION_LEN {
+            errors.push(FieldError::new(
+                "description",
+                "Opis może mieć najwyżej 3000 znaków.",
+            ));
+        }
+        if self.photos.is_empty() && !errors.iter().any(|error| error.field == "photos") {
+            errors.push(FieldError::new(
+                "photos",
+                "Dołącz co najmniej jedno zdjęcie wady.",
+            ));
+        } else if self.photos.len() > MAX_PHOTOS {
+            errors.push(FieldError::new(
+                "photos",
+                "Możesz dołączyć najwyżej 5 zdjęć.",
+            ));
+        }
+        errors
+    }
+}
+
+/// The image format recognised from the file's first bytes, so the name or type the
+/// browser gave does not matter.
+#[cfg(feature = "ssr")]
+fn image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.len() > 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Records the complaint and lets the customer and the shop know about it.
+#[cfg(feature = "ssr")]
+fn submit(
+    db: &crate::db::Db,
+    customer: Option<&crate::db::Customer>,
+    form: PostedComplaint,
+    public_url: &str,
+) -> Result<Complaint, ComplaintError> {
+    use crate::db::{ComplaintRepository, NewComplaint};
+    use crate::mail::{self, Email};
+
+    let order_id = form.order_id.ok_or(ComplaintError::NotFound)?;
+    let order = find_own_order(db, order_id, &form.email, customer)?;
+    let now = Utc::now();
+    complaint_order(db, &order, now)?;
+    let mut errors = form.validate();
+    let line = form
+        .product_id
+        .and_then(|id| order.lines.iter().find(|line| line.product_id == id));
+    if line.is_none() && form.product_id.is_some() {
+        errors.push(FieldError::new(
+            "product_id",
+            "Tej rzeczy nie ma w zamówieniu.",
+        ));
+    }
+    let (Some(line), Some(demand), true) = (line, form.demand, errors.is_empty()) else {
+        return Err(ComplaintError::Invalid(errors));
+    };
+
+    let complaint = db
+        .create_complaint(
+            &NewComplaint {
+                order_id: order.id,
+                product_id: line.product_id,
+                title: line.title.clone(),
+                price: line.price,
+                token: uuid::Uuid::new_v4().simple().to_string(),
+                description: form.description.trim().to_string(),
+                demand,
+            },
+            &form.photos,
+            now,
+            now + chrono::Duration::days(RESPONSE_DAYS),
+        )
+        .map_err(unavailable)?
+        .ok_or(ComplaintError::AlreadyReported)?;
+
+    // Reklamacja już jest zapisana; klient zobaczy ją zaraz na stronie zgłoszenia.
+    let link = format!("{public_url}/complaints/{}", complaint.token);
+    let emails = [
+        (
+            order.email.clone(),
+            Email::ComplaintSubmitted {
+                order: order.clone(),
+                complaint: complaint.clone(),
+                link,
+            },
+        ),
+        (
+            mail::shop_inbox(),
+            Email::NewComplaint {
+                order,
+                complaint: complaint.clone(),
+            },
+        ),
+    ];
+    for (recipient, email) in emails {
+        if let Err(e) = mail::queue(db, &recipient, &email) {
+            leptos::logging::log!("nie udało się zakolejkować e-maila o reklamacji: {e}");
+        }
+    }
+    Ok(complaint)
+}
+
+/// Receives the complaint form. Either way the browser is sent back to a page: the new
+/// case, or the form with what went wrong.
+#[cfg(feature = "ssr")]
+pub async fn receive_complaint(
+    axum::Extension(db): axum::Extension<crate::db::Db>,
+    axum::Extension(payments): axum::Extension<crate::payments::gateway::Payments>,
+    headers: axum::http::HeaderMap,
+    mut multipart: axum::extract::Multipart,
+) -> axum::response::Redirect {
+    use axum::response::Redirect;
+    use leptos_router::location::Url;
+
+    let form = PostedComplaint::read(&mut multipart).await;
+    let order_id = form.order_id;
+    let email = form.email.clone();
+    let customer = match crate::account::auth::customer_from_headers(&db, &headers) {
+        Ok(customer) => customer,
+        Err(e) => {
+            let error = unavailable(e);
+            return Redirect::to(&format!(
+                "/complaints?error={}",
+                Url::escape(&error.to_string())
+            ));
+        }
+    };
+    match submit(&db, customer.as_ref(), form, payments.public_url()) {
+        Ok(complaint) => Redirect::to(&format!("/complaints/{}", complaint.token)),
+        Err(error) => {
+            let mut url = format!("/complaints?error={}", Url::escape(&error.to_string()));
+            if let Some(order_id) = order_id {
+                url.push_str(&format!(
+                    "&order={order_id}&email={}",
+                    Url::escape(email.trim())
+                ));
+            }
+            Redirect::to(&url)
+        }
+    }
+}
+
+/// Serves a complaint's photo to whoever has the complaint's link.
+#[cfg(feature = "ssr")]
+pub async fn complaint_photo(
+    axum::extract::Path((token, photo_id)): axum::extract::Path<(String, i64)>,
+    axum::Extension(db): axum::Extension<crate::db::Db>,
+) -> axum::response::Response {
+    use crate::db::ComplaintRepository;
+    use axum::http::{StatusCode, header};
+    use axum::response::IntoResponse;
+
+    match db.complaint_photo(&token, photo_id) {
+        Ok(Some(photo)) => (
+            [
+                (header::CONTENT_TYPE, photo.content_type),
+                (header::CACHE_CONTROL, "private, max-age=86400".to_string()),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            ],
+            photo.data,
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            leptos::logging::log!("nie udało się odczytać zdjęcia reklamacji: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn format_date(at: DateTime<Utc>) -> String {
+    at.format("%d.%m.%Y").to_string()
+}
+
+#[component]
+pub fn ComplaintsPage() -> impl IntoView {
+    let query = use_query_map();
+    let order_id = move || {
+        query
+            .read()
+            .get("order")
+            .and_then(|id| id.trim().trim_start_matches('#').parse::<i64>().ok())
+    };
+    let error = move || {
+        query
+            .read()
+            .get("error")
+            .and_then(|error| error.parse::<ComplaintError>().ok())
+    };
+
+    view! {
+        <Title text="Reklamacje - Meg Joni" />
+        <main>
+            <section class="complaints">
+                <h2>"Reklamacje"</h2>
+                <p>
+                    "Jeśli rzecz okazała się uszkodzona lub niezgodna z opisem, zgłoś reklamację. "
+                    {format!("Odpowiemy w ciągu {RESPONSE_DAYS} dni, a o każdej zmianie poinformujemy Cię e-mailem.")}
+                </p>
+                {move || match order_id() {
+                    Some(order_id) => {
+                        let email = query.read().get("email").unwrap_or_default();
+                        view! { <ComplaintForm order_id email error=error() /> }.into_any()
+                    }
+                    None => {
+                        view! {
+                            {error()
+                                .map(|error| view! {
+                                    <p class="account-error" role="alert">{error.summary()}</p>
+                                })}
+                            <OrderLookup />
+                        }
+                            .into_any()
+                    }
+                }}
+            </section>
+        </main>
+    }
+}
+
+/// The signed-in customer's delivered orders, and a form for finding any order by its
+/// number and e-mail address.
+#[component]
+fn OrderLookup() -> impl IntoView {
+    let orders = Resource::new(|| (), |_| my_complaint_orders());
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || Suspend::new(async move {
+                let orders = orders.await.unwrap_or_default();
+                (!orders.is_empty())
+                    .then(|| {
+                        let items = orders
+                            .into_iter()
+                            .map(|order| {
+                                let titles = order
+                                    .items
+                                    .iter()
+                                    .map(|item| item.title.clone())
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                view! {
+                                    <li>
+                                        <a href=format!("/complaints?order={}", order.order_id)>
+                                            {format!("Zamówienie nr {}", order.order_id)}
+                                        </a>
+                                        {format!(" z {}", format_date(order.created_at))}
+                                        <br />
+                                        <small>{titles}</small>
+                                    </li>
+                                }
+                            })
+                            .collect_view();
+                        view! {
+                            <h3>"Twoje zamówienia"</h3>
+                            <ul class="complaints-orders">{items}</ul>
+                        }
+                    })
+            })}
+        </Suspense>
+        <h3>"Znajdź zamówienie"</h3>
+        <form method="get" action="/complaints" class="account-form">
+            <label for="complaints-order">"Numer zamówienia"</label>
+            <input type="text" id="complaints-order" name="order" inputmode="numeric" required />
+            <label for="complaints-email">"E-mail podany przy zamówieniu"</label>
+            <input type="email" id="complaints-email" name="email" autocomplete="email" required />
+            <button type="submit">"Dalej"</button>
+        </form>
+    }
+}
+
+#[component]
+fn ComplaintForm(order_id: i64, email: String, error: Option<ComplaintError>) -> impl IntoView {
+    let order = Resource::new(
+        {
+            let email = email.clone();
+            move || (order_id, email.clone())
+        },
+        |(order_id, email)| get_complaint_order(order_id, email),
+    );
+
+    view! {
+        <Suspense fallback=|| view! { <p>"Ładowanie zamówienia..."</p> }>
+            {move || {
+                let email = email.clone();
+                let error = error.clone();
+                Suspend::new(async move {
+                    match order.await {
+                        Ok(order) => {
+                            view! { <ComplaintFields order email error /> }.into_any()
+                        }
+                        Err(ServerFnError::WrappedServerError(error)) => view! {
+                            <p class="account-error" role="alert">{error.summary()}</p>
+                            <p><a href="/complaints">"Wróć"</a></p>
+                        }
+                        .into_any(),
+                        Err(_) => view! {
+                            <p class="account-error" role="alert">
+                                {ComplaintError::Unavailable.summary()}
+                            </p>
+                        }
+                        .into_any(),
+                    }
+                })
+            }}
+        </Suspense>
+    }
+}
+
+#[component]
+fn ComplaintFields(
+    order: ComplaintOrder,
+    email: String,
+    error: Option<ComplaintError>,
+) -> impl IntoView {
+    let field_error = |field: &str| {
+        error
+            .as_ref()
+            .and_then(|error| error.field(field))
+            .map(|message| view! { <p class="field-error">{message}</p> })
+    };
+    let previous = order
+        .complaints
+        .iter()
+        .map(|complaint| {
+            view! {
+                <li>
+                    <a href=format!("/complaints/{}", complaint.token)>{complaint.case_number()}</a>
+                    {format!(" – {}: {}", complaint.title, complaint.status.label())}
+                </li>
+            }
+        })
+        .collect_view();
+    let items = order
+        .items
+        .iter()
+        .map(|item| {
+            view! {
+                <label class="account-checkbox">
+                    <input type="radio" name="product_id" value=item.product_id required />
+                    {format!("{} – {}", item.title, item.price)}
+                </label>
+            }
+        })
+        .collect_view();
+    let demands = Demand::ALL
+        .into_iter()
+        .map(|demand| view! { <option value=demand.param()>{demand.label()}</option> })
+        .collect_view();
+
+    view! {
+        <h3>{format!("Zamówienie nr {}", order.order_id)}</h3>
+        {(!order.complaints.is_empty())
+            .then(|| view! {
+                <p>"Zgłoszone reklamacje:"</p>
+                <ul>{previous}</ul>
+            })}
+        <form
+            method="post"
+            action="/complaints/submit"
+            enctype="multipart/form-data"
+            class="account-form"
+        >
+            <input type="hidden" name="order_id" value=order.order_id />
+            <input type="hidden" name="email" value=email />
+            <fieldset class="complaints-items">
+                <legend>"Której rzeczy dotyczy reklamacja?"</legend>
+                {items}
+            </fieldset>
+            {field_error("product_id")}
+            <label for="complaint-description">"Opis wady"</label>
+            <textarea
+                id="complaint-description"
+                name="description"
+                rows="6"
+                minlength=MIN_DESCRIPTION_LEN
+                maxlength=MAX_DESCRIPTION_LEN
+                placeholder="Co jest nie tak i kiedy to zauważyłaś lub zauważyłeś?"
+                required
+            ></textarea>
+            {field_error("description")}
+            <label for="complaint-demand">"Czego oczekujesz?"</label>
+            <select id="complaint-demand" name="demand" required>
+                {demands}
+            </select>
+            {field_error("demand")}
+            <label for="complaint-photos">
+                {format!("Zdjęcia wady (od 1 do {MAX_PHOTOS}, każde do 5 MB)")}
+            </label>
+            <input
+                type="file"
+                id="complaint-photos"
+                name="photos"
+                accept=PHOTO_TYPES
+                multiple
+                required
+            />
+            {field_error("photos")}
+            {error
+                .as_ref()
+                .map(|error| view! { <p class="account-error" role="alert">{error.summary()}</p> })}
+            <button type="submit">"Zgłoś reklamację"</button>
+        </form>
+    }
+}
+
+/// The customer's complaint: its status, deadline, our answer and the photos.
+#[component]
+pub fn ComplaintPage() -> impl IntoView {
+    let params = use_params_map();
+    let complaint = Resource::new(
+        move || params.read().get("token").unwrap_or_default(),
+        get_complaint,
+    );
+
+    view! {
+        <Title text="Reklamacja - Meg Joni" />
+        <main>
+            <section class="complaints">
+                <Suspense fallback=|| view! { <p>"Ładowanie reklamacji..."</p> }>
+                    {move || Suspend::new(async move {
+                        match complaint.await {
+                            Ok(Some(complaint)) => view! { <ComplaintView complaint /> }.into_any(),
+                            Ok(None) => {
+                                view! { <p>"Nie znaleźliśmy tej reklamacji."</p> }.into_any()
+                            }
+                            Err(_) => {
+                                view! { <p>{ComplaintError::Unavailable.summary()}</p> }.into_any()
+                            }
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ComplaintView(complaint: Complaint) -> impl IntoView {
+    let photos = complaint
+        .photos
+        .iter()
+        .map(|&photo| {
+            let href = complaint.photo_href(photo);
+            view! {
+                <a href=href.clone() target="_blank">
+                    <img src=href alt="Zdjęcie wady" loading="lazy" />
+                </a>
+            }
+        })
+        .collect_view();
+    let deadline = match complaint.responded_at {
+        Some(at) => format!("Odpowiedzieliśmy {}.", format_date(at)),
+        None => format!(
+            "Odpowiemy najpóźniej {}.",
+            format_date(complaint.respond_by)
+        ),
+    };
+
+    view! {
+        <h2>{format!("Reklamacja {}", complaint.case_number())}</h2>
+        <p class=format!("complaints-status complaints-{}", complaint.status.param())>
+            {format!("Status: {}", complaint.status.label())}
+        </p>
+        <p>{deadline}</p>
+        {(!complaint.response.is_empty())
+            .then(|| view! {
+                <h3>"Nasza odpowiedź"</h3>
+                <p class="complaints-response">{complaint.response.clone()}</p>
+            })}
+        <dl class="packing-slip-details">
+            <dt>"Zamówienie"</dt>
+            <dd>{format!("nr {}", complaint.order_id)}</dd>
+            <dt>"Rzecz"</dt>
+            <dd>{format!("{} ({})", complaint.title, complaint.price)}</dd>
+            <dt>"Zgłoszona"</dt>
+            <dd>{format_date(complaint.created_at)}</dd>
+            <dt>"Żądanie"</dt>
+            <dd>{complaint.demand.label()}</dd>
+            <dt>"Opis wady"</dt>
+            <dd>{complaint.description.clone()}</dd>
+        </dl>
+        <div class="complaints-photos">{photos}</div>
+    }
+}
+
+/// The customer's complaints, for the account dashboard.
+#[component]
+pub fn ComplaintHistory(complaints: Vec<Complaint>) -> impl IntoView {
+    if complaints.is_empty() {
+        return view! { <p>"Nie masz żadnych reklamacji."</p> }.into_any();
+    }
+    view! {
+        <table class="account-orders">
+            <thead>
+                <tr>
+                    <th>"Nr"</th>
+                    <th>"Rzecz"</th>
+                    <th>"Status"</th>
+                    <th>"Odpowiedź"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {complaints
+                    .into_iter()
+                    .map(|complaint| {
+                        let answer = match complaint.responded_at {
+                            Some(at) => format_date(at),
+                            None => format!("do {}", format_date(complaint.respond_by)),
+                        };
+                        view! {
+                            <tr>
+                                <td>
+                                    <a href=format!("/complaints/{}", complaint.token)>
+                                        {complaint.case_number()}
+                                    </a>
+                                </td>
+                                <td>{format!("{} (zamówienie nr {})", complaint.title, complaint.order_id)}</td>
+                                <td>{complaint.status.label()}</td>
+                                <td>{answer}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+    .into_any()
+}
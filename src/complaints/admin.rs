@@ -0,0 +1,287 @@
+//! Complaints as staff see them at `/admin/complaints`: unanswered cases first, by how
+//! soon the answer is due, with those past the deadline flagged. Each case can be taken
+//! into review, accepted or rejected with an answer for the customer, and closed.
+
+use super::{Complaint, ComplaintStatus};
+use crate::staff::Permission;
+use chrono::{Duration, Utc};
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+
+/// Unanswered complaints due within this many days are flagged as urgent.
+const DUE_SOON_DAYS: i64 = 3;
+/// Filter value for complaints past their deadline.
+const OVERDUE_PARAM: &str = "overdue";
+
+#[server]
+pub async fn list_complaints(filter: Option<String>) -> Result<Vec<Complaint>, ServerFnError> {
+    use crate::db::{ComplaintRepository, use_db};
+
+    crate::staff::require_staff(Permission::AnswerCustomers)?;
+    let db = use_db()?;
+    match filter.as_deref() {
+        Some(OVERDUE_PARAM) => {
+            let now = Utc::now();
+            Ok(db
+                .list_complaints(None)?
+                .into_iter()
+                .filter(|complaint| complaint.is_overdue(now))
+                .collect())
+        }
+        filter => Ok(db.list_complaints(filter.and_then(ComplaintStatus::from_param))?),
+    }
+}
+
+/// Moves the complaint to `to`. Accepting or rejecting it needs an answer, which the
+/// customer gets by e-mail along with the news that the case was closed.
+#[server]
+pub async fn update_complaint(
+    complaint_id: i64,
+    to: ComplaintStatus,
+    #[server(default)] response: String,
+) -> Result<(), ServerFnError> {
+    use crate::db::{ComplaintRepository, OrderRepository, use_db};
+    use crate::mail::{self, Email};
+    use leptos::logging::log;
+
+    crate::staff::require_staff(Permission::AnswerCustomers)?;
+    let db = use_db()?;
+    let complaint = db
+        .find_complaint(complaint_id)?
+        .ok_or_else(|| ServerFnError::new("nie ma takiej reklamacji"))?;
+    let from = complaint.status;
+    if !from.next().contains(&to) {
+        return Err(ServerFnError::new(format!(
+            "reklamacji ze statusem „{}” nie można oznaczyć jako „{}”",
+            from.label(),
+            to.label()
+        )));
+    }
+    let response = response.trim();
+    if to.is_decision() && response.is_empty() {
+        return Err(ServerFnError::new("napisz odpowiedź dla klienta"));
+    }
+    let complaint = db
+        .change_complaint_status(complaint_id, from, to, response, Utc::now())?
+        .ok_or_else(|| ServerFnError::new("ktoś inny zmienił już status reklamacji"))?;
+
+    if to == ComplaintStatus::InReview {
+        return Ok(());
+    }
+    let Some(order) = db.find_order(complaint.order_id)? else {
+        return Ok(());
+    };
+    let link = format!(
+        "{}/complaints/{}",
+        crate::account::auth::public_url()?,
+        complaint.token
+    );
+    let recipient = order.email.clone();
+    let email = Email::ComplaintUpdated {
+        order,
+        complaint,
+        link,
+    };
+    // Status już się zmienił; brak e-maila nie może tego cofnąć.
+    if let Err(e) = mail::queue(&db, &recipient, &email) {
+        log!("nie udało się zakolejkować e-maila o reklamacji {complaint_id}: {e}");
+    }
+    Ok(())
+}
+
+#[component]
+pub fn ComplaintsAdminPage() -> impl IntoView {
+    let query = use_query_map();
+    let update = ServerAction::<UpdateComplaint>::new();
+    let filter = move || query.read().get("filter");
+    let complaints = Resource::new(
+        move || (filter(), update.version().get()),
+        |(filter, _)| list_complaints(filter),
+    );
+
+    let filters = move || {
+        let current = filter();
+        let link = |value: Option<&str>, label: &'static str| {
+            let href = match value {
+                Some(value) => format!("/admin/complaints?filter={value}"),
+                None => "/admin/complaints".to_string(),
+            };
+            view! {
+                <a href=href class:active=current.as_deref() == value>
+                    {label}
+                </a>
+            }
+        };
+        let by_status = ComplaintStatus::ALL
+            .into_iter()
+            .map(|status| link(Some(status.param()), status.label()))
+            .collect_view();
+        view! {
+            <nav class="inbox-filters">
+                {link(None, "Wszystkie")}
+                {link(Some(OVERDUE_PARAM), "Po terminie")}
+                {by_status}
+            </nav>
+        }
+    };
+    let error = move || match update.value().get() {
+        Some(Err(e)) => Some(view! {
+            <p class="inbox-error">{format!("Nie udało się zmienić statusu: {e}")}</p>
+        }),
+        _ => None,
+    };
+
+    view! {
+        <Title text="Reklamacje - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Reklamacje"</h2>
+                <p>
+                    {format!(
+                        "Na odpowiedź mamy {} dni od zgłoszenia. Bez odpowiedzi w terminie reklamację uważa się za uznaną.",
+                        super::RESPONSE_DAYS
+                    )}
+                </p>
+                {filters}
+                {error}
+                <Suspense fallback=|| view! { <p>"Ładowanie reklamacji..."</p> }>
+                    {move || Suspend::new(async move {
+                        match complaints.await {
+                            Ok(complaints) if complaints.is_empty() => {
+                                view! { <p>"Brak reklamacji."</p> }.into_any()
+                            }
+                            Ok(complaints) => view! { <ComplaintList complaints update /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do reklamacji."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ComplaintList(
+    complaints: Vec<Complaint>,
+    update: ServerAction<UpdateComplaint>,
+) -> impl IntoView {
+    let now = Utc::now();
+    let overdue = complaints
+        .iter()
+        .filter(|complaint| complaint.is_overdue(now))
+        .count();
+
+    view! {
+        {(overdue > 0)
+            .then(|| view! {
+                <p class="inbox-error" role="alert">
+                    {format!("Reklamacje po terminie odpowiedzi: {overdue}")}
+                </p>
+            })}
+        <table class="inbox-threads complaints-list">
+            <thead>
+                <tr>
+                    <th>"Sprawa"</th>
+                    <th>"Rzecz i żądanie"</th>
+                    <th>"Opis"</th>
+                    <th>"Termin"</th>
+                    <th>"Status"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {complaints
+                    .into_iter()
+                    .map(|complaint| {
+                        let order = format!("/admin/orders/{}/packing-slip", complaint.order_id);
+                        let late = complaint.is_overdue(now);
+                        let due_soon = !late
+                            && complaint.responded_at.is_none()
+                            && complaint.respond_by - now < Duration::days(DUE_SOON_DAYS);
+                        let deadline = match complaint.responded_at {
+                            Some(at) => format!("odpowiedź {}", at.format("%Y-%m-%d")),
+                            None if late => format!(
+                                "PO TERMINIE ({})",
+                                complaint.respond_by.format("%Y-%m-%d")
+                            ),
+                            None => format!("do {}", complaint.respond_by.format("%Y-%m-%d")),
+                        };
+                        let photos = complaint
+                            .photos
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &photo)| {
+                                view! {
+                                    <a href=complaint.photo_href(photo) target="_blank">
+                                        {format!("zdjęcie {} ", i + 1)}
+                                    </a>
+                                }
+                            })
+                            .collect_view();
+                        let next = complaint.status.next();
+                        let form = (!next.is_empty())
+                            .then(|| {
+                                let buttons = next
+                                    .iter()
+                                    .map(|to| {
+                                        view! {
+                                            <button type="submit" name="to" value=to.param()>
+                                                {format!("Oznacz: {}", to.label())}
+                                            </button>
+                                        }
+                                    })
+                                    .collect_view();
+                                let asks_for_answer = next.iter().any(|to| to.is_decision());
+                                view! {
+                                    <ActionForm action=update>
+                                        <input type="hidden" name="complaint_id" value=complaint.id />
+                                        {asks_for_answer
+                                            .then(|| view! {
+                                                <textarea
+                                                    name="response"
+                                                    rows="3"
+                                                    placeholder="Odpowiedź dla klienta (wymagana przy uznaniu lub odrzuceniu)"
+                                                ></textarea>
+                                            })}
+                                        {buttons}
+                                    </ActionForm>
+                                }
+                            });
+                        view! {
+                            <tr
+                                class=format!("complaints-{}", complaint.status.param())
+                                class:complaints-overdue=late
+                                class:complaints-due-soon=due_soon
+                            >
+                                <td>
+                                    {complaint.case_number()}
+                                    <br />
+                                    <a href=order>{format!("zamówienie nr {}", complaint.order_id)}</a>
+                                    <br />
+                                    <small>{complaint.created_at.format("%Y-%m-%d %H:%M").to_string()}</small>
+                                </td>
+                                <td>
+                                    {format!("{} ({})", complaint.title, complaint.price)}
+                                    <br />
+                                    <strong>{complaint.demand.label()}</strong>
+                                </td>
+                                <td>
+                                    {complaint.description.clone()}
+                                    <br />
+                                    {photos}
+                                </td>
+                                <td>{deadline}</td>
+                                <td>
+                                    {complaint.status.label()}
+                                    {(!complaint.response.is_empty())
+                                        .then(|| view! { <p><small>{complaint.response.clone()}</small></p> })}
+                                    {form}
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
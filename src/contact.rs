@@ -0,0 +1,327 @@
+//! The contact form at `/contact`. Submissions go to `/submit-contact-form`, are stored
+//! and forwarded to the shop inbox. The form works without JavaScript too: the server
+//! then redirects back, carrying the outcome in the URL.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::checkout::FieldError;
+use crate::settings::get_shop_settings;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+use serde::{Deserialize, Serialize};
+
+/// How many messages one sender (by e-mail or network address) may send per hour.
+pub const MAX_MESSAGES_PER_HOUR: usize = 3;
+#[cfg(feature = "ssr")]
+const MAX_MESSAGE_LEN: usize = 5000;
+/// Query parameter set after a successful submission without JavaScript.
+const SENT_PARAM: &str = "wyslano";
+
+/// Why a message was not accepted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ContactError {
+    Invalid(Vec<FieldError>),
+    TooManyMessages,
+    Unavailable,
+}
+
+impl ContactError {
+    fn field(&self, field: &str) -> Option<String> {
+        match self {
+            ContactError::Invalid(errors) => errors
+                .iter()
+                .find(|error| error.field == field)
+                .map(|error| error.message.clone()),
+            _ => None,
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            ContactError::Invalid(_) => "Popraw zaznaczone pola.",
+            ContactError::TooManyMessages => {
+                "Wysłano już kilka wiadomości z tego adresu. Spróbuj ponownie za godzinę \
+                 albo napisz bezpośrednio na kontakt@megjoni.pl."
+            }
+            ContactError::Unavailable => {
+                "Nie udało się wysłać wiadomości. Spróbuj ponownie za chwilę."
+            }
+        }
+    }
+}
+
+// Błąd przechodzi przez ServerFnError jako tekst (także w adresie URL przy wysyłce bez
+// JavaScriptu), więc zapisujemy go jako JSON, żeby dało się go odtworzyć.
+impl fmt::Display for ContactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl FromStr for ContactError {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn validate(name: &str, email: &str, subject: &str, message: &str) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if name.trim().is_empty() {
+        errors.push(FieldError::new("name", "Podaj swoje imię."));
+    } else if name.trim().chars().count() > 100 {
+        errors.push(FieldError::new(
+            "name",
+            "Imię może mieć najwyżej 100 znaków.",
+        ));
+    }
+    if !crate::checkout::is_email(email) {
+        errors.push(FieldError::new("email", "Podaj poprawny adres e-mail."));
+    }
+    if subject.trim().chars().count() > 200 {
+        errors.push(FieldError::new(
+            "subject",
+            "Temat może mieć najwyżej 200 znaków.",
+        ));
+    }
+    let length = message.trim().chars().count();
+    if length < 10 {
+        errors.push(FieldError::new(
+            "message",
+            "Napisz, w czym możemy pomóc (co najmniej 10 znaków).",
+        ));
+    } else if length > MAX_MESSAGE_LEN {
+        errors.push(FieldError::new(
+            "message",
+            "Wiadomość jest za długa (najwyżej 5000 znaków).",
+        ));
+    }
+    errors
+}
+
+/// Network address of the client. Behind a reverse proxy every request comes from the
+/// proxy, so `MEGJONI_TRUST_PROXY` gives the number of proxies in front of the shop and
+/// the address is read that many entries from the right of `X-Forwarded-For`. Entries
+/// further left come from the client itself and can be made up.
+#[cfg(feature = "ssr")]
+pub(crate) fn client_ip() -> Option<String> {
+    use axum::extract::ConnectInfo;
+    use std::net::SocketAddr;
+
+    let parts = use_context::<axum::http::request::Parts>()?;
+    let hops = std::env::var("MEGJONI_TRUST_PROXY")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if hops > 0 {
+        let forwarded = parts
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| forwarded_client(value, hops));
+        if let Some(ip) = forwarded {
+            return Some(ip.to_string());
+        }
+    }
+    parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+}
+
+/// The `X-Forwarded-For` entry added by the outermost of `hops` trusted proxies.
+#[cfg(feature = "ssr")]
+fn forwarded_client(header: &str, hops: usize) -> Option<&str> {
+    header
+        .rsplit(',')
+        .nth(hops.checked_sub(1)?)
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+}
+
+/// Whether the request is a plain HTML form post rather than a call from the app.
+#[cfg(feature = "ssr")]
+pub(crate) fn is_form_post() -> bool {
+    use_context::<axum::http::request::Parts>().is_some_and(|parts| {
+        parts
+            .headers
+            .get(axum::http::header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"))
+    })
+}
+
+/// `website` is a honeypot: hidden from people, but filled in by many spam bots.
+#[server(prefix = "", endpoint = "submit-contact-form")]
+pub async fn submit_contact_form(
+    name: String,
+    email: String,
+    subject: String,
+    message: String,
+    website: String,
+) -> Result<(), ServerFnError<ContactError>> {
+    use crate::db::{MessageRepository, NewContactMessage, use_db};
+    use crate::mail::{self, Email};
+    use chrono::{Duration, Utc};
+    use leptos::logging::log;
+
+    let unavailable = |e: &dyn fmt::Display| {
+        log!("nie udało się przyjąć wiadomości z formularza: {e}");
+        ServerFnError::WrappedServerError(ContactError::Unavailable)
+    };
+
+    let errors = validate(&name, &email, &subject, &message);
+    if !errors.is_empty() {
+        return Err(ServerFnError::WrappedServerError(ContactError::Invalid(
+            errors,
+        )));
+    }
+    // Boty, które wypełniły ukryte pole, dostają tę samą odpowiedź co ludzie.
+    if website.trim().is_empty() {
+        let db = use_db().map_err(|e| unavailable(&e))?;
+        let ip = client_ip();
+        let email = email.trim();
+        let since = Utc::now() - Duration::hours(1);
+        let recent = db
+            .count_recent_messages(email, ip.as_deref(), since)
+            .map_err(|e| unavailable(&e))?;
+        if recent >= MAX_MESSAGES_PER_HOUR {
+            return Err(ServerFnError::WrappedServerError(
+                ContactError::TooManyMessages,
+            ));
+        }
+        let saved = db
+            .save_message(&NewContactMessage {
+                name: name.trim().to_string(),
+                email: email.to_string(),
+                subject: subject.trim().to_string(),
+                message: message.trim().to_string(),
+                ip,
+            })
+            .map_err(|e| unavailable(&e))?;
+        // Wiadomość jest już zapisana, więc brak powiadomienia nie jest błędem dla klienta.
+        if let Err(e) = mail::queue(&db, &mail::shop_inbox(), &Email::ContactMessage(saved)) {
+            log!("nie udało się zakolejkować powiadomienia o wiadomości: {e}");
+        }
+    }
+
+    if is_form_post() {
+        leptos_axum::redirect(&format!("/contact?{SENT_PARAM}=1"));
+    }
+    Ok(())
+}
+
+#[component]
+pub fn ContactPage() -> impl IntoView {
+    let send = ServerAction::<SubmitContactForm>::new();
+    let settings = Resource::new(|| (), |_| get_shop_settings());
+    let query = use_query_map();
+    let sent = move || {
+        matches!(send.value().get(), Some(Ok(()))) || query.read().get(SENT_PARAM).is_some()
+    };
+    let error = move || match send.value().get() {
+        Some(Err(ServerFnError::WrappedServerError(error))) => Some(error),
+        Some(Err(_)) => Some(ContactError::Unavailable),
+        _ => None,
+    };
+    let field_error = move |field: &'static str| {
+        move || {
+            error()
+                .and_then(|error| error.field(field))
+                .map(|message| view! { <p class="field-error">{message}</p> })
+        }
+    };
+
+    view! {
+        <Title text="Kontakt - Meg Joni" />
+        <main>
+            <section class="contact-section" style="max-width: 600px; margin: var(--space-md) auto; background-color: var(--color-surface); padding: var(--space-md); border-radius: 8px; box-shadow: 0 2px 8px rgba(0, 0, 0, 0.08);"> // Proste style inline dla przykładu, możesz przenieść do CSS
+                <h2>Skontaktuj się z Nami</h2>
+                <p>
+                    Masz pytania dotyczące produktów, zamówień, czy współpracy? Chętnie pomożemy!
+                    Skontaktuj się z nami poprzez formularz poniżej lub bezpośrednio.
+                </p>
+
+                <div class="contact-info" style="margin-bottom: var(--space-md);">
+                    <Suspense fallback=|| ()>
+                        {move || Suspend::new(async move {
+                            let settings = settings.await.unwrap_or_default();
+                            let phone = (!settings.contact_phone.is_empty()).then(|| {
+                                let href = format!("tel:{}", settings.contact_phone.replace(' ', ""));
+                                view! {
+                                    <p><strong>Telefon: </strong> <a href=href>{settings.contact_phone.clone()}</a></p>
+                                }
+                            });
+                            view! {
+                                <p><strong>Email: </strong> <a href=format!("mailto:{}", settings.contact_email)>{settings.contact_email.clone()}</a></p>
+                                {phone}
+                            }
+                        })}
+                    </Suspense>
+                </div>
+
+                <Show
+                    when=sent
+                    fallback=move || view! {
+                        <ActionForm action=send>
+                            <div style="margin-bottom: var(--space-sm);">
+                                <label for="name" class="visually-hidden">Twoje imię:</label>
+                                <input type="text" id="name" name="name" placeholder="Twoje imię" required style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;" />
+                                {field_error("name")}
+                            </div>
+                            <div style="margin-bottom: var(--space-sm);">
+                                <label for="email" class="visually-hidden">Twój email:</label>
+                                <input type="email" id="email" name="email" placeholder="Twój email" required style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;" />
+                                {field_error("email")}
+                            </div>
+                            <div style="margin-bottom: var(--space-sm);">
+                                <label for="subject" class="visually-hidden">Temat:</label>
+                                <input type="text" id="subject" name="subject" placeholder="Temat wiadomości" style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;" />
+                                {field_error("subject")}
+                            </div>
+                            <div style="margin-bottom: var(--space-sm);">
+                                <label for="message" class="visually-hidden">Twoja wiadomość:</label>
+                                <textarea id="message" name="message" placeholder="Twoja wiadomość" rows="6" required style="width: 100%; padding: var(--space-xs); border: 1px solid var(--color-border); border-radius: 4px;"></textarea>
+                                {field_error("message")}
+                            </div>
+                            <div class="contact-honeypot" aria-hidden="true">
+                                <label for="website">"Nie wypełniaj tego pola"</label>
+                                <input type="text" id="website" name="website" tabindex="-1" autocomplete="off" />
+                            </div>
+                            {move || error().map(|error| view! {
+                                <p class="contact-error" role="alert">{error.summary()}</p>
+                            })}
+                            <button type="submit" disabled=move || send.pending().get()>
+                                {move || if send.pending().get() { "Wysyłanie..." } else { "Wyślij wiadomość" }}
+                            </button>
+                        </ActionForm>
+                    }
+                >
+                    <p class="contact-success" role="status">
+                        "Dziękujemy za wiadomość! Odpowiemy najszybciej, jak to możliwe, zwykle w ciągu jednego dnia roboczego."
+                    </p>
+                </Show>
+            </section>
+        </main>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_client_is_counted_from_the_right() {
+        let header = "6.6.6.6, 203.0.113.7, 10.0.0.2";
+        assert_eq!(forwarded_client(header, 1), Some("10.0.0.2"));
+        assert_eq!(forwarded_client(header, 2), Some("203.0.113.7"));
+        assert_eq!(forwarded_client(header, 4), None);
+        assert_eq!(forwarded_client(header, 0), None);
+        assert_eq!(forwarded_client("203.0.113.7, ", 1), None);
+    }
+}
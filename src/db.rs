@@ -0,0 +1,177 @@
+//! SQLite storage for the shop, available only on the server.
+//!
+//! A [`Db`] is opened once at startup, brought up to date by [`migrations`] and then
+//! shared with server functions through the Leptos context (see [`use_db`]). Each
+//! aggregate gets its own repository trait, implemented for [`Db`].
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use leptos::prelude::*;
+use rusqlite::{Connection, Transaction};
+use serde::{Serialize, de::DeserializeOwned};
+
+pub mod addresses;
+pub mod attempts;
+pub mod carts;
+pub mod complaints;
+pub mod customers;
+pub mod lockers;
+pub mod messages;
+pub mod migrations;
+pub mod orders;
+pub mod outbox;
+pub mod payments;
+pub mod privacy;
+pub mod products;
+pub mod refunds;
+pub mod reservations;
+pub mod retention;
+pub mod returns;
+pub mod sessions;
+pub mod settings;
+pub mod staff;
+pub mod support;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use addresses::AddressRepository;
+pub use attempts::{AttemptCounts, AttemptKind, AttemptRepository};
+pub use carts::CartRepository;
+pub use complaints::{ComplaintPhoto, ComplaintRepository, NewComplaint};
+pub use customers::{Customer, CustomerRepository};
+pub use lockers::LockerRepository;
+pub use messages::{ContactMessage, MessageRepository, NewContactMessage};
+pub use orders::{NewOrder, Order, OrderLine, OrderRepository, PlaceOrder, StatusChange};
+pub use outbox::{OutboxEmail, OutboxRepository, OutboxStatus};
+pub use payments::{Payment, PaymentRepository};
+pub use privacy::{Erasure, ErasureSummary, PrivacyRepository, PrivacyRequestKind};
+pub use products::ProductRepository;
+pub use refunds::{NewRefund, RefundRepository};
+pub use reservations::ReservationRepository;
+pub use retention::{LogPurge, RetentionRepository};
+pub use returns::ReturnRepository;
+pub use sessions::{PendingRegistration, SessionRepository, TokenPurpose, UsedToken};
+pub use settings::SettingsRepository;
+pub use staff::StaffRepository;
+pub use support::SupportRepository;
+
+/// Database file used when `MEGJONI_DATABASE` is not set.
+pub const DEFAULT_DATABASE_FILE: &str = "data/megjoni.db";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("błąd bazy danych: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("migracja {version} ({name}) nie powiodła się: {source}")]
+    Migration {
+        version: u32,
+        name: &'static str,
+        source: rusqlite::Error,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Handle to the shop database. Cloning is cheap; all clones share one connection.
+#[derive(Clone)]
+pub struct Db {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Db {
+    /// Opens (or creates) the database file at `path` and applies pending migrations.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            // Brak katalogu zgłosi sam SQLite przy otwieraniu pliku.
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        Self::init(conn)
+    }
+
+    /// Opens a private in-memory database, mostly useful for tooling.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// Opens the database configured by `MEGJONI_DATABASE`, or [`DEFAULT_DATABASE_FILE`].
+    pub fn open_configured() -> Result<Self> {
+        let path =
+            std::env::var("MEGJONI_DATABASE").unwrap_or_else(|_| DEFAULT_DATABASE_FILE.to_string());
+        Self::open(path)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrations::run(&mut conn)?;
+        Ok(Db {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` with exclusive access to the connection.
+    pub fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        let conn = self
+            .conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&conn)
+    }
+
+    /// Runs `f` inside a transaction that is committed only if `f` succeeds.
+    pub fn transaction<T>(&self, f: impl FnOnce(&Transaction) -> Result<T>) -> Result<T> {
+        let mut conn = self
+            .conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let tx = conn.transaction()?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+}
+
+/// Returns the database provided to the current request.
+pub fn use_db() -> Result<Db, ServerFnError> {
+    use_context::<Db>().ok_or_else(|| ServerFnError::new("baza danych nie jest dostępna"))
+}
+
+/// Stores a unit-like enum under the same name serde uses, e.g. `Category::Woman` as `woman`.
+pub(crate) fn enum_to_sql<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        other => panic!("{other:?} nie jest prostym wariantem enuma"),
+    }
+}
+
+pub(crate) fn enum_from_sql<T: DeserializeOwned>(
+    row: &rusqlite::Row,
+    column: &str,
+) -> rusqlite::Result<T> {
+    let name: String = row.get(column)?;
+    serde_json::from_value(serde_json::Value::String(name)).map_err(|e| decode_error(column, e))
+}
+
+pub(crate) fn json_to_sql<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("wartość musi dać się zapisać jako JSON")
+}
+
+pub(crate) fn json_from_sql<T: DeserializeOwned>(
+    row: &rusqlite::Row,
+    column: &str,
+) -> rusqlite::Result<T> {
+    let json: String = row.get(column)?;
+    serde_json::from_str(&json).map_err(|e| decode_error(column, e))
+}
+
+fn decode_error(column: &str, error: serde_json::Error) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(
+        0,
+        rusqlite::types::Type::Text,
+        format!("kolumna {column}: {error}").into(),
+    )
+}
@@ -0,0 +1,83 @@
+use chrono::Utc;
+use rusqlite::{Row, params};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::account::SavedAddress;
+use crate::checkout::Address;
+
+/// Shipping addresses and invoice details customers keep in their account.
+pub trait AddressRepository {
+    /// Oldest first.
+    fn list_addresses(&self, customer_id: i64) -> Result<Vec<SavedAddress>>;
+    fn add_address(&self, customer_id: i64, address: &SavedAddress) -> Result<SavedAddress>;
+    /// Returns whether the customer had such an address.
+    fn delete_address(&self, customer_id: i64, id: i64) -> Result<bool>;
+}
+
+fn address_from_row(row: &Row) -> rusqlite::Result<SavedAddress> {
+    Ok(SavedAddress {
+        id: row.get("id")?,
+        kind: enum_from_sql(row, "kind")?,
+        name: row.get("name")?,
+        phone: row.get("phone")?,
+        company: row.get("company")?,
+        nip: row.get("nip")?,
+        address: Address {
+            street: row.get("street")?,
+            postcode: row.get("postcode")?,
+            city: row.get("city")?,
+        },
+    })
+}
+
+impl AddressRepository for Db {
+    fn list_addresses(&self, customer_id: i64) -> Result<Vec<SavedAddress>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, kind, name, phone, company, nip, street, postcode, city
+                 FROM addresses
+                 WHERE customer_id = ?1 ORDER BY id",
+            )?;
+            let addresses = stmt
+                .query_map([customer_id], address_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(addresses)
+        })
+    }
+
+    fn add_address(&self, customer_id: i64, address: &SavedAddress) -> Result<SavedAddress> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO addresses (customer_id, kind, name, phone, company, nip, street,
+                    postcode, city, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    customer_id,
+                    enum_to_sql(&address.kind),
+                    address.name,
+                    address.phone,
+                    address.company,
+                    address.nip,
+                    address.address.street,
+                    address.address.postcode,
+                    address.address.city,
+                    Utc::now()
+                ],
+            )?;
+            Ok(SavedAddress {
+                id: conn.last_insert_rowid(),
+                ..address.clone()
+            })
+        })
+    }
+
+    fn delete_address(&self, customer_id: i64, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM addresses WHERE id = ?1 AND customer_id = ?2",
+                params![id, customer_id],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+}
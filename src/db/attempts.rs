@@ -0,0 +1,181 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::{Db, Result, enum_to_sql};
+
+/// What a throttled request tried to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptKind {
+    /// A failed sign-in to a customer account.
+    LogIn,
+    PasswordReset,
+    Register,
+    /// A failed sign-in to the shop panel.
+    StaffLogIn,
+}
+
+/// Recent attempts of one kind, counted both ways.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AttemptCounts {
+    /// From the same network address, whatever the e-mail.
+    pub by_ip: usize,
+    /// For the same e-mail address, from anywhere.
+    pub by_email: usize,
+}
+
+/// Attempts are kept this long; every limit uses a shorter window.
+const KEEP_ATTEMPTS_HOURS: i64 = 24;
+
+/// Sign-in failures and e-mail sending requests, for rate limiting. E-mail addresses are
+/// identified by their hash, as in the privacy audit trail.
+pub trait AttemptRepository {
+    /// Records an attempt and forgets those older than a day.
+    fn record_attempt(
+        &self,
+        kind: AttemptKind,
+        email_hash: &str,
+        ip: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<()>;
+    /// Attempts since `since`. Requests whose address is unknown are counted together,
+    /// so they share one limit instead of escaping it.
+    fn count_attempts(
+        &self,
+        kind: AttemptKind,
+        email_hash: &str,
+        ip: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> Result<AttemptCounts>;
+    /// Forgets the attempts for this e-mail address, e.g. after a successful sign-in.
+    fn clear_attempts(&self, kind: AttemptKind, email_hash: &str) -> Result<()>;
+}
+
+impl AttemptRepository for Db {
+    fn record_attempt(
+        &self,
+        kind: AttemptKind,
+        email_hash: &str,
+        ip: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        self.transaction(|tx| {
+            tx.execute(
+                "DELETE FROM auth_attempts WHERE created_at < ?1",
+                [now - Duration::hours(KEEP_ATTEMPTS_HOURS)],
+            )?;
+            tx.execute(
+                "INSERT INTO auth_attempts (kind, email_hash, ip, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![enum_to_sql(&kind), email_hash, ip, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn count_attempts(
+        &self,
+        kind: AttemptKind,
+        email_hash: &str,
+        ip: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> Result<AttemptCounts> {
+        self.with_conn(|conn| {
+            let (by_ip, by_email) = conn.query_row(
+                "SELECT COALESCE(SUM(ip IS ?3), 0), COALESCE(SUM(email_hash = ?2), 0)
+                 FROM auth_attempts WHERE kind = ?1 AND created_at >= ?4",
+                params![enum_to_sql(&kind), email_hash, ip, since],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            Ok(AttemptCounts { by_ip, by_email })
+        })
+    }
+
+    fn clear_attempts(&self, kind: AttemptKind, email_hash: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM auth_attempts WHERE kind = ?1 AND email_hash = ?2",
+                params![enum_to_sql(&kind), email_hash],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::db;
+
+    fn attempt(db: &Db, kind: AttemptKind, email_hash: &str, ip: Option<&str>, at: DateTime<Utc>) {
+        db.record_attempt(kind, email_hash, ip, at).unwrap();
+    }
+
+    #[test]
+    fn attempts_are_counted_by_address_and_by_email() {
+        let db = db();
+        let now = Utc::now();
+        attempt(&db, AttemptKind::LogIn, "anna", Some("10.0.0.1"), now);
+        attempt(&db, AttemptKind::LogIn, "anna", Some("10.0.0.2"), now);
+        attempt(&db, AttemptKind::LogIn, "ewa", Some("10.0.0.1"), now);
+        attempt(&db, AttemptKind::Register, "anna", Some("10.0.0.1"), now);
+
+        let since = now - Duration::minutes(15);
+        assert_eq!(
+            db.count_attempts(AttemptKind::LogIn, "anna", Some("10.0.0.1"), since)
+                .unwrap(),
+            AttemptCounts {
+                by_ip: 2,
+                by_email: 2,
+            }
+        );
+        assert_eq!(
+            db.count_attempts(AttemptKind::LogIn, "ola", Some("10.0.0.3"), since)
+                .unwrap(),
+            AttemptCounts::default()
+        );
+    }
+
+    #[test]
+    fn requests_without_an_address_share_one_count() {
+        let db = db();
+        let now = Utc::now();
+        attempt(&db, AttemptKind::LogIn, "anna", None, now);
+        attempt(&db, AttemptKind::LogIn, "ewa", None, now);
+        attempt(&db, AttemptKind::LogIn, "ola", Some("10.0.0.1"), now);
+
+        let counts = db
+            .count_attempts(AttemptKind::LogIn, "ola", None, now - Duration::minutes(15))
+            .unwrap();
+        assert_eq!(counts.by_ip, 2);
+    }
+
+    #[test]
+    fn old_and_cleared_attempts_stop_counting() {
+        let db = db();
+        let now = Utc::now();
+        attempt(
+            &db,
+            AttemptKind::LogIn,
+            "anna",
+            None,
+            now - Duration::minutes(20),
+        );
+        attempt(&db, AttemptKind::LogIn, "anna", None, now);
+        let since = now - Duration::minutes(15);
+        assert_eq!(
+            db.count_attempts(AttemptKind::LogIn, "anna", None, since)
+                .unwrap()
+                .by_email,
+            1
+        );
+
+        db.clear_attempts(AttemptKind::LogIn, "anna").unwrap();
+        assert_eq!(
+            db.count_attempts(AttemptKind::LogIn, "anna", None, since)
+                .unwrap(),
+            AttemptCounts::default()
+        );
+    }
+}
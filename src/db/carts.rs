@@ -0,0 +1,50 @@
+use chrono::Utc;
+use rusqlite::{OptionalExtension, params};
+
+use super::{Db, Result, json_from_sql, json_to_sql};
+use crate::cart::{CartContents, CartItem};
+
+/// Server-side carts of signed-in customers, so they follow the customer between devices.
+pub trait CartRepository {
+    fn load_cart(&self, customer_id: i64) -> Result<Option<CartContents>>;
+    fn save_cart(&self, customer_id: i64, cart: &CartContents) -> Result<()>;
+}
+
+impl CartRepository for Db {
+    fn load_cart(&self, customer_id: i64) -> Result<Option<CartContents>> {
+        self.with_conn(|conn| {
+            let cart = conn
+                .query_row(
+                    "SELECT cart_id, items FROM carts WHERE customer_id = ?1",
+                    [customer_id],
+                    |row| {
+                        Ok(CartContents {
+                            cart_id: row.get("cart_id")?,
+                            items: json_from_sql::<Vec<CartItem>>(row, "items")?,
+                        })
+                    },
+                )
+                .optional()?;
+            Ok(cart)
+        })
+    }
+
+    fn save_cart(&self, customer_id: i64, cart: &CartContents) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO carts (customer_id, cart_id, items, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (customer_id) DO UPDATE SET
+                    cart_id = excluded.cart_id, items = excluded.items,
+                    updated_at = excluded.updated_at",
+                params![
+                    customer_id,
+                    cart.cart_id,
+                    json_to_sql(&cart.items),
+                    Utc::now()
+                ],
+            )?;
+            Ok(())
+        })
+    }
+}
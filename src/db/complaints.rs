@@ -0,0 +1,269 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::catalog::Price;
+use crate::complaints::{Complaint, ComplaintStatus, Demand};
+
+/// A complaint as submitted by the customer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewComplaint {
+    pub order_id: i64,
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+    pub token: String,
+    pub description: String,
+    pub demand: Demand,
+}
+
+/// A photo attached to a complaint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComplaintPhoto {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+pub trait ComplaintRepository {
+    /// Records the complaint with its photos; the response is due `respond_by`. Returns
+    /// `None` without creating anything if the same item already has an open complaint.
+    fn create_complaint(
+        &self,
+        complaint: &NewComplaint,
+        photos: &[ComplaintPhoto],
+        now: DateTime<Utc>,
+        respond_by: DateTime<Utc>,
+    ) -> Result<Option<Complaint>>;
+    fn find_complaint(&self, id: i64) -> Result<Option<Complaint>>;
+    fn find_complaint_by_token(&self, token: &str) -> Result<Option<Complaint>>;
+    /// Newest first.
+    fn complaints_for_order(&self, order_id: i64) -> Result<Vec<Complaint>>;
+    /// Complaints about the customer's orders, newest first.
+    fn complaints_for_customer(&self, customer_id: i64) -> Result<Vec<Complaint>>;
+    /// Unanswered complaints with the nearest deadline first, then the rest newest first;
+    /// optionally only those in `status`.
+    fn list_complaints(&self, status: Option<ComplaintStatus>) -> Result<Vec<Complaint>>;
+    /// Moves the complaint from `from` to `to`, replacing our response when `response` is
+    /// not empty. Accepting or rejecting it records when we responded. Returns `None`
+    /// without changing anything if the complaint is no longer in `from`.
+    fn change_complaint_status(
+        &self,
+        id: i64,
+        from: ComplaintStatus,
+        to: ComplaintStatus,
+        response: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Complaint>>;
+    /// The photo, if it belongs to the complaint with this token.
+    fn complaint_photo(&self, token: &str, photo_id: i64) -> Result<Option<ComplaintPhoto>>;
+}
+
+const COMPLAINT_SELECT: &str = "SELECT id, order_id, product_id, title, price, token, \
+     description, demand, status, response, created_at, respond_by, responded_at, \
+     resolved_at FROM complaints";
+
+fn complaint_from_row(row: &Row) -> rusqlite::Result<Complaint> {
+    Ok(Complaint {
+        id: row.get("id")?,
+        order_id: row.get("order_id")?,
+        product_id: row.get("product_id")?,
+        title: row.get("title")?,
+        price: Price(row.get("price")?),
+        token: row.get("token")?,
+        description: row.get("description")?,
+        demand: enum_from_sql(row, "demand")?,
+        status: enum_from_sql(row, "status")?,
+        response: row.get("response")?,
+        photos: Vec::new(),
+        created_at: row.get("created_at")?,
+        respond_by: row.get("respond_by")?,
+        responded_at: row.get("responded_at")?,
+        resolved_at: row.get("resolved_at")?,
+    })
+}
+
+fn query_complaints(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<Complaint>> {
+    let mut stmt = conn.prepare(sql)?;
+    let mut complaints = stmt
+        .query_map(params, complaint_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut photos =
+        conn.prepare("SELECT id FROM complaint_photos WHERE complaint_id = ?1 ORDER BY id")?;
+    for complaint in &mut complaints {
+        complaint.photos = photos
+            .query_map([complaint.id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+    }
+    Ok(complaints)
+}
+
+fn find_complaint(conn: &Connection, id: i64) -> Result<Option<Complaint>> {
+    Ok(query_complaints(conn, &format!("{COMPLAINT_SELECT} WHERE id = ?1"), [id])?.pop())
+}
+
+impl ComplaintRepository for Db {
+    fn create_complaint(
+        &self,
+        complaint: &NewComplaint,
+        photos: &[ComplaintPhoto],
+        now: DateTime<Utc>,
+        respond_by: DateTime<Utc>,
+    ) -> Result<Option<Complaint>> {
+        self.transaction(|tx| {
+            let open = tx
+                .query_row(
+                    "SELECT 1 FROM complaints
+                     WHERE order_id = ?1 AND product_id = ?2 AND status NOT IN (?3, ?4)",
+                    params![
+                        complaint.order_id,
+                        complaint.product_id,
+                        enum_to_sql(&ComplaintStatus::Rejected),
+                        enum_to_sql(&ComplaintStatus::Resolved)
+                    ],
+                    |_| Ok(()),
+                )
+                .optional()?;
+            if open.is_some() {
+                return Ok(None);
+            }
+            tx.execute(
+                "INSERT INTO complaints (order_id, product_id, title, price, token, description,
+                     demand, status, created_at, respond_by)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    complaint.order_id,
+                    complaint.product_id,
+                    complaint.title,
+                    complaint.price.grosze(),
+                    complaint.token,
+                    complaint.description,
+                    enum_to_sql(&complaint.demand),
+                    enum_to_sql(&ComplaintStatus::Submitted),
+                    now,
+                    respond_by
+                ],
+            )?;
+            let id = tx.last_insert_rowid();
+            for photo in photos {
+                tx.execute(
+                    "INSERT INTO complaint_photos (complaint_id, content_type, data)
+                     VALUES (?1, ?2, ?3)",
+                    params![id, photo.content_type, photo.data],
+                )?;
+            }
+            find_complaint(tx, id)
+        })
+    }
+
+    fn find_complaint(&self, id: i64) -> Result<Option<Complaint>> {
+        self.with_conn(|conn| find_complaint(conn, id))
+    }
+
+    fn find_complaint_by_token(&self, token: &str) -> Result<Option<Complaint>> {
+        self.with_conn(|conn| {
+            Ok(query_complaints(
+                conn,
+                &format!("{COMPLAINT_SELECT} WHERE token = ?1"),
+                [token],
+            )?
+            .pop())
+        })
+    }
+
+    fn complaints_for_order(&self, order_id: i64) -> Result<Vec<Complaint>> {
+        self.with_conn(|conn| {
+            query_complaints(
+                conn,
+                &format!("{COMPLAINT_SELECT} WHERE order_id = ?1 ORDER BY id DESC"),
+                [order_id],
+            )
+        })
+    }
+
+    fn complaints_for_customer(&self, customer_id: i64) -> Result<Vec<Complaint>> {
+        self.with_conn(|conn| {
+            query_complaints(
+                conn,
+                &format!(
+                    "{COMPLAINT_SELECT} WHERE order_id IN
+                         (SELECT id FROM orders WHERE customer_id = ?1)
+                     ORDER BY id DESC"
+                ),
+                [customer_id],
+            )
+        })
+    }
+
+    fn list_complaints(&self, status: Option<ComplaintStatus>) -> Result<Vec<Complaint>> {
+        self.with_conn(|conn| {
+            query_complaints(
+                conn,
+                &format!(
+                    "{COMPLAINT_SELECT} WHERE ?1 IS NULL OR status = ?1
+                     ORDER BY responded_at IS NOT NULL,
+                         CASE WHEN responded_at IS NULL THEN respond_by END,
+                         id DESC"
+                ),
+                [status.as_ref().map(enum_to_sql)],
+            )
+        })
+    }
+
+    fn change_complaint_status(
+        &self,
+        id: i64,
+        from: ComplaintStatus,
+        to: ComplaintStatus,
+        response: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Complaint>> {
+        self.transaction(|tx| {
+            let updated = tx.execute(
+                "UPDATE complaints SET
+                     status = ?2,
+                     response = CASE WHEN ?4 = '' THEN response ELSE ?4 END,
+                     responded_at = CASE WHEN ?2 IN (?6, ?7) THEN COALESCE(responded_at, ?5)
+                         ELSE responded_at END,
+                     resolved_at = CASE WHEN ?2 = ?8 THEN ?5 ELSE resolved_at END
+                 WHERE id = ?1 AND status = ?3",
+                params![
+                    id,
+                    enum_to_sql(&to),
+                    enum_to_sql(&from),
+                    response,
+                    now,
+                    enum_to_sql(&ComplaintStatus::Accepted),
+                    enum_to_sql(&ComplaintStatus::Rejected),
+                    enum_to_sql(&ComplaintStatus::Resolved)
+                ],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            find_complaint(tx, id)
+        })
+    }
+
+    fn complaint_photo(&self, token: &str, photo_id: i64) -> Result<Option<ComplaintPhoto>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT p.content_type, p.data FROM complaint_photos p
+                     JOIN complaints c ON c.id = p.complaint_id
+                     WHERE c.token = ?1 AND p.id = ?2",
+                    params![token, photo_id],
+                    |row| {
+                        Ok(ComplaintPhoto {
+                            content_type: row.get("content_type")?,
+                            data: row.get("data")?,
+                        })
+                    },
+                )
+                .optional()?)
+        })
+    }
+}
@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, Row, params};
+
+use super::{Db, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Customer {
+    pub id: i64,
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    /// When the customer followed the link sent to their address; until then they
+    /// cannot sign in.
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Customer {
+    pub fn is_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+}
+
+pub trait CustomerRepository {
+    /// Creates an account that can sign in once its e-mail address is verified.
+    fn create_customer(&self, email: &str, name: &str, password_hash: &str) -> Result<Customer>;
+    fn find_customer(&self, id: i64) -> Result<Option<Customer>>;
+    /// Emails are matched case-insensitively.
+    fn find_customer_by_email(&self, email: &str) -> Result<Option<Customer>>;
+    /// The stored Argon2 hash, `None` for customers without a password.
+    fn password_hash(&self, id: i64) -> Result<Option<String>>;
+    fn set_password_hash(&self, id: i64, password_hash: &str) -> Result<()>;
+    /// Marks the address as verified and attaches the guest orders placed with it, in
+    /// one transaction. Does nothing for an already verified address.
+    fn mark_email_verified(&self, id: i64, now: DateTime<Utc>) -> Result<()>;
+    fn update_profile(&self, id: i64, name: &str, phone: &str) -> Result<()>;
+    fn record_login(&self, id: i64, now: DateTime<Utc>) -> Result<()>;
+}
+
+pub(super) const CUSTOMER_SELECT: &str = "SELECT customers.id, customers.email, customers.name, \
+     customers.phone, customers.email_verified_at, customers.last_login_at, \
+     customers.created_at FROM customers";
+
+pub(super) fn customer_from_row(row: &Row) -> rusqlite::Result<Customer> {
+    Ok(Customer {
+        id: row.get("id")?,
+        email: row.get("email")?,
+        name: row.get("name")?,
+        phone: row.get("phone")?,
+        email_verified_at: row.get("email_verified_at")?,
+        last_login_at: row.get("last_login_at")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+impl CustomerRepository for Db {
+    fn create_customer(&self, email: &str, name: &str, password_hash: &str) -> Result<Customer> {
+        self.with_conn(|conn| {
+            let created_at = Utc::now();
+            conn.execute(
+                "INSERT INTO customers (email, name, password_hash, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![email, name, password_hash, created_at],
+            )?;
+            Ok(Customer {
+                id: conn.last_insert_rowid(),
+                email: email.to_string(),
+                name: name.to_string(),
+                phone: String::new(),
+                email_verified_at: None,
+                last_login_at: None,
+                created_at,
+            })
+        })
+    }
+
+    fn find_customer(&self, id: i64) -> Result<Option<Customer>> {
+        self.with_conn(|conn| {
+            let customer = conn
+                .query_row(
+                    &format!("{CUSTOMER_SELECT} WHERE id = ?1"),
+                    [id],
+                    customer_from_row,
+                )
+                .optional()?;
+            Ok(customer)
+        })
+    }
+
+    fn find_customer_by_email(&self, email: &str) -> Result<Option<Customer>> {
+        self.with_conn(|conn| {
+            let customer = conn
+                .query_row(
+                    &format!("{CUSTOMER_SELECT} WHERE email = ?1"),
+                    [email],
+                    customer_from_row,
+                )
+                .optional()?;
+            Ok(customer)
+        })
+    }
+
+    fn password_hash(&self, id: i64) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            let hash = conn
+                .query_row(
+                    "SELECT password_hash FROM customers WHERE id = ?1",
+                    [id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(hash.flatten())
+        })
+    }
+
+    fn set_password_hash(&self, id: i64, password_hash: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE customers SET password_hash = ?2 WHERE id = ?1",
+                params![id, password_hash],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn mark_email_verified(&self, id: i64, now: DateTime<Utc>) -> Result<()> {
+        self.transaction(|tx| {
+            let verified = tx.execute(
+                "UPDATE customers SET email_verified_at = ?2
+                 WHERE id = ?1 AND email_verified_at IS NULL",
+                params![id, now],
+            )?;
+            // Dopiero potwierdzony adres dowodzi, że zamówienia gościa należą do tej osoby.
+            if verified > 0 {
+                tx.execute(
+                    "UPDATE orders SET customer_id = ?1
+                     WHERE customer_id IS NULL
+                       AND email = (SELECT email FROM customers WHERE id = ?1) COLLATE NOCASE",
+                    [id],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    fn update_profile(&self, id: i64, name: &str, phone: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE customers SET name = ?2, phone = ?3 WHERE id = ?1",
+                params![id, name, phone],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn record_login(&self, id: i64, now: DateTime<Utc>) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE customers SET last_login_at = ?2 WHERE id = ?1",
+                params![id, now],
+            )?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,109 @@
+use rusqlite::{OptionalExtension, Row, params};
+
+use super::{Db, Result};
+use crate::lockers::Locker;
+use crate::search::fold;
+
+pub trait LockerRepository {
+    /// Replaces the whole locker list with `lockers` in one transaction.
+    fn replace_lockers(&self, lockers: &[Locker]) -> Result<usize>;
+    /// Lockers whose postcode, code or city starts with `query`, at most `limit` of them.
+    fn search_lockers(&self, query: &str, limit: usize) -> Result<Vec<Locker>>;
+    /// Locker codes are matched case-insensitively.
+    fn find_locker(&self, code: &str) -> Result<Option<Locker>>;
+}
+
+const LOCKER_SELECT: &str = "SELECT code, city, postcode, street, description FROM lockers";
+
+fn locker_from_row(row: &Row) -> rusqlite::Result<Locker> {
+    Ok(Locker {
+        code: row.get("code")?,
+        city: row.get("city")?,
+        postcode: row.get("postcode")?,
+        street: row.get("street")?,
+        description: row.get("description")?,
+    })
+}
+
+/// Whether `query` looks like the start of a postcode, e.g. `00`, `00-6` or `00697`.
+fn is_postcode_prefix(query: &str) -> bool {
+    query.starts_with(|c: char| c.is_ascii_digit())
+        && query.chars().all(|c| c.is_ascii_digit() || c == '-')
+}
+
+impl LockerRepository for Db {
+    fn replace_lockers(&self, lockers: &[Locker]) -> Result<usize> {
+        self.transaction(|tx| {
+            tx.execute("DELETE FROM lockers", [])?;
+            let mut stmt = tx.prepare(
+                "INSERT INTO lockers (code, city, postcode, street, description, city_search)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for locker in lockers {
+                stmt.execute(params![
+                    locker.code.to_uppercase(),
+                    locker.city,
+                    locker.postcode,
+                    locker.street,
+                    locker.description,
+                    fold(&locker.city),
+                ])?;
+            }
+            Ok(lockers.len())
+        })
+    }
+
+    fn search_lockers(&self, query: &str, limit: usize) -> Result<Vec<Locker>> {
+        // Znaki specjalne LIKE nie mają sensu w kodach ani nazwach miast.
+        let query: String = query
+            .trim()
+            .chars()
+            .filter(|c| *c != '%' && *c != '_')
+            .collect();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.with_conn(|conn| {
+            let lockers = if is_postcode_prefix(&query) {
+                let digits: String = query.chars().filter(char::is_ascii_digit).collect();
+                let pattern = match digits.len() {
+                    0..=2 => format!("{digits}%"),
+                    _ => format!("{}-{}%", &digits[..2], &digits[2..]),
+                };
+                let mut stmt = conn.prepare(&format!(
+                    "{LOCKER_SELECT} WHERE postcode LIKE ?1 ORDER BY postcode, code LIMIT ?2"
+                ))?;
+                stmt.query_map(params![pattern, limit], locker_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?
+            } else {
+                let mut stmt = conn.prepare(&format!(
+                    "{LOCKER_SELECT} WHERE code LIKE ?1 OR city_search LIKE ?2
+                     ORDER BY city, code LIMIT ?3"
+                ))?;
+                stmt.query_map(
+                    params![
+                        format!("{}%", query.to_uppercase()),
+                        format!("{}%", fold(&query)),
+                        limit
+                    ],
+                    locker_from_row,
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+            };
+            Ok(lockers)
+        })
+    }
+
+    fn find_locker(&self, code: &str) -> Result<Option<Locker>> {
+        self.with_conn(|conn| {
+            let locker = conn
+                .query_row(
+                    &format!("{LOCKER_SELECT} WHERE code = ?1"),
+                    [code.trim().to_uppercase()],
+                    locker_from_row,
+                )
+                .optional()?;
+            Ok(locker)
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Row, params};
+
+use super::{Db, Result, enum_to_sql};
+use crate::inbox::ThreadStatus;
+
+/// A message sent through the contact form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactMessage {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub subject: String,
+    pub message: String,
+    /// Address the message was sent from, used for rate limiting.
+    pub ip: Option<String>,
+    /// Support thread the message belongs to; one per sender's e-mail address.
+    pub thread_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewContactMessage {
+    pub name: String,
+    pub email: String,
+    pub subject: String,
+    pub message: String,
+    pub ip: Option<String>,
+}
+
+pub trait MessageRepository {
+    /// Stores the message in its sender's support thread, starting the thread if needed
+    /// and marking it as new.
+    fn save_message(&self, message: &NewContactMessage) -> Result<ContactMessage>;
+    /// All messages, newest first.
+    fn list_messages(&self) -> Result<Vec<ContactMessage>>;
+    /// Messages sent from this e-mail address, oldest first.
+    fn messages_from_email(&self, email: &str) -> Result<Vec<ContactMessage>>;
+    /// How many messages came from this e-mail address or network address since `since`.
+    fn count_recent_messages(
+        &self,
+        email: &str,
+        ip: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> Result<usize>;
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<ContactMessage> {
+    Ok(ContactMessage {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        email: row.get("email")?,
+        subject: row.get("subject")?,
+        message: row.get("message")?,
+        ip: row.get("ip")?,
+        thread_id: row.get("thread_id")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+impl MessageRepository for Db {
+    fn save_message(&self, message: &NewContactMessage) -> Result<ContactMessage> {
+        self.transaction(|tx| {
+            let created_at = Utc::now();
+            let thread_id: i64 = tx.query_row(
+                "INSERT INTO support_threads (email, name, status, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT (email) DO UPDATE SET
+                    name = excluded.name, status = excluded.status,
+                    updated_at = excluded.updated_at
+                 RETURNING id",
+                params![
+                    message.email,
+                    message.name,
+                    enum_to_sql(&ThreadStatus::New),
+                    created_at
+                ],
+                |row| row.get(0),
+            )?;
+            tx.execute(
+                "INSERT INTO contact_messages (name, email, subject, message, ip, thread_id,
+                    created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    message.name,
+                    message.email,
+                    message.subject,
+                    message.message,
+                    message.ip,
+                    thread_id,
+                    created_at
+                ],
+            )?;
+            Ok(ContactMessage {
+                id: tx.last_insert_rowid(),
+                name: message.name.clone(),
+                email: message.email.clone(),
+                subject: message.subject.clone(),
+                message: message.message.clone(),
+                ip: message.ip.clone(),
+                thread_id: Some(thread_id),
+                created_at,
+            })
+        })
+    }
+
+    fn list_messages(&self) -> Result<Vec<ContactMessage>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, email, subject, message, ip, thread_id, created_at
+                 FROM contact_messages ORDER BY created_at DESC, id DESC",
+            )?;
+            let messages = stmt
+                .query_map([], message_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(messages)
+        })
+    }
+
+    fn messages_from_email(&self, email: &str) -> Result<Vec<ContactMessage>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, email, subject, message, ip, thread_id, created_at
+                 FROM contact_messages WHERE email = ?1 COLLATE NOCASE
+                 ORDER BY created_at, id",
+            )?;
+            let messages = stmt
+                .query_map([email], message_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(messages)
+        })
+    }
+
+    fn count_recent_messages(
+        &self,
+        email: &str,
+        ip: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> Result<usize> {
+        self.with_conn(|conn| {
+            let count = conn.query_row(
+                "SELECT COUNT(*) FROM contact_messages
+                 WHERE (email = ?1 COLLATE NOCASE OR ip = ?2) AND created_at >= ?3",
+                params![email, ip, since],
+                |row| row.get(0),
+            )?;
+            Ok(count)
+        })
+    }
+}
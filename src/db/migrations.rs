@@ -0,0 +1,217 @@
+//! Versioned schema migrations, applied in order at startup.
+//!
+//! To change the schema add a new `migrations/NNNN_name.sql` file and append it to
+//! [`MIGRATIONS`]. Never edit a migration that has already been released.
+
+use chrono::Utc;
+use rusqlite::{Connection, params};
+
+use super::{Error, Result};
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("../../migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "carts",
+        sql: include_str!("../../migrations/0002_carts.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "reservations",
+        sql: include_str!("../../migrations/0003_reservations.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "product_attributes",
+        sql: include_str!("../../migrations/0004_product_attributes.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "checkout",
+        sql: include_str!("../../migrations/0005_checkout.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "lockers",
+        sql: include_str!("../../migrations/0006_lockers.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "shipping_weight",
+        sql: include_str!("../../migrations/0007_shipping_weight.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "payments",
+        sql: include_str!("../../migrations/0008_payments.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "order_events",
+        sql: include_str!("../../migrations/0009_order_events.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "outbox",
+        sql: include_str!("../../migrations/0010_outbox.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "contact",
+        sql: include_str!("../../migrations/0011_contact.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "support_inbox",
+        sql: include_str!("../../migrations/0012_support_inbox.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "accounts",
+        sql: include_str!("../../migrations/0013_accounts.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "invoices",
+        sql: include_str!("../../migrations/0014_invoices.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "privacy_requests",
+        sql: include_str!("../../migrations/0015_privacy_requests.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "returns",
+        sql: include_str!("../../migrations/0016_returns.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "complaints",
+        sql: include_str!("../../migrations/0017_complaints.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "refunds",
+        sql: include_str!("../../migrations/0018_refunds.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "staff",
+        sql: include_str!("../../migrations/0019_staff.sql"),
+    },
+    Migration {
+        version: 20,
+        name: "listing_stages",
+        sql: include_str!("../../migrations/0020_listing_stages.sql"),
+    },
+    Migration {
+        version: 21,
+        name: "store_credit_redemption",
+        sql: include_str!("../../migrations/0021_store_credit_redemption.sql"),
+    },
+    Migration {
+        version: 22,
+        name: "auth_attempts",
+        sql: include_str!("../../migrations/0022_auth_attempts.sql"),
+    },
+    Migration {
+        version: 23,
+        name: "pending_registrations",
+        sql: include_str!("../../migrations/0023_pending_registrations.sql"),
+    },
+];
+
+/// Applies every migration newer than the recorded schema version, each in its own transaction.
+pub fn run(conn: &mut Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+    )?;
+    let current = current_version(conn)?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let apply = |conn: &mut Connection| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration.sql)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.name, Utc::now()],
+            )?;
+            tx.commit()
+        };
+        apply(conn).map_err(|source| Error::Migration {
+            version: migration.version,
+            name: migration.name,
+            source,
+        })?;
+        leptos::logging::log!(
+            "zastosowano migrację {} ({})",
+            migration.version,
+            migration.name
+        );
+    }
+    Ok(())
+}
+
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    let version = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_consecutive() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, index + 1, "{}", migration.name);
+        }
+    }
+
+    #[test]
+    fn every_migration_applies_to_an_empty_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        run(&mut conn).unwrap();
+        let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+        assert_eq!(current_version(&conn).unwrap(), latest);
+        let violations: usize = conn
+            .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(violations, 0);
+    }
+
+    #[test]
+    fn running_again_changes_nothing() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        run(&mut conn).unwrap();
+        let applied: usize = conn
+            .query_row("SELECT COUNT(*) FROM schema_migrations", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(applied, MIGRATIONS.len());
+    }
+}
@@ -0,0 +1,636 @@
+use chrono::{DateTime, Utc};
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::products::{PRODUCT_SELECT, product_from_row};
+use super::reservations::claim_items;
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::catalog::Price;
+use crate::checkout::{Address, DeliveryMethod, InvoiceDetails, PaymentMethod};
+use crate::orders::{Actor, OrderEvent, OrderStatus};
+
+/// One item of an order, with the title and price copied at the time of purchase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderLine {
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Order {
+    pub id: i64,
+    pub customer_id: Option<i64>,
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub address: Address,
+    pub delivery: DeliveryMethod,
+    /// InPost locker the parcel goes to, for locker deliveries.
+    pub locker_code: Option<String>,
+    pub payment: PaymentMethod,
+    pub status: OrderStatus,
+    /// Delivery cost, already included in `total`.
+    pub shipping: Price,
+    pub total: Price,
+    /// Part of `total` paid with the customer's store credit.
+    pub store_credit: Price,
+    /// Sum of completed refunds, already paid back to the customer.
+    pub refunded: Price,
+    /// Buyer details when the customer asked for a company invoice.
+    pub invoice: Option<InvoiceDetails>,
+    pub created_at: DateTime<Utc>,
+    pub terms_accepted_at: Option<DateTime<Utc>>,
+    /// Last day of the statutory return window, set on delivery.
+    pub return_window_ends_at: Option<DateTime<Utc>>,
+    pub lines: Vec<OrderLine>,
+}
+
+impl Order {
+    pub fn subtotal(&self) -> Price {
+        Price(self.lines.iter().map(|line| line.price.grosze()).sum())
+    }
+
+    /// What is left to pay after store credit.
+    pub fn amount_due(&self) -> Price {
+        Price(
+            self.total
+                .grosze()
+                .saturating_sub(self.store_credit.grosze()),
+        )
+    }
+}
+
+/// Everything about an order except its lines.
+#[derive(Clone, Debug)]
+pub struct NewOrder {
+    pub customer_id: Option<i64>,
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub address: Address,
+    pub delivery: DeliveryMethod,
+    pub locker_code: Option<String>,
+    pub payment: PaymentMethod,
+    pub shipping: Price,
+    pub invoice: Option<InvoiceDetails>,
+    pub terms_accepted_at: Option<DateTime<Utc>>,
+    /// Pay with the customer's store credit, as much of it as the order needs.
+    pub use_store_credit: bool,
+}
+
+/// A status change already checked against the order lifecycle, ready to be stored.
+#[derive(Clone, Debug)]
+pub struct StatusChange {
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+    pub actor: Actor,
+    pub note: Option<String>,
+    pub at: DateTime<Utc>,
+    /// Put the order's items back on sale.
+    pub release_items: bool,
+    pub return_window_ends_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of turning a cart into an order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlaceOrder {
+    Placed(Box<Order>),
+    /// These items were sold or are held by another cart; no order was created.
+    Unavailable(Vec<u32>),
+}
+
+pub trait OrderRepository {
+    fn create_order(&self, order: &NewOrder, lines: &[OrderLine]) -> Result<Order>;
+    /// Claims the cart's items and creates the order in one transaction, taking titles
+    /// and prices from the catalog rather than from the cart.
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        cart_id: &str,
+        product_ids: &[u32],
+        now: DateTime<Utc>,
+    ) -> Result<PlaceOrder>;
+    fn find_order(&self, id: i64) -> Result<Option<Order>>;
+    fn orders_for_customer(&self, customer_id: i64) -> Result<Vec<Order>>;
+    /// Orders placed with this e-mail address, by guests and account holders alike.
+    fn orders_for_email(&self, email: &str) -> Result<Vec<Order>>;
+    /// Newest first, optionally only those in `status`.
+    fn list_orders(&self, status: Option<OrderStatus>) -> Result<Vec<Order>>;
+    /// Ids of orders placed before `placed_before` and still waiting for payment.
+    fn unpaid_orders(&self, placed_before: DateTime<Utc>) -> Result<Vec<i64>>;
+    /// Applies the change and records it in the order's history, in one transaction.
+    /// Returns `None` without changing anything if the order is no longer in
+    /// `change.from`, e.g. because a concurrent request moved it first.
+    fn change_status(&self, id: i64, change: &StatusChange) -> Result<Option<Order>>;
+    /// The order's status history, oldest first.
+    fn order_events(&self, order_id: i64) -> Result<Vec<OrderEvent>>;
+}
+
+const ORDER_SELECT: &str = "SELECT id, customer_id, email, name, phone, street, postcode, \
+     city, delivery_method, locker_code, payment_method, status, shipping, total, store_credit, \
+     created_at, terms_accepted_at, return_window_ends_at, invoice_company, invoice_nip, \
+     invoice_street, invoice_postcode, invoice_city, (SELECT COALESCE(SUM(amount), 0) FROM refunds \
+     WHERE refunds.order_id = orders.id AND refunds.status = 'completed') AS refunded \
+     FROM orders";
+
+fn order_from_row(row: &Row) -> rusqlite::Result<Order> {
+    Ok(Order {
+        id: row.get("id")?,
+        customer_id: row.get("customer_id")?,
+        email: row.get("email")?,
+        name: row.get("name")?,
+        phone: row.get("phone")?,
+        address: Address {
+            street: row.get("street")?,
+            postcode: row.get("postcode")?,
+            city: row.get("city")?,
+        },
+        delivery: enum_from_sql(row, "delivery_method")?,
+        locker_code: row.get("locker_code")?,
+        payment: enum_from_sql(row, "payment_method")?,
+        status: enum_from_sql(row, "status")?,
+        shipping: Price(row.get("shipping")?),
+        total: Price(row.get("total")?),
+        store_credit: Price(row.get("store_credit")?),
+        refunded: Price(row.get("refunded")?),
+        invoice: match row.get::<_, Option<String>>("invoice_company")? {
+            Some(company) => Some(InvoiceDetails {
+                company,
+                nip: row.get("invoice_nip")?,
+                address: Address {
+                    street: row.get("invoice_street")?,
+                    postcode: row.get("invoice_postcode")?,
+                    city: row.get("invoice_city")?,
+                },
+            }),
+            None => None,
+        },
+        created_at: row.get("created_at")?,
+        terms_accepted_at: row.get("terms_accepted_at")?,
+        return_window_ends_at: row.get("return_window_ends_at")?,
+        lines: Vec::new(),
+    })
+}
+
+fn load_lines(conn: &Connection, order: &mut Order) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT product_id, title, price FROM order_lines WHERE order_id = ?1 ORDER BY id",
+    )?;
+    order.lines = stmt
+        .query_map([order.id], |row| {
+            Ok(OrderLine {
+                product_id: row.get("product_id")?,
+                title: row.get("title")?,
+                price: Price(row.get("price")?),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(())
+}
+
+fn insert_order(conn: &Connection, order: &NewOrder, lines: &[OrderLine]) -> Result<Order> {
+    let subtotal: u32 = lines.iter().map(|line| line.price.grosze()).sum();
+    let total = Price(subtotal + order.shipping.grosze());
+    let created_at = Utc::now();
+    let store_credit = match order.customer_id.filter(|_| order.use_store_credit) {
+        Some(customer_id) => Price(credit_balance(conn, customer_id)?.min(total.grosze())),
+        None => Price(0),
+    };
+    let invoice = order.invoice.as_ref();
+    conn.execute(
+        "INSERT INTO orders (customer_id, email, name, phone, street, postcode, city,
+            delivery_method, locker_code, payment_method, status, shipping, total,
+            created_at, terms_accepted_at, invoice_company, invoice_nip, invoice_street,
+            invoice_postcode, invoice_city, store_credit)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+            ?18, ?19, ?20, ?21)",
+        params![
+            order.customer_id,
+            order.email,
+            order.name,
+            order.phone,
+            order.address.street,
+            order.address.postcode,
+            order.address.city,
+            enum_to_sql(&order.delivery),
+            order.locker_code,
+            enum_to_sql(&order.payment),
+            enum_to_sql(&OrderStatus::PendingPayment),
+            order.shipping.grosze(),
+            total.grosze(),
+            created_at,
+            order.terms_accepted_at,
+            invoice.map(|invoice| &invoice.company),
+            invoice.map(|invoice| &invoice.nip),
+            invoice.map(|invoice| &invoice.address.street),
+            invoice.map(|invoice| &invoice.address.postcode),
+            invoice.map(|invoice| &invoice.address.city),
+            store_credit.grosze(),
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    if store_credit.grosze() > 0 {
+        conn.execute(
+            "INSERT INTO store_credit (customer_id, amount, order_id, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                order.customer_id,
+                -i64::from(store_credit.grosze()),
+                id,
+                created_at
+            ],
+        )?;
+    }
+    for line in lines {
+        conn.execute(
+            "INSERT INTO order_lines (order_id, product_id, title, price)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![id, line.product_id, line.title, line.price.grosze()],
+        )?;
+    }
+    insert_event(
+        conn,
+        id,
+        &OrderEvent {
+            from: None,
+            to: OrderStatus::PendingPayment,
+            actor: Actor::Customer(order.customer_id),
+            note: None,
+            at: created_at,
+        },
+    )?;
+    Ok(Order {
+        id,
+        customer_id: order.customer_id,
+        email: order.email.clone(),
+        name: order.name.clone(),
+        phone: order.phone.clone(),
+        address: order.address.clone(),
+        delivery: order.delivery,
+        locker_code: order.locker_code.clone(),
+        payment: order.payment,
+        status: OrderStatus::PendingPayment,
+        shipping: order.shipping,
+        total,
+        store_credit,
+        refunded: Price(0),
+        invoice: order.invoice.clone(),
+        created_at,
+        terms_accepted_at: order.terms_accepted_at,
+        return_window_ends_at: None,
+        lines: lines.to_vec(),
+    })
+}
+
+fn insert_event(conn: &Connection, order_id: i64, event: &OrderEvent) -> Result<()> {
+    conn.execute(
+        "INSERT INTO order_events (order_id, from_status, to_status, actor, note, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            order_id,
+            event.from.as_ref().map(enum_to_sql),
+            enum_to_sql(&event.to),
+            event.actor.to_string(),
+            event.note,
+            event.at,
+        ],
+    )?;
+    Ok(())
+}
+
+fn event_from_row(row: &Row) -> rusqlite::Result<OrderEvent> {
+    let from: Option<String> = row.get("from_status")?;
+    let actor: String = row.get("actor")?;
+    Ok(OrderEvent {
+        from: match from {
+            Some(_) => Some(enum_from_sql(row, "from_status")?),
+            None => None,
+        },
+        to: enum_from_sql(row, "to_status")?,
+        actor: actor.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                Type::Text,
+                format!("kolumna actor: {e}").into(),
+            )
+        })?,
+        note: row.get("note")?,
+        at: row.get("created_at")?,
+    })
+}
+
+/// The customer's store credit, in grosze; never negative.
+pub(super) fn credit_balance(conn: &Connection, customer_id: i64) -> Result<u32> {
+    let balance: i64 = conn.query_row(
+        "SELECT COALESCE(SUM(amount), 0) FROM store_credit WHERE customer_id = ?1",
+        [customer_id],
+        |row| row.get(0),
+    )?;
+    Ok(u32::try_from(balance.max(0)).unwrap_or(u32::MAX))
+}
+
+fn find_order(conn: &Connection, id: i64) -> Result<Option<Order>> {
+    let order = conn
+        .query_row(
+            &format!("{ORDER_SELECT} WHERE id = ?1"),
+            [id],
+            order_from_row,
+        )
+        .optional()?;
+    match order {
+        Some(mut order) => {
+            load_lines(conn, &mut order)?;
+            Ok(Some(order))
+        }
+        None => Ok(None),
+    }
+}
+
+impl OrderRepository for Db {
+    fn create_order(&self, order: &NewOrder, lines: &[OrderLine]) -> Result<Order> {
+        self.transaction(|tx| insert_order(tx, order, lines))
+    }
+
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        cart_id: &str,
+        product_ids: &[u32],
+        now: DateTime<Utc>,
+    ) -> Result<PlaceOrder> {
+        self.transaction(|tx| {
+            let unavailable = claim_items(tx, cart_id, product_ids, now)?;
+            if !unavailable.is_empty() {
+                return Ok(PlaceOrder::Unavailable(unavailable));
+            }
+            let mut lines = Vec::with_capacity(product_ids.len());
+            for &product_id in product_ids {
+                let product = tx.query_row(
+                    &format!("{PRODUCT_SELECT} WHERE id = ?1"),
+                    [product_id],
+                    product_from_row,
+                )?;
+                lines.push(OrderLine {
+                    product_id,
+                    title: product.title,
+                    price: product.price,
+                });
+            }
+            let order = insert_order(tx, order, &lines)?;
+            Ok(PlaceOrder::Placed(Box::new(order)))
+        })
+    }
+
+    fn find_order(&self, id: i64) -> Result<Option<Order>> {
+        self.with_conn(|conn| find_order(conn, id))
+    }
+
+    fn orders_for_customer(&self, customer_id: i64) -> Result<Vec<Order>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{ORDER_SELECT} WHERE customer_id = ?1 ORDER BY created_at DESC"
+            ))?;
+            let mut orders = stmt
+                .query_map([customer_id], order_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for order in &mut orders {
+                load_lines(conn, order)?;
+            }
+            Ok(orders)
+        })
+    }
+
+    fn orders_for_email(&self, email: &str) -> Result<Vec<Order>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{ORDER_SELECT} WHERE email = ?1 COLLATE NOCASE ORDER BY created_at DESC"
+            ))?;
+            let mut orders = stmt
+                .query_map([email.trim()], order_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for order in &mut orders {
+                load_lines(conn, order)?;
+            }
+            Ok(orders)
+        })
+    }
+
+    fn list_orders(&self, status: Option<OrderStatus>) -> Result<Vec<Order>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{ORDER_SELECT} WHERE ?1 IS NULL OR status = ?1 ORDER BY created_at DESC, id DESC"
+            ))?;
+            let mut orders = stmt
+                .query_map([status.as_ref().map(enum_to_sql)], order_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for order in &mut orders {
+                load_lines(conn, order)?;
+            }
+            Ok(orders)
+        })
+    }
+
+    fn unpaid_orders(&self, placed_before: DateTime<Utc>) -> Result<Vec<i64>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM orders WHERE status = ?1 AND created_at < ?2 ORDER BY id",
+            )?;
+            let ids = stmt
+                .query_map(
+                    params![enum_to_sql(&OrderStatus::PendingPayment), placed_before],
+                    |row| row.get(0),
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(ids)
+        })
+    }
+
+    fn change_status(&self, id: i64, change: &StatusChange) -> Result<Option<Order>> {
+        self.transaction(|tx| {
+            let updated = tx.execute(
+                "UPDATE orders SET status = ?2,
+                    return_window_ends_at = COALESCE(?4, return_window_ends_at)
+                 WHERE id = ?1 AND status = ?3",
+                params![
+                    id,
+                    enum_to_sql(&change.to),
+                    enum_to_sql(&change.from),
+                    change.return_window_ends_at,
+                ],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            insert_event(
+                tx,
+                id,
+                &OrderEvent {
+                    from: Some(change.from),
+                    to: change.to,
+                    actor: change.actor.clone(),
+                    note: change.note.clone(),
+                    at: change.at,
+                },
+            )?;
+            if change.from == OrderStatus::PendingPayment && change.to == OrderStatus::Cancelled {
+                // Nieopłacone zamówienie oddaje wykorzystane środki w sklepie.
+                tx.execute(
+                    "INSERT INTO store_credit (customer_id, amount, order_id, created_at)
+                     SELECT customer_id, store_credit, id, ?2 FROM orders
+                     WHERE id = ?1 AND store_credit > 0 AND customer_id IS NOT NULL",
+                    params![id, change.at],
+                )?;
+            }
+            if change.release_items {
+                tx.execute(
+                    "UPDATE products SET status = 'available'
+                     WHERE status = 'sold'
+                       AND id IN (SELECT product_id FROM order_lines WHERE order_id = ?1)",
+                    [id],
+                )?;
+            }
+            find_order(tx, id)
+        })
+    }
+
+    fn order_events(&self, order_id: i64) -> Result<Vec<OrderEvent>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT from_status, to_status, actor, note, created_at FROM order_events
+                 WHERE order_id = ?1 ORDER BY id",
+            )?;
+            let events = stmt
+                .query_map([order_id], event_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(events)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::db::ReservationRepository;
+    use crate::db::testing::{db, new_order, product};
+    use crate::inventory::Availability;
+
+    #[test]
+    fn placing_an_order_claims_the_items_at_catalog_prices() {
+        let db = db();
+        product(&db, 1, 5000);
+        product(&db, 2, 7000);
+        let now = Utc::now();
+        db.reserve(1, "cart", now + Duration::minutes(15)).unwrap();
+
+        let order = match db
+            .place_order(&new_order(None, "anna@example.com"), "cart", &[1, 2], now)
+            .unwrap()
+        {
+            PlaceOrder::Placed(order) => order,
+            other => panic!("zamówienie nie zostało złożone: {other:?}"),
+        };
+        assert_eq!(order.status, OrderStatus::PendingPayment);
+        assert_eq!(order.total, Price(5000 + 7000 + 1500));
+        assert_eq!(order.lines.len(), 2);
+        for id in [1, 2] {
+            assert_eq!(db.availability(id, None, now).unwrap(), Availability::Sold);
+        }
+        assert_eq!(db.order_events(order.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn an_order_with_a_taken_item_is_not_created() {
+        let db = db();
+        product(&db, 1, 5000);
+        product(&db, 2, 7000);
+        let now = Utc::now();
+        db.reserve(2, "other", now + Duration::minutes(15)).unwrap();
+
+        let placed = db
+            .place_order(&new_order(None, "anna@example.com"), "cart", &[1, 2], now)
+            .unwrap();
+        assert_eq!(placed, PlaceOrder::Unavailable(vec![2]));
+        assert!(db.list_orders(None).unwrap().is_empty());
+        assert_eq!(
+            db.availability(1, None, now).unwrap(),
+            Availability::Available
+        );
+    }
+
+    #[test]
+    fn cancelling_puts_the_items_back_on_sale() {
+        let db = db();
+        product(&db, 1, 5000);
+        let now = Utc::now();
+        let PlaceOrder::Placed(order) = db
+            .place_order(&new_order(None, "anna@example.com"), "cart", &[1], now)
+            .unwrap()
+        else {
+            panic!("zamówienie nie zostało złożone");
+        };
+        let cancel = StatusChange {
+            from: OrderStatus::PendingPayment,
+            to: OrderStatus::Cancelled,
+            actor: Actor::System,
+            note: None,
+            at: now,
+            release_items: true,
+            return_window_ends_at: None,
+        };
+
+        let cancelled = db.change_status(order.id, &cancel).unwrap().unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert_eq!(
+            db.availability(1, None, now).unwrap(),
+            Availability::Available
+        );
+        assert_eq!(db.order_events(order.id).unwrap().len(), 2);
+        // Drugi raz zamówienie nie jest już w statusie wyjściowym.
+        assert_eq!(db.change_status(order.id, &cancel).unwrap(), None);
+    }
+
+    #[test]
+    fn store_credit_is_spent_and_given_back_on_cancellation() {
+        let db = db();
+        product(&db, 1, 5000);
+        let customer = crate::db::testing::customer(&db, "anna@example.com");
+        let now = Utc::now();
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO store_credit (customer_id, amount, created_at) VALUES (?1, 2000, ?2)",
+                params![customer.id, now],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        let order = NewOrder {
+            use_store_credit: true,
+            ..new_order(Some(customer.id), &customer.email)
+        };
+        let PlaceOrder::Placed(order) = db.place_order(&order, "cart", &[1], now).unwrap() else {
+            panic!("zamówienie nie zostało złożone");
+        };
+        assert_eq!(order.store_credit, Price(2000));
+        assert_eq!(order.amount_due(), Price(5000 + 1500 - 2000));
+        let balance = || {
+            db.with_conn(|conn| credit_balance(conn, customer.id))
+                .unwrap()
+        };
+        assert_eq!(balance(), 0);
+
+        let cancel = StatusChange {
+            from: OrderStatus::PendingPayment,
+            to: OrderStatus::Cancelled,
+            actor: Actor::System,
+            note: None,
+            at: now,
+            release_items: true,
+            return_window_ends_at: None,
+        };
+        db.change_status(order.id, &cancel).unwrap();
+        assert_eq!(balance(), 2000);
+    }
+}
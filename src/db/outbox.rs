@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Row, params};
+use serde::{Deserialize, Serialize};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::mail::RenderedEmail;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    #[default]
+    Pending,
+    Sent,
+    /// Every attempt failed; the message will not be retried.
+    Failed,
+}
+
+/// An e-mail waiting in (or already sent from) the outbox.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxEmail {
+    pub id: i64,
+    pub recipient: String,
+    /// Where replies should go, when not to the shop itself.
+    pub reply_to: Option<String>,
+    pub template: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+pub trait OutboxRepository {
+    /// Queues a rendered e-mail to be sent as soon as the outbox worker gets to it.
+    fn enqueue_email(
+        &self,
+        recipient: &str,
+        email: &RenderedEmail,
+        now: DateTime<Utc>,
+    ) -> Result<OutboxEmail>;
+    /// Every e-mail queued for this address, oldest first.
+    fn emails_to(&self, recipient: &str) -> Result<Vec<OutboxEmail>>;
+    /// Pending e-mails whose next attempt is due, oldest first.
+    fn due_emails(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<OutboxEmail>>;
+    fn mark_email_sent(&self, id: i64, now: DateTime<Utc>) -> Result<()>;
+    /// Records a failed attempt. With `retry_at` the e-mail is tried again then,
+    /// otherwise it is given up on.
+    fn mark_email_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()>;
+}
+
+const OUTBOX_SELECT: &str = "SELECT id, recipient, reply_to, template, subject, html, text, status, \
+     attempts, next_attempt_at, last_error, created_at, sent_at FROM outbox";
+
+fn email_from_row(row: &Row) -> rusqlite::Result<OutboxEmail> {
+    Ok(OutboxEmail {
+        id: row.get("id")?,
+        recipient: row.get("recipient")?,
+        reply_to: row.get("reply_to")?,
+        template: row.get("template")?,
+        subject: row.get("subject")?,
+        html: row.get("html")?,
+        text: row.get("text")?,
+        status: enum_from_sql(row, "status")?,
+        attempts: row.get("attempts")?,
+        next_attempt_at: row.get("next_attempt_at")?,
+        last_error: row.get("last_error")?,
+        created_at: row.get("created_at")?,
+        sent_at: row.get("sent_at")?,
+    })
+}
+
+impl OutboxRepository for Db {
+    fn enqueue_email(
+        &self,
+        recipient: &str,
+        email: &RenderedEmail,
+        now: DateTime<Utc>,
+    ) -> Result<OutboxEmail> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO outbox (recipient, reply_to, template, subject, html, text, status,
+                    next_attempt_at, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+                params![
+                    recipient,
+                    email.reply_to,
+                    email.template,
+                    email.subject,
+                    email.html,
+                    email.text,
+                    enum_to_sql(&OutboxStatus::Pending),
+                    now,
+                ],
+            )?;
+            Ok(OutboxEmail {
+                id: conn.last_insert_rowid(),
+                recipient: recipient.to_string(),
+                reply_to: email.reply_to.clone(),
+                template: email.template.to_string(),
+                subject: email.subject.clone(),
+                html: email.html.clone(),
+                text: email.text.clone(),
+                status: OutboxStatus::Pending,
+                attempts: 0,
+                next_attempt_at: now,
+                last_error: None,
+                created_at: now,
+                sent_at: None,
+            })
+        })
+    }
+
+    fn emails_to(&self, recipient: &str) -> Result<Vec<OutboxEmail>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{OUTBOX_SELECT} WHERE recipient = ?1 COLLATE NOCASE ORDER BY created_at, id"
+            ))?;
+            let emails = stmt
+                .query_map([recipient], email_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(emails)
+        })
+    }
+
+    fn due_emails(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<OutboxEmail>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{OUTBOX_SELECT} WHERE status = ?1 AND next_attempt_at <= ?2
+                 ORDER BY next_attempt_at, id LIMIT ?3"
+            ))?;
+            let emails = stmt
+                .query_map(
+                    params![enum_to_sql(&OutboxStatus::Pending), now, limit as i64],
+                    email_from_row,
+                )?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(emails)
+        })
+    }
+
+    fn mark_email_sent(&self, id: i64, now: DateTime<Utc>) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE outbox SET status = ?2, attempts = attempts + 1, sent_at = ?3
+                 WHERE id = ?1",
+                params![id, enum_to_sql(&OutboxStatus::Sent), now],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn mark_email_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let status = match retry_at {
+            Some(_) => OutboxStatus::Pending,
+            None => OutboxStatus::Failed,
+        };
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE outbox SET status = ?2, attempts = attempts + 1, last_error = ?3,
+                    next_attempt_at = COALESCE(?4, next_attempt_at)
+                 WHERE id = ?1",
+                params![id, enum_to_sql(&status), error, retry_at],
+            )?;
+            Ok(())
+        })
+    }
+}
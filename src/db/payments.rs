@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::catalog::Price;
+use crate::checkout::PaymentMethod;
+use crate::payments::PaymentStatus;
+
+/// One attempt to pay for an order with a payment provider.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Payment {
+    /// Our identifier, sent to the provider as the session id.
+    pub id: String,
+    pub order_id: i64,
+    pub provider: String,
+    pub method: PaymentMethod,
+    pub amount: Price,
+    pub status: PaymentStatus,
+    /// The provider's transaction id, once known.
+    pub external_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub trait PaymentRepository {
+    fn create_payment(
+        &self,
+        order_id: i64,
+        provider: &str,
+        method: PaymentMethod,
+        amount: Price,
+    ) -> Result<Payment>;
+    fn find_payment(&self, id: &str) -> Result<Option<Payment>>;
+    fn payments_for_order(&self, order_id: i64) -> Result<Vec<Payment>>;
+    /// Records what the provider reported. A confirmed payment is final; moving its order
+    /// on is up to [`crate::orders::lifecycle`]. Returns the payment as stored.
+    fn update_payment_status(
+        &self,
+        id: &str,
+        status: PaymentStatus,
+        external_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Payment>>;
+}
+
+const PAYMENT_SELECT: &str = "SELECT id, order_id, provider, method, amount, status, \
+     external_id, created_at, updated_at FROM payments";
+
+fn payment_from_row(row: &Row) -> rusqlite::Result<Payment> {
+    Ok(Payment {
+        id: row.get("id")?,
+        order_id: row.get("order_id")?,
+        provider: row.get("provider")?,
+        method: enum_from_sql(row, "method")?,
+        amount: Price(row.get("amount")?),
+        status: enum_from_sql(row, "status")?,
+        external_id: row.get("external_id")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+fn find_payment(conn: &Connection, id: &str) -> Result<Option<Payment>> {
+    let payment = conn
+        .query_row(
+            &format!("{PAYMENT_SELECT} WHERE id = ?1"),
+            [id],
+            payment_from_row,
+        )
+        .optional()?;
+    Ok(payment)
+}
+
+impl PaymentRepository for Db {
+    fn create_payment(
+        &self,
+        order_id: i64,
+        provider: &str,
+        method: PaymentMethod,
+        amount: Price,
+    ) -> Result<Payment> {
+        self.with_conn(|conn| {
+            let payment = Payment {
+                id: uuid::Uuid::new_v4().to_string(),
+                order_id,
+                provider: provider.to_string(),
+                method,
+                amount,
+                status: PaymentStatus::Pending,
+                external_id: None,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            conn.execute(
+                "INSERT INTO payments (id, order_id, provider, method, amount, status,
+                    created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    payment.id,
+                    payment.order_id,
+                    payment.provider,
+                    enum_to_sql(&payment.method),
+                    payment.amount.grosze(),
+                    enum_to_sql(&payment.status),
+                    payment.created_at,
+                    payment.updated_at,
+                ],
+            )?;
+            Ok(payment)
+        })
+    }
+
+    fn find_payment(&self, id: &str) -> Result<Option<Payment>> {
+        self.with_conn(|conn| find_payment(conn, id))
+    }
+
+    fn payments_for_order(&self, order_id: i64) -> Result<Vec<Payment>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{PAYMENT_SELECT} WHERE order_id = ?1 ORDER BY created_at"
+            ))?;
+            let payments = stmt
+                .query_map([order_id], payment_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(payments)
+        })
+    }
+
+    fn update_payment_status(
+        &self,
+        id: &str,
+        status: PaymentStatus,
+        external_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Payment>> {
+        self.transaction(|tx| {
+            let Some(payment) = find_payment(tx, id)? else {
+                return Ok(None);
+            };
+            // Powtórzone lub spóźnione powiadomienia nie cofają potwierdzonej płatności.
+            if payment.status == PaymentStatus::Paid {
+                return Ok(Some(payment));
+            }
+            tx.execute(
+                "UPDATE payments SET status = ?2,
+                    external_id = COALESCE(?3, external_id), updated_at = ?4
+                 WHERE id = ?1",
+                params![id, enum_to_sql(&status), external_id, now],
+            )?;
+            find_payment(tx, id)
+        })
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Transaction, params};
+use serde::{Deserialize, Serialize};
+
+use super::orders::credit_balance;
+use super::{Db, Result, enum_to_sql};
+use crate::catalog::Price;
+use crate::orders::OrderStatus;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyRequestKind {
+    /// The customer downloaded a copy of their data.
+    Export,
+    /// The customer's account was deleted and their orders anonymised.
+    Erasure,
+    /// Like [`Erasure`](Self::Erasure), but done by the retention job after the account
+    /// stayed unused for too long.
+    Retention,
+}
+
+/// What an erasure removed, kept in the audit trail.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErasureSummary {
+    pub orders_anonymised: usize,
+    pub addresses_deleted: usize,
+    pub messages_deleted: usize,
+    pub emails_deleted: usize,
+}
+
+/// Outcome of an erasure request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Erasure {
+    Erased(ErasureSummary),
+    /// These orders are still being fulfilled and need the customer's details; nothing
+    /// was changed.
+    OrdersInProgress(Vec<i64>),
+    /// The customer still has this much store credit, which would be lost with the
+    /// account; nothing was changed.
+    StoreCreditLeft(Price),
+}
+
+/// Requests made under the GDPR, recorded so we can show when each was fulfilled.
+pub trait PrivacyRepository {
+    /// Records an export handed to the customer.
+    fn record_export(&self, customer_id: i64, email_hash: &str, now: DateTime<Utc>) -> Result<()>;
+    /// Deletes the customer with their addresses, messages and e-mails, anonymises their
+    /// orders with their complaints and returns, and records it as `kind`, all in one
+    /// transaction. Orders placed as a guest
+    /// with the same e-mail address are anonymised too. The store credit history stays
+    /// for accounting, without the customer.
+    fn erase_customer(
+        &self,
+        customer_id: i64,
+        email_hash: &str,
+        kind: PrivacyRequestKind,
+        now: DateTime<Utc>,
+    ) -> Result<Erasure>;
+}
+
+/// Statuses in which we still need the buyer's address and phone number.
+const IN_PROGRESS: [OrderStatus; 4] = [
+    OrderStatus::PendingPayment,
+    OrderStatus::Paid,
+    OrderStatus::Packed,
+    OrderStatus::Shipped,
+];
+
+fn record(
+    tx: &Transaction,
+    kind: PrivacyRequestKind,
+    customer_id: i64,
+    email_hash: &str,
+    now: DateTime<Utc>,
+    summary: Option<&ErasureSummary>,
+) -> Result<()> {
+    let summary = summary
+        .and_then(|summary| serde_json::to_string(summary).ok())
+        .unwrap_or_else(|| "{}".to_string());
+    tx.execute(
+        "INSERT INTO privacy_requests (kind, customer_id, email_hash, requested_at,
+            fulfilled_at, summary)
+         VALUES (?1, ?2, ?3, ?4, ?4, ?5)",
+        params![enum_to_sql(&kind), customer_id, email_hash, now, summary],
+    )?;
+    Ok(())
+}
+
+impl PrivacyRepository for Db {
+    fn record_export(&self, customer_id: i64, email_hash: &str, now: DateTime<Utc>) -> Result<()> {
+        self.transaction(|tx| {
+            record(
+                tx,
+                PrivacyRequestKind::Export,
+                customer_id,
+                email_hash,
+                now,
+                None,
+            )
+        })
+    }
+
+    fn erase_customer(
+        &self,
+        customer_id: i64,
+        email_hash: &str,
+        kind: PrivacyRequestKind,
+        now: DateTime<Utc>,
+    ) -> Result<Erasure> {
+        self.transaction(|tx| {
+            let email: String = tx.query_row(
+                "SELECT email FROM customers WHERE id = ?1",
+                [customer_id],
+                |row| row.get(0),
+            )?;
+            let owned = "(customer_id = ?1 OR email = ?2 COLLATE NOCASE)";
+            let statuses = IN_PROGRESS
+                .iter()
+                .map(|status| format!("'{}'", enum_to_sql(status)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let in_progress = tx
+                .prepare(&format!(
+                    "SELECT id FROM orders WHERE {owned} AND status IN ({statuses}) ORDER BY id"
+                ))?
+                .query_map(params![customer_id, email], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<i64>>>()?;
+            if !in_progress.is_empty() {
+                return Ok(Erasure::OrdersInProgress(in_progress));
+            }
+            let store_credit = credit_balance(tx, customer_id)?;
+            if store_credit > 0 {
+                return Ok(Erasure::StoreCreditLeft(Price(store_credit)));
+            }
+
+            // Numer konta podany do zwrotu to dane klienta; sam zwrot zostaje w rozliczeniach.
+            tx.execute(
+                &format!(
+                    "UPDATE refunds SET iban = NULL
+                     WHERE order_id IN (SELECT id FROM orders WHERE {owned})"
+                ),
+                params![customer_id, email],
+            )?;
+            // Opis wady i zdjęcia pochodzą od klienta; zostaje sama reklamacja z naszą
+            // odpowiedzią, a zwroty bez podanego powodu.
+            tx.execute(
+                &format!(
+                    "DELETE FROM complaint_photos WHERE complaint_id IN
+                        (SELECT id FROM complaints
+                         WHERE order_id IN (SELECT id FROM orders WHERE {owned}))"
+                ),
+                params![customer_id, email],
+            )?;
+            tx.execute(
+                &format!(
+                    "UPDATE complaints SET description = ''
+                     WHERE order_id IN (SELECT id FROM orders WHERE {owned})"
+                ),
+                params![customer_id, email],
+            )?;
+            tx.execute(
+                &format!(
+                    "UPDATE returns SET reason = ''
+                     WHERE order_id IN (SELECT id FROM orders WHERE {owned})"
+                ),
+                params![customer_id, email],
+            )?;
+            // Dane do faktury zostają: firma i NIP są potrzebne w księgowości, a nie są
+            // danymi osobowymi kupującego.
+            let orders_anonymised = tx.execute(
+                &format!(
+                    "UPDATE orders SET customer_id = NULL, email = '', name = '', phone = '',
+                        street = '', postcode = '', city = '', anonymised_at = ?3
+                     WHERE {owned}"
+                ),
+                params![customer_id, email, now],
+            )?;
+            tx.execute(
+                "UPDATE order_events SET actor = 'customer' WHERE actor = ?1",
+                [format!("customer:{customer_id}")],
+            )?;
+            let messages_deleted = tx.execute(
+                "DELETE FROM contact_messages WHERE email = ?1 COLLATE NOCASE",
+                [&email],
+            )?;
+            tx.execute(
+                "DELETE FROM support_replies WHERE thread_id IN
+                    (SELECT id FROM support_threads WHERE email = ?1)",
+                [&email],
+            )?;
+            tx.execute("DELETE FROM support_threads WHERE email = ?1", [&email])?;
+            let emails_deleted = tx.execute(
+                // Także powiadomienia o wiadomościach klienta, wysłane do sklepu.
+                "DELETE FROM outbox
+                 WHERE recipient = ?1 COLLATE NOCASE OR reply_to = ?1 COLLATE NOCASE",
+                [&email],
+            )?;
+            let addresses_deleted = tx.execute(
+                "DELETE FROM addresses WHERE customer_id = ?1",
+                [customer_id],
+            )?;
+            tx.execute(
+                "DELETE FROM auth_attempts WHERE email_hash = ?1",
+                [email_hash],
+            )?;
+            // Sesje, linki z e-maili i koszyk znikają kaskadowo razem z klientem.
+            tx.execute("DELETE FROM customers WHERE id = ?1", [customer_id])?;
+
+            let summary = ErasureSummary {
+                orders_anonymised,
+                addresses_deleted,
+                messages_deleted,
+                emails_deleted,
+            };
+            record(tx, kind, customer_id, email_hash, now, Some(&summary))?;
+            Ok(Erasure::Erased(summary))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::complaints::Demand;
+    use crate::db::testing::{customer, db, new_order, product};
+    use crate::db::{
+        ComplaintPhoto, ComplaintRepository, CustomerRepository, NewComplaint, OrderLine,
+        OrderRepository,
+    };
+
+    fn order_in(db: &Db, customer_id: Option<i64>, email: &str, status: OrderStatus) -> i64 {
+        let line = OrderLine {
+            product_id: 1,
+            title: "Rzecz 1".to_string(),
+            price: Price(5000),
+        };
+        let order = db
+            .create_order(&new_order(customer_id, email), &[line])
+            .unwrap();
+        db.with_conn(|conn| {
+            conn.execute(
+                "UPDATE orders SET status = ?2 WHERE id = ?1",
+                params![order.id, enum_to_sql(&status)],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        order.id
+    }
+
+    #[test]
+    fn orders_still_on_their_way_block_erasure() {
+        let db = db();
+        product(&db, 1, 5000);
+        let anna = customer(&db, "anna@example.com");
+        let id = order_in(&db, Some(anna.id), &anna.email, OrderStatus::Shipped);
+
+        let erasure = db
+            .erase_customer(anna.id, "hash", PrivacyRequestKind::Erasure, Utc::now())
+            .unwrap();
+        assert_eq!(erasure, Erasure::OrdersInProgress(vec![id]));
+        assert!(db.find_customer(anna.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn store_credit_blocks_erasure() {
+        let db = db();
+        let anna = customer(&db, "anna@example.com");
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO store_credit (customer_id, amount, created_at) VALUES (?1, 900, ?2)",
+                params![anna.id, Utc::now()],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+
+        let erasure = db
+            .erase_customer(anna.id, "hash", PrivacyRequestKind::Erasure, Utc::now())
+            .unwrap();
+        assert_eq!(erasure, Erasure::StoreCreditLeft(Price(900)));
+    }
+
+    #[test]
+    fn erasure_anonymises_orders_and_complaints() {
+        let db = db();
+        product(&db, 1, 5000);
+        let anna = customer(&db, "anna@example.com");
+        let own = order_in(&db, Some(anna.id), &anna.email, OrderStatus::Delivered);
+        // Zamówienie złożone wcześniej bez konta, na ten sam adres.
+        let guest = order_in(&db, None, "Anna@Example.com", OrderStatus::Cancelled);
+        let now = Utc::now();
+        let complaint = db
+            .create_complaint(
+                &NewComplaint {
+                    order_id: own,
+                    product_id: 1,
+                    title: "Rzecz 1".to_string(),
+                    price: Price(5000),
+                    token: "token".to_string(),
+                    description: "Pruje się szew.".to_string(),
+                    demand: Demand::Repair,
+                },
+                &[ComplaintPhoto {
+                    content_type: "image/png".to_string(),
+                    data: vec![1, 2, 3],
+                }],
+                now,
+                now + Duration::days(14),
+            )
+            .unwrap()
+            .unwrap();
+
+        let Erasure::Erased(summary) = db
+            .erase_customer(anna.id, "hash", PrivacyRequestKind::Erasure, now)
+            .unwrap()
+        else {
+            panic!("konto nie zostało usunięte");
+        };
+        assert_eq!(summary.orders_anonymised, 2);
+        assert!(db.find_customer(anna.id).unwrap().is_none());
+        for id in [own, guest] {
+            let order = db.find_order(id).unwrap().unwrap();
+            assert_eq!((order.customer_id, order.email.as_str()), (None, ""));
+            assert_eq!(order.address.street, "");
+        }
+        let complaint = db.find_complaint(complaint.id).unwrap().unwrap();
+        assert_eq!(complaint.description, "");
+        assert!(complaint.photos.is_empty());
+    }
+}
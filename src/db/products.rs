@@ -0,0 +1,529 @@
+use chrono::NaiveDate;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension, Row, params, params_from_iter};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql, json_from_sql, json_to_sql};
+use crate::catalog::{ListingStage, Price, Product, ProductQuery, ProductStatus};
+use crate::filters::{FacetKind, ProductFilters};
+
+pub trait ProductRepository {
+    fn list_products(&self) -> Result<Vec<Product>>;
+    fn find_product(&self, id: u32) -> Result<Option<Product>>;
+    fn find_product_by_slug(&self, slug: &str) -> Result<Option<Product>>;
+    fn count_products(&self) -> Result<usize>;
+    /// Inserts the product, or replaces the stored one with the same id.
+    fn save_product(&self, product: &Product) -> Result<()>;
+    /// Inserts the product under the next free id, ignoring `product.id`, and returns
+    /// that id.
+    fn insert_product(&self, product: &Product) -> Result<u32>;
+    /// Products that were ever ordered stay for the order history; for them this
+    /// returns `false` without deleting anything.
+    fn delete_product(&self, id: u32) -> Result<bool>;
+    /// Listed products a listing page with `query` shows once `filters` are applied, by
+    /// id. Its sort order and paging are left to the caller.
+    fn list_filtered_products(
+        &self,
+        query: &ProductQuery,
+        filters: &ProductFilters,
+    ) -> Result<Vec<Product>>;
+    /// How many of the listing's products have each value of `kind`, counting as if
+    /// nothing in `kind` itself were selected. Products without a value are left out.
+    fn count_facet_values(
+        &self,
+        query: &ProductQuery,
+        filters: &ProductFilters,
+        kind: FacetKind,
+    ) -> Result<Vec<(String, usize)>>;
+    /// How many of the listing's products fall in each price range, in whole złoty with
+    /// `None` unbounded, ignoring the price already chosen in `filters`.
+    fn count_price_ranges(
+        &self,
+        query: &ProductQuery,
+        filters: &ProductFilters,
+        ranges: &[(Option<u32>, Option<u32>)],
+    ) -> Result<Vec<usize>>;
+    /// Products at `stage`, in the order they were taken in.
+    fn products_in_stage(&self, stage: ListingStage) -> Result<Vec<Product>>;
+    /// How many products are at each stage; stages with none are left out.
+    fn count_by_stage(&self) -> Result<Vec<(ListingStage, usize)>>;
+    /// Moves an unsold product from `from` to `to`, listing it as new on `today` when `to`
+    /// is [`ListingStage::Listed`]. Returns `false` if the product is no longer at `from`.
+    fn change_product_stage(
+        &self,
+        id: u32,
+        from: ListingStage,
+        to: ListingStage,
+        today: NaiveDate,
+    ) -> Result<bool>;
+}
+
+const PRODUCT_COLUMNS: &str = "id, slug, title, category, price, original_price, images, \
+     condition, size, brand, color, material, description, measurements, listed_on, status, \
+     featured, weight_grams, stage";
+
+/// Reads products with `reserved` reported for items under an unexpired cart hold; the
+/// stored status itself only ever says whether an item is still for sale. Sold items are
+/// reported at the `sold` stage whatever stage is stored.
+pub(crate) const PRODUCT_SELECT: &str = "SELECT id, slug, title, category, price, \
+     original_price, images, condition, size, brand, color, material, description, \
+     measurements, listed_on, featured, weight_grams, CASE WHEN status = 'available' AND EXISTS (
+         SELECT 1 FROM reservations r
+         WHERE r.product_id = products.id AND r.expires_at > unixepoch()
+     ) THEN 'reserved' ELSE status END AS status, \
+     CASE WHEN status = 'sold' THEN 'sold' ELSE stage END AS stage
+     FROM products";
+
+/// The stage as [`PRODUCT_SELECT`] reports it, for use in conditions.
+const STAGE_EXPR: &str = "CASE WHEN status = 'sold' THEN 'sold' ELSE stage END";
+
+/// Column a facet filters on.
+fn facet_column(kind: FacetKind) -> &'static str {
+    match kind {
+        FacetKind::Size => "size",
+        FacetKind::Brand => "brand",
+        FacetKind::Color => "color",
+        FacetKind::Condition => "condition",
+        FacetKind::Material => "material",
+    }
+}
+
+/// `WHERE` clause of a listing, built up condition by condition with anonymous
+/// parameters in the order they appear.
+#[derive(Default)]
+struct Conditions {
+    sql: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl Conditions {
+    /// The page's own query: listed products of its category, on sale or featured.
+    fn listing(query: &ProductQuery) -> Self {
+        let mut conditions = Conditions::default();
+        conditions.push(format!("{STAGE_EXPR} = 'listed'"), vec![]);
+        if let Some(category) = query.category {
+            conditions.push(
+                "category = ?".into(),
+                vec![Value::Text(enum_to_sql(&category))],
+            );
+        }
+        if query.on_sale {
+            conditions.push("original_price > price".into(), vec![]);
+        }
+        if query.featured {
+            conditions.push("featured = 1".into(), vec![]);
+        }
+        conditions
+    }
+
+    fn push(&mut self, sql: String, params: Vec<Value>) {
+        self.sql.push(sql);
+        self.params.extend(params);
+    }
+
+    /// Any selected value within a facet, every facet in use except `except`.
+    fn facets(mut self, filters: &ProductFilters, except: Option<FacetKind>) -> Self {
+        for kind in FacetKind::ALL
+            .into_iter()
+            .filter(|kind| Some(*kind) != except)
+        {
+            let selected = filters.values(kind);
+            if selected.is_empty() {
+                continue;
+            }
+            let placeholders = vec!["?"; selected.len()].join(", ");
+            self.push(
+                format!("{} IN ({placeholders})", facet_column(kind)),
+                selected.iter().cloned().map(Value::from).collect(),
+            );
+        }
+        self
+    }
+
+    fn price(mut self, min: Option<u32>, max: Option<u32>) -> Self {
+        if let Some(min) = min {
+            self.push("price >= ?".into(), vec![grosze_bound(min)]);
+        }
+        if let Some(max) = max {
+            self.push("price <= ?".into(), vec![grosze_bound(max)]);
+        }
+        self
+    }
+
+    fn clause(&self) -> String {
+        format!(" WHERE {}", self.sql.join(" AND "))
+    }
+}
+
+fn grosze_bound(zloty: u32) -> Value {
+    Value::Integer(i64::from(zloty) * 100)
+}
+
+pub(crate) fn product_from_row(row: &Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        id: row.get("id")?,
+        slug: row.get("slug")?,
+        title: row.get("title")?,
+        category: enum_from_sql(row, "category")?,
+        price: Price(row.get("price")?),
+        original_price: row.get::<_, Option<u32>>("original_price")?.map(Price),
+        images: json_from_sql(row, "images")?,
+        condition: enum_from_sql(row, "condition")?,
+        size: row.get("size")?,
+        brand: row.get("brand")?,
+        color: row.get("color")?,
+        material: row.get("material")?,
+        description: row.get("description")?,
+        measurements: json_from_sql(row, "measurements")?,
+        listed_on: row.get("listed_on")?,
+        status: enum_from_sql(row, "status")?,
+        featured: row.get("featured")?,
+        weight_grams: row.get("weight_grams")?,
+        stage: enum_from_sql(row, "stage")?,
+    })
+}
+
+fn write_product(conn: &Connection, product: &Product) -> Result<()> {
+    // Rezerwacje żyją w osobnej tabeli, więc "reserved" nigdy nie trafia do kolumny.
+    let status = match product.status {
+        ProductStatus::Reserved => ProductStatus::Available,
+        status => status,
+    };
+    // Sprzedaż wynika ze statusu; w kolumnie zostaje etap, na którym rzecz była.
+    let (status, stage) = match product.stage {
+        ListingStage::Sold => (ProductStatus::Sold, ListingStage::Listed),
+        stage => (status, stage),
+    };
+    conn.execute(
+        &format!(
+            "INSERT INTO products ({PRODUCT_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+                     ?19)
+             ON CONFLICT (id) DO UPDATE SET
+                slug = excluded.slug, title = excluded.title,
+                category = excluded.category, price = excluded.price,
+                original_price = excluded.original_price, images = excluded.images,
+                condition = excluded.condition, size = excluded.size,
+                brand = excluded.brand, color = excluded.color,
+                material = excluded.material, description = excluded.description,
+                measurements = excluded.measurements, listed_on = excluded.listed_on,
+                status = excluded.status, featured = excluded.featured,
+                weight_grams = excluded.weight_grams, stage = excluded.stage"
+        ),
+        params![
+            product.id,
+            product.slug,
+            product.title,
+            enum_to_sql(&product.category),
+            product.price.grosze(),
+            product.original_price.map(Price::grosze),
+            json_to_sql(&product.images),
+            enum_to_sql(&product.condition),
+            product.size,
+            product.brand,
+            product.color,
+            product.material,
+            product.description,
+            json_to_sql(&product.measurements),
+            product.listed_on,
+            enum_to_sql(&status),
+            product.featured,
+            product.weight_grams,
+            enum_to_sql(&stage),
+        ],
+    )?;
+    Ok(())
+}
+
+impl ProductRepository for Db {
+    fn list_products(&self) -> Result<Vec<Product>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!("{PRODUCT_SELECT} ORDER BY id"))?;
+            let products = stmt
+                .query_map([], product_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(products)
+        })
+    }
+
+    fn find_product(&self, id: u32) -> Result<Option<Product>> {
+        self.with_conn(|conn| {
+            let product = conn
+                .query_row(
+                    &format!("{PRODUCT_SELECT} WHERE id = ?1"),
+                    [id],
+                    product_from_row,
+                )
+                .optional()?;
+            Ok(product)
+        })
+    }
+
+    fn find_product_by_slug(&self, slug: &str) -> Result<Option<Product>> {
+        self.with_conn(|conn| {
+            let product = conn
+                .query_row(
+                    &format!("{PRODUCT_SELECT} WHERE slug = ?1"),
+                    [slug],
+                    product_from_row,
+                )
+                .optional()?;
+            Ok(product)
+        })
+    }
+
+    fn count_products(&self) -> Result<usize> {
+        self.with_conn(|conn| {
+            let count = conn.query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))?;
+            Ok(count)
+        })
+    }
+
+    fn save_product(&self, product: &Product) -> Result<()> {
+        self.with_conn(|conn| write_product(conn, product))
+    }
+
+    fn insert_product(&self, product: &Product) -> Result<u32> {
+        self.transaction(|tx| {
+            let id = tx.query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM products", [], |row| {
+                row.get(0)
+            })?;
+            write_product(
+                tx,
+                &Product {
+                    id,
+                    ..product.clone()
+                },
+            )?;
+            Ok(id)
+        })
+    }
+
+    fn delete_product(&self, id: u32) -> Result<bool> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM products WHERE id = ?1
+                 AND NOT EXISTS (SELECT 1 FROM order_lines WHERE product_id = ?1)",
+                [id],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn list_filtered_products(
+        &self,
+        query: &ProductQuery,
+        filters: &ProductFilters,
+    ) -> Result<Vec<Product>> {
+        let conditions = Conditions::listing(query)
+            .facets(filters, None)
+            .price(filters.price_min, filters.price_max);
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{PRODUCT_SELECT}{} ORDER BY id",
+                conditions.clause()
+            ))?;
+            let products = stmt
+                .query_map(params_from_iter(&conditions.params), product_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(products)
+        })
+    }
+
+    fn count_facet_values(
+        &self,
+        query: &ProductQuery,
+        filters: &ProductFilters,
+        kind: FacetKind,
+    ) -> Result<Vec<(String, usize)>> {
+        let column = facet_column(kind);
+        let mut conditions = Conditions::listing(query)
+            .facets(filters, Some(kind))
+            .price(filters.price_min, filters.price_max);
+        conditions.push(format!("{column} IS NOT NULL"), vec![]);
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {column}, COUNT(*) FROM products{} GROUP BY 1",
+                conditions.clause()
+            ))?;
+            let counts = stmt
+                .query_map(params_from_iter(&conditions.params), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(counts)
+        })
+    }
+
+    fn count_price_ranges(
+        &self,
+        query: &ProductQuery,
+        filters: &ProductFilters,
+        ranges: &[(Option<u32>, Option<u32>)],
+    ) -> Result<Vec<usize>> {
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conditions = Conditions::listing(query).facets(filters, None);
+        // Parametry zakresów stoją w zapytaniu przed warunkami WHERE.
+        let mut params = Vec::new();
+        let sums: Vec<String> = ranges
+            .iter()
+            .map(|&(min, max)| {
+                let mut range = Conditions::default().price(min, max);
+                params.append(&mut range.params);
+                if range.sql.is_empty() {
+                    range.sql.push("1".into());
+                }
+                format!("COALESCE(SUM({}), 0)", range.sql.join(" AND "))
+            })
+            .collect();
+        params.extend(conditions.params.iter().cloned());
+        self.with_conn(|conn| {
+            let counts = conn.query_row(
+                &format!(
+                    "SELECT {} FROM products{}",
+                    sums.join(", "),
+                    conditions.clause()
+                ),
+                params_from_iter(&params),
+                |row| (0..ranges.len()).map(|i| row.get(i)).collect(),
+            )?;
+            Ok(counts)
+        })
+    }
+
+    fn products_in_stage(&self, stage: ListingStage) -> Result<Vec<Product>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{PRODUCT_SELECT} WHERE {STAGE_EXPR} = ?1 ORDER BY id"
+            ))?;
+            let products = stmt
+                .query_map([enum_to_sql(&stage)], product_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(products)
+        })
+    }
+
+    fn count_by_stage(&self) -> Result<Vec<(ListingStage, usize)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {STAGE_EXPR} AS stage, COUNT(*) AS count FROM products GROUP BY 1"
+            ))?;
+            let counts = stmt
+                .query_map([], |row| {
+                    Ok((enum_from_sql(row, "stage")?, row.get("count")?))
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(counts)
+        })
+    }
+
+    fn change_product_stage(
+        &self,
+        id: u32,
+        from: ListingStage,
+        to: ListingStage,
+        today: NaiveDate,
+    ) -> Result<bool> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE products SET stage = ?3,
+                    listed_on = CASE WHEN ?3 = 'listed' THEN ?4 ELSE listed_on END
+                 WHERE id = ?1 AND stage = ?2 AND status != 'sold'",
+                params![id, enum_to_sql(&from), enum_to_sql(&to), today],
+            )?;
+            Ok(changed > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::Category;
+    use crate::db::testing::{db, product};
+
+    /// Two listed women's items, one sold, one in intake and one men's item.
+    fn shelf(db: &Db) {
+        use Category::{Man, Woman};
+        use ListingStage::{Intake, Listed, Sold};
+
+        let items = [
+            (1, "M", Some("Zara"), 4000, Woman, Listed),
+            (2, "L", Some("Zara"), 8000, Woman, Listed),
+            (3, "M", None, 5000, Woman, Sold),
+            (4, "M", Some("Mango"), 6000, Woman, Intake),
+            (5, "M", Some("Mango"), 15000, Man, Listed),
+        ];
+        for (id, size, brand, price, category, stage) in items {
+            let item = Product {
+                size: size.into(),
+                brand: brand.map(str::to_string),
+                category,
+                stage,
+                ..product(db, id, price)
+            };
+            db.save_product(&item).unwrap();
+        }
+    }
+
+    fn ids(products: Vec<Product>) -> Vec<u32> {
+        products.into_iter().map(|product| product.id).collect()
+    }
+
+    #[test]
+    fn listings_show_only_listed_products_matching_every_facet() {
+        let db = db();
+        shelf(&db);
+        let women = ProductQuery::category(Category::Woman);
+        let filters = |sizes: &[&str], brands: &[&str], price_min| ProductFilters {
+            sizes: sizes.iter().map(|s| s.to_string()).collect(),
+            brands: brands.iter().map(|s| s.to_string()).collect(),
+            price_min,
+            ..Default::default()
+        };
+
+        let all = db.list_filtered_products(&women, &filters(&[], &[], None));
+        assert_eq!(ids(all.unwrap()), [1, 2]);
+        let medium = db.list_filtered_products(&women, &filters(&["M"], &["Zara"], None));
+        assert_eq!(ids(medium.unwrap()), [1]);
+        let any_brand = db.list_filtered_products(
+            &ProductQuery::default(),
+            &filters(&[], &["Zara", "Mango"], None),
+        );
+        assert_eq!(ids(any_brand.unwrap()), [1, 2, 5]);
+        let dearer = db.list_filtered_products(&women, &filters(&[], &[], Some(50)));
+        assert_eq!(ids(dearer.unwrap()), [2]);
+    }
+
+    #[test]
+    fn facets_are_counted_without_their_own_selection() {
+        let db = db();
+        shelf(&db);
+        let women = ProductQuery::category(Category::Woman);
+        let filters = ProductFilters {
+            sizes: vec!["M".into()],
+            ..Default::default()
+        };
+
+        let mut sizes = db
+            .count_facet_values(&women, &filters, FacetKind::Size)
+            .unwrap();
+        sizes.sort();
+        assert_eq!(sizes, [("L".to_string(), 1), ("M".to_string(), 1)]);
+        let brands = db
+            .count_facet_values(&women, &filters, FacetKind::Brand)
+            .unwrap();
+        assert_eq!(brands, [("Zara".to_string(), 1)]);
+
+        let ranges = [(None, Some(50)), (Some(50), Some(100)), (Some(100), None)];
+        let counts = db
+            .count_price_ranges(
+                &ProductQuery::default(),
+                &ProductFilters::default(),
+                &ranges,
+            )
+            .unwrap();
+        assert_eq!(counts, [1, 1, 1]);
+    }
+}
@@ -0,0 +1,268 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::orders::credit_balance;
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::catalog::Price;
+use crate::refunds::{Refund, RefundLine, RefundMethod, RefundStatus, StoreCreditEntry};
+
+/// A refund as staff issue it, before any money moves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewRefund {
+    pub order_id: i64,
+    /// The return of items this refund pays for, if any.
+    pub return_id: Option<i64>,
+    pub method: RefundMethod,
+    pub lines: Vec<RefundLine>,
+    /// Part of the delivery cost being refunded.
+    pub shipping: Price,
+    /// The payment sent back, for refunds to the original payment method.
+    pub payment_id: Option<String>,
+    /// The customer's account, for refunds by bank transfer.
+    pub iban: Option<String>,
+    pub created_by: String,
+}
+
+impl NewRefund {
+    pub fn amount(&self) -> Price {
+        let lines: u32 = self.lines.iter().map(|line| line.price.grosze()).sum();
+        Price(lines + self.shipping.grosze())
+    }
+}
+
+pub trait RefundRepository {
+    /// Records a pending refund. Returns `None` without creating anything if one of the
+    /// lines was already refunded, or the refund would take back more of the delivery
+    /// cost or of the order total than is left. Failed refunds do not count.
+    fn create_refund(&self, refund: &NewRefund, now: DateTime<Utc>) -> Result<Option<Refund>>;
+    fn find_refund(&self, id: i64) -> Result<Option<Refund>>;
+    /// Oldest first.
+    fn refunds_for_order(&self, order_id: i64) -> Result<Vec<Refund>>;
+    /// Newest first, optionally only those in `status`.
+    fn list_refunds(&self, status: Option<RefundStatus>) -> Result<Vec<Refund>>;
+    /// Marks a pending refund as completed or failed. Completing a refund to store
+    /// credit adds the amount to the customer's balance. Returns `None` without changing
+    /// anything if the refund is no longer pending.
+    fn settle_refund(
+        &self,
+        id: i64,
+        status: RefundStatus,
+        external_id: Option<&str>,
+        note: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Refund>>;
+    /// What the customer has in store credit.
+    fn store_credit_balance(&self, customer_id: i64) -> Result<Price>;
+    /// Every change of the customer's store credit, oldest first.
+    fn store_credit_history(&self, customer_id: i64) -> Result<Vec<StoreCreditEntry>>;
+}
+
+const REFUND_SELECT: &str = "SELECT id, order_id, return_id, method, status, amount, shipping, \
+     payment_id, iban, external_id, note, created_by, created_at, completed_at FROM refunds";
+
+fn refund_from_row(row: &Row) -> rusqlite::Result<Refund> {
+    Ok(Refund {
+        id: row.get("id")?,
+        order_id: row.get("order_id")?,
+        return_id: row.get("return_id")?,
+        method: enum_from_sql(row, "method")?,
+        status: enum_from_sql(row, "status")?,
+        amount: Price(row.get("amount")?),
+        shipping: Price(row.get("shipping")?),
+        lines: Vec::new(),
+        payment_id: row.get("payment_id")?,
+        iban: row.get("iban")?,
+        external_id: row.get("external_id")?,
+        note: row.get("note")?,
+        created_by: row.get("created_by")?,
+        created_at: row.get("created_at")?,
+        completed_at: row.get("completed_at")?,
+    })
+}
+
+fn query_refunds(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<Refund>> {
+    let mut stmt = conn.prepare(sql)?;
+    let mut refunds = stmt
+        .query_map(params, refund_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut lines = conn.prepare(
+        "SELECT product_id, title, price FROM refund_lines WHERE refund_id = ?1
+         ORDER BY product_id",
+    )?;
+    for refund in &mut refunds {
+        refund.lines = lines
+            .query_map([refund.id], |row| {
+                Ok(RefundLine {
+                    product_id: row.get("product_id")?,
+                    title: row.get("title")?,
+                    price: Price(row.get("price")?),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+    }
+    Ok(refunds)
+}
+
+fn find_refund(conn: &Connection, id: i64) -> Result<Option<Refund>> {
+    Ok(query_refunds(conn, &format!("{REFUND_SELECT} WHERE id = ?1"), [id])?.pop())
+}
+
+impl RefundRepository for Db {
+    fn create_refund(&self, refund: &NewRefund, now: DateTime<Utc>) -> Result<Option<Refund>> {
+        self.transaction(|tx| {
+            let failed = enum_to_sql(&RefundStatus::Failed);
+            let (shipping, total): (u32, u32) = tx.query_row(
+                "SELECT shipping, total FROM orders WHERE id = ?1",
+                [refund.order_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            let (shipping_refunded, refunded): (u32, u32) = tx.query_row(
+                "SELECT COALESCE(SUM(shipping), 0), COALESCE(SUM(amount), 0) FROM refunds
+                 WHERE order_id = ?1 AND status != ?2",
+                params![refund.order_id, failed],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            if shipping_refunded + refund.shipping.grosze() > shipping
+                || refunded + refund.amount().grosze() > total
+            {
+                return Ok(None);
+            }
+            let mut already_refunded = tx.prepare(
+                "SELECT 1 FROM refund_lines l JOIN refunds r ON r.id = l.refund_id
+                 WHERE r.order_id = ?1 AND r.status != ?2 AND l.product_id = ?3",
+            )?;
+            for line in &refund.lines {
+                let refunded = already_refunded
+                    .query_row(
+                        params![refund.order_id, failed, line.product_id],
+                        |_| Ok(()),
+                    )
+                    .optional()?;
+                if refunded.is_some() {
+                    return Ok(None);
+                }
+            }
+
+            tx.execute(
+                "INSERT INTO refunds (order_id, return_id, method, status, amount, shipping,
+                     payment_id, iban, created_by, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    refund.order_id,
+                    refund.return_id,
+                    enum_to_sql(&refund.method),
+                    enum_to_sql(&RefundStatus::Pending),
+                    refund.amount().grosze(),
+                    refund.shipping.grosze(),
+                    refund.payment_id,
+                    refund.iban,
+                    refund.created_by,
+                    now
+                ],
+            )?;
+            let id = tx.last_insert_rowid();
+            for line in &refund.lines {
+                tx.execute(
+                    "INSERT INTO refund_lines (refund_id, product_id, title, price)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![id, line.product_id, line.title, line.price.grosze()],
+                )?;
+            }
+            find_refund(tx, id)
+        })
+    }
+
+    fn find_refund(&self, id: i64) -> Result<Option<Refund>> {
+        self.with_conn(|conn| find_refund(conn, id))
+    }
+
+    fn refunds_for_order(&self, order_id: i64) -> Result<Vec<Refund>> {
+        self.with_conn(|conn| {
+            query_refunds(
+                conn,
+                &format!("{REFUND_SELECT} WHERE order_id = ?1 ORDER BY id"),
+                [order_id],
+            )
+        })
+    }
+
+    fn list_refunds(&self, status: Option<RefundStatus>) -> Result<Vec<Refund>> {
+        self.with_conn(|conn| {
+            query_refunds(
+                conn,
+                &format!("{REFUND_SELECT} WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC"),
+                [status.as_ref().map(enum_to_sql)],
+            )
+        })
+    }
+
+    fn settle_refund(
+        &self,
+        id: i64,
+        status: RefundStatus,
+        external_id: Option<&str>,
+        note: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Refund>> {
+        self.transaction(|tx| {
+            let updated = tx.execute(
+                "UPDATE refunds SET
+                     status = ?2,
+                     external_id = COALESCE(?3, external_id),
+                     note = ?4,
+                     completed_at = CASE WHEN ?2 = ?6 THEN ?5 END
+                 WHERE id = ?1 AND status = ?7",
+                params![
+                    id,
+                    enum_to_sql(&status),
+                    external_id,
+                    note,
+                    now,
+                    enum_to_sql(&RefundStatus::Completed),
+                    enum_to_sql(&RefundStatus::Pending)
+                ],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            if status == RefundStatus::Completed {
+                tx.execute(
+                    "INSERT INTO store_credit (customer_id, amount, refund_id, created_at)
+                     SELECT o.customer_id, r.amount, r.id, ?2 FROM refunds r
+                     JOIN orders o ON o.id = r.order_id
+                     WHERE r.id = ?1 AND r.method = ?3 AND o.customer_id IS NOT NULL",
+                    params![id, now, enum_to_sql(&RefundMethod::StoreCredit)],
+                )?;
+            }
+            find_refund(tx, id)
+        })
+    }
+
+    fn store_credit_balance(&self, customer_id: i64) -> Result<Price> {
+        self.with_conn(|conn| Ok(Price(credit_balance(conn, customer_id)?)))
+    }
+
+    fn store_credit_history(&self, customer_id: i64) -> Result<Vec<StoreCreditEntry>> {
+        self.with_conn(|conn| {
+            let entries = conn
+                .prepare(
+                    "SELECT amount, refund_id, order_id, created_at FROM store_credit
+                     WHERE customer_id = ?1 ORDER BY id",
+                )?
+                .query_map([customer_id], |row| {
+                    Ok(StoreCreditEntry {
+                        amount: row.get(0)?,
+                        refund_id: row.get(1)?,
+                        order_id: row.get(2)?,
+                        created_at: row.get(3)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(entries)
+        })
+    }
+}
@@ -0,0 +1,281 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::{Db, Result};
+use crate::inventory::Availability;
+
+/// Timed holds on one-of-a-kind items. Expired holds are ignored everywhere, so an item
+/// goes back on sale the moment its hold runs out, even before the sweeper deletes it.
+pub trait ReservationRepository {
+    /// Holds the item for `cart_id` until `until`, or extends that cart's existing hold.
+    /// Returns `false` when the item is sold or held by another cart.
+    fn reserve(&self, product_id: u32, cart_id: &str, until: DateTime<Utc>) -> Result<bool>;
+    fn release(&self, product_id: u32, cart_id: &str) -> Result<()>;
+    /// Gives up every hold of the cart; returns how many there were.
+    fn release_cart(&self, cart_id: &str) -> Result<usize>;
+    /// Hands every hold of cart `from` over to cart `to`, e.g. when a guest cart is
+    /// merged into the customer's saved one on sign-in.
+    fn move_holds(&self, from: &str, to: &str) -> Result<()>;
+    /// Deletes holds that ran out before `now`; returns how many were removed.
+    fn release_expired(&self, now: DateTime<Utc>) -> Result<usize>;
+    fn availability(
+        &self,
+        product_id: u32,
+        cart_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Availability>;
+    /// Marks the items as sold if every one of them can still go to `cart_id`.
+    /// Returns the ids that could not be claimed; nothing is changed unless it is empty.
+    fn claim_items(
+        &self,
+        cart_id: &str,
+        product_ids: &[u32],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<u32>>;
+}
+
+pub(crate) fn availability(
+    conn: &Connection,
+    product_id: u32,
+    cart_id: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Availability> {
+    // Rzeczy w przygotowaniu nie są jeszcze na sprzedaż.
+    let status: Option<String> = conn
+        .query_row(
+            "SELECT status FROM products WHERE id = ?1 AND stage = 'listed'",
+            [product_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if status.as_deref() != Some("available") {
+        return Ok(Availability::Sold);
+    }
+
+    let hold: Option<(String, i64)> = conn
+        .query_row(
+            "SELECT cart_id, expires_at FROM reservations
+             WHERE product_id = ?1 AND expires_at > ?2",
+            params![product_id, now.timestamp()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(match hold {
+        None => Availability::Available,
+        Some((holder, expires_at)) if Some(holder.as_str()) == cart_id => Availability::HeldByYou {
+            expires_at: DateTime::from_timestamp(expires_at, 0).unwrap_or(now),
+        },
+        Some(_) => Availability::Reserved,
+    })
+}
+
+/// Connection-level version of [`ReservationRepository::claim_items`], so checkout can
+/// claim items inside the same transaction that creates the order.
+pub(crate) fn claim_items(
+    conn: &Connection,
+    cart_id: &str,
+    product_ids: &[u32],
+    now: DateTime<Utc>,
+) -> Result<Vec<u32>> {
+    let mut unavailable = Vec::new();
+    for &product_id in product_ids {
+        match availability(conn, product_id, Some(cart_id), now)? {
+            Availability::HeldByYou { .. } | Availability::Available => {}
+            Availability::Reserved | Availability::Sold => unavailable.push(product_id),
+        }
+    }
+    if !unavailable.is_empty() {
+        return Ok(unavailable);
+    }
+    for &product_id in product_ids {
+        conn.execute(
+            "UPDATE products SET status = 'sold' WHERE id = ?1",
+            [product_id],
+        )?;
+        conn.execute(
+            "DELETE FROM reservations WHERE product_id = ?1",
+            [product_id],
+        )?;
+    }
+    Ok(unavailable)
+}
+
+impl ReservationRepository for Db {
+    fn reserve(&self, product_id: u32, cart_id: &str, until: DateTime<Utc>) -> Result<bool> {
+        self.transaction(|tx| {
+            let now = Utc::now();
+            match availability(tx, product_id, Some(cart_id), now)? {
+                Availability::Available | Availability::HeldByYou { .. } => {}
+                Availability::Reserved | Availability::Sold => return Ok(false),
+            }
+            tx.execute(
+                "INSERT INTO reservations (product_id, cart_id, expires_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (product_id) DO UPDATE SET
+                    cart_id = excluded.cart_id, expires_at = excluded.expires_at",
+                params![product_id, cart_id, until.timestamp()],
+            )?;
+            Ok(true)
+        })
+    }
+
+    fn release(&self, product_id: u32, cart_id: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM reservations WHERE product_id = ?1 AND cart_id = ?2",
+                params![product_id, cart_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn release_cart(&self, cart_id: &str) -> Result<usize> {
+        self.with_conn(|conn| {
+            let released =
+                conn.execute("DELETE FROM reservations WHERE cart_id = ?1", [cart_id])?;
+            Ok(released)
+        })
+    }
+
+    fn move_holds(&self, from: &str, to: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE reservations SET cart_id = ?2 WHERE cart_id = ?1",
+                params![from, to],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn release_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM reservations WHERE expires_at <= ?1",
+                [now.timestamp()],
+            )?;
+            Ok(removed)
+        })
+    }
+
+    fn availability(
+        &self,
+        product_id: u32,
+        cart_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Availability> {
+        self.with_conn(|conn| availability(conn, product_id, cart_id, now))
+    }
+
+    fn claim_items(
+        &self,
+        cart_id: &str,
+        product_ids: &[u32],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<u32>> {
+        self.transaction(|tx| claim_items(tx, cart_id, product_ids, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::db::testing::{db, product};
+
+    #[test]
+    fn a_hold_keeps_other_carts_out_until_it_expires() {
+        let db = db();
+        product(&db, 1, 5000);
+        let now = Utc::now();
+        assert!(db.reserve(1, "a", now + Duration::minutes(15)).unwrap());
+        assert!(!db.reserve(1, "b", now + Duration::minutes(15)).unwrap());
+        assert_eq!(
+            db.availability(1, Some("b"), now).unwrap(),
+            Availability::Reserved
+        );
+        assert!(matches!(
+            db.availability(1, Some("a"), now).unwrap(),
+            Availability::HeldByYou { .. }
+        ));
+
+        let later = now + Duration::minutes(16);
+        assert_eq!(
+            db.availability(1, Some("b"), later).unwrap(),
+            Availability::Available
+        );
+        assert_eq!(db.claim_items("b", &[1], later).unwrap(), Vec::<u32>::new());
+        assert_eq!(db.availability(1, None, later).unwrap(), Availability::Sold);
+    }
+
+    #[test]
+    fn claiming_changes_nothing_unless_every_item_is_free() {
+        let db = db();
+        product(&db, 1, 5000);
+        product(&db, 2, 7000);
+        let now = Utc::now();
+        assert!(db.reserve(2, "other", now + Duration::minutes(15)).unwrap());
+
+        assert_eq!(db.claim_items("mine", &[1, 2], now).unwrap(), vec![2]);
+        assert_eq!(
+            db.availability(1, None, now).unwrap(),
+            Availability::Available
+        );
+        assert_eq!(
+            db.availability(2, None, now).unwrap(),
+            Availability::Reserved
+        );
+    }
+
+    #[test]
+    fn expired_holds_are_swept() {
+        let db = db();
+        product(&db, 1, 5000);
+        product(&db, 2, 7000);
+        let now = Utc::now();
+        db.reserve(1, "a", now + Duration::minutes(1)).unwrap();
+        db.reserve(2, "a", now + Duration::minutes(30)).unwrap();
+
+        assert_eq!(db.release_expired(now + Duration::minutes(5)).unwrap(), 1);
+        assert!(matches!(
+            db.availability(2, Some("a"), now).unwrap(),
+            Availability::HeldByYou { .. }
+        ));
+    }
+
+    #[test]
+    fn releasing_a_cart_frees_only_its_holds() {
+        let db = db();
+        product(&db, 1, 5000);
+        product(&db, 2, 7000);
+        let now = Utc::now();
+        db.reserve(1, "a", now + Duration::minutes(15)).unwrap();
+        db.reserve(2, "b", now + Duration::minutes(15)).unwrap();
+
+        assert_eq!(db.release_cart("a").unwrap(), 1);
+        assert_eq!(
+            db.availability(1, None, now).unwrap(),
+            Availability::Available
+        );
+        assert_eq!(
+            db.availability(2, None, now).unwrap(),
+            Availability::Reserved
+        );
+    }
+
+    #[test]
+    fn moved_holds_belong_to_the_new_cart() {
+        let db = db();
+        product(&db, 1, 5000);
+        let now = Utc::now();
+        db.reserve(1, "guest", now + Duration::minutes(15)).unwrap();
+        db.move_holds("guest", "saved").unwrap();
+        assert!(matches!(
+            db.availability(1, Some("saved"), now).unwrap(),
+            Availability::HeldByYou { .. }
+        ));
+        assert_eq!(
+            db.availability(1, Some("guest"), now).unwrap(),
+            Availability::Reserved
+        );
+    }
+}
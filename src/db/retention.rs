@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+
+use super::{Db, OutboxStatus, Result, enum_to_sql};
+
+/// What [`RetentionRepository::purge_logs`] removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogPurge {
+    /// Sent and failed e-mails deleted from the outbox.
+    pub emails: usize,
+    pub sessions: usize,
+    pub account_tokens: usize,
+    /// Contact messages whose sender's network address was cleared.
+    pub message_ips: usize,
+}
+
+/// Deletes data kept longer than the privacy policy allows. Every method removes what is
+/// older than `before` and returns how much it removed.
+pub trait RetentionRepository {
+    /// Contact messages and the shop's replies, then threads left without messages.
+    fn purge_contact_messages(&self, before: DateTime<Utc>) -> Result<usize>;
+    /// Saved carts of signed-in customers that have not changed since `before`.
+    fn purge_carts(&self, before: DateTime<Utc>) -> Result<usize>;
+    /// Customers who have not signed in since `before` (or never did, having registered
+    /// before it), as id and e-mail address.
+    fn inactive_customers(&self, before: DateTime<Utc>) -> Result<Vec<(i64, String)>>;
+    /// Delivered and abandoned outbox e-mails and old network addresses, plus customer
+    /// and staff sessions and one-time links that expired before `now`.
+    fn purge_logs(&self, before: DateTime<Utc>, now: DateTime<Utc>) -> Result<LogPurge>;
+}
+
+impl RetentionRepository for Db {
+    fn purge_contact_messages(&self, before: DateTime<Utc>) -> Result<usize> {
+        self.transaction(|tx| {
+            let messages = tx.execute(
+                "DELETE FROM contact_messages WHERE created_at < ?1",
+                [before],
+            )?;
+            tx.execute(
+                "DELETE FROM support_replies WHERE created_at < ?1",
+                [before],
+            )?;
+            tx.execute(
+                "DELETE FROM support_replies WHERE thread_id NOT IN
+                    (SELECT thread_id FROM contact_messages WHERE thread_id IS NOT NULL)",
+                [],
+            )?;
+            tx.execute(
+                "DELETE FROM support_threads WHERE id NOT IN
+                    (SELECT thread_id FROM contact_messages WHERE thread_id IS NOT NULL)",
+                [],
+            )?;
+            Ok(messages)
+        })
+    }
+
+    fn purge_carts(&self, before: DateTime<Utc>) -> Result<usize> {
+        self.with_conn(|conn| {
+            let carts = conn.execute("DELETE FROM carts WHERE updated_at < ?1", [before])?;
+            Ok(carts)
+        })
+    }
+
+    fn inactive_customers(&self, before: DateTime<Utc>) -> Result<Vec<(i64, String)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, email FROM customers
+                 WHERE COALESCE(last_login_at, created_at) < ?1 ORDER BY id",
+            )?;
+            let customers = stmt
+                .query_map([before], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(customers)
+        })
+    }
+
+    fn purge_logs(&self, before: DateTime<Utc>, now: DateTime<Utc>) -> Result<LogPurge> {
+        self.transaction(|tx| {
+            let emails = tx.execute(
+                "DELETE FROM outbox WHERE status != ?1 AND created_at < ?2",
+                params![enum_to_sql(&OutboxStatus::Pending), before],
+            )?;
+            let sessions = tx.execute("DELETE FROM sessions WHERE expires_at < ?1", [now])?
+                + tx.execute("DELETE FROM staff_sessions WHERE expires_at < ?1", [now])?;
+            let account_tokens =
+                tx.execute("DELETE FROM account_tokens WHERE expires_at < ?1", [now])?;
+            let message_ips = tx.execute(
+                "UPDATE contact_messages SET ip = NULL WHERE ip IS NOT NULL AND created_at < ?1",
+                [before],
+            )?;
+            Ok(LogPurge {
+                emails,
+                sessions,
+                account_tokens,
+                message_ips,
+            })
+        })
+    }
+}
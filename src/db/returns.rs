@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::catalog::Price;
+use crate::returns::{ReturnLine, ReturnRequest, ReturnStatus};
+
+pub trait ReturnRepository {
+    /// Records a return of `lines` from the order. Returns `None` without creating
+    /// anything if one of the items is already part of another return of the order.
+    fn create_return(
+        &self,
+        order_id: i64,
+        token: &str,
+        lines: &[ReturnLine],
+        reason: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ReturnRequest>>;
+    fn find_return(&self, id: i64) -> Result<Option<ReturnRequest>>;
+    fn find_return_by_token(&self, token: &str) -> Result<Option<ReturnRequest>>;
+    /// Oldest first.
+    fn returns_for_order(&self, order_id: i64) -> Result<Vec<ReturnRequest>>;
+    /// Newest first, optionally only those in `status`.
+    fn list_returns(&self, status: Option<ReturnStatus>) -> Result<Vec<ReturnRequest>>;
+    /// Moves the return from `from` to `to` and records when it happened. Receiving a
+    /// return puts its items back on sale. Returns `None` without changing anything if
+    /// the return is no longer in `from`.
+    fn change_return_status(
+        &self,
+        id: i64,
+        from: ReturnStatus,
+        to: ReturnStatus,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ReturnRequest>>;
+}
+
+const RETURN_SELECT: &str = "SELECT id, order_id, token, status, reason, created_at, \
+     received_at, inspected_at, refunded_at FROM returns";
+
+fn return_from_row(row: &Row) -> rusqlite::Result<ReturnRequest> {
+    Ok(ReturnRequest {
+        id: row.get("id")?,
+        order_id: row.get("order_id")?,
+        token: row.get("token")?,
+        status: enum_from_sql(row, "status")?,
+        reason: row.get("reason")?,
+        lines: Vec::new(),
+        created_at: row.get("created_at")?,
+        received_at: row.get("received_at")?,
+        inspected_at: row.get("inspected_at")?,
+        refunded_at: row.get("refunded_at")?,
+    })
+}
+
+fn load_lines(conn: &Connection, request: &mut ReturnRequest) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT product_id, title, price FROM return_lines WHERE return_id = ?1
+         ORDER BY product_id",
+    )?;
+    request.lines = stmt
+        .query_map([request.id], |row| {
+            Ok(ReturnLine {
+                product_id: row.get("product_id")?,
+                title: row.get("title")?,
+                price: Price(row.get("price")?),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(())
+}
+
+fn query_returns(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<ReturnRequest>> {
+    let mut stmt = conn.prepare(sql)?;
+    let mut returns = stmt
+        .query_map(params, return_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for request in &mut returns {
+        load_lines(conn, request)?;
+    }
+    Ok(returns)
+}
+
+fn find_return(conn: &Connection, id: i64) -> Result<Option<ReturnRequest>> {
+    Ok(query_returns(conn, &format!("{RETURN_SELECT} WHERE id = ?1"), [id])?.pop())
+}
+
+/// Column recording when a return reached `status`.
+fn timestamp_column(status: ReturnStatus) -> &'static str {
+    match status {
+        ReturnStatus::Requested => "created_at",
+        ReturnStatus::Received => "received_at",
+        ReturnStatus::Inspected => "inspected_at",
+        ReturnStatus::Refunded => "refunded_at",
+    }
+}
+
+impl ReturnRepository for Db {
+    fn create_return(
+        &self,
+        order_id: i64,
+        token: &str,
+        lines: &[ReturnLine],
+        reason: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ReturnRequest>> {
+        self.transaction(|tx| {
+            for line in lines {
+                let taken = tx
+                    .query_row(
+                        "SELECT 1 FROM return_lines l JOIN returns r ON r.id = l.return_id
+                         WHERE r.order_id = ?1 AND l.product_id = ?2",
+                        params![order_id, line.product_id],
+                        |_| Ok(()),
+                    )
+                    .optional()?;
+                if taken.is_some() {
+                    return Ok(None);
+                }
+            }
+            tx.execute(
+                "INSERT INTO returns (order_id, token, status, reason, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    order_id,
+                    token,
+                    enum_to_sql(&ReturnStatus::Requested),
+                    reason,
+                    now
+                ],
+            )?;
+            let id = tx.last_insert_rowid();
+            for line in lines {
+                tx.execute(
+                    "INSERT INTO return_lines (return_id, product_id, title, price)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![id, line.product_id, line.title, line.price.grosze()],
+                )?;
+            }
+            find_return(tx, id)
+        })
+    }
+
+    fn find_return(&self, id: i64) -> Result<Option<ReturnRequest>> {
+        self.with_conn(|conn| find_return(conn, id))
+    }
+
+    fn find_return_by_token(&self, token: &str) -> Result<Option<ReturnRequest>> {
+        self.with_conn(|conn| {
+            Ok(query_returns(conn, &format!("{RETURN_SELECT} WHERE token = ?1"), [token])?.pop())
+        })
+    }
+
+    fn returns_for_order(&self, order_id: i64) -> Result<Vec<ReturnRequest>> {
+        self.with_conn(|conn| {
+            query_returns(
+                conn,
+                &format!("{RETURN_SELECT} WHERE order_id = ?1 ORDER BY id"),
+                [order_id],
+            )
+        })
+    }
+
+    fn list_returns(&self, status: Option<ReturnStatus>) -> Result<Vec<ReturnRequest>> {
+        self.with_conn(|conn| {
+            query_returns(
+                conn,
+                &format!("{RETURN_SELECT} WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC"),
+                [status.as_ref().map(enum_to_sql)],
+            )
+        })
+    }
+
+    fn change_return_status(
+        &self,
+        id: i64,
+        from: ReturnStatus,
+        to: ReturnStatus,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ReturnRequest>> {
+        self.transaction(|tx| {
+            let updated = tx.execute(
+                &format!(
+                    "UPDATE returns SET status = ?2, {} = ?4 WHERE id = ?1 AND status = ?3",
+                    timestamp_column(to)
+                ),
+                params![id, enum_to_sql(&to), enum_to_sql(&from), now],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            if to == ReturnStatus::Received {
+                tx.execute(
+                    "UPDATE products SET status = 'available'
+                     WHERE status = 'sold'
+                       AND id IN (SELECT product_id FROM return_lines WHERE return_id = ?1)",
+                    [id],
+                )?;
+            }
+            find_return(tx, id)
+        })
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+
+use super::customers::{CUSTOMER_SELECT, customer_from_row};
+use super::{Customer, Db, Result, enum_to_sql};
+
+/// What a one-time link sent by e-mail allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+/// Name and password given when an address that is not verified yet registers again.
+/// They travel with the verification link and replace the account's only when that link
+/// is used, so only whoever reads the inbox decides the password.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingRegistration {
+    pub name: String,
+    pub password_hash: String,
+}
+
+/// A one-time link that has just been used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsedToken {
+    pub customer_id: i64,
+    pub pending: Option<PendingRegistration>,
+}
+
+/// Sign-in sessions and one-time links. Only SHA-256 hashes of the tokens are stored, so
+/// a leaked database does not let anyone sign in.
+pub trait SessionRepository {
+    fn create_session(
+        &self,
+        token_hash: &str,
+        customer_id: i64,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()>;
+    /// The customer signed in with this session, unless it has expired.
+    fn session_customer(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<Customer>>;
+    fn delete_session(&self, token_hash: &str) -> Result<()>;
+    /// Signs the customer out on every device.
+    fn delete_customer_sessions(&self, customer_id: i64) -> Result<()>;
+    fn create_account_token(
+        &self,
+        token_hash: &str,
+        customer_id: i64,
+        purpose: TokenPurpose,
+        pending: Option<&PendingRegistration>,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()>;
+    /// Invalidates every link sent to the customer that has not been used yet.
+    fn delete_customer_tokens(&self, customer_id: i64) -> Result<()>;
+    /// Marks the token as used and returns its customer, with the registration details
+    /// it carried. Unknown, expired and already used tokens give `None`.
+    fn use_account_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<UsedToken>>;
+}
+
+impl SessionRepository for Db {
+    fn create_session(
+        &self,
+        token_hash: &str,
+        customer_id: i64,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO sessions (token_hash, customer_id, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![token_hash, customer_id, now, expires_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn session_customer(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<Customer>> {
+        self.with_conn(|conn| {
+            let customer = conn
+                .query_row(
+                    &format!(
+                        "{CUSTOMER_SELECT} JOIN sessions ON sessions.customer_id = customers.id
+                         WHERE sessions.token_hash = ?1 AND sessions.expires_at > ?2"
+                    ),
+                    params![token_hash, now],
+                    customer_from_row,
+                )
+                .optional()?;
+            Ok(customer)
+        })
+    }
+
+    fn delete_session(&self, token_hash: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM sessions WHERE token_hash = ?1", [token_hash])?;
+            Ok(())
+        })
+    }
+
+    fn delete_customer_sessions(&self, customer_id: i64) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM sessions WHERE customer_id = ?1", [customer_id])?;
+            Ok(())
+        })
+    }
+
+    fn create_account_token(
+        &self,
+        token_hash: &str,
+        customer_id: i64,
+        purpose: TokenPurpose,
+        pending: Option<&PendingRegistration>,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO account_tokens (token_hash, customer_id, purpose, created_at,
+                    expires_at, name, password_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    token_hash,
+                    customer_id,
+                    enum_to_sql(&purpose),
+                    now,
+                    expires_at,
+                    pending.map(|pending| &pending.name),
+                    pending.map(|pending| &pending.password_hash),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn delete_customer_tokens(&self, customer_id: i64) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM account_tokens WHERE customer_id = ?1 AND used_at IS NULL",
+                [customer_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn use_account_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<UsedToken>> {
+        self.transaction(|tx| {
+            let used = tx
+                .query_row(
+                    "UPDATE account_tokens SET used_at = ?3
+                     WHERE token_hash = ?1 AND purpose = ?2 AND used_at IS NULL
+                       AND expires_at > ?3
+                     RETURNING customer_id, name, password_hash",
+                    params![token_hash, enum_to_sql(&purpose), now],
+                    |row| {
+                        let name: Option<String> = row.get(1)?;
+                        let password_hash: Option<String> = row.get(2)?;
+                        Ok(UsedToken {
+                            customer_id: row.get(0)?,
+                            pending: name.zip(password_hash).map(|(name, password_hash)| {
+                                PendingRegistration {
+                                    name,
+                                    password_hash,
+                                }
+                            }),
+                        })
+                    },
+                )
+                .optional()?;
+            // Skrót hasła nie jest potrzebny dłużej niż do użycia linku.
+            tx.execute(
+                "UPDATE account_tokens SET name = NULL, password_hash = NULL
+                 WHERE token_hash = ?1",
+                [token_hash],
+            )?;
+            Ok(used)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::db::testing::{customer, db};
+
+    #[test]
+    fn a_link_hands_over_its_registration_once() {
+        let db = db();
+        let anna = customer(&db, "anna@example.com");
+        let now = Utc::now();
+        let pending = PendingRegistration {
+            name: "Anna Kowalska".into(),
+            password_hash: "nowy-hash".into(),
+        };
+        let expires = now + Duration::hours(24);
+        db.create_account_token(
+            "a",
+            anna.id,
+            TokenPurpose::VerifyEmail,
+            Some(&pending),
+            now,
+            expires,
+        )
+        .unwrap();
+        db.create_account_token("b", anna.id, TokenPurpose::VerifyEmail, None, now, expires)
+            .unwrap();
+
+        assert_eq!(
+            db.use_account_token("a", TokenPurpose::ResetPassword, now)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            db.use_account_token("a", TokenPurpose::VerifyEmail, now)
+                .unwrap(),
+            Some(UsedToken {
+                customer_id: anna.id,
+                pending: Some(pending),
+            })
+        );
+        assert_eq!(
+            db.use_account_token("a", TokenPurpose::VerifyEmail, now)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            db.use_account_token("b", TokenPurpose::VerifyEmail, now)
+                .unwrap(),
+            Some(UsedToken {
+                customer_id: anna.id,
+                pending: None,
+            })
+        );
+    }
+}
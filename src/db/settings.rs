@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, params};
+
+use super::{Db, Result, json_from_sql, json_to_sql};
+use crate::settings::ShopSettings;
+
+pub trait SettingsRepository {
+    /// The saved settings, or the defaults if nobody has saved any yet.
+    fn load_settings(&self) -> Result<ShopSettings>;
+    fn save_settings(
+        &self,
+        settings: &ShopSettings,
+        updated_by: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()>;
+}
+
+impl SettingsRepository for Db {
+    fn load_settings(&self) -> Result<ShopSettings> {
+        self.with_conn(|conn| {
+            let settings = conn
+                .query_row("SELECT value FROM shop_settings WHERE id = 1", [], |row| {
+                    json_from_sql(row, "value")
+                })
+                .optional()?;
+            Ok(settings.unwrap_or_default())
+        })
+    }
+
+    fn save_settings(
+        &self,
+        settings: &ShopSettings,
+        updated_by: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO shop_settings (id, value, updated_by, updated_at)
+                 VALUES (1, ?1, ?2, ?3)
+                 ON CONFLICT (id) DO UPDATE SET
+                    value = excluded.value, updated_by = excluded.updated_by,
+                    updated_at = excluded.updated_at",
+                params![json_to_sql(settings), updated_by, now],
+            )?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, Row, params};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::staff::{Role, StaffMember};
+
+/// Staff accounts and their sign-in sessions, kept apart from customers so that a
+/// customer session never opens the admin panel.
+pub trait StaffRepository {
+    /// Returns `None` without creating anything if the e-mail is already used.
+    fn create_staff(
+        &self,
+        email: &str,
+        name: &str,
+        role: Role,
+        password_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<StaffMember>>;
+    fn find_staff(&self, id: i64) -> Result<Option<StaffMember>>;
+    /// Emails are matched case-insensitively.
+    fn find_staff_by_email(&self, email: &str) -> Result<Option<StaffMember>>;
+    /// Active accounts first, then by name.
+    fn list_staff(&self) -> Result<Vec<StaffMember>>;
+    fn count_staff(&self) -> Result<usize>;
+    fn staff_password_hash(&self, id: i64) -> Result<Option<String>>;
+    fn set_staff_password_hash(&self, id: i64, password_hash: &str) -> Result<()>;
+    /// Changes the role and whether the account can sign in. Deactivating an account
+    /// also signs it out everywhere.
+    fn update_staff(&self, id: i64, role: Role, active: bool) -> Result<()>;
+    fn record_staff_login(&self, id: i64, now: DateTime<Utc>) -> Result<()>;
+    fn create_staff_session(
+        &self,
+        token_hash: &str,
+        staff_id: i64,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()>;
+    /// The active staff member signed in with this session, unless it has expired.
+    fn session_staff(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<StaffMember>>;
+    fn delete_staff_session(&self, token_hash: &str) -> Result<()>;
+}
+
+const STAFF_SELECT: &str = "SELECT staff.id, staff.email, staff.name, staff.role, \
+     staff.active, staff.last_login_at, staff.created_at FROM staff";
+
+fn staff_from_row(row: &Row) -> rusqlite::Result<StaffMember> {
+    Ok(StaffMember {
+        id: row.get("id")?,
+        email: row.get("email")?,
+        name: row.get("name")?,
+        role: enum_from_sql(row, "role")?,
+        active: row.get("active")?,
+        last_login_at: row.get("last_login_at")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+impl StaffRepository for Db {
+    fn create_staff(
+        &self,
+        email: &str,
+        name: &str,
+        role: Role,
+        password_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<StaffMember>> {
+        self.with_conn(|conn| {
+            let created = conn.execute(
+                "INSERT INTO staff (email, name, role, password_hash, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (email) DO NOTHING",
+                params![email, name, enum_to_sql(&role), password_hash, now],
+            )?;
+            if created == 0 {
+                return Ok(None);
+            }
+            Ok(Some(StaffMember {
+                id: conn.last_insert_rowid(),
+                email: email.to_string(),
+                name: name.to_string(),
+                role,
+                active: true,
+                last_login_at: None,
+                created_at: now,
+            }))
+        })
+    }
+
+    fn find_staff(&self, id: i64) -> Result<Option<StaffMember>> {
+        self.with_conn(|conn| {
+            let staff = conn
+                .query_row(
+                    &format!("{STAFF_SELECT} WHERE id = ?1"),
+                    [id],
+                    staff_from_row,
+                )
+                .optional()?;
+            Ok(staff)
+        })
+    }
+
+    fn find_staff_by_email(&self, email: &str) -> Result<Option<StaffMember>> {
+        self.with_conn(|conn| {
+            let staff = conn
+                .query_row(
+                    &format!("{STAFF_SELECT} WHERE email = ?1"),
+                    [email],
+                    staff_from_row,
+                )
+                .optional()?;
+            Ok(staff)
+        })
+    }
+
+    fn list_staff(&self) -> Result<Vec<StaffMember>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{STAFF_SELECT} ORDER BY active DESC, name COLLATE NOCASE, id"
+            ))?;
+            let staff = stmt
+                .query_map([], staff_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(staff)
+        })
+    }
+
+    fn count_staff(&self) -> Result<usize> {
+        self.with_conn(|conn| {
+            let count = conn.query_row("SELECT COUNT(*) FROM staff", [], |row| row.get(0))?;
+            Ok(count)
+        })
+    }
+
+    fn staff_password_hash(&self, id: i64) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            let hash = conn
+                .query_row(
+                    "SELECT password_hash FROM staff WHERE id = ?1",
+                    [id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(hash)
+        })
+    }
+
+    fn set_staff_password_hash(&self, id: i64, password_hash: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE staff SET password_hash = ?2 WHERE id = ?1",
+                params![id, password_hash],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn update_staff(&self, id: i64, role: Role, active: bool) -> Result<()> {
+        self.transaction(|tx| {
+            tx.execute(
+                "UPDATE staff SET role = ?2, active = ?3 WHERE id = ?1",
+                params![id, enum_to_sql(&role), active],
+            )?;
+            if !active {
+                tx.execute("DELETE FROM staff_sessions WHERE staff_id = ?1", [id])?;
+            }
+            Ok(())
+        })
+    }
+
+    fn record_staff_login(&self, id: i64, now: DateTime<Utc>) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE staff SET last_login_at = ?2 WHERE id = ?1",
+                params![id, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn create_staff_session(
+        &self,
+        token_hash: &str,
+        staff_id: i64,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO staff_sessions (token_hash, staff_id, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![token_hash, staff_id, now, expires_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn session_staff(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<StaffMember>> {
+        self.with_conn(|conn| {
+            let staff = conn
+                .query_row(
+                    &format!(
+                        "{STAFF_SELECT} JOIN staff_sessions ON staff_sessions.staff_id = staff.id
+                         WHERE staff_sessions.token_hash = ?1 AND staff_sessions.expires_at > ?2
+                           AND staff.active"
+                    ),
+                    params![token_hash, now],
+                    staff_from_row,
+                )
+                .optional()?;
+            Ok(staff)
+        })
+    }
+
+    fn delete_staff_session(&self, token_hash: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM staff_sessions WHERE token_hash = ?1",
+                [token_hash],
+            )?;
+            Ok(())
+        })
+    }
+}
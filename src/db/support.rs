@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::{Db, Result, enum_from_sql, enum_to_sql};
+use crate::inbox::{EntryAuthor, SupportThread, ThreadEntry, ThreadStatus, ThreadSummary};
+
+/// How much of the latest message is shown in the thread list.
+const PREVIEW_CHARS: usize = 120;
+
+pub trait SupportRepository {
+    /// Threads with the most recent activity first, optionally only those in `status`.
+    fn list_threads(&self, status: Option<ThreadStatus>) -> Result<Vec<ThreadSummary>>;
+    /// The thread with its messages and replies in the order they were written.
+    fn find_thread(&self, id: i64) -> Result<Option<SupportThread>>;
+    /// The thread of messages sent from this e-mail address, if there is one.
+    fn thread_for_email(&self, email: &str) -> Result<Option<SupportThread>>;
+    /// Records a reply sent from the shop and marks the thread as answered.
+    fn add_reply(
+        &self,
+        thread_id: i64,
+        body: &str,
+        sent_by: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()>;
+    fn set_thread_status(&self, id: i64, status: ThreadStatus, now: DateTime<Utc>) -> Result<()>;
+}
+
+fn summary_from_row(row: &Row) -> rusqlite::Result<ThreadSummary> {
+    let preview: Option<String> = row.get("preview")?;
+    Ok(ThreadSummary {
+        id: row.get("id")?,
+        email: row.get("email")?,
+        name: row.get("name")?,
+        status: enum_from_sql(row, "status")?,
+        messages: row.get("messages")?,
+        updated_at: row.get("updated_at")?,
+        preview: preview
+            .unwrap_or_default()
+            .chars()
+            .take(PREVIEW_CHARS)
+            .collect(),
+    })
+}
+
+const SUMMARY_SELECT: &str = "SELECT t.id, t.email, t.name, t.status, t.updated_at,
+        (SELECT COUNT(*) FROM contact_messages m WHERE m.thread_id = t.id) AS messages,
+        (SELECT m.message FROM contact_messages m WHERE m.thread_id = t.id
+         ORDER BY m.created_at DESC, m.id DESC LIMIT 1) AS preview
+     FROM support_threads t";
+
+fn load_entries(conn: &Connection, thread_id: i64) -> Result<Vec<ThreadEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT 'customer' AS author, name, subject, message AS body, created_at
+         FROM contact_messages WHERE thread_id = ?1
+         UNION ALL
+         SELECT 'shop' AS author, sent_by AS name, '' AS subject, body, created_at
+         FROM support_replies WHERE thread_id = ?1
+         ORDER BY created_at",
+    )?;
+    let entries = stmt
+        .query_map([thread_id], |row| {
+            Ok(ThreadEntry {
+                author: enum_from_sql::<EntryAuthor>(row, "author")?,
+                name: row.get("name")?,
+                subject: row.get("subject")?,
+                body: row.get("body")?,
+                at: row.get("created_at")?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(entries)
+}
+
+impl SupportRepository for Db {
+    fn list_threads(&self, status: Option<ThreadStatus>) -> Result<Vec<ThreadSummary>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{SUMMARY_SELECT} WHERE ?1 IS NULL OR t.status = ?1 ORDER BY t.updated_at DESC"
+            ))?;
+            let threads = stmt
+                .query_map([status.as_ref().map(enum_to_sql)], summary_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(threads)
+        })
+    }
+
+    fn find_thread(&self, id: i64) -> Result<Option<SupportThread>> {
+        self.with_conn(|conn| {
+            let summary = conn
+                .query_row(
+                    &format!("{SUMMARY_SELECT} WHERE t.id = ?1"),
+                    [id],
+                    summary_from_row,
+                )
+                .optional()?;
+            match summary {
+                Some(summary) => Ok(Some(SupportThread {
+                    entries: load_entries(conn, id)?,
+                    summary,
+                })),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn thread_for_email(&self, email: &str) -> Result<Option<SupportThread>> {
+        let id = self.with_conn(|conn| {
+            let id = conn
+                .query_row(
+                    "SELECT id FROM support_threads WHERE email = ?1",
+                    [email],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(id)
+        })?;
+        match id {
+            Some(id) => self.find_thread(id),
+            None => Ok(None),
+        }
+    }
+
+    fn add_reply(
+        &self,
+        thread_id: i64,
+        body: &str,
+        sent_by: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT INTO support_replies (thread_id, body, sent_by, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![thread_id, body, sent_by, now],
+            )?;
+            tx.execute(
+                "UPDATE support_threads SET status = ?2, updated_at = ?3 WHERE id = ?1",
+                params![thread_id, enum_to_sql(&ThreadStatus::Answered), now],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn set_thread_status(&self, id: i64, status: ThreadStatus, now: DateTime<Utc>) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE support_threads SET status = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, enum_to_sql(&status), now],
+            )?;
+            Ok(())
+        })
+    }
+}
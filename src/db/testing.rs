@@ -0,0 +1,87 @@
+//! Fixtures for the database tests: a fresh database file and the few rows most tests
+//! need.
+
+use std::ops::Deref;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use super::{Customer, CustomerRepository, Db, NewOrder, ProductRepository};
+use crate::catalog::{Price, Product};
+use crate::checkout::{Address, DeliveryMethod, PaymentMethod};
+
+/// A database in its own temporary file, opened like the shop opens its own, so WAL and
+/// locking behave as in production. The files are removed when it is dropped.
+pub(crate) struct TestDb {
+    db: Db,
+    path: PathBuf,
+}
+
+impl Deref for TestDb {
+    type Target = Db;
+
+    fn deref(&self) -> &Db {
+        &self.db
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// An empty database with every migration applied.
+pub(crate) fn db() -> TestDb {
+    let path = std::env::temp_dir().join(format!("megjoni-test-{}.db", uuid::Uuid::new_v4()));
+    let db = Db::open(&path).expect("baza testowa się otwiera");
+    TestDb { db, path }
+}
+
+/// Saves a listed, available product with this id and price in grosze.
+pub(crate) fn product(db: &Db, id: u32, price: u32) -> Product {
+    let product: Product = serde_json::from_value(json!({
+        "id": id,
+        "slug": format!("rzecz-{id}"),
+        "title": format!("Rzecz {id}"),
+        "category": "woman",
+        "price": price,
+        "condition": "very_good",
+        "size": "M",
+        "listed_on": "2026-01-01",
+    }))
+    .expect("produkt testowy jest poprawny");
+    db.save_product(&product).expect("produkt się zapisuje");
+    product
+}
+
+pub(crate) fn customer(db: &Db, email: &str) -> Customer {
+    db.create_customer(email, "Anna Nowak", "hash")
+        .expect("klient się zapisuje")
+}
+
+/// A courier order to a made-up address, without an invoice.
+pub(crate) fn new_order(customer_id: Option<i64>, email: &str) -> NewOrder {
+    NewOrder {
+        customer_id,
+        email: email.to_string(),
+        name: "Anna Nowak".to_string(),
+        phone: "600100200".to_string(),
+        address: Address {
+            street: "Kwiatowa 1".to_string(),
+            postcode: "00-001".to_string(),
+            city: "Warszawa".to_string(),
+        },
+        delivery: DeliveryMethod::Courier,
+        locker_code: None,
+        payment: PaymentMethod::OnlineTransfer,
+        shipping: Price(1500),
+        invoice: None,
+        terms_accepted_at: None,
+        use_store_credit: false,
+    }
+}
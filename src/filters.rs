@@ -0,0 +1,868 @@
+//! Facet filters, sort order and pagination for listing pages. The whole state lives in
+//! the query string (`?size=M&brand=Zara&price_max=100&sort=price_asc&after=...`), so
+//! every filtered view and every page of it has its own shareable, crawlable URL.
+
+use std::cmp::Ordering;
+
+use crate::catalog::{Condition, Product, ProductCard, ProductQuery, product_cards};
+use chrono::NaiveDate;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos_router::components::Form;
+use leptos_router::hooks::{use_location, use_query_map};
+use leptos_router::params::ParamsMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Newest,
+    PriceAsc,
+    PriceDesc,
+    /// Biggest markdown (as a percentage of the original price) first.
+    Discount,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 4] = [
+        SortOrder::Newest,
+        SortOrder::PriceAsc,
+        SortOrder::PriceDesc,
+        SortOrder::Discount,
+    ];
+
+    pub fn param(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "newest",
+            SortOrder::PriceAsc => "price_asc",
+            SortOrder::PriceDesc => "price_desc",
+            SortOrder::Discount => "discount",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        SortOrder::ALL
+            .into_iter()
+            .find(|order| order.param() == param)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "Najnowsze",
+            SortOrder::PriceAsc => "Cena: od najniższej",
+            SortOrder::PriceDesc => "Cena: od najwyższej",
+            SortOrder::Discount => "Największa obniżka",
+        }
+    }
+
+    /// Total order of a listing; `None` is catalog order (by id). Ties are broken by id,
+    /// so a cursor always points at one exact place.
+    pub fn compare(order: Option<SortOrder>, a: &PageCursor, b: &PageCursor) -> Ordering {
+        let by_key = match order {
+            None => Ordering::Equal,
+            Some(SortOrder::Newest) => b.listed_on.cmp(&a.listed_on).then(b.id.cmp(&a.id)),
+            Some(SortOrder::PriceAsc) => a.price.cmp(&b.price),
+            Some(SortOrder::PriceDesc) => b.price.cmp(&a.price),
+            Some(SortOrder::Discount) => b.discount.cmp(&a.discount),
+        };
+        by_key.then(a.id.cmp(&b.id))
+    }
+}
+
+/// Position of a product in a listing: everything any sort order looks at. The last
+/// product of a page, encoded, is the cursor for the next page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageCursor {
+    id: u32,
+    listed_on: NaiveDate,
+    price: u32,
+    discount: u32,
+}
+
+impl PageCursor {
+    pub fn of(product: &Product) -> Self {
+        PageCursor {
+            id: product.id,
+            listed_on: product.listed_on,
+            price: product.price.grosze(),
+            discount: discount_basis_points(product),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        format!(
+            "{}-{}-{}-{}",
+            self.listed_on.format("%Y%m%d"),
+            self.price,
+            self.discount,
+            self.id
+        )
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let mut parts = cursor.split('-');
+        let listed_on = NaiveDate::parse_from_str(parts.next()?, "%Y%m%d").ok()?;
+        let price = parts.next()?.parse().ok()?;
+        let discount = parts.next()?.parse().ok()?;
+        let id = parts.next()?.parse().ok()?;
+        parts.next().is_none().then_some(PageCursor {
+            id,
+            listed_on,
+            price,
+            discount,
+        })
+    }
+}
+
+/// Products per page of a listing.
+pub const PAGE_SIZE: usize = 24;
+
+/// Markdown in hundredths of a percent; zero for items that are not on sale.
+fn discount_basis_points(product: &Product) -> u32 {
+    match product.original_price {
+        Some(original) if product.is_on_sale() => {
+            let cut = u64::from(original.grosze() - product.price.grosze());
+            (cut * 10_000 / u64::from(original.grosze())) as u32
+        }
+        _ => 0,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FacetKind {
+    Size,
+    Brand,
+    Color,
+    Condition,
+    Material,
+}
+
+impl FacetKind {
+    pub const ALL: [FacetKind; 5] = [
+        FacetKind::Size,
+        FacetKind::Brand,
+        FacetKind::Color,
+        FacetKind::Condition,
+        FacetKind::Material,
+    ];
+
+    /// Query string key holding the selected values.
+    pub fn param(self) -> &'static str {
+        match self {
+            FacetKind::Size => "size",
+            FacetKind::Brand => "brand",
+            FacetKind::Color => "color",
+            FacetKind::Condition => "condition",
+            FacetKind::Material => "material",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FacetKind::Size => "Rozmiar",
+            FacetKind::Brand => "Marka",
+            FacetKind::Color => "Kolor",
+            FacetKind::Condition => "Stan",
+            FacetKind::Material => "Materiał",
+        }
+    }
+}
+
+/// Price ranges offered as facet links, in whole złoty; `None` is unbounded.
+pub const PRICE_RANGES: [(Option<u32>, Option<u32>); 4] = [
+    (None, Some(50)),
+    (Some(50), Some(100)),
+    (Some(100), Some(200)),
+    (Some(200), None),
+];
+
+fn price_range_label(min: Option<u32>, max: Option<u32>) -> String {
+    match (min, max) {
+        (None, Some(max)) => format!("do {max} zł"),
+        (Some(min), None) => format!("od {min} zł"),
+        (Some(min), Some(max)) => format!("{min}–{max} zł"),
+        (None, None) => "Dowolna".to_string(),
+    }
+}
+
+/// Filters picked on a listing page. Within a facet any selected value matches; across
+/// facets every facet in use must match.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProductFilters {
+    pub sizes: Vec<String>,
+    pub brands: Vec<String>,
+    pub colors: Vec<String>,
+    pub conditions: Vec<String>,
+    pub materials: Vec<String>,
+    /// Lowest price in whole złoty, inclusive.
+    pub price_min: Option<u32>,
+    /// Highest price in whole złoty, inclusive.
+    pub price_max: Option<u32>,
+    /// `None` keeps the page's own order.
+    pub sort: Option<SortOrder>,
+}
+
+impl ProductFilters {
+    pub fn from_params(params: &ParamsMap) -> Self {
+        let mut filters = ProductFilters::default();
+        for kind in FacetKind::ALL {
+            *filters.values_mut(kind) = params
+                .get_all(kind.param())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|value| !value.trim().is_empty())
+                .collect();
+        }
+        filters.price_min = params.get_str("price_min").and_then(parse_price_bound);
+        filters.price_max = params.get_str("price_max").and_then(parse_price_bound);
+        filters.sort = params.get_str("sort").and_then(SortOrder::from_param);
+        filters
+    }
+
+    pub fn to_params(&self) -> ParamsMap {
+        let mut params = ParamsMap::new();
+        for kind in FacetKind::ALL {
+            for value in self.values(kind) {
+                params.insert(kind.param(), value.clone());
+            }
+        }
+        if let Some(min) = self.price_min {
+            params.insert("price_min", min.to_string());
+        }
+        if let Some(max) = self.price_max {
+            params.insert("price_max", max.to_string());
+        }
+        if let Some(sort) = self.sort {
+            params.insert("sort", sort.param().to_string());
+        }
+        params
+    }
+
+    /// Link to `path` with these filters applied.
+    pub fn href(&self, path: &str) -> String {
+        format!("{path}{}", self.to_params().to_query_string())
+    }
+
+    /// Link to the page that starts after `cursor`.
+    pub fn page_href(&self, path: &str, cursor: &str) -> String {
+        let mut params = self.to_params();
+        params.insert("after", cursor.to_string());
+        format!("{path}{}", params.to_query_string())
+    }
+
+    pub fn values(&self, kind: FacetKind) -> &[String] {
+        match kind {
+            FacetKind::Size => &self.sizes,
+            FacetKind::Brand => &self.brands,
+            FacetKind::Color => &self.colors,
+            FacetKind::Condition => &self.conditions,
+            FacetKind::Material => &self.materials,
+        }
+    }
+
+    fn values_mut(&mut self, kind: FacetKind) -> &mut Vec<String> {
+        match kind {
+            FacetKind::Size => &mut self.sizes,
+            FacetKind::Brand => &mut self.brands,
+            FacetKind::Color => &mut self.colors,
+            FacetKind::Condition => &mut self.conditions,
+            FacetKind::Material => &mut self.materials,
+        }
+    }
+
+    pub fn is_selected(&self, kind: FacetKind, value: &str) -> bool {
+        self.values(kind).iter().any(|selected| selected == value)
+    }
+
+    /// Whether anything narrows the listing; the sort order does not count.
+    pub fn is_active(&self) -> bool {
+        FacetKind::ALL
+            .into_iter()
+            .any(|kind| !self.values(kind).is_empty())
+            || self.price_min.is_some()
+            || self.price_max.is_some()
+    }
+
+    /// The same filters with `value` selected or, if it already was, deselected.
+    pub fn toggled(&self, kind: FacetKind, value: &str) -> Self {
+        let mut filters = self.clone();
+        let values = filters.values_mut(kind);
+        if let Some(index) = values.iter().position(|selected| selected == value) {
+            values.remove(index);
+        } else {
+            values.push(value.to_string());
+        }
+        filters
+    }
+
+    pub fn with_price(&self, min: Option<u32>, max: Option<u32>) -> Self {
+        ProductFilters {
+            price_min: min,
+            price_max: max,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_sort(&self, sort: Option<SortOrder>) -> Self {
+        ProductFilters {
+            sort,
+            ..self.clone()
+        }
+    }
+
+    /// Only the sort order, so clearing filters keeps the chosen order.
+    pub fn cleared(&self) -> Self {
+        ProductFilters {
+            sort: self.sort,
+            ..Default::default()
+        }
+    }
+}
+
+/// Highest bound in złoty that still fits in grosze; larger values mean "no limit" anyway.
+const MAX_PRICE_BOUND: u32 = u32::MAX / 100;
+
+fn parse_price_bound(value: &str) -> Option<u32> {
+    value
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|zloty| zloty.min(u64::from(MAX_PRICE_BOUND)) as u32)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FacetValue {
+    pub value: String,
+    pub label: String,
+    /// Products the listing would show if this value were (also) selected.
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Facet {
+    pub kind: FacetKind,
+    pub values: Vec<FacetValue>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PriceRangeCount {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub count: usize,
+}
+
+/// One page of a filtered listing together with the counts for its filter panel.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Listing {
+    pub products: Vec<Product>,
+    /// All products matching the filters, across every page.
+    pub total: usize,
+    /// Cursor for the following page, if there is one.
+    pub next_cursor: Option<String>,
+    pub facets: Vec<Facet>,
+    pub price_ranges: Vec<PriceRangeCount>,
+}
+
+/// Letter sizes first in their natural order, then numeric sizes, then anything else.
+#[cfg(feature = "ssr")]
+fn size_order(size: &str) -> (u8, u32, String) {
+    const LETTER_SIZES: [&str; 8] = ["XXS", "XS", "S", "M", "L", "XL", "XXL", "XXXL"];
+    let upper = size.to_uppercase();
+    if let Some(rank) = LETTER_SIZES.iter().position(|s| *s == upper) {
+        (0, rank as u32, upper)
+    } else if let Ok(number) = size.parse() {
+        (1, number, upper)
+    } else {
+        (2, 0, upper)
+    }
+}
+
+/// Loads the products of a listing page with `query` that match `filters`, sorts them,
+/// cuts out the page that follows `after`, and counts every facet value among all of
+/// them.
+#[cfg(feature = "ssr")]
+pub fn build_listing(
+    db: &crate::db::Db,
+    query: &ProductQuery,
+    filters: &ProductFilters,
+    order: Option<SortOrder>,
+    after: Option<PageCursor>,
+) -> crate::db::Result<Listing> {
+    use std::collections::BTreeMap;
+
+    use crate::db::ProductRepository;
+
+    let mut facets = Vec::new();
+    for kind in FacetKind::ALL {
+        let mut counts: BTreeMap<String, usize> = db
+            .count_facet_values(query, filters, kind)?
+            .into_iter()
+            .collect();
+        // Zaznaczone wartości zostają na liście, żeby dało się je odznaczyć.
+        for value in filters.values(kind) {
+            counts.entry(value.clone()).or_default();
+        }
+        let mut values: Vec<FacetValue> = counts
+            .into_iter()
+            .map(|(value, count)| FacetValue {
+                label: match kind {
+                    FacetKind::Condition => Condition::ALL
+                        .into_iter()
+                        .find(|c| c.param() == value)
+                        .map_or_else(|| value.clone(), |c| c.label().to_string()),
+                    _ => value.clone(),
+                },
+                value,
+                count,
+            })
+            .collect();
+        match kind {
+            FacetKind::Size => values.sort_by_key(|v| size_order(&v.value)),
+            FacetKind::Condition => values.sort_by_key(|v| {
+                Condition::ALL
+                    .into_iter()
+                    .position(|c| c.param() == v.value)
+            }),
+            _ => values.sort_by_key(|v| crate::search::fold(&v.label)),
+        }
+        facets.push(Facet { kind, values });
+    }
+
+    let price_ranges = PRICE_RANGES
+        .into_iter()
+        .zip(db.count_price_ranges(query, filters, &PRICE_RANGES)?)
+        .map(|((min, max), count)| PriceRangeCount { min, max, count })
+        .collect();
+
+    let mut products = db.list_filtered_products(query, filters)?;
+    products.sort_by(|a, b| SortOrder::compare(order, &PageCursor::of(a), &PageCursor::of(b)));
+    let total = products.len();
+
+    // Kursor nie musi wskazywać istniejącego produktu - sprzedana sztuka znika z listy,
+    // a kolejna strona i tak zaczyna się we właściwym miejscu.
+    let start = after.map_or(0, |after| {
+        products.partition_point(|p| {
+            SortOrder::compare(order, &PageCursor::of(p), &after) != Ordering::Greater
+        })
+    });
+    let mut page: Vec<Product> = products
+        .into_iter()
+        .skip(start)
+        .take(PAGE_SIZE + 1)
+        .collect();
+    let next_cursor = if page.len() > PAGE_SIZE {
+        page.truncate(PAGE_SIZE);
+        page.last().map(|last| PageCursor::of(last).encode())
+    } else {
+        None
+    };
+
+    Ok(Listing {
+        products: page,
+        total,
+        next_cursor,
+        facets,
+        price_ranges,
+    })
+}
+
+/// One page of a listing page's products. `after` is the `next_cursor` of the previous
+/// page; `None` starts from the top.
+#[server]
+pub async fn get_listing(
+    query: ProductQuery,
+    filters: ProductFilters,
+    after: Option<String>,
+) -> Result<Listing, ServerFnError> {
+    use crate::db::use_db;
+
+    let after = match after {
+        Some(cursor) => Some(
+            PageCursor::decode(&cursor)
+                .ok_or_else(|| ServerFnError::new("nieprawidłowy kursor strony"))?,
+        ),
+        None => None,
+    };
+    let order = filters
+        .sort
+        .or(query.newest_first.then_some(SortOrder::Newest));
+    Ok(build_listing(&use_db()?, &query, &filters, order, after)?)
+}
+
+/// Product grid with a filter panel, driven by the current URL's query string.
+#[component]
+pub fn FilteredProductGrid(query: ProductQuery) -> impl IntoView {
+    let query_map = use_query_map();
+    let location = use_location();
+    let filters = Memo::new(move |_| ProductFilters::from_params(&query_map.read()));
+    let after = Memo::new(move |_| query_map.read().get("after"));
+    let default_sort = query.newest_first.then_some(SortOrder::Newest);
+    let page_query = query.clone();
+    let listing = Resource::new(
+        move || (query.clone(), filters.get(), after.get()),
+        |(query, filters, after)| get_listing(query, filters, after),
+    );
+
+    view! {
+        <div class="listing">
+            <Suspense fallback=|| view! { <p>"Ładowanie produktów..."</p> }>
+                {move || {
+                    let page_query = page_query.clone();
+                    Suspend::new(async move {
+                        let filters = filters.get_untracked();
+                        let path = location.pathname.get_untracked();
+                        match listing.await {
+                            Ok(listing) => {
+                                let results = if listing.products.is_empty() {
+                                    let message = if filters.is_active() {
+                                        "Brak produktów spełniających wybrane kryteria."
+                                    } else {
+                                        "Brak produktów w tej kategorii."
+                                    };
+                                    view! { <p>{message}</p> }.into_any()
+                                } else {
+                                    view! {
+                                        <ListingProducts
+                                            query=page_query
+                                            filters=filters.clone()
+                                            products=listing.products
+                                            next_cursor=listing.next_cursor
+                                            path=path.clone()
+                                        />
+                                    }
+                                    .into_any()
+                                };
+                                let first_page_href = filters.href(&path);
+                                let first_page = after.get_untracked().is_some().then(|| view! {
+                                    <a class="first-page" href=first_page_href>
+                                        "« Wróć do początku listy"
+                                    </a>
+                                });
+                                view! {
+                                    <FilterPanel
+                                        facets=listing.facets
+                                        price_ranges=listing.price_ranges
+                                        filters
+                                        default_sort
+                                        path
+                                    />
+                                    <div class="listing-results">
+                                        <p class="listing-total">
+                                            {format!("Liczba produktów: {}", listing.total)}
+                                        </p>
+                                        {first_page}
+                                        {results}
+                                    </div>
+                                }
+                                .into_any()
+                            }
+                            Err(_) => view! {
+                                <p>"Nie udało się wczytać produktów. Spróbuj ponownie później."</p>
+                            }
+                            .into_any(),
+                        }
+                    })
+                }}
+            </Suspense>
+        </div>
+    }
+}
+
+/// First page from the server plus the pages fetched while scrolling. The "next page"
+/// link is a plain URL for crawlers and visitors without JavaScript; after hydration the
+/// following page is also appended as soon as the link comes close to the viewport, and
+/// again after each appended page for as long as the link stays there.
+#[component]
+fn ListingProducts(
+    query: ProductQuery,
+    filters: ProductFilters,
+    products: Vec<Product>,
+    next_cursor: Option<String>,
+    path: String,
+) -> impl IntoView {
+    let more = RwSignal::new(Vec::<Product>::new());
+    let next = RwSignal::new(next_cursor);
+    let loading = RwSignal::new(false);
+    let next_href = {
+        let filters = filters.clone();
+        move || next.get().map(|cursor| filters.page_href(&path, &cursor))
+    };
+
+    let load_more = move || {
+        let Some(after) = next.get_untracked() else {
+            return;
+        };
+        if loading.get_untracked() {
+            return;
+        }
+        loading.set(true);
+        let (query, filters) = (query.clone(), filters.clone());
+        spawn_local(async move {
+            if let Ok(page) = get_listing(query, filters, Some(after)).await {
+                more.update(|more| more.extend(page.products));
+                next.set(page.next_cursor);
+            }
+            loading.set(false);
+        });
+    };
+    let sentinel = NodeRef::<leptos::html::Div>::new();
+    let appended = Signal::derive(move || more.with(Vec::len));
+    Effect::new(move |_| {
+        if let Some(element) = sentinel.get() {
+            observer::when_visible(&element, appended, load_more.clone());
+        }
+    });
+
+    view! {
+        <div class="product-grid">
+            {product_cards(products)}
+            <For each=move || more.get() key=|product| product.id let:product>
+                <ProductCard product />
+            </For>
+        </div>
+        <div class="listing-more" node_ref=sentinel>
+            {move || {
+                next_href()
+                    .map(|href| {
+                        view! {
+                            <a class="next-page" href=href>
+                                {move || {
+                                    if loading.get() { "Ładowanie..." } else { "Następna strona »" }
+                                }}
+                            </a>
+                        }
+                    })
+            }}
+        </div>
+    }
+}
+
+/// Infinite scroll needs the browser's `IntersectionObserver`; there is nothing to
+/// observe during SSR.
+mod observer {
+    use leptos::prelude::Signal;
+
+    /// Calls `on_visible` when `element` comes close to the viewport. The observer only
+    /// reports changes, so it looks again whenever `recheck` changes: a page appended
+    /// above may leave the element in view, and then nothing else would load the next.
+    #[cfg(feature = "hydrate")]
+    pub fn when_visible(
+        element: &web_sys::Element,
+        recheck: Signal<usize>,
+        on_visible: impl Fn() + 'static,
+    ) {
+        use leptos::prelude::{Effect, StoredValue, Track, WithValue, on_cleanup};
+        use wasm_bindgen::{JsCast, closure::Closure};
+        use web_sys::js_sys::Array;
+        use web_sys::{IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit};
+
+        let callback = Closure::<dyn Fn(Array)>::new(move |entries: Array| {
+            let visible = entries.iter().any(|entry| {
+                entry
+                    .unchecked_into::<IntersectionObserverEntry>()
+                    .is_intersecting()
+            });
+            if visible {
+                on_visible();
+            }
+        });
+        // Następną stronę zaczynamy wczytywać, zanim link faktycznie pojawi się na ekranie.
+        let options = IntersectionObserverInit::new();
+        options.set_root_margin("600px 0px");
+        let Ok(observer) =
+            IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &options)
+        else {
+            return;
+        };
+        observer.observe(element);
+        let observer = StoredValue::new_local((observer, callback));
+        let element = element.clone();
+        Effect::new(move |_| {
+            recheck.track();
+            // Ponowne obserwowanie daje świeży odczyt po ułożeniu nowej strony.
+            observer.with_value(|(observer, _)| {
+                observer.unobserve(&element);
+                observer.observe(&element);
+            });
+        });
+        on_cleanup(move || observer.with_value(|(observer, _)| observer.disconnect()));
+    }
+
+    #[cfg(not(feature = "hydrate"))]
+    pub fn when_visible(
+        _element: &leptos::web_sys::Element,
+        _recheck: Signal<usize>,
+        _on_visible: impl Fn() + 'static,
+    ) {
+    }
+}
+
+/// Every choice is a plain link, so the panel works before hydration and crawlers can
+/// follow filtered views.
+#[component]
+fn FilterPanel(
+    facets: Vec<Facet>,
+    price_ranges: Vec<PriceRangeCount>,
+    filters: ProductFilters,
+    default_sort: Option<SortOrder>,
+    path: String,
+) -> impl IntoView {
+    let current_sort = filters.sort.or(default_sort);
+    let sort_links = SortOrder::ALL
+        .into_iter()
+        .map(|order| {
+            let href = filters.with_sort(Some(order)).href(&path);
+            view! {
+                <li>
+                    <a href=href class:selected=current_sort == Some(order)>
+                        {order.label()}
+                    </a>
+                </li>
+            }
+        })
+        .collect_view();
+
+    let facet_groups = facets
+        .into_iter()
+        .filter(|facet| !facet.values.is_empty())
+        .map(|facet| {
+            let kind = facet.kind;
+            let values = facet
+                .values
+                .into_iter()
+                .map(|value| {
+                    let href = filters.toggled(kind, &value.value).href(&path);
+                    let selected = filters.is_selected(kind, &value.value);
+                    view! {
+                        <li>
+                            <a href=href class:selected=selected>
+                                {value.label}
+                                " "
+                                <span class="filter-count">{format!("({})", value.count)}</span>
+                            </a>
+                        </li>
+                    }
+                })
+                .collect_view();
+            view! {
+                <details class="filter-group" open>
+                    <summary>{kind.label()}</summary>
+                    <ul>{values}</ul>
+                </details>
+            }
+        })
+        .collect_view();
+
+    let price_links = price_ranges
+        .into_iter()
+        .map(|range| {
+            let selected = filters.price_min == range.min && filters.price_max == range.max;
+            let href = if selected {
+                filters.with_price(None, None)
+            } else {
+                filters.with_price(range.min, range.max)
+            }
+            .href(&path);
+            view! {
+                <li>
+                    <a href=href class:selected=selected>
+                        {price_range_label(range.min, range.max)}
+                        " "
+                        <span class="filter-count">{format!("({})", range.count)}</span>
+                    </a>
+                </li>
+            }
+        })
+        .collect_view();
+
+    // Pozostałe filtry jadą w ukrytych polach, żeby formularz ceny ich nie gubił.
+    let hidden_fields = filters
+        .with_price(None, None)
+        .to_params()
+        .into_iter()
+        .map(|(name, value)| view! { <input type="hidden" name=name.to_string() value=value /> })
+        .collect_view();
+    let clear_href = filters.cleared().href(&path);
+
+    view! {
+        <aside class="product-filters">
+            <details class="filter-group" open>
+                <summary>"Sortuj"</summary>
+                <ul>{sort_links}</ul>
+            </details>
+            {facet_groups}
+            <details class="filter-group" open>
+                <summary>"Cena"</summary>
+                <ul>{price_links}</ul>
+                <Form method="GET" action=path.clone()>
+                    <div class="price-range">
+                        {hidden_fields}
+                        <input
+                            type="number"
+                            name="price_min"
+                            min="0"
+                            placeholder="od"
+                            aria-label="Cena od (zł)"
+                            value=filters.price_min.map(|v| v.to_string())
+                        />
+                        <input
+                            type="number"
+                            name="price_max"
+                            min="0"
+                            placeholder="do"
+                            aria-label="Cena do (zł)"
+                            value=filters.price_max.map(|v| v.to_string())
+                        />
+                        <button type="submit">"Zastosuj"</button>
+                    </div>
+                </Form>
+            </details>
+            {filters
+                .is_active()
+                .then(|| view! { <a class="clear-filters" href=clear_href>"Wyczyść filtry"</a> })}
+        </aside>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_survives_a_round_trip() {
+        for cursor in [
+            PageCursor {
+                id: 1,
+                listed_on: NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+                price: 0,
+                discount: 0,
+            },
+            PageCursor {
+                id: u32::MAX,
+                listed_on: NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(),
+                price: 129_900,
+                discount: 3_500,
+            },
+        ] {
+            assert_eq!(PageCursor::decode(&cursor.encode()), Some(cursor));
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        for cursor in [
+            "",
+            "20260101",
+            "20260101-5000-0",
+            "20260101-5000-0-7-1",
+            "20261301-5000-0-7",
+            "20260101--0-7",
+            "20260101-5000-0-x",
+            "20260101-5000--1-7",
+        ] {
+            assert_eq!(PageCursor::decode(cursor), None, "{cursor:?}");
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! Customer support inbox at `/admin/inbox`. Contact form messages are grouped into one
+//! thread per sender's e-mail address; staff reply from the shop's address and the
+//! customer's orders placed with the same address are shown next to the conversation.
+//!
+//! Open to staff who answer customers (see [`crate::staff::Permission::AnswerCustomers`]).
+
+use crate::orders::OrderSummary;
+use crate::staff::Permission;
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::{use_params_map, use_query_map};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadStatus {
+    /// The customer wrote and nobody has answered yet.
+    #[default]
+    New,
+    Answered,
+    Closed,
+}
+
+impl ThreadStatus {
+    pub const ALL: [ThreadStatus; 3] = [
+        ThreadStatus::New,
+        ThreadStatus::Answered,
+        ThreadStatus::Closed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ThreadStatus::New => "Nowy",
+            ThreadStatus::Answered => "Odpowiedziano",
+            ThreadStatus::Closed => "Zamknięty",
+        }
+    }
+
+    /// The value used in links and forms, e.g. `answered`.
+    pub fn param(self) -> &'static str {
+        match self {
+            ThreadStatus::New => "new",
+            ThreadStatus::Answered => "answered",
+            ThreadStatus::Closed => "closed",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.param() == param)
+    }
+}
+
+/// Who wrote an entry in a thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryAuthor {
+    Customer,
+    Shop,
+}
+
+/// A thread as shown in the inbox list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    pub id: i64,
+    pub email: String,
+    /// Name from the customer's latest message.
+    pub name: String,
+    pub status: ThreadStatus,
+    /// How many messages the customer sent.
+    pub messages: usize,
+    pub updated_at: DateTime<Utc>,
+    /// Beginning of the customer's latest message.
+    pub preview: String,
+}
+
+/// A customer's message or a reply from the shop.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThreadEntry {
+    pub author: EntryAuthor,
+    /// The customer's name, or who replied on behalf of the shop.
+    pub name: String,
+    /// Empty for replies and messages sent without a subject.
+    pub subject: String,
+    pub body: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SupportThread {
+    pub summary: ThreadSummary,
+    /// Oldest first.
+    pub entries: Vec<ThreadEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThreadDetails {
+    pub thread: SupportThread,
+    /// Orders placed with the thread's e-mail address, newest first.
+    pub orders: Vec<OrderSummary>,
+}
+
+#[server]
+pub async fn list_support_threads(
+    status: Option<ThreadStatus>,
+) -> Result<Vec<ThreadSummary>, ServerFnError> {
+    use crate::db::{SupportRepository, use_db};
+
+    crate::staff::require_staff(Permission::AnswerCustomers)?;
+    Ok(use_db()?.list_threads(status)?)
+}
+
+#[server]
+pub async fn get_support_thread(id: i64) -> Result<Option<ThreadDetails>, ServerFnError> {
+    use crate::db::{OrderRepository, SupportRepository, use_db};
+
+    crate::staff::require_staff(Permission::AnswerCustomers)?;
+    let db = use_db()?;
+    let Some(thread) = db.find_thread(id)? else {
+        return Ok(None);
+    };
+    let orders = db
+        .orders_for_email(&thread.summary.email)?
+        .iter()
+        .map(OrderSummary::from)
+        .collect();
+    Ok(Some(ThreadDetails { thread, orders }))
+}
+
+/// Sends `body` to the customer from the shop's address and marks the thread as answered.
+#[server]
+pub async fn reply_to_thread(thread_id: i64, body: String) -> Result<(), ServerFnError> {
+    use crate::db::{SupportRepository, use_db};
+    use crate::mail::{self, Email};
+    use chrono::Utc;
+
+    let staff = crate::staff::require_staff(Permission::AnswerCustomers)?;
+    let body = body.trim();
+    if body.is_empty() {
+        return Err(ServerFnError::new("odpowiedź jest pusta"));
+    }
+    let db = use_db()?;
+    let Some(thread) = db.find_thread(thread_id)? else {
+        return Err(ServerFnError::new("nie ma takiego wątku"));
+    };
+    // Odpowiadamy na temat ostatniej wiadomości klienta.
+    let subject = thread
+        .entries
+        .iter()
+        .rev()
+        .find(|entry| entry.author == EntryAuthor::Customer)
+        .map(|entry| entry.subject.clone())
+        .unwrap_or_default();
+    mail::queue(
+        &db,
+        &thread.summary.email,
+        &Email::SupportReply {
+            name: thread.summary.name.clone(),
+            subject,
+            body: body.to_string(),
+        },
+    )?;
+    let sent_by = staff.actor().to_string();
+    db.add_reply(thread_id, body, &sent_by, Utc::now())?;
+    Ok(())
+}
+
+#[server]
+pub async fn change_thread_status(
+    thread_id: i64,
+    status: ThreadStatus,
+) -> Result<(), ServerFnError> {
+    use crate::db::{SupportRepository, use_db};
+
+    crate::staff::require_staff(Permission::AnswerCustomers)?;
+    use_db()?.set_thread_status(thread_id, status, Utc::now())?;
+    Ok(())
+}
+
+fn format_time(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M").to_string()
+}
+
+#[component]
+pub fn InboxPage() -> impl IntoView {
+    let query = use_query_map();
+    let status = move || {
+        query
+            .read()
+            .get("status")
+            .and_then(|status| ThreadStatus::from_param(&status))
+    };
+    let threads = Resource::new(status, list_support_threads);
+
+    let filters = move || {
+        let current = status();
+        let all = view! {
+            <a href="/admin/inbox" class:active=current.is_none()>
+                "Wszystkie"
+            </a>
+        };
+        let by_status = ThreadStatus::ALL
+            .into_iter()
+            .map(|filter| {
+                let href = format!("/admin/inbox?status={}", filter.param());
+                view! {
+                    <a href=href class:active=current == Some(filter)>
+                        {filter.label()}
+                    </a>
+                }
+            })
+            .collect_view();
+        view! { <nav class="inbox-filters">{all} {by_status}</nav> }
+    };
+
+    view! {
+        <Title text="Wiadomości od klientów - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Wiadomości od klientów"</h2>
+                {filters}
+                <Suspense fallback=|| view! { <p>"Ładowanie wiadomości..."</p> }>
+                    {move || Suspend::new(async move {
+                        match threads.await {
+                            Ok(threads) if threads.is_empty() => {
+                                view! { <p>"Brak wiadomości."</p> }.into_any()
+                            }
+                            Ok(threads) => view! { <ThreadList threads /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do wiadomości."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ThreadList(threads: Vec<ThreadSummary>) -> impl IntoView {
+    view! {
+        <table class="inbox-threads">
+            <thead>
+                <tr>
+                    <th>"Od"</th>
+                    <th>"Wiadomość"</th>
+                    <th>"Status"</th>
+                    <th>"Ostatnia zmiana"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {threads
+                    .into_iter()
+                    .map(|thread| {
+                        let href = format!("/admin/inbox/{}", thread.id);
+                        view! {
+                            <tr class=format!("inbox-thread-{}", thread.status.param())>
+                                <td>
+                                    <a href=href>{thread.name}</a>
+                                    <br />
+                                    <small>{thread.email}</small>
+                                </td>
+                                <td>
+                                    {thread.preview}
+                                    {(thread.messages > 1)
+                                        .then(|| format!(" ({} wiad.)", thread.messages))}
+                                </td>
+                                <td>{thread.status.label()}</td>
+                                <td>{format_time(thread.updated_at)}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
+
+#[component]
+pub fn ThreadPage() -> impl IntoView {
+    let params = use_params_map();
+    let reply = ServerAction::<ReplyToThread>::new();
+    let change_status = ServerAction::<ChangeThreadStatus>::new();
+    let details = Resource::new(
+        move || {
+            let id = params
+                .read()
+                .get("id")
+                .and_then(|id| id.parse::<i64>().ok());
+            (id, reply.version().get(), change_status.version().get())
+        },
+        |(id, _, _)| async move {
+            match id {
+                Some(id) => get_support_thread(id).await,
+                None => Ok(None),
+            }
+        },
+    );
+
+    view! {
+        <Title text="Wątek - Meg Joni" />
+        <main>
+            <Suspense fallback=|| view! { <p>"Ładowanie wątku..."</p> }>
+                {move || Suspend::new(async move {
+                    match details.await {
+                        Ok(Some(details)) => {
+                            view! { <ThreadView details reply change_status /> }.into_any()
+                        }
+                        Ok(None) => view! { <p>"Nie ma takiego wątku."</p> }.into_any(),
+                        Err(_) => view! { <p>"Brak dostępu do wiadomości."</p> }.into_any(),
+                    }
+                })}
+            </Suspense>
+        </main>
+    }
+}
+
+#[component]
+fn ThreadView(
+    details: ThreadDetails,
+    reply: ServerAction<ReplyToThread>,
+    change_status: ServerAction<ChangeThreadStatus>,
+) -> impl IntoView {
+    let ThreadDetails { thread, orders } = details;
+    let summary = thread.summary;
+    let reply_error = move || {
+        matches!(reply.value().get(), Some(Err(_)))
+            .then(|| view! { <p class="inbox-error">"Nie udało się wysłać odpowiedzi."</p> })
+    };
+    let status_buttons = ThreadStatus::ALL
+        .into_iter()
+        .filter(|&status| status != summary.status)
+        .map(|status| {
+            view! {
+                <button type="submit" name="status" value=status.param()>
+                    {status.label()}
+                </button>
+            }
+        })
+        .collect_view();
+    let entries = thread
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let class = match entry.author {
+                EntryAuthor::Customer => "inbox-entry",
+                EntryAuthor::Shop => "inbox-entry inbox-entry-shop",
+            };
+            let subject = (!entry.subject.is_empty()).then(|| view! { <h4>{entry.subject}</h4> });
+            view! {
+                <li class=class>
+                    <p class="inbox-entry-meta">
+                        <strong>{entry.name}</strong> " " {format_time(entry.at)}
+                    </p>
+                    {subject}
+                    <p class="inbox-entry-body">{entry.body}</p>
+                </li>
+            }
+        })
+        .collect_view();
+    let orders = if orders.is_empty() {
+        view! { <p>"Brak zamówień złożonych z tego adresu."</p> }.into_any()
+    } else {
+        let rows = orders
+            .into_iter()
+            .map(|order| {
+                let href = format!("/admin/orders/{}/packing-slip", order.id);
+                view! {
+                    <tr>
+                        <td><a href=href>{format!("nr {}", order.id)}</a></td>
+                        <td>{format_time(order.created_at)}</td>
+                        <td>{order.status.label()}</td>
+                        <td>{order.total.to_string()}</td>
+                    </tr>
+                }
+            })
+            .collect_view();
+        view! {
+            <table class="inbox-orders">
+                <tbody>{rows}</tbody>
+            </table>
+        }
+        .into_any()
+    };
+    view! {
+        <article class="inbox-thread">
+            <p>
+                <a href="/admin/inbox">"← Wszystkie wiadomości"</a>
+            </p>
+            <h2>{summary.name.clone()}</h2>
+            <p>
+                <a href=format!("mailto:{}", summary.email)>{summary.email.clone()}</a>
+                " · " {summary.status.label()}
+            </p>
+            <ActionForm action=change_status>
+                <input type="hidden" name="thread_id" value=summary.id />
+                <div class="inbox-status-actions">"Oznacz jako: " {status_buttons}</div>
+            </ActionForm>
+            <ol class="inbox-entries">{entries}</ol>
+            <ActionForm action=reply attr:class="inbox-reply">
+                <input type="hidden" name="thread_id" value=summary.id />
+                <label for="reply-body">{format!("Odpowiedź do {}", summary.email)}</label>
+                <textarea id="reply-body" name="body" rows="6" required></textarea>
+                {reply_error}
+                <button type="submit" disabled=move || reply.pending().get()>
+                    "Wyślij odpowiedź"
+                </button>
+            </ActionForm>
+            <h3>"Zamówienia klienta"</h3>
+            {orders}
+        </article>
+    }
+}
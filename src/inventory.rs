@@ -0,0 +1,160 @@
+//! Stock of one-of-a-kind items: putting an item in a cart holds it on the server for
+//! a limited time, and checkout can only claim items that are not held by someone else.
+
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Default length of a cart hold, overridable with `MEGJONI_RESERVATION_MINUTES`.
+pub const DEFAULT_RESERVATION_MINUTES: i64 = 15;
+
+/// Default time an order may wait for payment before it is cancelled and its items go
+/// back on sale, overridable with `MEGJONI_UNPAID_ORDER_HOURS`. Long enough for a
+/// traditional bank transfer to arrive.
+pub const DEFAULT_UNPAID_ORDER_HOURS: i64 = 72;
+
+/// Whether an item can still be bought by a given cart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Availability {
+    /// Held for the asking cart until `expires_at`.
+    HeldByYou { expires_at: DateTime<Utc> },
+    /// Not held by anyone; the first cart to reserve it gets it.
+    Available,
+    /// Held by another cart.
+    Reserved,
+    /// Sold or no longer listed.
+    Sold,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReserveOutcome {
+    Reserved {
+        cart_id: String,
+        expires_at: DateTime<Utc>,
+    },
+    Unavailable,
+}
+
+#[cfg(feature = "ssr")]
+pub fn reservation_window() -> chrono::Duration {
+    let minutes = std::env::var("MEGJONI_RESERVATION_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse().ok())
+        .filter(|minutes: &i64| *minutes > 0)
+        .unwrap_or(DEFAULT_RESERVATION_MINUTES);
+    chrono::Duration::minutes(minutes)
+}
+
+/// Periodically deletes expired holds. Expired holds are already ignored when reading,
+/// so this only keeps the table small.
+#[cfg(feature = "ssr")]
+pub fn spawn_reservation_sweeper(db: crate::db::Db) {
+    use crate::db::ReservationRepository;
+    use leptos::logging::log;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            match db.release_expired(Utc::now()) {
+                Ok(0) => {}
+                Ok(count) => log!("zwolniono {count} wygasłych rezerwacji"),
+                Err(e) => log!("nie udało się zwolnić rezerwacji: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(feature = "ssr")]
+pub fn unpaid_order_window() -> chrono::Duration {
+    let hours = std::env::var("MEGJONI_UNPAID_ORDER_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .filter(|hours: &i64| *hours > 0)
+        .unwrap_or(DEFAULT_UNPAID_ORDER_HOURS);
+    chrono::Duration::hours(hours)
+}
+
+/// Periodically cancels orders left waiting for payment longer than
+/// [`unpaid_order_window`], so an abandoned checkout does not keep its items off sale.
+#[cfg(feature = "ssr")]
+pub fn spawn_unpaid_order_sweeper(db: crate::db::Db) {
+    use crate::orders::lifecycle;
+    use leptos::logging::log;
+
+    let window = unpaid_order_window();
+    let note = format!("brak płatności w ciągu {} godz.", window.num_hours());
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5 * 60));
+        loop {
+            interval.tick().await;
+            match lifecycle::cancel_unpaid(&db, Utc::now() - window, &note) {
+                Ok(0) => {}
+                Ok(count) => log!("anulowano {count} nieopłaconych zamówień"),
+                Err(e) => log!("nie udało się anulować nieopłaconych zamówień: {e}"),
+            }
+        }
+    });
+}
+
+/// Cart ids are random UUIDs handed out by the server; anything else gets a fresh one.
+#[cfg(feature = "ssr")]
+fn cart_id_or_new(cart_id: Option<String>) -> String {
+    cart_id
+        .filter(|id| uuid::Uuid::parse_str(id).is_ok())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+#[server]
+pub async fn reserve_item(
+    cart_id: Option<String>,
+    product_id: u32,
+) -> Result<ReserveOutcome, ServerFnError> {
+    use crate::db::{ReservationRepository, use_db};
+
+    let cart_id = cart_id_or_new(cart_id);
+    let expires_at = Utc::now() + reservation_window();
+    if use_db()?.reserve(product_id, &cart_id, expires_at)? {
+        Ok(ReserveOutcome::Reserved {
+            cart_id,
+            expires_at,
+        })
+    } else {
+        Ok(ReserveOutcome::Unavailable)
+    }
+}
+
+#[server]
+pub async fn release_item(cart_id: String, product_id: u32) -> Result<(), ServerFnError> {
+    use crate::db::{ReservationRepository, use_db};
+
+    use_db()?.release(product_id, &cart_id)?;
+    Ok(())
+}
+
+/// Gives up every hold of the cart at once, e.g. when it is emptied.
+#[server]
+pub async fn release_cart(cart_id: String) -> Result<(), ServerFnError> {
+    use crate::db::{ReservationRepository, use_db};
+
+    use_db()?.release_cart(&cart_id)?;
+    Ok(())
+}
+
+#[server]
+pub async fn check_availability(
+    cart_id: Option<String>,
+    product_ids: Vec<u32>,
+) -> Result<Vec<(u32, Availability)>, ServerFnError> {
+    use crate::db::{ReservationRepository, use_db};
+
+    let db = use_db()?;
+    let now = Utc::now();
+    product_ids
+        .into_iter()
+        .map(|product_id| {
+            let availability = db.availability(product_id, cart_id.as_deref(), now)?;
+            Ok((product_id, availability))
+        })
+        .collect()
+}
@@ -1,4 +1,31 @@
+pub mod account;
+pub mod admin;
 pub mod app;
+pub mod cart;
+pub mod catalog;
+pub mod checkout;
+pub mod complaints;
+pub mod contact;
+#[cfg(feature = "ssr")]
+pub mod db;
+pub mod filters;
+pub mod inbox;
+pub mod inventory;
+pub mod lockers;
+#[cfg(feature = "ssr")]
+pub mod mail;
+pub mod orders;
+pub mod packing_slip;
+pub mod payments;
+pub mod product_page;
+pub mod refunds;
+#[cfg(feature = "ssr")]
+pub mod retention;
+pub mod returns;
+pub mod search;
+pub mod settings;
+pub mod shipping;
+pub mod staff;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -0,0 +1,142 @@
+//! InPost parcel lockers. The locker list is imported from a local copy of the InPost
+//! dataset at startup, so choosing and validating a locker at checkout does not depend on
+//! the live InPost API.
+
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Shortest query worth searching for.
+pub const MIN_QUERY_LEN: usize = 2;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Locker {
+    /// InPost locker code, e.g. `WAW01M`.
+    pub code: String,
+    pub city: String,
+    pub postcode: String,
+    pub street: String,
+    /// Where exactly the locker stands, e.g. "Przy wejściu do sklepu".
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Locker {
+    pub fn address(&self) -> String {
+        format!("{}, {} {}", self.street, self.postcode, self.city)
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod source {
+    use super::Locker;
+    use crate::db::{Db, LockerRepository};
+
+    /// Location of the locker dataset, relative to the directory the server is started from.
+    pub const DEFAULT_LOCKERS_FILE: &str = "data/inpost_lockers.json";
+
+    /// Reads the locker dataset from disk.
+    pub fn load_lockers() -> Result<Vec<Locker>, String> {
+        let path = std::env::var("MEGJONI_LOCKERS_FILE")
+            .unwrap_or_else(|_| DEFAULT_LOCKERS_FILE.to_string());
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("nie można odczytać {path}: {e}"))?;
+        serde_json::from_str(&contents).map_err(|e| format!("błędny format {path}: {e}"))
+    }
+
+    /// Replaces the stored lockers with the dataset. Returns how many lockers were imported.
+    pub fn import_lockers(db: &Db) -> Result<usize, String> {
+        let lockers = load_lockers()?;
+        db.replace_lockers(&lockers).map_err(|e| e.to_string())
+    }
+}
+
+#[server]
+pub async fn search_lockers(query: String) -> Result<Vec<Locker>, ServerFnError> {
+    use crate::db::{LockerRepository, use_db};
+
+    /// Most lockers returned for one query.
+    const MAX_RESULTS: usize = 10;
+
+    let query: String = query.chars().take(50).collect();
+    if query.trim().chars().count() < MIN_QUERY_LEN {
+        return Ok(Vec::new());
+    }
+    Ok(use_db()?.search_lockers(&query, MAX_RESULTS)?)
+}
+
+/// Search box for choosing a locker by city, postcode or locker code.
+#[component]
+pub fn LockerPicker(selected: RwSignal<Option<Locker>>) -> impl IntoView {
+    let query = RwSignal::new(String::new());
+    let results = Resource::new(move || query.get(), search_lockers);
+
+    view! {
+        <div class="locker-picker">
+            {move || selected.get().map(|locker| view! {
+                <p class="locker-selected">
+                    "Wybrany paczkomat: " <strong>{locker.code.clone()}</strong> " - "
+                    {locker.address()}
+                </p>
+            })}
+            <div class="form-field">
+                <label for="locker-query">"Znajdź paczkomat"</label>
+                <input
+                    id="locker-query"
+                    type="search"
+                    placeholder="Miasto, kod pocztowy lub kod paczkomatu"
+                    autocomplete="off"
+                    prop:value=move || query.get()
+                    on:input=move |ev| query.set(event_target_value(&ev))
+                />
+            </div>
+            <Transition fallback=|| view! { <p>"Szukam paczkomatów..."</p> }>
+                {move || Suspend::new(async move {
+                    let searched = query.with(|q| q.trim().chars().count() >= MIN_QUERY_LEN);
+                    match results.await {
+                        Ok(lockers) if lockers.is_empty() && searched => view! {
+                            <p>"Nie znaleźliśmy paczkomatów pasujących do zapytania."</p>
+                        }
+                        .into_any(),
+                        Ok(lockers) => view! {
+                            <ul class="locker-results">
+                                {lockers
+                                    .into_iter()
+                                    .map(|locker| view! { <LockerOption locker selected /> })
+                                    .collect_view()}
+                            </ul>
+                        }
+                        .into_any(),
+                        Err(_) => view! {
+                            <p>"Wyszukiwarka paczkomatów jest chwilowo niedostępna."</p>
+                        }
+                        .into_any(),
+                    }
+                })}
+            </Transition>
+        </div>
+    }
+}
+
+#[component]
+fn LockerOption(locker: Locker, selected: RwSignal<Option<Locker>>) -> impl IntoView {
+    let code = locker.code.clone();
+    let is_selected = move || selected.with(|s| s.as_ref().is_some_and(|s| s.code == code));
+    let address = locker.address();
+    let description = locker.description.clone();
+    let title = locker.code.clone();
+
+    view! {
+        <li>
+            <button
+                type="button"
+                class="locker-option"
+                class:selected=is_selected
+                on:click=move |_| selected.set(Some(locker.clone()))
+            >
+                <strong>{title}</strong>
+                <span>{address}</span>
+                <small>{description}</small>
+            </button>
+        </li>
+    }
+}
@@ -0,0 +1,602 @@
+//! Polish e-mail templates. Each message is described once as a list of blocks and
+//! rendered from it to both HTML and plain text, so the two versions never drift apart.
+
+use crate::catalog::Price;
+use crate::checkout::DeliveryMethod;
+use crate::complaints::{Complaint, ComplaintStatus};
+use crate::db::{ContactMessage, Order};
+use crate::refunds::RefundMethod;
+use crate::returns::ReturnRequest;
+
+/// A message ready to be queued.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedEmail {
+    /// Which template produced it, kept with the queued message.
+    pub template: &'static str,
+    /// Where replies should go instead of the shop's address.
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Every e-mail the shop sends: to customers, and to its own inbox.
+#[derive(Clone, Debug)]
+pub enum Email {
+    OrderConfirmation(Order),
+    PaymentReceived(Order),
+    Shipped {
+        order: Order,
+        /// Parcel number, when staff entered one.
+        tracking: Option<String>,
+    },
+    Delivered(Order),
+    OrderCancelled(Order),
+    /// The customer chose items to send back and can print the withdrawal form.
+    ReturnRequested {
+        order: Order,
+        request: ReturnRequest,
+        /// Page with the withdrawal form and return instructions.
+        link: String,
+        return_address: Vec<String>,
+    },
+    ReturnReceived(Order),
+    RefundIssued {
+        order: Order,
+        amount: Price,
+        method: RefundMethod,
+    },
+    /// Confirms a complaint with its case number and response deadline.
+    ComplaintSubmitted {
+        order: Order,
+        complaint: Complaint,
+        /// Page where the customer follows the case.
+        link: String,
+    },
+    /// Our decision on a complaint, or news that it was resolved.
+    ComplaintUpdated {
+        order: Order,
+        complaint: Complaint,
+        link: String,
+    },
+    VerifyEmail {
+        name: String,
+        link: String,
+        valid_for_hours: u32,
+    },
+    PasswordReset {
+        name: String,
+        link: String,
+        valid_for_hours: u32,
+    },
+    /// A contact form submission forwarded to the shop inbox.
+    ContactMessage(ContactMessage),
+    /// A new complaint, sent to the shop inbox.
+    NewComplaint {
+        order: Order,
+        complaint: Complaint,
+    },
+    /// The shop's answer to a customer's message.
+    SupportReply {
+        name: String,
+        /// Subject of the customer's message, if they gave one.
+        subject: String,
+        body: String,
+    },
+}
+
+enum Block {
+    Paragraph(String),
+    /// Label and value rows, e.g. order lines and totals.
+    Table(Vec<(String, String)>),
+    /// A link the reader is expected to follow.
+    Button {
+        label: String,
+        url: String,
+    },
+}
+
+impl Email {
+    pub fn template(&self) -> &'static str {
+        match self {
+            Email::OrderConfirmation(_) => "order_confirmation",
+            Email::PaymentReceived(_) => "payment_received",
+            Email::Shipped { .. } => "shipped",
+            Email::Delivered(_) => "delivered",
+            Email::OrderCancelled(_) => "order_cancelled",
+            Email::ReturnRequested { .. } => "return_requested",
+            Email::ReturnReceived(_) => "return_received",
+            Email::RefundIssued { .. } => "refund_issued",
+            Email::ComplaintSubmitted { .. } => "complaint_submitted",
+            Email::ComplaintUpdated { .. } => "complaint_updated",
+            Email::VerifyEmail { .. } => "verify_email",
+            Email::PasswordReset { .. } => "password_reset",
+            Email::ContactMessage(_) => "contact_message",
+            Email::NewComplaint { .. } => "new_complaint",
+            Email::SupportReply { .. } => "support_reply",
+        }
+    }
+
+    pub fn render(&self) -> RenderedEmail {
+        let (subject, blocks) = self.content();
+        RenderedEmail {
+            template: self.template(),
+            reply_to: match self {
+                Email::ContactMessage(message) => Some(message.email.clone()),
+                Email::NewComplaint { order, .. } => Some(order.email.clone()),
+                _ => None,
+            },
+            html: render_html(&subject, &blocks),
+            text: render_text(&blocks),
+            subject,
+        }
+    }
+
+    fn content(&self) -> (String, Vec<Block>) {
+        use Block::Paragraph;
+
+        match self {
+            Email::OrderConfirmation(order) => {
+                let mut blocks = vec![
+                    greeting(&order.name),
+                    Paragraph(format!(
+                        "dziękujemy za zakupy w Meg Joni! Przyjęliśmy zamówienie nr {}. \
+                         Wyślemy je, gdy tylko zaksięgujemy płatność ({}).",
+                        order.id,
+                        order.payment.label().to_lowercase()
+                    )),
+                    order_summary(order),
+                    destination(order),
+                ];
+                blocks.extend(invoice_buyer(order));
+                (format!("Potwierdzenie zamówienia nr {}", order.id), blocks)
+            }
+            Email::PaymentReceived(order) => (
+                format!("Otrzymaliśmy płatność za zamówienie nr {}", order.id),
+                vec![
+                    greeting(&order.name),
+                    Paragraph(format!(
+                        "płatność {} za zamówienie nr {} została zaksięgowana. \
+                         Pakujemy Twoje rzeczy i damy znać, gdy paczka wyruszy.",
+                        order.total, order.id
+                    )),
+                ],
+            ),
+            Email::Shipped { order, tracking } => {
+                let mut blocks = vec![
+                    greeting(&order.name),
+                    Paragraph(format!(
+                        "zamówienie nr {} jest już w drodze ({}).",
+                        order.id,
+                        order.delivery.label().to_lowercase()
+                    )),
+                ];
+                if let Some(tracking) = tracking {
+                    blocks.push(Paragraph(format!("Numer przesyłki: {tracking}")));
+                }
+                blocks.push(destination(order));
+                (
+                    format!("Zamówienie nr {} zostało wysłane", order.id),
+                    blocks,
+                )
+            }
+            Email::Delivered(order) => {
+                let deadline = order
+                    .return_window_ends_at
+                    .map(|end| format!(" (do {})", end.format("%d.%m.%Y")))
+                    .unwrap_or_default();
+                (
+                    format!("Zamówienie nr {} zostało dostarczone", order.id),
+                    vec![
+                        greeting(&order.name),
+                        Paragraph(format!(
+                            "zamówienie nr {} zostało dostarczone. Mamy nadzieję, że wszystko \
+                             pasuje!",
+                            order.id
+                        )),
+                        Paragraph(format!(
+                            "Jeśli coś nie przypadło Ci do gustu, możesz to zwrócić bez podania \
+                             przyczyny w ciągu {} dni{deadline}.",
+                            crate::orders::RETURN_WINDOW_DAYS
+                        )),
+                    ],
+                )
+            }
+            Email::OrderCancelled(order) => (
+                format!("Zamówienie nr {} zostało anulowane", order.id),
+                vec![
+                    greeting(&order.name),
+                    Paragraph(format!(
+                        "zamówienie nr {} zostało anulowane. Jeśli było już opłacone, zwrócimy \
+                         pieniądze tą samą drogą, którą zapłaciłaś lub zapłaciłeś.",
+                        order.id
+                    )),
+                    contact_us(),
+                ],
+            ),
+            Email::ReturnRequested {
+                order,
+                request,
+                link,
+                return_address,
+            } => {
+                let rows = request
+                    .lines
+                    .iter()
+                    .map(|line| (line.title.clone(), line.price.to_string()))
+                    .collect();
+                (
+                    format!("Zgłoszenie zwrotu z zamówienia nr {}", order.id),
+                    vec![
+                        greeting(&order.name),
+                        Paragraph(format!(
+                            "przyjęliśmy zgłoszenie zwrotu nr {} z zamówienia nr {}:",
+                            request.id, order.id
+                        )),
+                        Block::Table(rows),
+                        Paragraph(format!(
+                            "Wydrukuj formularz odstąpienia od umowy, dołącz go do paczki i \
+                             odeślij rzeczy w ciągu {} dni na adres:\n{}",
+                            crate::orders::RETURN_WINDOW_DAYS,
+                            return_address.join("\n")
+                        )),
+                        Block::Button {
+                            label: "Formularz zwrotu".to_string(),
+                            url: link.clone(),
+                        },
+                        contact_us(),
+                    ],
+                )
+            }
+            Email::ReturnReceived(order) => (
+                format!("Otrzymaliśmy zwrot zamówienia nr {}", order.id),
+                vec![
+                    greeting(&order.name),
+                    Paragraph(format!(
+                        "paczka ze zwrotem zamówienia nr {} dotarła do nas. Sprawdzimy rzeczy \
+                         i zwrócimy pieniądze w ciągu 14 dni.",
+                        order.id
+                    )),
+                ],
+            ),
+            Email::RefundIssued {
+                order,
+                amount,
+                method,
+            } => (
+                format!("Zwrot pieniędzy za zamówienie nr {}", order.id),
+                vec![
+                    greeting(&order.name),
+                    Paragraph(match method {
+                        RefundMethod::OriginalPayment => format!(
+                            "zleciliśmy zwrot {amount} za zamówienie nr {} tą samą drogą, \
+                             którą zapłacono. Pieniądze powinny pojawić się na Twoim koncie \
+                             w ciągu kilku dni roboczych.",
+                            order.id
+                        ),
+                        RefundMethod::BankTransfer => format!(
+                            "wysłaliśmy przelew {amount} za zamówienie nr {} na podany przez \
+                             Ciebie numer konta. Pieniądze powinny dotrzeć w ciągu jednego \
+                             lub dwóch dni roboczych.",
+                            order.id
+                        ),
+                        RefundMethod::StoreCredit => format!(
+                            "dodaliśmy {amount} za zamówienie nr {} do środków na Twoim koncie \
+                             w sklepie. Aktualne saldo zobaczysz po zalogowaniu.",
+                            order.id
+                        ),
+                    }),
+                    contact_us(),
+                ],
+            ),
+            Email::ComplaintSubmitted {
+                order,
+                complaint,
+                link,
+            } => (
+                format!("Przyjęliśmy reklamację {}", complaint.case_number()),
+                vec![
+                    greeting(&order.name),
+                    Paragraph(format!(
+                        "przyjęliśmy reklamację {} dotyczącą rzeczy „{}” z zamówienia nr {}.",
+                        complaint.case_number(),
+                        complaint.title,
+                        order.id
+                    )),
+                    Block::Table(vec![
+                        ("Żądanie".to_string(), complaint.demand.label().to_string()),
+                        (
+                            "Odpowiemy do".to_string(),
+                            complaint.respond_by.format("%d.%m.%Y").to_string(),
+                        ),
+                    ]),
+                    Paragraph(
+                        "Pod poniższym linkiem sprawdzisz, na jakim etapie jest reklamacja."
+                            .to_string(),
+                    ),
+                    Block::Button {
+                        label: "Status reklamacji".to_string(),
+                        url: link.clone(),
+                    },
+                    contact_us(),
+                ],
+            ),
+            Email::ComplaintUpdated {
+                order,
+                complaint,
+                link,
+            } => {
+                let news = match complaint.status {
+                    ComplaintStatus::Accepted => format!(
+                        "uznaliśmy reklamację {}. Przepraszamy za kłopot!",
+                        complaint.case_number()
+                    ),
+                    ComplaintStatus::Rejected => format!(
+                        "niestety nie możemy uznać reklamacji {}.",
+                        complaint.case_number()
+                    ),
+                    ComplaintStatus::Resolved => {
+                        format!("reklamacja {} została zakończona.", complaint.case_number())
+                    }
+                    ComplaintStatus::Submitted | ComplaintStatus::InReview => format!(
+                        "reklamacja {} ma nowy status: {}.",
+                        complaint.case_number(),
+                        complaint.status.label().to_lowercase()
+                    ),
+                };
+                let mut blocks = vec![greeting(&order.name), Paragraph(news)];
+                if !complaint.response.is_empty() {
+                    blocks.push(Paragraph(complaint.response.clone()));
+                }
+                blocks.push(Block::Button {
+                    label: "Status reklamacji".to_string(),
+                    url: link.clone(),
+                });
+                blocks.push(contact_us());
+                (
+                    format!(
+                        "Reklamacja {}: {}",
+                        complaint.case_number(),
+                        complaint.status.label()
+                    ),
+                    blocks,
+                )
+            }
+            Email::VerifyEmail {
+                name,
+                link,
+                valid_for_hours,
+            } => (
+                "Potwierdź adres e-mail w Meg Joni".to_string(),
+                vec![
+                    greeting(name),
+                    Paragraph(
+                        "dziękujemy za założenie konta w Meg Joni! Aby zacząć z niego \
+                         korzystać, potwierdź swój adres e-mail."
+                            .to_string(),
+                    ),
+                    Block::Button {
+                        label: "Potwierdź adres e-mail".to_string(),
+                        url: link.clone(),
+                    },
+                    Paragraph(format!(
+                        "Link jest ważny przez {valid_for_hours} godz. Jeśli to nie Ty zakładałaś \
+                         lub zakładałeś konto, zignoruj tę wiadomość."
+                    )),
+                ],
+            ),
+            Email::PasswordReset {
+                name,
+                link,
+                valid_for_hours,
+            } => (
+                "Ustaw nowe hasło do konta Meg Joni".to_string(),
+                vec![
+                    greeting(name),
+                    Paragraph(
+                        "otrzymaliśmy prośbę o zmianę hasła do Twojego konta. Aby ustawić nowe \
+                         hasło, kliknij w poniższy link."
+                            .to_string(),
+                    ),
+                    Block::Button {
+                        label: "Ustaw nowe hasło".to_string(),
+                        url: link.clone(),
+                    },
+                    Paragraph(format!(
+                        "Link jest ważny przez {valid_for_hours} godz. Jeśli to nie Ty prosiłaś \
+                         lub prosiłeś o zmianę, zignoruj tę wiadomość – hasło pozostanie bez zmian."
+                    )),
+                ],
+            ),
+            Email::ContactMessage(message) => {
+                let subject = match message.subject.trim() {
+                    "" => "(bez tematu)",
+                    subject => subject,
+                };
+                (
+                    format!("Formularz kontaktowy: {subject}"),
+                    vec![
+                        Block::Table(vec![
+                            ("Od".to_string(), message.name.clone()),
+                            ("E-mail".to_string(), message.email.clone()),
+                            ("Temat".to_string(), subject.to_string()),
+                        ]),
+                        Paragraph(message.message.clone()),
+                        Paragraph("Odpowiedź na ten e-mail trafi prosto do klienta.".to_string()),
+                    ],
+                )
+            }
+            Email::NewComplaint { order, complaint } => (
+                format!(
+                    "Nowa reklamacja {} (zamówienie nr {})",
+                    complaint.case_number(),
+                    order.id
+                ),
+                vec![
+                    Block::Table(vec![
+                        ("Od".to_string(), order.name.clone()),
+                        ("E-mail".to_string(), order.email.clone()),
+                        (
+                            "Rzecz".to_string(),
+                            format!("{} ({})", complaint.title, complaint.price),
+                        ),
+                        ("Żądanie".to_string(), complaint.demand.label().to_string()),
+                        ("Zdjęcia".to_string(), complaint.photos.len().to_string()),
+                        (
+                            "Odpowiedź do".to_string(),
+                            complaint.respond_by.format("%d.%m.%Y").to_string(),
+                        ),
+                    ]),
+                    Paragraph(complaint.description.clone()),
+                    Paragraph(
+                        "Bez odpowiedzi w terminie reklamację uważa się za uznaną. Zdjęcia \
+                         i zmianę statusu znajdziesz w panelu reklamacji."
+                            .to_string(),
+                    ),
+                ],
+            ),
+            Email::SupportReply {
+                name,
+                subject,
+                body,
+            } => {
+                let subject = match subject.trim() {
+                    "" => "Odpowiedź na Twoją wiadomość".to_string(),
+                    subject => format!("Re: {subject}"),
+                };
+                (subject, vec![greeting(name), Paragraph(body.clone())])
+            }
+        }
+    }
+}
+
+fn greeting(name: &str) -> Block {
+    match name.split_whitespace().next() {
+        Some(first) => Block::Paragraph(format!("Dzień dobry {first},")),
+        None => Block::Paragraph("Dzień dobry,".to_string()),
+    }
+}
+
+fn contact_us() -> Block {
+    Block::Paragraph(
+        "Masz pytania? Odpisz na tę wiadomość albo napisz na kontakt@megjoni.pl.".to_string(),
+    )
+}
+
+fn order_summary(order: &Order) -> Block {
+    let mut rows: Vec<(String, String)> = order
+        .lines
+        .iter()
+        .map(|line| (line.title.clone(), line.price.to_string()))
+        .collect();
+    rows.push(("Dostawa".to_string(), order.shipping.to_string()));
+    rows.push(("Razem".to_string(), order.total.to_string()));
+    if order.store_credit.grosze() > 0 {
+        rows.push((
+            "Środki w sklepie".to_string(),
+            format!("-{}", order.store_credit),
+        ));
+        rows.push(("Do zapłaty".to_string(), order.amount_due().to_string()));
+    }
+    Block::Table(rows)
+}
+
+fn destination(order: &Order) -> Block {
+    let address = match (&order.locker_code, order.delivery) {
+        (Some(code), DeliveryMethod::InpostLocker) => format!("paczkomat {code}"),
+        (_, DeliveryMethod::PersonalPickup) => "odbiór osobisty w sklepie".to_string(),
+        _ => format!(
+            "{}, {} {}",
+            order.address.street, order.address.postcode, order.address.city
+        ),
+    };
+    Block::Paragraph(format!("Dostawa: {address}."))
+}
+
+fn invoice_buyer(order: &Order) -> Option<Block> {
+    order.invoice.as_ref().map(|invoice| {
+        Block::Paragraph(format!(
+            "Fakturę wystawimy na: {}, NIP {}, {}, {} {}.",
+            invoice.company,
+            invoice.nip,
+            invoice.address.street,
+            invoice.address.postcode,
+            invoice.address.city
+        ))
+    })
+}
+
+const SIGNATURE: &str = "Pozdrawiamy,\nZespół Meg Joni";
+
+fn render_text(blocks: &[Block]) -> String {
+    let mut text = String::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph(paragraph) => text.push_str(paragraph),
+            Block::Table(rows) => {
+                let lines: Vec<String> = rows
+                    .iter()
+                    .map(|(label, value)| format!("  {label}: {value}"))
+                    .collect();
+                text.push_str(&lines.join("\n"));
+            }
+            Block::Button { label, url } => text.push_str(&format!("{label}: {url}")),
+        }
+        text.push_str("\n\n");
+    }
+    text.push_str(SIGNATURE);
+    text.push('\n');
+    text
+}
+
+fn render_html(subject: &str, blocks: &[Block]) -> String {
+    let mut body = String::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph(paragraph) => {
+                let paragraph = escape(paragraph).replace('\n', "<br>");
+                body.push_str(&format!("<p>{paragraph}</p>\n"));
+            }
+            Block::Table(rows) => {
+                body.push_str("<table style=\"border-collapse: collapse; width: 100%;\">\n");
+                for (label, value) in rows {
+                    body.push_str(&format!(
+                        "<tr><td style=\"padding: 4px 0;\">{}</td>\
+                         <td style=\"padding: 4px 0; text-align: right;\">{}</td></tr>\n",
+                        escape(label),
+                        escape(value)
+                    ));
+                }
+                body.push_str("</table>\n");
+            }
+            Block::Button { label, url } => body.push_str(&format!(
+                "<p><a href=\"{}\" style=\"display: inline-block; padding: 10px 20px; \
+                 background: #333; color: #fff; text-decoration: none;\">{}</a></p>\n",
+                escape(url),
+                escape(label)
+            )),
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"pl\">\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n\
+         <body style=\"font-family: Arial, sans-serif; color: #333; max-width: 600px;\">\n\
+         <h1 style=\"font-size: 20px;\">Meg Joni</h1>\n{body}<p>{}</p>\n</body>\n</html>\n",
+        escape(subject),
+        escape(SIGNATURE).replace('\n', "<br>")
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
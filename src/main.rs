@@ -1,11 +1,25 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
-    use axum::Router;
-    use leptos::logging::log;
+    use axum::extract::DefaultBodyLimit;
+    use axum::routing::{get, post};
+    use axum::{Extension, Router};
+    use leptos::logging::{error, log};
     use leptos::prelude::*;
     use leptos_axum::{LeptosRoutes, generate_route_list};
     use megjoni_shop::app::*;
+    use megjoni_shop::catalog::source::seed_database;
+    use megjoni_shop::complaints::{self, complaint_photo, receive_complaint};
+    use megjoni_shop::db::Db;
+    use megjoni_shop::inventory::{spawn_reservation_sweeper, spawn_unpaid_order_sweeper};
+    use megjoni_shop::lockers::source::import_lockers;
+    use megjoni_shop::mail::{Mailer, spawn_outbox_worker};
+    use megjoni_shop::payments::gateway::Payments;
+    use megjoni_shop::payments::receive_webhook;
+    use megjoni_shop::retention::{RetentionPolicy, spawn_retention_job};
+    use megjoni_shop::search::index::SharedSearchIndex;
+    use megjoni_shop::shipping::source::load_shipping_rates;
+    use megjoni_shop::staff::auth::bootstrap_owner;
 
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
@@ -13,11 +27,68 @@ async fn main() {
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(App);
 
+    let db = Db::open_configured().expect("nie można otworzyć bazy danych");
+    match seed_database(&db) {
+        Ok(0) => {}
+        Ok(count) => log!("zaimportowano {count} produktów do pustej bazy"),
+        Err(e) => log!("nie udało się zaimportować produktów: {e}"),
+    }
+    match import_lockers(&db) {
+        Ok(count) => log!("zaimportowano {count} paczkomatów"),
+        Err(e) => log!("nie udało się zaimportować paczkomatów: {e}"),
+    }
+    match bootstrap_owner(&db) {
+        Ok(Some(owner)) => log!("utworzono konto właściciela {}", owner.email),
+        Ok(None) => {}
+        Err(e) => log!("panel sklepu jest niedostępny: {e}"),
+    }
+    spawn_reservation_sweeper(db.clone());
+    spawn_unpaid_order_sweeper(db.clone());
+    let mailer = Mailer::from_env().expect("nie można skonfigurować poczty");
+    spawn_outbox_worker(db.clone(), mailer);
+    spawn_retention_job(db.clone(), RetentionPolicy::from_env());
+    let search_index =
+        SharedSearchIndex::build(&db).expect("nie można zbudować indeksu wyszukiwania");
+    let shipping_rates = load_shipping_rates().expect("nie można wczytać cennika dostaw");
+    let payments = match Payments::from_env(&format!("http://{addr}")) {
+        Ok(payments) => payments,
+        Err(e) => {
+            error!("nie można uruchomić sklepu: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Powiadomienia od operatorów płatności trafiają do zwykłej trasy axum.
+    let webhooks = Router::new()
+        .route("/payments/webhook/:provider", post(receive_webhook))
+        .layer(Extension(db.clone()))
+        .layer(Extension(payments.clone()));
+
+    // Reklamacje przychodzą ze zdjęciami, więc formularz też trafia do zwykłej trasy axum.
+    let complaints = Router::new()
+        .route("/complaints/submit", post(receive_complaint))
+        .route("/complaints/:token/photos/:photo_id", get(complaint_photo))
+        .layer(DefaultBodyLimit::max(complaints::MAX_UPLOAD_BYTES))
+        .layer(Extension(db.clone()))
+        .layer(Extension(payments.clone()));
+
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, {
-            let leptos_options = leptos_options.clone();
-            move || shell(leptos_options.clone())
-        })
+        .merge(webhooks)
+        .merge(complaints)
+        .leptos_routes_with_context(
+            &leptos_options,
+            routes,
+            move || {
+                provide_context(db.clone());
+                provide_context(search_index.clone());
+                provide_context(shipping_rates.clone());
+                provide_context(payments.clone());
+            },
+            {
+                let leptos_options = leptos_options.clone();
+                move || shell(leptos_options.clone())
+            },
+        )
         .fallback(leptos_axum::file_and_error_handler(shell))
         .with_state(leptos_options);
 
@@ -25,9 +96,13 @@ async fn main() {
     // `axum::Server` is a re-export of `hyper::Server`
     log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    // Adres klienta jest potrzebny do ograniczania liczby wiadomości z formularzy.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 #[cfg(not(feature = "ssr"))]
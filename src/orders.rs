@@ -0,0 +1,339 @@
+//! Order lifecycle. An order moves through [`OrderStatus`] only along the transitions
+//! allowed by [`OrderStatus::can_become`]; every change is recorded with who made it and
+//! when, and may trigger side effects (see [`lifecycle`]).
+
+use crate::catalog::Price;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub mod admin;
+
+/// How long after delivery a customer may return an order without giving a reason.
+pub const RETURN_WINDOW_DAYS: i64 = 14;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    #[default]
+    PendingPayment,
+    Paid,
+    Packed,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Returned,
+    Refunded,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 8] = [
+        OrderStatus::PendingPayment,
+        OrderStatus::Paid,
+        OrderStatus::Packed,
+        OrderStatus::Shipped,
+        OrderStatus::Delivered,
+        OrderStatus::Cancelled,
+        OrderStatus::Returned,
+        OrderStatus::Refunded,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OrderStatus::PendingPayment => "Oczekuje na płatność",
+            OrderStatus::Paid => "Opłacone",
+            OrderStatus::Packed => "Spakowane",
+            OrderStatus::Shipped => "Wysłane",
+            OrderStatus::Delivered => "Dostarczone",
+            OrderStatus::Cancelled => "Anulowane",
+            OrderStatus::Returned => "Zwrócone",
+            OrderStatus::Refunded => "Zwrot pieniędzy wykonany",
+        }
+    }
+
+    /// The value used in forms and links, e.g. `pending_payment`.
+    pub fn param(self) -> &'static str {
+        match self {
+            OrderStatus::PendingPayment => "pending_payment",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Packed => "packed",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Returned => "returned",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.param() == param)
+    }
+
+    /// Statuses this one may change into.
+    pub fn next(self) -> &'static [OrderStatus] {
+        use OrderStatus::*;
+        match self {
+            PendingPayment => &[Paid, Cancelled],
+            Paid => &[Packed, Cancelled],
+            Packed => &[Shipped, Cancelled],
+            // Przesyłka nieodebrana wraca do nas bez doręczenia.
+            Shipped => &[Delivered, Returned],
+            Delivered => &[Returned],
+            Cancelled | Returned => &[Refunded],
+            Refunded => &[],
+        }
+    }
+
+    pub fn can_become(self, to: OrderStatus) -> bool {
+        self.next().contains(&to)
+    }
+
+    /// Whether the items are back with us (or never left) and can be sold again.
+    pub fn releases_items(self) -> bool {
+        matches!(self, OrderStatus::Cancelled | OrderStatus::Returned)
+    }
+}
+
+/// Who changed an order's status.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Actor {
+    /// Background jobs and automatic rules.
+    System,
+    /// A payment provider confirming or rejecting a payment.
+    PaymentProvider(String),
+    Customer(Option<i64>),
+    Staff(String),
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Actor::System => write!(f, "system"),
+            Actor::PaymentProvider(name) => write!(f, "payment:{name}"),
+            Actor::Customer(Some(id)) => write!(f, "customer:{id}"),
+            Actor::Customer(None) => write!(f, "customer"),
+            Actor::Staff(name) => write!(f, "staff:{name}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Actor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "system" => Ok(Actor::System),
+            None if s == "customer" => Ok(Actor::Customer(None)),
+            Some(("payment", name)) => Ok(Actor::PaymentProvider(name.to_string())),
+            Some(("staff", name)) => Ok(Actor::Staff(name.to_string())),
+            Some(("customer", id)) => id
+                .parse()
+                .map(|id| Actor::Customer(Some(id)))
+                .map_err(|_| format!("nieznany klient: {s}")),
+            _ => Err(format!("nieznany autor zmiany: {s}")),
+        }
+    }
+}
+
+/// One recorded status change.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub from: Option<OrderStatus>,
+    pub to: OrderStatus,
+    pub actor: Actor,
+    pub note: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// An order as listed for a customer, without its lines and addresses.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderSummary {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub status: OrderStatus,
+    pub total: Price,
+    /// How much of `total` has been refunded.
+    pub refunded: Price,
+    /// Titles of the ordered items.
+    pub items: Vec<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<&crate::db::Order> for OrderSummary {
+    fn from(order: &crate::db::Order) -> Self {
+        OrderSummary {
+            id: order.id,
+            created_at: order.created_at,
+            status: order.status,
+            total: order.total,
+            refunded: order.refunded,
+            items: order.lines.iter().map(|line| line.title.clone()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum TransitionError {
+    #[error("nie ma takiego zamówienia")]
+    NotFound,
+    #[error("zamówienia ze statusem „{}” nie można oznaczyć jako „{}”", .from.label(), .to.label())]
+    Illegal { from: OrderStatus, to: OrderStatus },
+    #[error("minął {RETURN_WINDOW_DAYS}-dniowy termin na zwrot")]
+    ReturnWindowClosed,
+    #[error("zamówienie nie zostało opłacone, więc nie ma czego zwracać")]
+    NeverPaid,
+    #[error("błąd bazy danych: {0}")]
+    Storage(String),
+}
+
+/// Applying transitions and their side effects.
+#[cfg(feature = "ssr")]
+pub mod lifecycle {
+    use chrono::{DateTime, Duration, Utc};
+    use leptos::logging::log;
+
+    use super::{Actor, OrderStatus, RETURN_WINDOW_DAYS, TransitionError};
+    use crate::db::{Db, Order, OrderRepository, StatusChange};
+    use crate::mail::{self, Email};
+
+    /// The e-mail telling the customer that their order has just reached its status.
+    fn status_email(order: &Order, note: Option<&str>) -> Option<Email> {
+        let order = order.clone();
+        match order.status {
+            OrderStatus::Paid => Some(Email::PaymentReceived(order)),
+            OrderStatus::Shipped => Some(Email::Shipped {
+                order,
+                tracking: note.map(str::to_string),
+            }),
+            OrderStatus::Delivered => Some(Email::Delivered(order)),
+            OrderStatus::Cancelled => Some(Email::OrderCancelled(order)),
+            OrderStatus::Returned => Some(Email::ReturnReceived(order)),
+            // Każdy zwrot pieniędzy ma własny e-mail z kwotą i sposobem (zob. crate::refunds).
+            OrderStatus::PendingPayment | OrderStatus::Packed | OrderStatus::Refunded => None,
+        }
+    }
+
+    /// Moves the order to `to` if the state machine allows it, recording the change.
+    ///
+    /// Cancelling or taking back an order puts its items back on sale, delivery opens the
+    /// return window and customers are told about the changes that concern them.
+    pub fn transition(
+        db: &Db,
+        order_id: i64,
+        to: OrderStatus,
+        actor: &Actor,
+        note: Option<&str>,
+    ) -> Result<Order, TransitionError> {
+        let storage = |e: crate::db::Error| TransitionError::Storage(e.to_string());
+        let order = db
+            .find_order(order_id)
+            .map_err(storage)?
+            .ok_or(TransitionError::NotFound)?;
+        let from = order.status;
+        if !from.can_become(to) {
+            return Err(TransitionError::Illegal { from, to });
+        }
+        let now = Utc::now();
+        if from == OrderStatus::Delivered
+            && to == OrderStatus::Returned
+            && order.return_window_ends_at.is_some_and(|end| now > end)
+            && !matches!(actor, Actor::Staff(_))
+        {
+            return Err(TransitionError::ReturnWindowClosed);
+        }
+        if to == OrderStatus::Refunded {
+            let events = db.order_events(order_id).map_err(storage)?;
+            if !events.iter().any(|event| event.to == OrderStatus::Paid) {
+                return Err(TransitionError::NeverPaid);
+            }
+        }
+
+        let change = StatusChange {
+            from,
+            to,
+            actor: actor.clone(),
+            note: note.map(str::to_string),
+            at: now,
+            release_items: to.releases_items(),
+            return_window_ends_at: (to == OrderStatus::Delivered)
+                .then(|| now + Duration::days(RETURN_WINDOW_DAYS)),
+        };
+        let order = db
+            .change_status(order_id, &change)
+            .map_err(storage)?
+            // Ktoś inny zmienił status w międzyczasie.
+            .ok_or(TransitionError::Illegal { from, to })?;
+
+        if let Some(email) = status_email(&order, note) {
+            // Zmiana statusu już się dokonała; brak e-maila nie może jej cofnąć.
+            if let Err(e) = mail::queue(db, &order.email, &email) {
+                log!("nie udało się zakolejkować e-maila do zamówienia {order_id}: {e}");
+            }
+        }
+        Ok(order)
+    }
+
+    /// Cancels orders placed before `placed_before` that were never paid, which puts
+    /// their items back on sale. Returns how many were cancelled.
+    pub fn cancel_unpaid(
+        db: &Db,
+        placed_before: DateTime<Utc>,
+        note: &str,
+    ) -> Result<usize, TransitionError> {
+        let storage = |e: crate::db::Error| TransitionError::Storage(e.to_string());
+        let mut cancelled = 0;
+        for order_id in db.unpaid_orders(placed_before).map_err(storage)? {
+            match transition(
+                db,
+                order_id,
+                OrderStatus::Cancelled,
+                &Actor::System,
+                Some(note),
+            ) {
+                Ok(_) => cancelled += 1,
+                // Płatność doszła w międzyczasie.
+                Err(TransitionError::Illegal { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderStatus::{self, *};
+
+    /// Every change the lifecycle allows; any other pair must be refused.
+    const ALLOWED: [(OrderStatus, OrderStatus); 11] = [
+        (PendingPayment, Paid),
+        (PendingPayment, Cancelled),
+        (Paid, Packed),
+        (Paid, Cancelled),
+        (Packed, Shipped),
+        (Packed, Cancelled),
+        (Shipped, Delivered),
+        (Shipped, Returned),
+        (Delivered, Returned),
+        (Cancelled, Refunded),
+        (Returned, Refunded),
+    ];
+
+    #[test]
+    fn only_lifecycle_changes_are_allowed() {
+        for from in OrderStatus::ALL {
+            for to in OrderStatus::ALL {
+                assert_eq!(
+                    from.can_become(to),
+                    ALLOWED.contains(&(from, to)),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn refunded_is_final() {
+        assert!(Refunded.next().is_empty());
+    }
+}
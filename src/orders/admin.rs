@@ -0,0 +1,137 @@
+//! Orders as staff see them at `/admin/orders`, newest first and filtered by status, each
+//! linking to its packing slip where it is moved to the next status.
+
+use super::{OrderStatus, OrderSummary};
+use crate::checkout::DeliveryMethod;
+use crate::staff::Permission;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+use serde::{Deserialize, Serialize};
+
+/// An order in the staff list: its summary and who it goes to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AdminOrder {
+    pub summary: OrderSummary,
+    pub name: String,
+    pub email: String,
+    pub delivery: DeliveryMethod,
+}
+
+#[server]
+pub async fn list_admin_orders(
+    status: Option<OrderStatus>,
+) -> Result<Vec<AdminOrder>, ServerFnError> {
+    use crate::db::{OrderRepository, use_db};
+
+    crate::staff::require_staff(Permission::HandleOrders)?;
+    Ok(use_db()?
+        .list_orders(status)?
+        .into_iter()
+        .map(|order| AdminOrder {
+            summary: OrderSummary::from(&order),
+            name: order.name,
+            email: order.email,
+            delivery: order.delivery,
+        })
+        .collect())
+}
+
+#[component]
+pub fn OrdersAdminPage() -> impl IntoView {
+    let query = use_query_map();
+    let status = move || {
+        query
+            .read()
+            .get("status")
+            .and_then(|status| OrderStatus::from_param(&status))
+    };
+    let orders = Resource::new(status, list_admin_orders);
+
+    let filters = move || {
+        let current = status();
+        let all = view! {
+            <a href="/admin/orders" class:active=current.is_none()>
+                "Wszystkie"
+            </a>
+        };
+        let by_status = OrderStatus::ALL
+            .into_iter()
+            .map(|filter| {
+                let href = format!("/admin/orders?status={}", filter.param());
+                view! {
+                    <a href=href class:active=current == Some(filter)>
+                        {filter.label()}
+                    </a>
+                }
+            })
+            .collect_view();
+        view! { <nav class="inbox-filters">{all} {by_status}</nav> }
+    };
+
+    view! {
+        <Title text="Zamówienia - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Zamówienia"</h2>
+                {filters}
+                <Suspense fallback=|| view! { <p>"Ładowanie zamówień..."</p> }>
+                    {move || Suspend::new(async move {
+                        match orders.await {
+                            Ok(orders) if orders.is_empty() => {
+                                view! { <p>"Brak zamówień."</p> }.into_any()
+                            }
+                            Ok(orders) => view! { <OrderList orders /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do zamówień."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn OrderList(orders: Vec<AdminOrder>) -> impl IntoView {
+    view! {
+        <table class="inbox-threads">
+            <thead>
+                <tr>
+                    <th>"Zamówienie"</th>
+                    <th>"Klient"</th>
+                    <th>"Rzeczy"</th>
+                    <th>"Dostawa"</th>
+                    <th>"Kwota"</th>
+                    <th>"Status"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {orders
+                    .into_iter()
+                    .map(|order| {
+                        let summary = order.summary;
+                        let href = format!("/admin/orders/{}/packing-slip", summary.id);
+                        view! {
+                            <tr>
+                                <td>
+                                    <a href=href>{format!("nr {}", summary.id)}</a>
+                                    <br />
+                                    <small>{summary.created_at.format("%Y-%m-%d %H:%M").to_string()}</small>
+                                </td>
+                                <td>
+                                    {order.name}
+                                    <br />
+                                    <small>{order.email}</small>
+                                </td>
+                                <td>{summary.items.join(", ")}</td>
+                                <td>{order.delivery.label()}</td>
+                                <td>{summary.total.to_string()}</td>
+                                <td>{summary.status.label()}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
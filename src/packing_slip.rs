@@ -0,0 +1,336 @@
+//! Printable packing slip for one order at `/admin/orders/:id/packing-slip`, with the
+//! controls staff use to move the order through its lifecycle and refund it (see
+//! [`crate::refunds`]). `?return=<id>` starts the refund form with that return's items.
+//!
+//! Open to staff who handle orders; the refund panel only shows for those who may issue
+//! refunds.
+
+use crate::catalog::Price;
+use crate::checkout::{Address, DeliveryMethod, InvoiceDetails};
+use crate::lockers::Locker;
+use crate::orders::{OrderEvent, OrderStatus, TransitionError};
+use crate::refunds::{IssueRefund, RefundPanel, SettleRefund, get_order_refunds};
+use crate::staff::Permission;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::{use_params_map, use_query_map};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackingSlipLine {
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+}
+
+/// What the packer needs to know about an order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackingSlip {
+    pub order_id: i64,
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub address: Address,
+    pub delivery: DeliveryMethod,
+    pub locker_code: Option<String>,
+    /// The locker's details, if its code is still in the imported dataset.
+    pub locker: Option<Locker>,
+    /// Buyer details for the invoice that goes into the parcel.
+    pub invoice: Option<InvoiceDetails>,
+    pub lines: Vec<PackingSlipLine>,
+    pub status: OrderStatus,
+    pub history: Vec<OrderEvent>,
+}
+
+#[server]
+pub async fn get_packing_slip(order_id: i64) -> Result<Option<PackingSlip>, ServerFnError> {
+    use crate::db::{LockerRepository, OrderRepository, use_db};
+
+    crate::staff::require_staff(Permission::HandleOrders)?;
+    let db = use_db()?;
+    let Some(order) = db.find_order(order_id)? else {
+        return Ok(None);
+    };
+    let locker = match &order.locker_code {
+        Some(code) => db.find_locker(code)?,
+        None => None,
+    };
+    let history = db.order_events(order.id)?;
+    Ok(Some(PackingSlip {
+        order_id: order.id,
+        name: order.name,
+        phone: order.phone,
+        email: order.email,
+        address: order.address,
+        delivery: order.delivery,
+        locker_code: order.locker_code,
+        locker,
+        invoice: order.invoice,
+        lines: order
+            .lines
+            .into_iter()
+            .map(|line| PackingSlipLine {
+                product_id: line.product_id,
+                title: line.title,
+                price: line.price,
+            })
+            .collect(),
+        status: order.status,
+        history,
+    }))
+}
+
+/// Moves the order to `to` on behalf of staff. The outer error means no access or a
+/// storage failure; the inner one a transition the lifecycle does not allow.
+#[server]
+pub async fn change_order_status(
+    order_id: i64,
+    to: OrderStatus,
+    note: Option<String>,
+) -> Result<Result<OrderStatus, TransitionError>, ServerFnError> {
+    use crate::db::use_db;
+    use crate::orders::lifecycle;
+
+    let staff = crate::staff::require_staff(Permission::HandleOrders)?;
+    if to == OrderStatus::Refunded {
+        return Err(ServerFnError::new(
+            "pieniądze zwraca się w sekcji „Zwroty pieniędzy”",
+        ));
+    }
+    let db = use_db()?;
+    let note = note
+        .as_deref()
+        .map(str::trim)
+        .filter(|note| !note.is_empty());
+    match lifecycle::transition(&db, order_id, to, &staff.actor(), note) {
+        Ok(order) => Ok(Ok(order.status)),
+        Err(TransitionError::Storage(e)) => Err(ServerFnError::new(e)),
+        Err(e) => Ok(Err(e)),
+    }
+}
+
+#[component]
+pub fn PackingSlipPage() -> impl IntoView {
+    let params = use_params_map();
+    let query = use_query_map();
+    let change_status = ServerAction::<ChangeOrderStatus>::new();
+    let issue_refund = ServerAction::<IssueRefund>::new();
+    let settle_refund = ServerAction::<SettleRefund>::new();
+    // Zwrot pieniędzy może zmienić status zamówienia, więc obie części odświeżają się razem.
+    let source = move || {
+        let id = params
+            .read()
+            .get("id")
+            .and_then(|id| id.parse::<i64>().ok());
+        (
+            id,
+            change_status.version().get(),
+            issue_refund.version().get(),
+            settle_refund.version().get(),
+        )
+    };
+    let slip = Resource::new(source, |(id, ..)| async move {
+        match id {
+            Some(id) => get_packing_slip(id).await,
+            None => Ok(None),
+        }
+    });
+    let refunds = Resource::new(source, |(id, ..)| async move {
+        match id {
+            Some(id) => get_order_refunds(id).await,
+            None => Ok(None),
+        }
+    });
+
+    view! {
+        <Title text="List przewozowy - Meg Joni" />
+        <main>
+            <Suspense fallback=|| view! { <p>"Ładowanie zamówienia..."</p> }>
+                {move || Suspend::new(async move {
+                    match (slip.await, refunds.await) {
+                        (Ok(Some(slip)), refunds) => {
+                            let return_id = query
+                                .read_untracked()
+                                .get("return")
+                                .and_then(|id| id.parse::<i64>().ok());
+                            let refunds = refunds.ok().flatten().map(|order| {
+                                view! {
+                                    <RefundPanel
+                                        order
+                                        return_id
+                                        issue=issue_refund
+                                        settle=settle_refund
+                                    />
+                                }
+                            });
+                            view! {
+                                <PackingSlipView slip=slip.clone() />
+                                <OrderStatusPanel slip action=change_status />
+                                {refunds}
+                            }
+                            .into_any()
+                        }
+                        (Ok(None), _) => {
+                            view! { <p>"Nie ma takiego zamówienia."</p> }.into_any()
+                        }
+                        (Err(_), _) => view! {
+                            <p>"Brak dostępu do listu przewozowego."</p>
+                        }
+                        .into_any(),
+                    }
+                })}
+            </Suspense>
+        </main>
+    }
+}
+
+#[component]
+fn PackingSlipView(slip: PackingSlip) -> impl IntoView {
+    let destination = match (&slip.locker_code, &slip.locker) {
+        (Some(code), Some(locker)) => view! {
+            <dt>"Paczkomat"</dt>
+            <dd class="packing-slip-locker">
+                <strong>{code.clone()}</strong>
+                <br />
+                {locker.address()}
+            </dd>
+        }
+        .into_any(),
+        (Some(code), None) => view! {
+            <dt>"Paczkomat"</dt>
+            <dd class="packing-slip-locker">
+                <strong>{code.clone()}</strong>
+            </dd>
+        }
+        .into_any(),
+        (None, _) => view! {
+            <dt>"Adres"</dt>
+            <dd>
+                {slip.address.street.clone()}
+                <br />
+                {format!("{} {}", slip.address.postcode, slip.address.city)}
+            </dd>
+        }
+        .into_any(),
+    };
+
+    view! {
+        <article class="packing-slip">
+            <div class="packing-slip-header">
+                <h2>{format!("Zamówienie nr {}", slip.order_id)}</h2>
+                <button class="packing-slip-print" onclick="window.print()">"Drukuj"</button>
+            </div>
+            <dl class="packing-slip-details">
+                <dt>"Odbiorca"</dt>
+                <dd>{slip.name.clone()}</dd>
+                <dt>"Telefon"</dt>
+                <dd>{slip.phone.clone()}</dd>
+                <dt>"E-mail"</dt>
+                <dd>{slip.email.clone()}</dd>
+                <dt>"Dostawa"</dt>
+                <dd>{slip.delivery.label()}</dd>
+                {destination}
+                {slip.invoice.clone().map(|invoice| view! {
+                    <dt>"Faktura"</dt>
+                    <dd>
+                        {invoice.company}
+                        <br />
+                        {format!("NIP {}", invoice.nip)}
+                        <br />
+                        {invoice.address.street}
+                        <br />
+                        {format!("{} {}", invoice.address.postcode, invoice.address.city)}
+                    </dd>
+                })}
+            </dl>
+            <table class="packing-slip-lines">
+                <thead>
+                    <tr>
+                        <th>"Nr"</th>
+                        <th>"Produkt"</th>
+                        <th>"Cena"</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {slip
+                        .lines
+                        .into_iter()
+                        .map(|line| view! {
+                            <tr>
+                                <td>{line.product_id}</td>
+                                <td>{line.title}</td>
+                                <td>{line.price.to_string()}</td>
+                            </tr>
+                        })
+                        .collect_view()}
+                </tbody>
+            </table>
+        </article>
+    }
+}
+
+/// Current status, the transitions allowed from it and the order's history. Hidden when
+/// printing.
+#[component]
+fn OrderStatusPanel(slip: PackingSlip, action: ServerAction<ChangeOrderStatus>) -> impl IntoView {
+    let error = move || match action.value().get() {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się zmienić statusu.".to_string()),
+        _ => None,
+    };
+    // Status „zwrot pieniędzy wykonany” ustawiają same zwroty pieniędzy, gdy obejmą całą
+    // kwotę zamówienia.
+    let next: Vec<OrderStatus> = slip
+        .status
+        .next()
+        .iter()
+        .copied()
+        .filter(|&to| to != OrderStatus::Refunded)
+        .collect();
+    let buttons = next
+        .iter()
+        .map(|&to| {
+            view! {
+                <button type="submit" name="to" value=to.param()>
+                    {to.label()}
+                </button>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <section class="order-status-panel">
+            <h3>"Status: " {slip.status.label()}</h3>
+            {(!next.is_empty())
+                .then(|| {
+                    view! {
+                        <ActionForm action>
+                            <input type="hidden" name="order_id" value=slip.order_id />
+                            <label>
+                                "Notatka (np. numer przesyłki)" <input type="text" name="note" />
+                            </label>
+                            <div class="order-status-actions">{buttons}</div>
+                        </ActionForm>
+                    }
+                })}
+            {move || error().map(|e| view! { <p class="order-status-error">{e}</p> })}
+            <h3>"Historia"</h3>
+            <ol class="order-history">
+                {slip
+                    .history
+                    .into_iter()
+                    .map(|event| {
+                        let note = event.note.map(|note| format!(" ({note})"));
+                        view! {
+                            <li>
+                                {event.at.format("%Y-%m-%d %H:%M").to_string()} " "
+                                <strong>{event.to.label()}</strong> " " {event.actor.to_string()}
+                                {note}
+                            </li>
+                        }
+                    })
+                    .collect_view()}
+            </ol>
+        </section>
+    }
+}
@@ -0,0 +1,309 @@
+//! Paying for orders. Each order gets a [`gateway::PaymentProvider`] picked by its payment
+//! method: Przelewy24 for online transfers and BLIK, plain bank transfer, or the local
+//! mock used in development. Providers confirm payments through signed webhooks at
+//! `/payments/webhook/:provider`; the customer comes back to `/payments/return/:provider`.
+
+use std::collections::BTreeMap;
+
+use crate::catalog::Price;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::{use_params_map, use_query_map};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+pub mod bank_transfer;
+#[cfg(feature = "ssr")]
+pub mod gateway;
+#[cfg(feature = "ssr")]
+pub mod mock;
+#[cfg(feature = "ssr")]
+pub mod przelewy24;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    #[default]
+    Pending,
+    Paid,
+    Failed,
+}
+
+/// Where to send bank transfer payments for an order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransferInstructions {
+    pub recipient: String,
+    pub account_number: String,
+    /// Transfer title that lets us match the money to the payment.
+    pub title: String,
+    pub amount: Price,
+}
+
+/// What the customer has to do to pay.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PaymentStart {
+    /// Continue on the provider's payment page.
+    Redirect {
+        url: String,
+    },
+    BankTransfer(TransferInstructions),
+    /// Store credit covered the whole order; there is nothing left to pay.
+    StoreCredit,
+}
+
+/// State of a payment as shown on the return page.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaymentReturn {
+    pub order_id: i64,
+    pub amount: Price,
+    pub status: PaymentStatus,
+}
+
+/// Registers a payment for a freshly placed order with the provider for its method.
+#[cfg(feature = "ssr")]
+pub async fn start_payment(
+    db: &crate::db::Db,
+    payments: &gateway::Payments,
+    order: &crate::db::Order,
+) -> Result<PaymentStart, ServerFnError> {
+    use crate::db::PaymentRepository;
+    use chrono::Utc;
+    use gateway::PaymentRequest;
+
+    let provider = payments.for_method(order.payment);
+    let amount = order.amount_due();
+    let payment = db.create_payment(order.id, provider.name(), order.payment, amount)?;
+    let request = PaymentRequest {
+        payment_id: payment.id.clone(),
+        order_id: order.id,
+        amount,
+        method: order.payment,
+        email: order.email.clone(),
+        description: format!("Meg Joni, zamówienie nr {}", order.id),
+        return_url: payments.return_url(provider.name(), &payment.id),
+        notify_url: payments.notify_url(provider.name()),
+    };
+    match provider.create_payment(&request).await {
+        Ok(start) => Ok(start),
+        Err(e) => {
+            db.update_payment_status(&payment.id, PaymentStatus::Failed, None, Utc::now())?;
+            Err(ServerFnError::new(e))
+        }
+    }
+}
+
+/// Moves the order of a confirmed payment from waiting for payment to paid.
+/// Repeated confirmations of the same payment change nothing.
+#[cfg(feature = "ssr")]
+fn settle_order(
+    db: &crate::db::Db,
+    payment: &crate::db::Payment,
+) -> Result<(), crate::orders::TransitionError> {
+    use crate::orders::{Actor, OrderStatus, TransitionError, lifecycle};
+    use leptos::logging::log;
+
+    if payment.status != PaymentStatus::Paid {
+        return Ok(());
+    }
+    let actor = Actor::PaymentProvider(payment.provider.clone());
+    let note = format!("płatność {}", payment.id);
+    match lifecycle::transition(db, payment.order_id, OrderStatus::Paid, &actor, Some(&note)) {
+        Ok(_) => Ok(()),
+        Err(TransitionError::Illegal { from, .. }) => {
+            if from != OrderStatus::Paid {
+                // Np. zamówienie anulowane przed wpłatą: pieniądze trzeba oddać ręcznie.
+                log!(
+                    "płatność {} przyszła dla zamówienia {} ze statusem {from:?}",
+                    payment.id,
+                    payment.order_id
+                );
+            }
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Axum handler for provider notifications.
+#[cfg(feature = "ssr")]
+pub async fn receive_webhook(
+    axum::extract::Path(provider): axum::extract::Path<String>,
+    axum::Extension(db): axum::Extension<crate::db::Db>,
+    axum::Extension(payments): axum::Extension<gateway::Payments>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> axum::http::StatusCode {
+    use crate::db::PaymentRepository;
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use leptos::logging::log;
+
+    let Some(provider) = payments.by_name(&provider) else {
+        return StatusCode::NOT_FOUND;
+    };
+    let notification = match provider.verify_webhook(&headers, &body).await {
+        Ok(notification) => notification,
+        Err(e) => {
+            log!("odrzucono powiadomienie od {}: {e}", provider.name());
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    let payment = match db.find_payment(&notification.payment_id) {
+        Ok(Some(payment)) => payment,
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            log!("nie udało się odczytać płatności: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    if payment.provider != provider.name() || payment.amount != notification.amount {
+        log!(
+            "powiadomienie od {} nie pasuje do płatności {}",
+            provider.name(),
+            payment.id
+        );
+        return StatusCode::BAD_REQUEST;
+    }
+    let payment = match db.update_payment_status(
+        &payment.id,
+        notification.status,
+        notification.external_id.as_deref(),
+        Utc::now(),
+    ) {
+        Ok(updated) => updated.unwrap_or(payment),
+        Err(e) => {
+            log!("nie udało się zapisać płatności: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    match settle_order(&db, &payment) {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            log!("nie udało się oznaczyć zamówienia jako opłaconego: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[server]
+pub async fn confirm_payment_return(
+    provider: String,
+    params: BTreeMap<String, String>,
+) -> Result<Option<PaymentReturn>, ServerFnError> {
+    use crate::db::{PaymentRepository, use_db};
+    use chrono::Utc;
+
+    let payments = use_context::<gateway::Payments>()
+        .ok_or_else(|| ServerFnError::new("płatności nie są dostępne"))?;
+    let Some(provider) = payments.by_name(&provider) else {
+        return Ok(None);
+    };
+    let outcome = provider.handle_return(&params)?;
+    let db = use_db()?;
+    let Some(payment) = db.find_payment(&outcome.payment_id)? else {
+        return Ok(None);
+    };
+    let payment = match outcome.status {
+        Some(status) if payment.provider == provider.name() => db
+            .update_payment_status(&payment.id, status, None, Utc::now())?
+            .unwrap_or(payment),
+        _ => payment,
+    };
+    settle_order(&db, &payment)?;
+    Ok(Some(PaymentReturn {
+        order_id: payment.order_id,
+        amount: payment.amount,
+        status: payment.status,
+    }))
+}
+
+/// Page the customer lands on after leaving the provider's payment page.
+#[component]
+pub fn PaymentReturnPage() -> impl IntoView {
+    let params = use_params_map();
+    let query = use_query_map();
+    let payment = Resource::new(
+        move || {
+            let provider = params.read().get("provider").unwrap_or_default();
+            let query: BTreeMap<String, String> = query
+                .get()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect();
+            (provider, query)
+        },
+        |(provider, query)| confirm_payment_return(provider, query),
+    );
+
+    view! {
+        <Title text="Płatność - Meg Joni" />
+        <main>
+            <section class="payment-return">
+                <h2>"Płatność"</h2>
+                <Suspense fallback=|| view! { <p>"Sprawdzamy status płatności..."</p> }>
+                    {move || Suspend::new(async move {
+                        match payment.await {
+                            Ok(Some(payment)) => view! { <PaymentResult payment /> }.into_any(),
+                            Ok(None) => view! { <p>"Nie znaleźliśmy tej płatności."</p> }.into_any(),
+                            Err(_) => view! {
+                                <p>"Nie udało się sprawdzić płatności. Spróbuj ponownie za chwilę."</p>
+                            }
+                            .into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn PaymentResult(payment: PaymentReturn) -> impl IntoView {
+    let order = format!(
+        "Zamówienie nr {}, kwota {}.",
+        payment.order_id, payment.amount
+    );
+    let message = match payment.status {
+        PaymentStatus::Paid => view! {
+            <p class="payment-paid">"Dziękujemy! Płatność została przyjęta."</p>
+            <p>"Potwierdzenie wyślemy na podany adres e-mail."</p>
+        }
+        .into_any(),
+        PaymentStatus::Pending => view! {
+            <p>"Czekamy na potwierdzenie płatności od operatora. Może to potrwać kilka minut."</p>
+            <p><a href="" rel="external">"Odśwież status"</a></p>
+        }
+        .into_any(),
+        PaymentStatus::Failed => view! {
+            <p class="payment-failed">"Płatność nie powiodła się."</p>
+            <p>
+                "Zamówienie czeka na opłacenie. "
+                <a href="/contact">"Skontaktuj się z nami"</a>
+                ", a pomożemy dokończyć płatność."
+            </p>
+        }
+        .into_any(),
+    };
+
+    view! {
+        <p>{order}</p>
+        {message}
+    }
+}
+
+/// Bank details shown after placing an order paid by transfer.
+#[component]
+pub fn TransferDetails(instructions: TransferInstructions) -> impl IntoView {
+    view! {
+        <dl class="transfer-details">
+            <dt>"Odbiorca"</dt>
+            <dd>{instructions.recipient}</dd>
+            <dt>"Numer rachunku"</dt>
+            <dd>{instructions.account_number}</dd>
+            <dt>"Tytuł przelewu"</dt>
+            <dd>{instructions.title}</dd>
+            <dt>"Kwota"</dt>
+            <dd>{instructions.amount.to_string()}</dd>
+        </dl>
+    }
+}
@@ -0,0 +1,169 @@
+//! Traditional bank transfer. The customer gets the account number and a transfer title
+//! that carries the payment id; incoming transfers are reported by the bank statement
+//! integration as JSON signed with HMAC-SHA256 in the `X-Signature` header. Refunds of
+//! such payments are sent by hand to an account number the customer gives us.
+
+use std::collections::BTreeMap;
+
+use axum::http::HeaderMap;
+use serde::Deserialize;
+
+use super::gateway::{
+    BoxFuture, PaymentError, PaymentNotification, PaymentProvider, PaymentRequest, RefundRequest,
+    ReturnOutcome, required_env, verify_hmac_sha256,
+};
+use super::{PaymentStart, PaymentStatus, TransferInstructions};
+use crate::catalog::Price;
+
+pub struct BankTransfer {
+    recipient: String,
+    account_number: String,
+    webhook_secret: String,
+}
+
+/// Body of a notification about a booked transfer.
+#[derive(Deserialize)]
+struct Notification {
+    payment_id: String,
+    /// Amount credited, in grosze.
+    amount: u32,
+    /// Bank's reference of the booked transfer.
+    #[serde(default)]
+    transaction_id: Option<String>,
+}
+
+impl BankTransfer {
+    /// Reads `MEGJONI_BANK_RECIPIENT`, `MEGJONI_BANK_ACCOUNT` and
+    /// `MEGJONI_BANK_WEBHOOK_SECRET`.
+    pub fn from_env() -> Result<Self, PaymentError> {
+        Ok(BankTransfer {
+            recipient: required_env("MEGJONI_BANK_RECIPIENT")?,
+            account_number: required_env("MEGJONI_BANK_ACCOUNT")?,
+            webhook_secret: required_env("MEGJONI_BANK_WEBHOOK_SECRET")?,
+        })
+    }
+}
+
+impl PaymentProvider for BankTransfer {
+    fn name(&self) -> &'static str {
+        "bank_transfer"
+    }
+
+    fn create_payment<'a>(
+        &'a self,
+        request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentStart, PaymentError>> {
+        let start = PaymentStart::BankTransfer(TransferInstructions {
+            recipient: self.recipient.clone(),
+            account_number: self.account_number.clone(),
+            title: format!(
+                "Zamówienie {}, płatność {}",
+                request.order_id, request.payment_id
+            ),
+            amount: request.amount,
+        });
+        Box::pin(async move { Ok(start) })
+    }
+
+    fn handle_return(
+        &self,
+        params: &BTreeMap<String, String>,
+    ) -> Result<ReturnOutcome, PaymentError> {
+        let payment_id = params
+            .get("payment")
+            .ok_or_else(|| PaymentError::Malformed("brak identyfikatora płatności".into()))?;
+        Ok(ReturnOutcome {
+            payment_id: payment_id.clone(),
+            status: None,
+        })
+    }
+
+    fn verify_webhook<'a>(
+        &'a self,
+        headers: &'a HeaderMap,
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<PaymentNotification, PaymentError>> {
+        Box::pin(async move {
+            let signature = headers
+                .get("x-signature")
+                .and_then(|value| value.to_str().ok())
+                .ok_or(PaymentError::InvalidSignature)?;
+            if !verify_hmac_sha256(&self.webhook_secret, body, signature) {
+                return Err(PaymentError::InvalidSignature);
+            }
+            let notification: Notification =
+                serde_json::from_slice(body).map_err(|e| PaymentError::Malformed(e.to_string()))?;
+            Ok(PaymentNotification {
+                payment_id: notification.payment_id,
+                status: PaymentStatus::Paid,
+                amount: Price(notification.amount),
+                external_id: notification.transaction_id,
+            })
+        })
+    }
+
+    fn refund<'a>(
+        &'a self,
+        _request: &'a RefundRequest,
+    ) -> BoxFuture<'a, Result<Option<String>, PaymentError>> {
+        Box::pin(async { Err(PaymentError::RefundUnsupported) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::payments::gateway::hmac_sha256_hex;
+
+    fn verify(body: &[u8], signature: Option<&str>) -> Result<PaymentNotification, PaymentError> {
+        let bank = BankTransfer {
+            recipient: "Meg & Joni".into(),
+            account_number: "PL61109010140000071219812874".into(),
+            webhook_secret: "sekret-banku".into(),
+        };
+        let mut headers = HeaderMap::new();
+        if let Some(signature) = signature {
+            headers.insert("x-signature", HeaderValue::from_str(signature).unwrap());
+        }
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(bank.verify_webhook(&headers, body))
+    }
+
+    #[test]
+    fn a_signed_transfer_is_paid() {
+        let body = br#"{"payment_id":"platnosc-1","amount":8900,"transaction_id":"WB-77"}"#;
+        let signature = hmac_sha256_hex("sekret-banku", body);
+        assert_eq!(
+            verify(body, Some(&signature)).unwrap(),
+            PaymentNotification {
+                payment_id: "platnosc-1".into(),
+                status: PaymentStatus::Paid,
+                amount: Price(8900),
+                external_id: Some("WB-77".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn an_unsigned_or_altered_transfer_is_refused() {
+        let body = br#"{"payment_id":"platnosc-1","amount":8900}"#;
+        let signature = hmac_sha256_hex("sekret-banku", body);
+        let altered = br#"{"payment_id":"platnosc-1","amount":89000}"#;
+        assert!(matches!(
+            verify(altered, Some(&signature)),
+            Err(PaymentError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify(body, Some(&hmac_sha256_hex("inny-sekret", body))),
+            Err(PaymentError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify(body, None),
+            Err(PaymentError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,238 @@
+//! The [`PaymentProvider`] trait and the [`Payments`] registry that picks a provider for
+//! each payment method.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha384};
+
+use super::bank_transfer::BankTransfer;
+use super::mock::MockProvider;
+use super::przelewy24::Przelewy24;
+use super::{PaymentStart, PaymentStatus};
+use crate::catalog::Price;
+use crate::checkout::PaymentMethod;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PaymentError {
+    #[error("nieprawidłowy podpis powiadomienia")]
+    InvalidSignature,
+    #[error("nieprawidłowe dane od operatora płatności: {0}")]
+    Malformed(String),
+    #[error("operator płatności odrzucił żądanie: {0}")]
+    Rejected(String),
+    #[error("błąd połączenia z operatorem płatności: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("brak konfiguracji płatności: {0}")]
+    Config(String),
+    #[error("tej płatności nie da się zwrócić przez operatora")]
+    RefundUnsupported,
+}
+
+/// What a provider needs to start collecting money for an order.
+#[derive(Clone, Debug)]
+pub struct PaymentRequest {
+    /// Our payment id, passed to the provider as its session id.
+    pub payment_id: String,
+    pub order_id: i64,
+    pub amount: Price,
+    pub method: PaymentMethod,
+    pub email: String,
+    pub description: String,
+    /// Where the customer's browser comes back after paying.
+    pub return_url: String,
+    /// Where the provider posts its signed notification.
+    pub notify_url: String,
+}
+
+/// What the customer's return from the provider tells us.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReturnOutcome {
+    pub payment_id: String,
+    /// Status the provider vouches for in the return URL itself. Most providers only
+    /// confirm payments through the webhook, so this is usually `None`.
+    pub status: Option<PaymentStatus>,
+}
+
+/// What a provider needs to send (part of) a collected payment back.
+#[derive(Clone, Debug)]
+pub struct RefundRequest {
+    /// Our refund id, so retries of the same refund can be recognised.
+    pub refund_id: i64,
+    /// The payment being refunded, as passed to the provider when it was created.
+    pub payment_id: String,
+    /// The provider's transaction id, from its notification.
+    pub external_id: Option<String>,
+    pub amount: Price,
+    pub description: String,
+}
+
+/// A verified notification from the provider.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentNotification {
+    pub payment_id: String,
+    pub status: PaymentStatus,
+    pub amount: Price,
+    /// The provider's transaction id.
+    pub external_id: Option<String>,
+}
+
+/// A payment gateway. Implementations must not trust anything in a return URL or webhook
+/// body they have not verified.
+pub trait PaymentProvider: Send + Sync {
+    /// Name used in URLs and stored with each payment.
+    fn name(&self) -> &'static str;
+
+    /// Registers the payment with the provider and tells the customer what to do next.
+    fn create_payment<'a>(
+        &'a self,
+        request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentStart, PaymentError>>;
+
+    /// Reads the query parameters the customer's browser came back with.
+    fn handle_return(
+        &self,
+        params: &BTreeMap<String, String>,
+    ) -> Result<ReturnOutcome, PaymentError>;
+
+    /// Checks the signature of a webhook call and returns what it reports.
+    fn verify_webhook<'a>(
+        &'a self,
+        headers: &'a HeaderMap,
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<PaymentNotification, PaymentError>>;
+
+    /// Sends `request.amount` back the way the payment came. Returns the provider's id
+    /// of the refund, if it gives one.
+    fn refund<'a>(
+        &'a self,
+        request: &'a RefundRequest,
+    ) -> BoxFuture<'a, Result<Option<String>, PaymentError>>;
+}
+
+/// The configured providers, shared with server functions and the webhook handler.
+#[derive(Clone)]
+pub struct Payments {
+    public_url: String,
+    online: Arc<dyn PaymentProvider>,
+    bank_transfer: Arc<dyn PaymentProvider>,
+}
+
+impl Payments {
+    /// Builds the providers from the environment. `MEGJONI_PAYMENTS=live` uses
+    /// Przelewy24 for online payments and BLIK and real bank transfer details;
+    /// `MEGJONI_PAYMENTS=mock` sends every payment to the local mock provider. The mock
+    /// marks orders paid without collecting anything, so only debug builds fall back to
+    /// it when the variable is unset; in a release build that is an error, as is any
+    /// other value.
+    pub fn from_env(default_public_url: &str) -> Result<Self, PaymentError> {
+        let public_url = std::env::var("MEGJONI_PUBLIC_URL")
+            .unwrap_or_else(|_| default_public_url.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let mode = std::env::var("MEGJONI_PAYMENTS")
+            .ok()
+            .or_else(|| cfg!(debug_assertions).then(|| "mock".to_string()));
+        match mode.as_deref() {
+            Some("live") => Ok(Payments {
+                public_url,
+                online: Arc::new(Przelewy24::from_env()?),
+                bank_transfer: Arc::new(BankTransfer::from_env()?),
+            }),
+            Some("mock") => {
+                let mock: Arc<dyn PaymentProvider> = Arc::new(MockProvider::from_env());
+                Ok(Payments {
+                    public_url,
+                    online: mock.clone(),
+                    bank_transfer: mock,
+                })
+            }
+            Some(other) => Err(PaymentError::Config(format!(
+                "nieznany tryb płatności MEGJONI_PAYMENTS={other}, użyj live albo mock"
+            ))),
+            None => Err(PaymentError::Config(
+                "ustaw MEGJONI_PAYMENTS na live albo mock".to_string(),
+            )),
+        }
+    }
+
+    pub fn for_method(&self, method: PaymentMethod) -> &Arc<dyn PaymentProvider> {
+        match method {
+            PaymentMethod::OnlineTransfer | PaymentMethod::Blik => &self.online,
+            PaymentMethod::BankTransfer => &self.bank_transfer,
+        }
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Arc<dyn PaymentProvider>> {
+        [&self.online, &self.bank_transfer]
+            .into_iter()
+            .find(|provider| provider.name() == name)
+    }
+
+    /// Address the shop is reachable at from outside, e.g. `https://megjoni.pl`; also
+    /// used for links in e-mails.
+    pub fn public_url(&self) -> &str {
+        &self.public_url
+    }
+
+    pub fn return_url(&self, provider: &str, payment_id: &str) -> String {
+        format!(
+            "{}/payments/return/{provider}?payment={payment_id}",
+            self.public_url
+        )
+    }
+
+    pub fn notify_url(&self, provider: &str) -> String {
+        format!("{}/payments/webhook/{provider}", self.public_url)
+    }
+}
+
+/// Reads a required setting, e.g. a provider's credentials.
+pub(crate) fn required_env(name: &str) -> Result<String, PaymentError> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| PaymentError::Config(format!("ustaw zmienną {name}")))
+}
+
+pub(crate) fn sha384_hex(data: &str) -> String {
+    hex::encode(Sha384::digest(data.as_bytes()))
+}
+
+/// Compares a SHA-384 signature given as hex in constant time.
+pub(crate) fn verify_sha384(data: &str, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature.trim()) else {
+        return false;
+    };
+    let expected = Sha384::digest(data.as_bytes());
+    expected.len() == signature.len()
+        && expected
+            .iter()
+            .zip(&signature)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub(crate) fn hmac_sha256_hex(secret: &str, data: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC przyjmuje klucz dowolnej długości");
+    mac.update(data);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Compares an HMAC-SHA256 signature given as hex in constant time.
+pub(crate) fn verify_hmac_sha256(secret: &str, data: &[u8], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature.trim()) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC przyjmuje klucz dowolnej długości");
+    mac.update(data);
+    mac.verify_slice(&signature).is_ok()
+}
@@ -0,0 +1,182 @@
+//! Local stand-in for a payment gateway, so the whole order lifecycle can be exercised
+//! offline. It "collects" every payment at once and reports the configured outcome
+//! through the same signed webhook path a real provider would use.
+//!
+//! `MEGJONI_MOCK_PAYMENT_OUTCOME` is `success` (default) or `failure`;
+//! `MEGJONI_MOCK_PAYMENT_DELAY_SECS` holds the notification back, as a slow bank would.
+//! Without a delay the outcome is also carried, signed, in the return URL. Refunds get
+//! the same outcome, at once.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use super::gateway::{
+    BoxFuture, PaymentError, PaymentNotification, PaymentProvider, PaymentRequest, RefundRequest,
+    ReturnOutcome, hmac_sha256_hex, verify_hmac_sha256,
+};
+use super::{PaymentStart, PaymentStatus};
+use crate::catalog::Price;
+
+pub struct MockProvider {
+    outcome: PaymentStatus,
+    delay: Duration,
+    /// Signs the mock's own notifications; a fresh one every time the server starts.
+    secret: String,
+    client: reqwest::Client,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Notification {
+    payment_id: String,
+    status: PaymentStatus,
+    amount: u32,
+}
+
+fn status_param(status: PaymentStatus) -> &'static str {
+    match status {
+        PaymentStatus::Pending => "pending",
+        PaymentStatus::Paid => "paid",
+        PaymentStatus::Failed => "failed",
+    }
+}
+
+/// What the signature in the return URL covers.
+fn return_message(payment_id: &str, status: &str) -> String {
+    format!("{payment_id}:{status}")
+}
+
+impl MockProvider {
+    pub fn from_env() -> Self {
+        let outcome = match std::env::var("MEGJONI_MOCK_PAYMENT_OUTCOME").as_deref() {
+            Ok("failure") => PaymentStatus::Failed,
+            _ => PaymentStatus::Paid,
+        };
+        let delay = std::env::var("MEGJONI_MOCK_PAYMENT_DELAY_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(0);
+        MockProvider {
+            outcome,
+            delay: Duration::from_secs(delay),
+            secret: uuid::Uuid::new_v4().to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn return_signature(&self, payment_id: &str, status: &str) -> String {
+        hmac_sha256_hex(&self.secret, return_message(payment_id, status).as_bytes())
+    }
+}
+
+impl PaymentProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn create_payment<'a>(
+        &'a self,
+        request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentStart, PaymentError>> {
+        let body = serde_json::to_vec(&Notification {
+            payment_id: request.payment_id.clone(),
+            status: self.outcome,
+            amount: request.amount.grosze(),
+        })
+        .expect("powiadomienie daje się zapisać jako JSON");
+        let signature = hmac_sha256_hex(&self.secret, &body);
+        let (client, url, delay) = (self.client.clone(), request.notify_url.clone(), self.delay);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let sent = client
+                .post(&url)
+                .header("content-type", "application/json")
+                .header("x-mock-signature", signature)
+                .body(body)
+                .send()
+                .await;
+            if let Err(e) = sent {
+                leptos::logging::log!("próbna płatność: nie udało się wysłać powiadomienia: {e}");
+            }
+        });
+
+        let url = if self.delay.is_zero() {
+            let status = status_param(self.outcome);
+            format!(
+                "{}&status={status}&signature={}",
+                request.return_url,
+                self.return_signature(&request.payment_id, status)
+            )
+        } else {
+            request.return_url.clone()
+        };
+        Box::pin(async move { Ok(PaymentStart::Redirect { url }) })
+    }
+
+    fn handle_return(
+        &self,
+        params: &BTreeMap<String, String>,
+    ) -> Result<ReturnOutcome, PaymentError> {
+        let payment_id = params
+            .get("payment")
+            .ok_or_else(|| PaymentError::Malformed("brak identyfikatora płatności".into()))?;
+        let status = match (params.get("status"), params.get("signature")) {
+            (Some(status), Some(signature)) => {
+                let message = return_message(payment_id, status);
+                if !verify_hmac_sha256(&self.secret, message.as_bytes(), signature) {
+                    return Err(PaymentError::InvalidSignature);
+                }
+                match status.as_str() {
+                    "paid" => Some(PaymentStatus::Paid),
+                    "failed" => Some(PaymentStatus::Failed),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        Ok(ReturnOutcome {
+            payment_id: payment_id.clone(),
+            status,
+        })
+    }
+
+    fn verify_webhook<'a>(
+        &'a self,
+        headers: &'a HeaderMap,
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<PaymentNotification, PaymentError>> {
+        Box::pin(async move {
+            let signature = headers
+                .get("x-mock-signature")
+                .and_then(|value| value.to_str().ok())
+                .ok_or(PaymentError::InvalidSignature)?;
+            if !verify_hmac_sha256(&self.secret, body, signature) {
+                return Err(PaymentError::InvalidSignature);
+            }
+            let notification: Notification =
+                serde_json::from_slice(body).map_err(|e| PaymentError::Malformed(e.to_string()))?;
+            Ok(PaymentNotification {
+                payment_id: notification.payment_id,
+                status: notification.status,
+                amount: Price(notification.amount),
+                external_id: None,
+            })
+        })
+    }
+
+    fn refund<'a>(
+        &'a self,
+        request: &'a RefundRequest,
+    ) -> BoxFuture<'a, Result<Option<String>, PaymentError>> {
+        Box::pin(async move {
+            match self.outcome {
+                PaymentStatus::Failed => Err(PaymentError::Rejected(
+                    "próbna płatność: zwrot odrzucony".into(),
+                )),
+                _ => Ok(Some(format!("mock-refund-{}", request.refund_id))),
+            }
+        })
+    }
+}
@@ -0,0 +1,481 @@
+//! Przelewy24 (REST API v1), used for fast online transfers and BLIK.
+//!
+//! The transaction is registered server-side and the customer is redirected to the
+//! returned token. Przelewy24 notifies `urlStatus` only about completed payments; each
+//! notification is checked against its SHA-384 signature, our merchant and POS ids and
+//! the currency, and then confirmed with the `transaction/verify` call, without which the money is not released. Refunds go
+//! through `transaction/refund` against the Przelewy24 order id from that notification.
+
+use std::collections::BTreeMap;
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use super::gateway::{
+    BoxFuture, PaymentError, PaymentNotification, PaymentProvider, PaymentRequest, RefundRequest,
+    ReturnOutcome, required_env, sha384_hex, verify_sha384,
+};
+use super::{PaymentStart, PaymentStatus};
+use crate::catalog::Price;
+use crate::checkout::PaymentMethod;
+
+const LIVE_URL: &str = "https://secure.przelewy24.pl";
+const SANDBOX_URL: &str = "https://sandbox.przelewy24.pl";
+/// Payment channel that offers only BLIK.
+const CHANNEL_BLIK: u32 = 8192;
+
+pub struct Przelewy24 {
+    merchant_id: u32,
+    pos_id: u32,
+    /// Key used in transaction signatures ("CRC" in the merchant panel).
+    crc: String,
+    /// REST API key ("klucz do raportów").
+    api_key: String,
+    base_url: &'static str,
+    client: reqwest::Client,
+}
+
+/// Fields signed when registering a transaction, in the order Przelewy24 expects.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterSign<'a> {
+    session_id: &'a str,
+    merchant_id: u32,
+    amount: u32,
+    currency: &'a str,
+    crc: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterRequest<'a> {
+    merchant_id: u32,
+    pos_id: u32,
+    session_id: &'a str,
+    amount: u32,
+    currency: &'a str,
+    description: &'a str,
+    email: &'a str,
+    country: &'a str,
+    language: &'a str,
+    url_return: &'a str,
+    url_status: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<u32>,
+    sign: String,
+}
+
+#[derive(Deserialize)]
+struct RegisterResponse {
+    data: RegisterData,
+}
+
+#[derive(Deserialize)]
+struct RegisterData {
+    token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    merchant_id: u32,
+    pos_id: u32,
+    session_id: String,
+    amount: u32,
+    origin_amount: u32,
+    currency: String,
+    order_id: u64,
+    method_id: u32,
+    statement: String,
+    sign: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationSign<'a> {
+    merchant_id: u32,
+    pos_id: u32,
+    session_id: &'a str,
+    amount: u32,
+    origin_amount: u32,
+    currency: &'a str,
+    order_id: u64,
+    method_id: u32,
+    statement: &'a str,
+    crc: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifySign<'a> {
+    session_id: &'a str,
+    order_id: u64,
+    amount: u32,
+    currency: &'a str,
+    crc: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyRequest<'a> {
+    merchant_id: u32,
+    pos_id: u32,
+    session_id: &'a str,
+    amount: u32,
+    currency: &'a str,
+    order_id: u64,
+    sign: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefundBody<'a> {
+    request_id: &'a str,
+    refunds: [RefundItem<'a>; 1],
+    /// Unique per refund, at most 35 characters.
+    refunds_uuid: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefundItem<'a> {
+    order_id: u64,
+    session_id: &'a str,
+    amount: u32,
+    description: &'a str,
+}
+
+#[derive(Deserialize)]
+struct RefundResponse {
+    data: Vec<RefundResult>,
+}
+
+#[derive(Deserialize)]
+struct RefundResult {
+    status: bool,
+    #[serde(default)]
+    message: String,
+}
+
+/// Przelewy24 signs the compact JSON of the fields, with slashes and Polish letters left
+/// unescaped, which is exactly what serde_json produces.
+fn signed_json<T: Serialize>(fields: &T) -> String {
+    serde_json::to_string(fields).expect("pola podpisu dają się zapisać jako JSON")
+}
+
+fn sign<T: Serialize>(fields: &T) -> String {
+    sha384_hex(&signed_json(fields))
+}
+
+impl Przelewy24 {
+    /// Reads `MEGJONI_P24_MERCHANT_ID`, `MEGJONI_P24_POS_ID` (defaults to the merchant
+    /// id), `MEGJONI_P24_CRC` and `MEGJONI_P24_API_KEY`; `MEGJONI_P24_SANDBOX=1` switches
+    /// to the sandbox.
+    pub fn from_env() -> Result<Self, PaymentError> {
+        let number = |name: &str, value: String| {
+            value
+                .parse::<u32>()
+                .map_err(|_| PaymentError::Config(format!("{name} musi być liczbą")))
+        };
+        let merchant_id = number(
+            "MEGJONI_P24_MERCHANT_ID",
+            required_env("MEGJONI_P24_MERCHANT_ID")?,
+        )?;
+        let pos_id = match std::env::var("MEGJONI_P24_POS_ID") {
+            Ok(pos_id) => number("MEGJONI_P24_POS_ID", pos_id)?,
+            Err(_) => merchant_id,
+        };
+        let sandbox = std::env::var("MEGJONI_P24_SANDBOX").is_ok_and(|v| v == "1");
+        Ok(Przelewy24 {
+            merchant_id,
+            pos_id,
+            crc: required_env("MEGJONI_P24_CRC")?,
+            api_key: required_env("MEGJONI_P24_API_KEY")?,
+            base_url: if sandbox { SANDBOX_URL } else { LIVE_URL },
+            client: reqwest::Client::new(),
+        })
+    }
+
+    async fn register(&self, request: &PaymentRequest) -> Result<PaymentStart, PaymentError> {
+        let amount = request.amount.grosze();
+        let body = RegisterRequest {
+            merchant_id: self.merchant_id,
+            pos_id: self.pos_id,
+            session_id: &request.payment_id,
+            amount,
+            currency: "PLN",
+            description: &request.description,
+            email: &request.email,
+            country: "PL",
+            language: "pl",
+            url_return: &request.return_url,
+            url_status: &request.notify_url,
+            channel: (request.method == PaymentMethod::Blik).then_some(CHANNEL_BLIK),
+            sign: sign(&RegisterSign {
+                session_id: &request.payment_id,
+                merchant_id: self.merchant_id,
+                amount,
+                currency: "PLN",
+                crc: &self.crc,
+            }),
+        };
+        let response = self
+            .client
+            .post(format!("{}/api/v1/transaction/register", self.base_url))
+            .basic_auth(self.pos_id, Some(&self.api_key))
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(PaymentError::Rejected(format!("{status}: {text}")));
+        }
+        let registered: RegisterResponse = response.json().await?;
+        Ok(PaymentStart::Redirect {
+            url: format!("{}/trnRequest/{}", self.base_url, registered.data.token),
+        })
+    }
+
+    /// Parses a notification and checks that it is signed with our key and is about a
+    /// payment in złoty to our own shop.
+    fn check_notification(&self, body: &[u8]) -> Result<Notification, PaymentError> {
+        let notification: Notification =
+            serde_json::from_slice(body).map_err(|e| PaymentError::Malformed(e.to_string()))?;
+        let signed = signed_json(&NotificationSign {
+            merchant_id: notification.merchant_id,
+            pos_id: notification.pos_id,
+            session_id: &notification.session_id,
+            amount: notification.amount,
+            origin_amount: notification.origin_amount,
+            currency: &notification.currency,
+            order_id: notification.order_id,
+            method_id: notification.method_id,
+            statement: &notification.statement,
+            crc: &self.crc,
+        });
+        if !verify_sha384(&signed, &notification.sign) {
+            return Err(PaymentError::InvalidSignature);
+        }
+        if notification.merchant_id != self.merchant_id || notification.pos_id != self.pos_id {
+            return Err(PaymentError::Malformed(
+                "powiadomienie dotyczy innego sklepu".into(),
+            ));
+        }
+        if notification.currency != "PLN" {
+            return Err(PaymentError::Malformed(format!(
+                "płatność w walucie {}",
+                notification.currency
+            )));
+        }
+        Ok(notification)
+    }
+
+    async fn verify(&self, body: &[u8]) -> Result<PaymentNotification, PaymentError> {
+        let notification = self.check_notification(body)?;
+        let verify = VerifyRequest {
+            merchant_id: self.merchant_id,
+            pos_id: self.pos_id,
+            session_id: &notification.session_id,
+            amount: notification.amount,
+            currency: &notification.currency,
+            order_id: notification.order_id,
+            sign: sign(&VerifySign {
+                session_id: &notification.session_id,
+                order_id: notification.order_id,
+                amount: notification.amount,
+                currency: &notification.currency,
+                crc: &self.crc,
+            }),
+        };
+        let response = self
+            .client
+            .put(format!("{}/api/v1/transaction/verify", self.base_url))
+            .basic_auth(self.pos_id, Some(&self.api_key))
+            .json(&verify)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(PaymentError::Rejected(format!("{status}: {text}")));
+        }
+
+        Ok(PaymentNotification {
+            payment_id: notification.session_id,
+            status: PaymentStatus::Paid,
+            amount: Price(notification.amount),
+            external_id: Some(notification.order_id.to_string()),
+        })
+    }
+
+    async fn send_refund(&self, request: &RefundRequest) -> Result<Option<String>, PaymentError> {
+        let order_id = request
+            .external_id
+            .as_deref()
+            .and_then(|id| id.parse::<u64>().ok())
+            .ok_or_else(|| PaymentError::Malformed("brak numeru transakcji Przelewy24".into()))?;
+        // Ten sam zwrot ponawiany po błędzie ma ten sam identyfikator, więc nie wyjdzie dwa razy.
+        let uuid = format!("megjoni-zwrot-{}", request.refund_id);
+        let body = RefundBody {
+            request_id: &uuid,
+            refunds: [RefundItem {
+                order_id,
+                session_id: &request.payment_id,
+                amount: request.amount.grosze(),
+                description: &request.description,
+            }],
+            refunds_uuid: &uuid,
+        };
+        let response = self
+            .client
+            .post(format!("{}/api/v1/transaction/refund", self.base_url))
+            .basic_auth(self.pos_id, Some(&self.api_key))
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(PaymentError::Rejected(format!("{status}: {text}")));
+        }
+        let refunded: RefundResponse = response.json().await?;
+        match refunded.data.into_iter().next() {
+            Some(result) if result.status => Ok(Some(uuid)),
+            Some(result) => Err(PaymentError::Rejected(result.message)),
+            None => Err(PaymentError::Malformed("pusta odpowiedź na zwrot".into())),
+        }
+    }
+}
+
+impl PaymentProvider for Przelewy24 {
+    fn name(&self) -> &'static str {
+        "przelewy24"
+    }
+
+    fn create_payment<'a>(
+        &'a self,
+        request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentStart, PaymentError>> {
+        Box::pin(self.register(request))
+    }
+
+    fn handle_return(
+        &self,
+        params: &BTreeMap<String, String>,
+    ) -> Result<ReturnOutcome, PaymentError> {
+        // Powrót z Przelewy24 niczego nie potwierdza; status przychodzi tylko w webhooku.
+        let payment_id = params
+            .get("payment")
+            .ok_or_else(|| PaymentError::Malformed("brak identyfikatora płatności".into()))?;
+        Ok(ReturnOutcome {
+            payment_id: payment_id.clone(),
+            status: None,
+        })
+    }
+
+    fn verify_webhook<'a>(
+        &'a self,
+        _headers: &'a HeaderMap,
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<PaymentNotification, PaymentError>> {
+        Box::pin(self.verify(body))
+    }
+
+    fn refund<'a>(
+        &'a self,
+        request: &'a RefundRequest,
+    ) -> BoxFuture<'a, Result<Option<String>, PaymentError>> {
+        Box::pin(self.send_refund(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn p24() -> Przelewy24 {
+        Przelewy24 {
+            merchant_id: 11111,
+            pos_id: 11111,
+            crc: "tajny-crc".into(),
+            api_key: "klucz".into(),
+            base_url: SANDBOX_URL,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// A notification as Przelewy24 would send it, signed with `crc`.
+    fn notification(pos_id: u32, currency: &str, crc: &str) -> serde_json::Value {
+        let sign = sign(&NotificationSign {
+            merchant_id: 11111,
+            pos_id,
+            session_id: "platnosc-1",
+            amount: 8900,
+            origin_amount: 8900,
+            currency,
+            order_id: 31337,
+            method_id: 25,
+            statement: "p24-A1-B2-C3",
+            crc,
+        });
+        json!({
+            "merchantId": 11111,
+            "posId": pos_id,
+            "sessionId": "platnosc-1",
+            "amount": 8900,
+            "originAmount": 8900,
+            "currency": currency,
+            "orderId": 31337,
+            "methodId": 25,
+            "statement": "p24-A1-B2-C3",
+            "sign": sign,
+        })
+    }
+
+    fn check(body: &serde_json::Value) -> Result<Notification, PaymentError> {
+        p24().check_notification(body.to_string().as_bytes())
+    }
+
+    #[test]
+    fn a_signed_notification_is_accepted() {
+        let checked = check(&notification(11111, "PLN", "tajny-crc")).unwrap();
+        assert_eq!(checked.session_id, "platnosc-1");
+        assert_eq!(checked.amount, 8900);
+        assert_eq!(checked.order_id, 31337);
+    }
+
+    #[test]
+    fn a_tampered_notification_is_refused() {
+        let mut body = notification(11111, "PLN", "tajny-crc");
+        body["amount"] = json!(1);
+        assert!(matches!(check(&body), Err(PaymentError::InvalidSignature)));
+
+        let forged = notification(11111, "PLN", "inny-crc");
+        assert!(matches!(
+            check(&forged),
+            Err(PaymentError::InvalidSignature)
+        ));
+
+        let mut garbage = notification(11111, "PLN", "tajny-crc");
+        garbage["sign"] = json!("nie-hex");
+        assert!(matches!(
+            check(&garbage),
+            Err(PaymentError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn other_shops_and_currencies_are_refused() {
+        assert!(matches!(
+            check(&notification(22222, "PLN", "tajny-crc")),
+            Err(PaymentError::Malformed(_))
+        ));
+        assert!(matches!(
+            check(&notification(11111, "EUR", "tajny-crc")),
+            Err(PaymentError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::cart::AddToCartButton;
+use crate::catalog::{PLACEHOLDER_IMAGE, PriceTag, Product, ProductStatus, get_product};
+use leptos::prelude::*;
+use leptos_meta::{Meta, Title};
+use leptos_router::hooks::use_params_map;
+
+/// Renders `/product/:slug`, or a 404 page when no product has that slug.
+#[component]
+pub fn ProductPage() -> impl IntoView {
+    let params = use_params_map();
+    let slug = move || params.read().get("slug").unwrap_or_default();
+    let product = Resource::new(slug, get_product);
+
+    view! {
+        <main>
+            <Suspense fallback=|| view! { <p>"Ładowanie produktu..."</p> }>
+                {move || Suspend::new(async move {
+                    match product.await {
+                        Ok(Some(product)) => view! { <ProductDetails product /> }.into_any(),
+                        Ok(None) => view! { <ProductNotFound /> }.into_any(),
+                        Err(_) => view! {
+                            <p>"Nie udało się wczytać produktu. Spróbuj ponownie później."</p>
+                        }
+                        .into_any(),
+                    }
+                })}
+            </Suspense>
+        </main>
+    }
+}
+
+#[component]
+fn ProductDetails(product: Product) -> impl IntoView {
+    let available = product.status == ProductStatus::Available;
+    let description = product.description.clone();
+
+    view! {
+        <Title text=format!("{} - Meg Joni", product.title) />
+        <Meta name="description" content=description.clone() />
+
+        <article class="product-page">
+            <ProductGallery images=product.images.clone() title=product.title.clone() />
+
+            <div class="product-details">
+                <h2>{product.title.clone()}</h2>
+                <PriceTag price=product.price original_price=product.original_price />
+
+                <dl class="product-attributes">
+                    <dt>"Rozmiar"</dt>
+                    <dd>{product.size.clone()}</dd>
+                    <dt>"Stan"</dt>
+                    <dd>{product.condition.label()}</dd>
+                    {product.brand.clone().map(|brand| view! {
+                        <dt>"Marka"</dt>
+                        <dd>{brand}</dd>
+                    })}
+                    {product.color.clone().map(|color| view! {
+                        <dt>"Kolor"</dt>
+                        <dd>{color}</dd>
+                    })}
+                    {product.material.clone().map(|material| view! {
+                        <dt>"Materiał"</dt>
+                        <dd>{material}</dd>
+                    })}
+                    <dt>"Kategoria"</dt>
+                    <dd>{product.category.label()}</dd>
+                </dl>
+
+                {(!available).then(|| view! {
+                    <p class="product-status">{product.status.label()}</p>
+                })}
+                <AddToCartButton product=product.clone() available />
+
+                {(!description.is_empty()).then(|| view! {
+                    <section class="product-description">
+                        <h3>"Opis"</h3>
+                        <p>{description}</p>
+                    </section>
+                })}
+
+                {(!product.measurements.is_empty()).then(|| view! {
+                    <section class="product-measurements">
+                        <h3>"Wymiary"</h3>
+                        <table>
+                            <tbody>
+                                {product.measurements.into_iter().map(|measurement| view! {
+                                    <tr>
+                                        <th scope="row">{measurement.name}</th>
+                                        <td>{format!("{} cm", measurement.cm)}</td>
+                                    </tr>
+                                }).collect_view()}
+                            </tbody>
+                        </table>
+                    </section>
+                })}
+            </div>
+        </article>
+    }
+}
+
+/// Large photo with a row of thumbnails that swap it.
+#[component]
+fn ProductGallery(images: Vec<String>, title: String) -> impl IntoView {
+    let images = if images.is_empty() {
+        vec![PLACEHOLDER_IMAGE.to_string()]
+    } else {
+        images
+    };
+    let selected = RwSignal::new(images[0].clone());
+    let thumbnails = (images.len() > 1).then(|| {
+        let title = title.clone();
+        view! {
+            <ul class="product-gallery-thumbs">
+                {images.into_iter().enumerate().map(|(index, src)| {
+                    let is_selected = {
+                        let src = src.clone();
+                        move || selected.get() == src
+                    };
+                    let select = {
+                        let src = src.clone();
+                        move |_| selected.set(src.clone())
+                    };
+                    let alt = format!("{} - zdjęcie {}", title, index + 1);
+                    view! {
+                        <li>
+                            <button type="button" class:selected=is_selected on:click=select>
+                                <img src=src alt=alt width="75" height="100" />
+                            </button>
+                        </li>
+                    }
+                }).collect_view()}
+            </ul>
+        }
+    });
+
+    view! {
+        <div class="product-gallery">
+            <figure class="product-gallery-main">
+                <img src=move || selected.get() alt=title width="600" height="800" />
+            </figure>
+            {thumbnails}
+        </div>
+    }
+}
+
+#[component]
+fn ProductNotFound() -> impl IntoView {
+    #[cfg(feature = "ssr")]
+    if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+        response.set_status(axum::http::StatusCode::NOT_FOUND);
+    }
+
+    view! {
+        <Title text="Nie znaleziono produktu - Meg Joni" />
+        <section class="product-not-found">
+            <h2>"Nie znaleziono produktu"</h2>
+            <p>"Ten produkt nie istnieje albo został usunięty z oferty."</p>
+            <a href="/woman">
+                <button>"Wróć do sklepu"</button>
+            </a>
+        </section>
+    }
+}
@@ -0,0 +1,956 @@
+//! Giving money back for paid orders. Staff refund chosen items and part or all of the
+//! delivery cost from the order page, usually once a return has been inspected, in one of
+//! three ways: through the payment provider the customer paid with, by a bank transfer
+//! to an account number the customer gives us, or as store credit on their account.
+//!
+//! Every refund is recorded against its order, so the order's refunded amount never
+//! exceeds what was paid. Once the whole total is back with the customer the order
+//! becomes refunded. `/admin/refunds` lists all refunds for bookkeeping and lets staff
+//! confirm bank transfers they sent by hand (see [`admin`]).
+//!
+//! Only owners may give money back (see [`crate::staff::Permission::IssueRefunds`]).
+
+use crate::catalog::Price;
+use crate::orders::OrderStatus;
+use crate::returns::{ReturnRequest, ReturnStatus};
+use crate::staff::Permission;
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod admin;
+
+/// Days from receiving returned items within which the customer gets their money back.
+pub const REFUND_DAYS: i64 = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundMethod {
+    /// Back through the payment provider, to the card or account the order was paid from.
+    OriginalPayment,
+    /// A transfer we send by hand to the account number the customer gave us.
+    BankTransfer,
+    /// Credit on the customer's account in the shop.
+    StoreCredit,
+}
+
+impl RefundMethod {
+    pub const ALL: [RefundMethod; 3] = [
+        RefundMethod::OriginalPayment,
+        RefundMethod::BankTransfer,
+        RefundMethod::StoreCredit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RefundMethod::OriginalPayment => "Tą samą drogą, którą zapłacono",
+            RefundMethod::BankTransfer => "Przelewem na numer konta klienta",
+            RefundMethod::StoreCredit => "Środki w sklepie",
+        }
+    }
+
+    /// The value used in forms, e.g. `store_credit`.
+    pub fn param(self) -> &'static str {
+        match self {
+            RefundMethod::OriginalPayment => "original_payment",
+            RefundMethod::BankTransfer => "bank_transfer",
+            RefundMethod::StoreCredit => "store_credit",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundStatus {
+    /// Recorded; a bank transfer waits here until staff confirm they sent it.
+    #[default]
+    Pending,
+    Completed,
+    /// The provider refused it or staff cancelled it; its items can be refunded again.
+    Failed,
+}
+
+impl RefundStatus {
+    pub const ALL: [RefundStatus; 3] = [
+        RefundStatus::Pending,
+        RefundStatus::Completed,
+        RefundStatus::Failed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RefundStatus::Pending => "Do wysłania",
+            RefundStatus::Completed => "Zwrócony",
+            RefundStatus::Failed => "Nieudany",
+        }
+    }
+
+    /// The value used in links and forms, e.g. `completed`.
+    pub fn param(self) -> &'static str {
+        match self {
+            RefundStatus::Pending => "pending",
+            RefundStatus::Completed => "completed",
+            RefundStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.param() == param)
+    }
+}
+
+/// An item paid back, with the title and price from the order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RefundLine {
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Refund {
+    pub id: i64,
+    pub order_id: i64,
+    /// The return of items this refund pays for, if any.
+    pub return_id: Option<i64>,
+    pub method: RefundMethod,
+    pub status: RefundStatus,
+    /// Items and delivery together.
+    pub amount: Price,
+    /// The part of `amount` that pays back delivery.
+    pub shipping: Price,
+    pub lines: Vec<RefundLine>,
+    /// The payment sent back, for refunds to the original payment method.
+    pub payment_id: Option<String>,
+    /// The customer's account, for refunds by bank transfer.
+    pub iban: Option<String>,
+    /// The provider's id of the refund.
+    pub external_id: Option<String>,
+    /// Why the refund failed, if it did.
+    pub note: String,
+    /// Who issued it, as in the order history, e.g. `staff:ania@megjoni.pl`.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// One change of a customer's store credit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoreCreditEntry {
+    /// In grosze; positive when credit was added, negative when it was spent.
+    pub amount: i64,
+    /// The refund that added it, if any.
+    pub refund_id: Option<i64>,
+    /// The order paid with it, or cancelled before payment and given it back.
+    pub order_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An order's money as staff see it when refunding.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderRefunds {
+    pub order_id: i64,
+    pub status: OrderStatus,
+    pub total: Price,
+    pub shipping: Price,
+    pub lines: Vec<RefundLine>,
+    /// Whether the order has ever been paid.
+    pub paid: bool,
+    /// Provider of the payment refunds to the original payment method go through.
+    pub paid_with: Option<String>,
+    /// Whether the order belongs to an account, which store credit needs.
+    pub has_account: bool,
+    /// Oldest first.
+    pub refunds: Vec<Refund>,
+    /// Returns of the order's items, oldest first.
+    pub returns: Vec<ReturnRequest>,
+}
+
+impl OrderRefunds {
+    /// Refunds that are done or on their way.
+    fn counted(&self) -> impl Iterator<Item = &Refund> {
+        self.refunds
+            .iter()
+            .filter(|refund| refund.status != RefundStatus::Failed)
+    }
+
+    pub fn is_refunded(&self, product_id: u32) -> bool {
+        self.counted()
+            .flat_map(|refund| &refund.lines)
+            .any(|line| line.product_id == product_id)
+    }
+
+    /// What of the delivery cost has not been refunded yet.
+    pub fn shipping_left(&self) -> Price {
+        let refunded: u32 = self.counted().map(|refund| refund.shipping.grosze()).sum();
+        Price(self.shipping.grosze().saturating_sub(refunded))
+    }
+
+    /// What of the total has not been refunded yet.
+    pub fn left(&self) -> Price {
+        let refunded: u32 = self.counted().map(|refund| refund.amount.grosze()).sum();
+        Price(self.total.grosze().saturating_sub(refunded))
+    }
+
+    /// Sum of completed refunds.
+    pub fn refunded(&self) -> Price {
+        Price(
+            self.refunds
+                .iter()
+                .filter(|refund| refund.status == RefundStatus::Completed)
+                .map(|refund| refund.amount.grosze())
+                .sum(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum RefundError {
+    #[error("nie ma takiego zamówienia albo zwrotu")]
+    NotFound,
+    #[error("zamówienie nie zostało opłacone, więc nie ma czego zwracać")]
+    NeverPaid,
+    #[error("zaznacz rzeczy do zwrotu albo podaj kwotę za dostawę")]
+    NothingSelected,
+    #[error("część zaznaczonych rzeczy została już zwrócona")]
+    AlreadyRefunded,
+    #[error("za dostawę można zwrócić jeszcze najwyżej {left}")]
+    ShippingTooHigh { left: Price },
+    #[error("nieprawidłowa kwota za dostawę")]
+    InvalidAmount,
+    #[error("nieprawidłowy numer konta")]
+    InvalidIban,
+    #[error("zamówienie złożono bez konta, więc nie ma gdzie zapisać środków")]
+    NoAccount,
+    #[error("zamówienie nie ma opłaconej płatności, którą można zwrócić")]
+    NoPayment,
+    #[error(
+        "tą samą drogą można zwrócić jeszcze najwyżej {left}; resztę zapłacono środkami w sklepie, więc zwróć ją jako środki"
+    )]
+    ExceedsPayment { left: Price },
+    #[error("rzeczy ze zwrotu trzeba najpierw przyjąć i sprawdzić")]
+    ReturnNotInspected,
+    #[error("operator płatności nie przyjął zwrotu: {0}")]
+    Provider(String),
+    #[error("ten zwrot został już rozliczony")]
+    NotPending,
+    #[error("błąd bazy danych: {0}")]
+    Storage(String),
+}
+
+/// The IBAN in its electronic form, e.g. `PL61109010140000071219812874`, if its check
+/// digits are right. Spaces and dashes are ignored, and a Polish account number may be
+/// given without the `PL` prefix.
+pub fn normalize_iban(iban: &str) -> Option<String> {
+    let mut iban: String = iban
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-'))
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if iban.len() == 26 && iban.chars().all(|c| c.is_ascii_digit()) {
+        iban.insert_str(0, "PL");
+    }
+    if !(15..=34).contains(&iban.len()) || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let (country, check) = (&iban[..2], &iban[2..4]);
+    if !country.chars().all(|c| c.is_ascii_alphabetic())
+        || !check.chars().all(|c| c.is_ascii_digit())
+        || (country == "PL" && iban.len() != 28)
+    {
+        return None;
+    }
+    // Mod 97 z numeru z pierwszymi czterema znakami przeniesionymi na koniec, gdzie
+    // litery liczą się jako 10-35; poprawny numer daje resztę 1.
+    let remainder = iban[4..]
+        .chars()
+        .chain(iban[..4].chars())
+        .try_fold(0u32, |remainder, c| {
+            let value = c.to_digit(36)?;
+            let shift = if value < 10 { 10 } else { 100 };
+            Some((remainder * shift + value) % 97)
+        })?;
+    (remainder == 1).then_some(iban)
+}
+
+/// The IBAN in groups of four, as printed on statements.
+pub fn format_iban(iban: &str) -> String {
+    iban.as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// An amount in złoty as typed by staff, e.g. `12,99` or `12.99`; empty means nothing.
+pub fn parse_amount(amount: &str) -> Option<Price> {
+    let amount = amount.trim();
+    if amount.is_empty() {
+        return Some(Price(0));
+    }
+    let (zloty, grosze) = amount.split_once([',', '.']).unwrap_or((amount, ""));
+    if grosze.len() > 2 || !grosze.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let zloty: u32 = zloty.parse().ok()?;
+    let grosze: u32 = format!("{grosze:0<2}").parse().ok()?;
+    zloty.checked_mul(100)?.checked_add(grosze).map(Price)
+}
+
+/// The amount as a form value, e.g. `12.99`.
+pub fn amount_value(amount: Price) -> String {
+    format!("{}.{:02}", amount.grosze() / 100, amount.grosze() % 100)
+}
+
+/// Issuing refunds and settling them, with what follows: the e-mail to the customer, the
+/// return marked as refunded and the order as refunded once nothing of it is left.
+#[cfg(feature = "ssr")]
+pub mod processing {
+    use chrono::Utc;
+    use leptos::logging::log;
+
+    use super::{Refund, RefundError, RefundLine, RefundMethod, RefundStatus, normalize_iban};
+    use crate::catalog::Price;
+    use crate::db::{
+        Db, NewRefund, OrderRepository, PaymentRepository, RefundRepository, ReturnRepository,
+    };
+    use crate::mail::{self, Email};
+    use crate::orders::{Actor, OrderStatus, lifecycle};
+    use crate::payments::PaymentStatus;
+    use crate::payments::gateway::{Payments, RefundRequest};
+    use crate::returns::ReturnStatus;
+
+    /// What staff chose to refund.
+    #[derive(Clone, Debug)]
+    pub struct RefundOrder {
+        pub order_id: i64,
+        pub return_id: Option<i64>,
+        pub method: RefundMethod,
+        pub product_ids: Vec<u32>,
+        pub shipping: Price,
+        /// Only for refunds by bank transfer.
+        pub iban: String,
+    }
+
+    fn storage(e: crate::db::Error) -> RefundError {
+        RefundError::Storage(e.to_string())
+    }
+
+    /// Records the refund and, unless it is a bank transfer staff send by hand, pays it
+    /// out at once. A refund the provider turns down stays recorded as failed.
+    pub async fn issue(
+        db: &Db,
+        payments: &Payments,
+        request: RefundOrder,
+        actor: &Actor,
+    ) -> Result<Refund, RefundError> {
+        let order = db
+            .find_order(request.order_id)
+            .map_err(storage)?
+            .ok_or(RefundError::NotFound)?;
+        let events = db.order_events(order.id).map_err(storage)?;
+        if !events.iter().any(|event| event.to == OrderStatus::Paid) {
+            return Err(RefundError::NeverPaid);
+        }
+        if request.product_ids.is_empty() && request.shipping == Price(0) {
+            return Err(RefundError::NothingSelected);
+        }
+        if let Some(return_id) = request.return_id {
+            let returned = db
+                .find_return(return_id)
+                .map_err(storage)?
+                .filter(|returned| returned.order_id == order.id)
+                .ok_or(RefundError::NotFound)?;
+            if returned.status != ReturnStatus::Inspected {
+                return Err(RefundError::ReturnNotInspected);
+            }
+        }
+
+        let earlier: Vec<Refund> = db
+            .refunds_for_order(order.id)
+            .map_err(storage)?
+            .into_iter()
+            .filter(|refund| refund.status != RefundStatus::Failed)
+            .collect();
+        let lines: Vec<RefundLine> = order
+            .lines
+            .iter()
+            .filter(|line| request.product_ids.contains(&line.product_id))
+            .filter(|line| {
+                !earlier
+                    .iter()
+                    .flat_map(|refund| &refund.lines)
+                    .any(|refunded| refunded.product_id == line.product_id)
+            })
+            .map(|line| RefundLine {
+                product_id: line.product_id,
+                title: line.title.clone(),
+                price: line.price,
+            })
+            .collect();
+        if lines.len() != request.product_ids.len() {
+            return Err(RefundError::AlreadyRefunded);
+        }
+        let shipping_refunded: u32 = earlier.iter().map(|refund| refund.shipping.grosze()).sum();
+        let shipping_left = Price(order.shipping.grosze().saturating_sub(shipping_refunded));
+        if request.shipping > shipping_left {
+            return Err(RefundError::ShippingTooHigh {
+                left: shipping_left,
+            });
+        }
+
+        let (payment, iban) = match request.method {
+            RefundMethod::OriginalPayment => {
+                let payment = db
+                    .payments_for_order(order.id)
+                    .map_err(storage)?
+                    .into_iter()
+                    .rev()
+                    .find(|payment| payment.status == PaymentStatus::Paid)
+                    .ok_or(RefundError::NoPayment)?;
+                // Przy zamówieniu opłaconym częściowo środkami w sklepie operator zwróci
+                // najwyżej tyle, ile przez niego przeszło.
+                let sent_back: u32 = earlier
+                    .iter()
+                    .filter(|refund| refund.payment_id.as_deref() == Some(payment.id.as_str()))
+                    .map(|refund| refund.amount.grosze())
+                    .sum();
+                let left = Price(payment.amount.grosze().saturating_sub(sent_back));
+                let amount: u32 = lines.iter().map(|line| line.price.grosze()).sum::<u32>()
+                    + request.shipping.grosze();
+                if amount > left.grosze() {
+                    return Err(RefundError::ExceedsPayment { left });
+                }
+                (Some(payment), None)
+            }
+            RefundMethod::BankTransfer => {
+                let iban = normalize_iban(&request.iban).ok_or(RefundError::InvalidIban)?;
+                (None, Some(iban))
+            }
+            RefundMethod::StoreCredit if order.customer_id.is_none() => {
+                return Err(RefundError::NoAccount);
+            }
+            RefundMethod::StoreCredit => (None, None),
+        };
+        let refund = db
+            .create_refund(
+                &NewRefund {
+                    order_id: order.id,
+                    return_id: request.return_id,
+                    method: request.method,
+                    lines,
+                    shipping: request.shipping,
+                    payment_id: payment.as_ref().map(|payment| payment.id.clone()),
+                    iban,
+                    created_by: actor.to_string(),
+                },
+                Utc::now(),
+            )
+            .map_err(storage)?
+            // Ktoś inny zwrócił w międzyczasie te same rzeczy.
+            .ok_or(RefundError::AlreadyRefunded)?;
+
+        match (request.method, payment) {
+            (RefundMethod::OriginalPayment, Some(payment)) => {
+                let Some(provider) = payments.by_name(&payment.provider) else {
+                    let note = format!("operator {} nie jest skonfigurowany", payment.provider);
+                    settle(db, &refund, RefundStatus::Failed, None, &note, actor)?;
+                    return Err(RefundError::NoPayment);
+                };
+                let sent = provider
+                    .refund(&RefundRequest {
+                        refund_id: refund.id,
+                        payment_id: payment.id,
+                        external_id: payment.external_id,
+                        amount: refund.amount,
+                        description: format!("Zwrot za zamówienie nr {}", order.id),
+                    })
+                    .await;
+                match sent {
+                    Ok(external_id) => settle(
+                        db,
+                        &refund,
+                        RefundStatus::Completed,
+                        external_id.as_deref(),
+                        "",
+                        actor,
+                    ),
+                    Err(e) => {
+                        let note = e.to_string();
+                        settle(db, &refund, RefundStatus::Failed, None, &note, actor)?;
+                        Err(RefundError::Provider(note))
+                    }
+                }
+            }
+            (RefundMethod::StoreCredit, _) => {
+                settle(db, &refund, RefundStatus::Completed, None, "", actor)
+            }
+            // Przelew wysyła ktoś z obsługi i potwierdza to w spisie zwrotów.
+            _ => Ok(refund),
+        }
+    }
+
+    /// Confirms that staff sent a pending bank transfer, or cancels it.
+    pub fn settle_transfer(
+        db: &Db,
+        refund_id: i64,
+        to: RefundStatus,
+        actor: &Actor,
+    ) -> Result<Refund, RefundError> {
+        let refund = db
+            .find_refund(refund_id)
+            .map_err(storage)?
+            .ok_or(RefundError::NotFound)?;
+        if refund.method != RefundMethod::BankTransfer
+            || refund.status != RefundStatus::Pending
+            || to == RefundStatus::Pending
+        {
+            return Err(RefundError::NotPending);
+        }
+        let note = match to {
+            RefundStatus::Failed => "anulowany",
+            _ => "",
+        };
+        settle(db, &refund, to, None, note, actor)
+    }
+
+    fn settle(
+        db: &Db,
+        refund: &Refund,
+        to: RefundStatus,
+        external_id: Option<&str>,
+        note: &str,
+        actor: &Actor,
+    ) -> Result<Refund, RefundError> {
+        let refund = db
+            .settle_refund(refund.id, to, external_id, note, Utc::now())
+            .map_err(storage)?
+            .ok_or(RefundError::NotPending)?;
+        if to == RefundStatus::Completed {
+            completed(db, &refund, actor);
+        }
+        Ok(refund)
+    }
+
+    /// Tells the customer and moves the return and the order on. The money is already
+    /// back, so failures here are only logged.
+    fn completed(db: &Db, refund: &Refund, actor: &Actor) {
+        let order = match db.find_order(refund.order_id) {
+            Ok(Some(order)) => order,
+            Ok(None) => return,
+            Err(e) => {
+                log!("nie udało się wczytać zamówienia {}: {e}", refund.order_id);
+                return;
+            }
+        };
+        // Zamówienia zanonimizowane na prośbę klienta nie mają już adresu e-mail.
+        if !order.email.is_empty() {
+            let email = Email::RefundIssued {
+                order: order.clone(),
+                amount: refund.amount,
+                method: refund.method,
+            };
+            if let Err(e) = mail::queue(db, &order.email, &email) {
+                log!(
+                    "nie udało się zakolejkować e-maila o zwrocie {}: {e}",
+                    refund.id
+                );
+            }
+        }
+        if let Some(return_id) = refund.return_id {
+            let inspected = db.find_return(return_id).is_ok_and(|returned| {
+                returned.is_some_and(|r| r.status == ReturnStatus::Inspected)
+            });
+            if inspected {
+                if let Err(e) =
+                    crate::returns::lifecycle::advance(db, return_id, ReturnStatus::Refunded, actor)
+                {
+                    log!("nie udało się oznaczyć zwrotu {return_id} jako rozliczonego: {e}");
+                }
+            }
+        }
+        if order.refunded >= order.total && order.status.can_become(OrderStatus::Refunded) {
+            let note = format!("zwrot pieniędzy nr {}", refund.id);
+            if let Err(e) =
+                lifecycle::transition(db, order.id, OrderStatus::Refunded, actor, Some(&note))
+            {
+                log!(
+                    "nie udało się oznaczyć zamówienia {} jako rozliczonego: {e}",
+                    order.id
+                );
+            }
+        }
+    }
+}
+
+#[server]
+pub async fn get_order_refunds(order_id: i64) -> Result<Option<OrderRefunds>, ServerFnError> {
+    use crate::db::{
+        OrderRepository, PaymentRepository, RefundRepository, ReturnRepository, use_db,
+    };
+    use crate::payments::PaymentStatus;
+
+    crate::staff::require_staff(Permission::IssueRefunds)?;
+    let db = use_db()?;
+    let Some(order) = db.find_order(order_id)? else {
+        return Ok(None);
+    };
+    let paid = db
+        .order_events(order.id)?
+        .iter()
+        .any(|event| event.to == OrderStatus::Paid);
+    let paid_with = db
+        .payments_for_order(order.id)?
+        .into_iter()
+        .rev()
+        .find(|payment| payment.status == PaymentStatus::Paid)
+        .map(|payment| payment.provider);
+    Ok(Some(OrderRefunds {
+        order_id: order.id,
+        status: order.status,
+        total: order.total,
+        shipping: order.shipping,
+        lines: order
+            .lines
+            .into_iter()
+            .map(|line| RefundLine {
+                product_id: line.product_id,
+                title: line.title,
+                price: line.price,
+            })
+            .collect(),
+        paid,
+        paid_with,
+        has_account: order.customer_id.is_some(),
+        refunds: db.refunds_for_order(order.id)?,
+        returns: db.returns_for_order(order.id)?,
+    }))
+}
+
+/// Refunds the chosen items and part of the delivery cost. The outer error means no
+/// access or a storage failure; the inner one a refund that cannot be made.
+#[server]
+pub async fn issue_refund(
+    order_id: i64,
+    return_id: Option<i64>,
+    method: RefundMethod,
+    #[server(default)] product_ids: Vec<u32>,
+    #[server(default)] shipping: String,
+    #[server(default)] iban: String,
+) -> Result<Result<Refund, RefundError>, ServerFnError> {
+    use crate::db::use_db;
+    use crate::payments::gateway::Payments;
+
+    let staff = crate::staff::require_staff(Permission::IssueRefunds)?;
+    let Some(shipping) = parse_amount(&shipping) else {
+        return Ok(Err(RefundError::InvalidAmount));
+    };
+    let db = use_db()?;
+    let payments = use_context::<Payments>()
+        .ok_or_else(|| ServerFnError::new("płatności nie są skonfigurowane"))?;
+    let request = processing::RefundOrder {
+        order_id,
+        return_id,
+        method,
+        product_ids,
+        shipping,
+        iban,
+    };
+    match processing::issue(&db, &payments, request, &staff.actor()).await {
+        Ok(refund) => Ok(Ok(refund)),
+        Err(RefundError::Storage(e)) => Err(ServerFnError::new(e)),
+        Err(e) => Ok(Err(e)),
+    }
+}
+
+/// Marks a bank transfer refund as sent (`completed`) or cancels it (`failed`).
+#[server]
+pub async fn settle_refund(
+    refund_id: i64,
+    to: RefundStatus,
+) -> Result<Result<Refund, RefundError>, ServerFnError> {
+    use crate::db::use_db;
+
+    let staff = crate::staff::require_staff(Permission::IssueRefunds)?;
+    let db = use_db()?;
+    match processing::settle_transfer(&db, refund_id, to, &staff.actor()) {
+        Ok(refund) => Ok(Ok(refund)),
+        Err(RefundError::Storage(e)) => Err(ServerFnError::new(e)),
+        Err(e) => Ok(Err(e)),
+    }
+}
+
+/// Refunds of one order and the form for a new one, shown on the staff order page. With
+/// `return_id` the form starts with that return's items selected.
+#[component]
+pub fn RefundPanel(
+    order: OrderRefunds,
+    return_id: Option<i64>,
+    issue: ServerAction<IssueRefund>,
+    settle: ServerAction<SettleRefund>,
+) -> impl IntoView {
+    let now = Utc::now();
+    let error = move || match issue.value().get() {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się zwrócić pieniędzy.".to_string()),
+        _ => match settle.value().get() {
+            Some(Ok(Err(e))) => Some(e.to_string()),
+            Some(Err(_)) => Some("Nie udało się zmienić statusu zwrotu.".to_string()),
+            _ => None,
+        },
+    };
+    let returned = return_id.and_then(|id| order.returns.iter().find(|r| r.id == id).cloned());
+    // Przy odstąpieniu od całej umowy oddajemy też koszt dostawy.
+    let whole_order_returned = returned
+        .as_ref()
+        .is_some_and(|returned| returned.lines.len() == order.lines.len());
+    let shipping_left = order.shipping_left();
+    let summary = format!(
+        "Zapłacono {}, zwrócono {}, do zwrotu zostało najwyżej {}.",
+        order.total,
+        order.refunded(),
+        order.left()
+    );
+
+    let returns = (!order.returns.is_empty()).then(|| {
+        let items = order
+            .returns
+            .iter()
+            .map(|request| {
+                let due = match request.refund_due_by() {
+                    Some(due) if request.status != ReturnStatus::Refunded => {
+                        format!(", pieniądze do {}", due.format("%Y-%m-%d"))
+                    }
+                    _ => String::new(),
+                };
+                let href = format!(
+                    "/admin/orders/{}/packing-slip?return={}",
+                    order.order_id, request.id
+                );
+                let refundable = request.status == ReturnStatus::Inspected;
+                view! {
+                    <li class:refund-overdue=request.is_refund_overdue(now)>
+                        {format!(
+                            "Zwrot rzeczy nr {} ({}): {}{due} ",
+                            request.id,
+                            request.amount(),
+                            request.status.label()
+                        )}
+                        {refundable.then(|| view! { <a href=href>"Zwróć pieniądze"</a> })}
+                    </li>
+                }
+            })
+            .collect_view();
+        view! { <ul class="refund-returns">{items}</ul> }
+    });
+
+    let history = (!order.refunds.is_empty()).then(|| {
+        let rows = order
+            .refunds
+            .iter()
+            .map(|refund| {
+                let items = refund
+                    .lines
+                    .iter()
+                    .map(|line| line.title.clone())
+                    .chain(
+                        (refund.shipping.grosze() > 0)
+                            .then(|| format!("dostawa {}", refund.shipping)),
+                    )
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let account = refund.iban.as_deref().map(format_iban);
+                view! {
+                    <tr class=format!("refund-{}", refund.status.param())>
+                        <td>
+                            {format!("nr {}", refund.id)}
+                            <br />
+                            <small>{refund.created_at.format("%Y-%m-%d %H:%M").to_string()}</small>
+                        </td>
+                        <td>{items}</td>
+                        <td>
+                            {refund.method.label()}
+                            {account.map(|account| view! { <br /> <small>{account}</small> })}
+                        </td>
+                        <td>{refund.amount.to_string()}</td>
+                        <td>
+                            {refund.status.label()}
+                            {(!refund.note.is_empty())
+                                .then(|| view! { <br /> <small>{refund.note.clone()}</small> })}
+                            <SettleTransfer refund=refund.clone() settle />
+                        </td>
+                    </tr>
+                }
+            })
+            .collect_view();
+        view! {
+            <table class="refund-history">
+                <thead>
+                    <tr>
+                        <th>"Zwrot"</th>
+                        <th>"Za co"</th>
+                        <th>"Sposób"</th>
+                        <th>"Kwota"</th>
+                        <th>"Status"</th>
+                    </tr>
+                </thead>
+                <tbody>{rows}</tbody>
+            </table>
+        }
+    });
+
+    let form = (order.paid && order.left().grosze() > 0).then(|| {
+        let lines = order
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let refunded = order.is_refunded(line.product_id);
+                let selected = returned.as_ref().is_some_and(|returned| {
+                    returned
+                        .lines
+                        .iter()
+                        .any(|item| item.product_id == line.product_id)
+                });
+                view! {
+                    <label class:refund-line-refunded=refunded>
+                        <input
+                            type="checkbox"
+                            name=format!("product_ids[{i}]")
+                            value=line.product_id
+                            checked=(selected && !refunded)
+                            disabled=refunded
+                        />
+                        {format!("{} ({})", line.title, line.price)}
+                    </label>
+                }
+            })
+            .collect_view();
+        let available = |method: RefundMethod| match method {
+            RefundMethod::OriginalPayment => order.paid_with.is_some(),
+            RefundMethod::BankTransfer => true,
+            RefundMethod::StoreCredit => order.has_account,
+        };
+        let default_method = RefundMethod::ALL
+            .into_iter()
+            .find(|&method| available(method));
+        let methods = RefundMethod::ALL
+            .into_iter()
+            .map(|method| {
+                let label = match (method, &order.paid_with) {
+                    (RefundMethod::OriginalPayment, Some(provider)) => {
+                        format!("{} ({provider})", method.label())
+                    }
+                    _ => method.label().to_string(),
+                };
+                view! {
+                    <label>
+                        <input
+                            type="radio"
+                            name="method"
+                            value=method.param()
+                            checked=(default_method == Some(method))
+                            disabled=(!available(method))
+                        />
+                        {label}
+                    </label>
+                }
+            })
+            .collect_view();
+        let shipping = if whole_order_returned {
+            amount_value(shipping_left)
+        } else {
+            String::new()
+        };
+        view! {
+            <ActionForm action=issue>
+                <input type="hidden" name="order_id" value=order.order_id />
+                {return_id.map(|id| view! { <input type="hidden" name="return_id" value=id /> })}
+                <fieldset class="refund-lines">
+                    <legend>"Rzeczy"</legend>
+                    {lines}
+                </fieldset>
+                <label>
+                    {format!("Koszt dostawy do zwrotu, zł (najwyżej {shipping_left})")}
+                    <input type="text" name="shipping" inputmode="decimal" value=shipping />
+                </label>
+                <fieldset class="refund-methods">
+                    <legend>"Sposób zwrotu"</legend>
+                    {methods}
+                    <label>
+                        "Numer konta klienta (przy zwrocie przelewem)"
+                        <input type="text" name="iban" autocomplete="off" />
+                    </label>
+                </fieldset>
+                <button type="submit">"Zwróć pieniądze"</button>
+            </ActionForm>
+        }
+    });
+
+    view! {
+        <section class="refund-panel">
+            <h3>"Zwroty pieniędzy"</h3>
+            <p>{summary}</p>
+            {(!order.paid).then(|| view! { <p>"Zamówienie nie zostało opłacone."</p> })}
+            {returns}
+            {history}
+            {form}
+            {move || error().map(|e| view! { <p class="order-status-error">{e}</p> })}
+        </section>
+    }
+}
+
+/// Buttons confirming or cancelling a bank transfer refund that waits to be sent.
+#[component]
+pub(crate) fn SettleTransfer(refund: Refund, settle: ServerAction<SettleRefund>) -> impl IntoView {
+    let waiting =
+        refund.method == RefundMethod::BankTransfer && refund.status == RefundStatus::Pending;
+    waiting.then(|| {
+        view! {
+            <ActionForm action=settle>
+                <input type="hidden" name="refund_id" value=refund.id />
+                <button type="submit" name="to" value=RefundStatus::Completed.param()>
+                    "Przelew wysłany"
+                </button>
+                <button type="submit" name="to" value=RefundStatus::Failed.param()>
+                    "Anuluj"
+                </button>
+            </ActionForm>
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iban_is_normalised_when_its_check_digits_are_right() {
+        for (input, expected) in [
+            (
+                "PL61109010140000071219812874",
+                Some("PL61109010140000071219812874"),
+            ),
+            (
+                "pl61 1090 1014 0000 0712 1981 2874",
+                Some("PL61109010140000071219812874"),
+            ),
+            (
+                "61 1090-1014-0000-0712-1981-2874",
+                Some("PL61109010140000071219812874"),
+            ),
+            ("DE89370400440532013000", Some("DE89370400440532013000")),
+            (
+                "GB82 WEST 1234 5698 7654 32",
+                Some("GB82WEST12345698765432"),
+            ),
+            ("PL61109010140000071219812875", None),
+            ("PL6110901014000007121981287", None),
+            ("6110901014000007121981287", None),
+            ("DE89 3704 0044 0532 0130 0!", None),
+            ("", None),
+        ] {
+            assert_eq!(normalize_iban(input).as_deref(), expected, "{input:?}");
+        }
+    }
+}
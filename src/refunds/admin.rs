@@ -0,0 +1,152 @@
+//! Every refund at `/admin/refunds`, newest first, with the amount paid back in the
+//! listed period for bookkeeping. Bank transfer refunds wait here until staff confirm
+//! they sent the money. Owners only.
+
+use super::{Refund, RefundStatus, SettleRefund, SettleTransfer, format_iban};
+use crate::catalog::Price;
+use crate::staff::Permission;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+
+#[server]
+pub async fn list_refunds(status: Option<RefundStatus>) -> Result<Vec<Refund>, ServerFnError> {
+    use crate::db::{RefundRepository, use_db};
+
+    crate::staff::require_staff(Permission::IssueRefunds)?;
+    Ok(use_db()?.list_refunds(status)?)
+}
+
+#[component]
+pub fn RefundsAdminPage() -> impl IntoView {
+    let query = use_query_map();
+    let settle = ServerAction::<SettleRefund>::new();
+    let status = move || {
+        query
+            .read()
+            .get("status")
+            .and_then(|status| RefundStatus::from_param(&status))
+    };
+    let refunds = Resource::new(
+        move || (status(), settle.version().get()),
+        |(status, _)| list_refunds(status),
+    );
+
+    let filters = move || {
+        let current = status();
+        let all = view! {
+            <a href="/admin/refunds" class:active=current.is_none()>
+                "Wszystkie"
+            </a>
+        };
+        let by_status = RefundStatus::ALL
+            .into_iter()
+            .map(|filter| {
+                let href = format!("/admin/refunds?status={}", filter.param());
+                view! {
+                    <a href=href class:active=current == Some(filter)>
+                        {filter.label()}
+                    </a>
+                }
+            })
+            .collect_view();
+        view! { <nav class="inbox-filters">{all} {by_status}</nav> }
+    };
+    let error = move || match settle.value().get() {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się zmienić statusu zwrotu.".to_string()),
+        _ => None,
+    };
+
+    view! {
+        <Title text="Zwroty pieniędzy - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Zwroty pieniędzy"</h2>
+                {filters}
+                {move || error().map(|e| view! { <p class="inbox-error">{e}</p> })}
+                <Suspense fallback=|| view! { <p>"Ładowanie zwrotów..."</p> }>
+                    {move || Suspend::new(async move {
+                        match refunds.await {
+                            Ok(refunds) if refunds.is_empty() => {
+                                view! { <p>"Brak zwrotów pieniędzy."</p> }.into_any()
+                            }
+                            Ok(refunds) => view! { <RefundList refunds settle /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do zwrotów."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn RefundList(refunds: Vec<Refund>, settle: ServerAction<SettleRefund>) -> impl IntoView {
+    let completed = Price(
+        refunds
+            .iter()
+            .filter(|refund| refund.status == RefundStatus::Completed)
+            .map(|refund| refund.amount.grosze())
+            .sum(),
+    );
+
+    view! {
+        <p>{format!("Zwrócono razem: {completed}")}</p>
+        <table class="inbox-threads refund-history">
+            <thead>
+                <tr>
+                    <th>"Zwrot"</th>
+                    <th>"Zamówienie"</th>
+                    <th>"Sposób"</th>
+                    <th>"Kwota"</th>
+                    <th>"Status"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {refunds
+                    .into_iter()
+                    .map(|refund| {
+                        let order = format!("/admin/orders/{}/packing-slip", refund.order_id);
+                        let account = refund.iban.as_deref().map(format_iban);
+                        let settled = refund
+                            .completed_at
+                            .map(|at| format!("zwrócono {}", at.format("%Y-%m-%d")));
+                        view! {
+                            <tr class=format!("refund-{}", refund.status.param())>
+                                <td>
+                                    {format!("nr {}", refund.id)}
+                                    <br />
+                                    <small>{refund.created_at.format("%Y-%m-%d %H:%M").to_string()}</small>
+                                </td>
+                                <td>
+                                    <a href=order>{format!("nr {}", refund.order_id)}</a>
+                                    {refund.return_id.map(|id| format!(" (zwrot rzeczy nr {id})"))}
+                                </td>
+                                <td>
+                                    {refund.method.label()}
+                                    {account.map(|account| view! { <br /> <small>{account}</small> })}
+                                </td>
+                                <td>
+                                    {refund.amount.to_string()}
+                                    {(refund.shipping.grosze() > 0)
+                                        .then(|| view! {
+                                            <br />
+                                            <small>{format!("w tym dostawa {}", refund.shipping)}</small>
+                                        })}
+                                </td>
+                                <td>
+                                    {refund.status.label()}
+                                    {settled.map(|settled| view! { <br /> <small>{settled}</small> })}
+                                    {(!refund.note.is_empty())
+                                        .then(|| view! { <br /> <small>{refund.note.clone()}</small> })}
+                                    <SettleTransfer refund=refund.clone() settle />
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
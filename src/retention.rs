@@ -0,0 +1,183 @@
+//! Enforces the retention periods from section "4. Czas przechowywania danych" of the
+//! privacy policy, available only on the server.
+//!
+//! [`spawn_retention_job`] runs [`enforce`] once at startup and then every hour. Each
+//! period can be changed with an environment variable holding a number of days.
+//! Inactive accounts are erased the same way as when customers delete them
+//! themselves, so their orders stay for accounting without personal details.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use leptos::logging::log;
+
+use crate::account::privacy::email_hash;
+use crate::db::{
+    self, Db, Erasure, LogPurge, PrivacyRepository, PrivacyRequestKind, RetentionRepository,
+};
+
+/// How often the job runs.
+const RUN_EVERY: Duration = Duration::from_secs(60 * 60);
+
+/// How long each kind of data is kept, in days.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Contact form messages and our replies; `MEGJONI_RETENTION_MESSAGES_DAYS`.
+    pub messages_days: i64,
+    /// Saved carts nobody touched; `MEGJONI_RETENTION_CARTS_DAYS`.
+    pub carts_days: i64,
+    /// Accounts nobody signed in to; `MEGJONI_RETENTION_ACCOUNTS_DAYS`.
+    pub accounts_days: i64,
+    /// Sent e-mails and network addresses; `MEGJONI_RETENTION_LOGS_DAYS`.
+    pub logs_days: i64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            messages_days: 365,
+            carts_days: 30,
+            accounts_days: 3 * 365,
+            logs_days: 90,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// The defaults, overridden by any of the `MEGJONI_RETENTION_*_DAYS` variables that
+    /// hold a positive number.
+    pub fn from_env() -> Self {
+        let days = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|days| days.parse().ok())
+                .filter(|days: &i64| *days > 0)
+                .unwrap_or(default)
+        };
+        let defaults = RetentionPolicy::default();
+        RetentionPolicy {
+            messages_days: days("MEGJONI_RETENTION_MESSAGES_DAYS", defaults.messages_days),
+            carts_days: days("MEGJONI_RETENTION_CARTS_DAYS", defaults.carts_days),
+            accounts_days: days("MEGJONI_RETENTION_ACCOUNTS_DAYS", defaults.accounts_days),
+            logs_days: days("MEGJONI_RETENTION_LOGS_DAYS", defaults.logs_days),
+        }
+    }
+}
+
+/// What one run removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionReport {
+    pub messages: usize,
+    pub carts: usize,
+    pub accounts: usize,
+    /// Inactive accounts kept because an order of theirs is still on its way or they
+    /// still hold store credit.
+    pub accounts_skipped: usize,
+    pub logs: LogPurge,
+}
+
+/// Removes everything older than the policy allows.
+pub fn enforce(
+    db: &Db,
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> db::Result<RetentionReport> {
+    let before = |days: i64| now - chrono::Duration::days(days);
+    let mut report = RetentionReport {
+        messages: db.purge_contact_messages(before(policy.messages_days))?,
+        carts: db.purge_carts(before(policy.carts_days))?,
+        logs: db.purge_logs(before(policy.logs_days), now)?,
+        ..RetentionReport::default()
+    };
+    for (id, email) in db.inactive_customers(before(policy.accounts_days))? {
+        match db.erase_customer(id, &email_hash(&email), PrivacyRequestKind::Retention, now)? {
+            Erasure::Erased(_) => report.accounts += 1,
+            Erasure::OrdersInProgress(_) | Erasure::StoreCreditLeft(_) => {
+                report.accounts_skipped += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn log_report(report: &RetentionReport) {
+    let logs = &report.logs;
+    for (count, what) in [
+        (
+            report.messages,
+            "usunięte wiadomości z formularza kontaktowego",
+        ),
+        (report.carts, "usunięte porzucone koszyki"),
+        (report.accounts, "usunięte nieaktywne konta"),
+        (
+            report.accounts_skipped,
+            "nieaktywne konta z zamówieniami w realizacji albo środkami w sklepie, pominięte",
+        ),
+        (logs.emails, "usunięte wysłane e-maile"),
+        (logs.sessions, "usunięte wygasłe sesje"),
+        (logs.account_tokens, "usunięte wygasłe linki z e-maili"),
+        (logs.message_ips, "usunięte adresy IP przy wiadomościach"),
+    ] {
+        if count > 0 {
+            log!("retencja: {what}: {count}");
+        }
+    }
+}
+
+/// Runs [`enforce`] at startup and then every hour.
+pub fn spawn_retention_job(db: Db, policy: RetentionPolicy) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RUN_EVERY);
+        loop {
+            interval.tick().await;
+            match enforce(&db, &policy, Utc::now()) {
+                Ok(report) => log_report(&report),
+                Err(e) => log!("nie udało się usunąć przeterminowanych danych: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{customer, db};
+    use crate::db::{CartRepository, CustomerRepository};
+
+    #[test]
+    fn inactive_accounts_and_old_carts_are_removed() {
+        let db = db();
+        let anna = customer(&db, "anna@example.com");
+        db.save_cart(anna.id, &Default::default()).unwrap();
+        let policy = RetentionPolicy::default();
+
+        let soon = enforce(&db, &policy, Utc::now()).unwrap();
+        assert_eq!(soon, RetentionReport::default());
+
+        let later = Utc::now() + chrono::Duration::days(policy.accounts_days + 1);
+        let report = enforce(&db, &policy, later).unwrap();
+        assert_eq!(report.carts, 1);
+        assert_eq!(report.accounts, 1);
+        assert!(db.find_customer(anna.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn accounts_with_store_credit_are_kept() {
+        let db = db();
+        let anna = customer(&db, "anna@example.com");
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO store_credit (customer_id, amount, created_at) VALUES (?1, 900, ?2)",
+                rusqlite::params![anna.id, Utc::now()],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        let policy = RetentionPolicy::default();
+
+        let later = Utc::now() + chrono::Duration::days(policy.accounts_days + 1);
+        let report = enforce(&db, &policy, later).unwrap();
+        assert_eq!((report.accounts, report.accounts_skipped), (0, 1));
+        assert!(db.find_customer(anna.id).unwrap().is_some());
+    }
+}
@@ -0,0 +1,956 @@
+//! Returns without giving a reason at `/returns`. Within the return window after delivery
+//! a customer picks items from their order, optionally says why, and gets a printable
+//! withdrawal form with instructions at `/returns/:token`. Staff then mark the parcel as
+//! received and the items as inspected (see [`admin`]), and pay the money back from the
+//! order page (see [`crate::refunds`]).
+//!
+//! Signed-in customers see their orders that can still be returned; guests look their
+//! order up by its number and e-mail address.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::catalog::Price;
+use crate::checkout::{Address, FieldError};
+use crate::orders::RETURN_WINDOW_DAYS;
+use crate::refunds::REFUND_DAYS;
+use chrono::{DateTime, Duration, Utc};
+use leptos::prelude::*;
+use leptos::server_fn::ServerFn;
+use leptos_meta::Title;
+use leptos_router::hooks::{use_params_map, use_query_map};
+use serde::{Deserialize, Serialize};
+
+pub mod admin;
+
+/// Longest reason a customer may give.
+pub const MAX_REASON_LEN: usize = 1000;
+/// Shown when `MEGJONI_RETURN_ADDRESS` is not set.
+#[cfg(feature = "ssr")]
+pub const DEFAULT_RETURN_ADDRESS: &str = "Meg Joni; Łódź";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnStatus {
+    /// The customer filled in the form; the parcel is on its way.
+    #[default]
+    Requested,
+    Received,
+    Inspected,
+    Refunded,
+}
+
+impl ReturnStatus {
+    pub const ALL: [ReturnStatus; 4] = [
+        ReturnStatus::Requested,
+        ReturnStatus::Received,
+        ReturnStatus::Inspected,
+        ReturnStatus::Refunded,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReturnStatus::Requested => "Zgłoszony",
+            ReturnStatus::Received => "Paczka dotarła",
+            ReturnStatus::Inspected => "Sprawdzony",
+            ReturnStatus::Refunded => "Pieniądze zwrócone",
+        }
+    }
+
+    /// The value used in links and forms, e.g. `received`.
+    pub fn param(self) -> &'static str {
+        match self {
+            ReturnStatus::Requested => "requested",
+            ReturnStatus::Received => "received",
+            ReturnStatus::Inspected => "inspected",
+            ReturnStatus::Refunded => "refunded",
+        }
+    }
+
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.param() == param)
+    }
+
+    /// The step that follows this one; returns go through every step in order.
+    pub fn next(self) -> Option<ReturnStatus> {
+        match self {
+            ReturnStatus::Requested => Some(ReturnStatus::Received),
+            ReturnStatus::Received => Some(ReturnStatus::Inspected),
+            ReturnStatus::Inspected => Some(ReturnStatus::Refunded),
+            ReturnStatus::Refunded => None,
+        }
+    }
+}
+
+/// An item being sent back, with the title and price from the order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReturnLine {
+    pub product_id: u32,
+    pub title: String,
+    pub price: Price,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReturnRequest {
+    pub id: i64,
+    pub order_id: i64,
+    /// Identifies the return in the customer's link; nobody else knows it.
+    pub token: String,
+    pub status: ReturnStatus,
+    /// Empty when the customer gave no reason.
+    pub reason: String,
+    pub lines: Vec<ReturnLine>,
+    pub created_at: DateTime<Utc>,
+    pub received_at: Option<DateTime<Utc>>,
+    pub inspected_at: Option<DateTime<Utc>>,
+    pub refunded_at: Option<DateTime<Utc>>,
+}
+
+impl ReturnRequest {
+    /// What the returned items cost.
+    pub fn amount(&self) -> Price {
+        Price(self.lines.iter().map(|line| line.price.grosze()).sum())
+    }
+
+    /// When the customer must have their money back: [`REFUND_DAYS`] after the parcel
+    /// reached us.
+    pub fn refund_due_by(&self) -> Option<DateTime<Utc>> {
+        self.received_at
+            .map(|received| received + Duration::days(REFUND_DAYS))
+    }
+
+    /// Whether the parcel arrived but the money has not gone back in time.
+    pub fn is_refund_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.status != ReturnStatus::Refunded && self.refund_due_by().is_some_and(|due| due < now)
+    }
+
+    /// When the return reached `status`, if it has.
+    pub fn reached_at(&self, status: ReturnStatus) -> Option<DateTime<Utc>> {
+        match status {
+            ReturnStatus::Requested => Some(self.created_at),
+            ReturnStatus::Received => self.received_at,
+            ReturnStatus::Inspected => self.inspected_at,
+            ReturnStatus::Refunded => self.refunded_at,
+        }
+    }
+}
+
+/// A delivered order as offered on the returns form.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReturnableOrder {
+    pub order_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub return_window_ends_at: DateTime<Utc>,
+    /// Items not yet part of any return.
+    pub lines: Vec<ReturnLine>,
+    /// Returns already requested for this order, oldest first.
+    pub returns: Vec<ReturnRequest>,
+}
+
+/// Everything the withdrawal form and return instructions show.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReturnDetails {
+    pub request: ReturnRequest,
+    pub name: String,
+    pub email: String,
+    pub address: Address,
+    pub ordered_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    /// Lines of the address returns go to.
+    pub return_address: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReturnError {
+    Invalid(Vec<FieldError>),
+    /// No order with this number was placed with this e-mail address.
+    NotFound,
+    /// The order has not reached the customer yet.
+    NotDelivered,
+    WindowClosed,
+    /// Every item of the order is already being returned, or the order was cancelled.
+    NothingToReturn,
+    Unavailable,
+}
+
+impl ReturnError {
+    fn field(&self, field: &str) -> Option<String> {
+        match self {
+            ReturnError::Invalid(errors) => errors
+                .iter()
+                .find(|error| error.field == field)
+                .map(|error| error.message.clone()),
+            _ => None,
+        }
+    }
+
+    fn summary(&self) -> String {
+        match self {
+            ReturnError::Invalid(_) => "Popraw zaznaczone pola.".to_string(),
+            ReturnError::NotFound => {
+                "Nie znaleźliśmy zamówienia o tym numerze złożonego z tego adresu e-mail."
+                    .to_string()
+            }
+            ReturnError::NotDelivered => {
+                "To zamówienie jeszcze do Ciebie nie dotarło. Zwrot zgłosisz po jego odebraniu."
+                    .to_string()
+            }
+            ReturnError::WindowClosed => format!(
+                "Minął {RETURN_WINDOW_DAYS}-dniowy termin na zwrot tego zamówienia. Jeśli \
+                 rzecz jest wadliwa, możesz ją zareklamować."
+            ),
+            ReturnError::NothingToReturn => {
+                "Wszystkie rzeczy z tego zamówienia zostały już zgłoszone do zwrotu.".to_string()
+            }
+            ReturnError::Unavailable => {
+                "Coś poszło nie tak. Spróbuj ponownie za chwilę.".to_string()
+            }
+        }
+    }
+}
+
+// Jak w formularzu kontaktowym: błąd musi przejść przez adres URL przy wysyłce bez
+// JavaScriptu, więc zapisujemy go jako JSON.
+impl fmt::Display for ReturnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl FromStr for ReturnError {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn fail(error: ReturnError) -> ServerFnError<ReturnError> {
+    ServerFnError::WrappedServerError(error)
+}
+
+#[cfg(feature = "ssr")]
+fn unavailable(e: impl fmt::Display) -> ServerFnError<ReturnError> {
+    leptos::logging::log!("błąd obsługi zwrotu: {e}");
+    fail(ReturnError::Unavailable)
+}
+
+/// Where customers send returned items: `MEGJONI_RETURN_ADDRESS` with lines separated by
+/// semicolons, e.g. `Meg Joni; ul. Piotrkowska 1; 90-001 Łódź`.
+#[cfg(feature = "ssr")]
+pub fn return_address() -> Vec<String> {
+    let address = std::env::var("MEGJONI_RETURN_ADDRESS")
+        .ok()
+        .filter(|address| !address.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_RETURN_ADDRESS.to_string());
+    address
+        .split(';')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The order if it belongs to the signed-in customer or was placed with `email`.
+#[cfg(feature = "ssr")]
+fn find_own_order(
+    db: &crate::db::Db,
+    order_id: i64,
+    email: &str,
+) -> Result<crate::db::Order, ServerFnError<ReturnError>> {
+    use crate::db::OrderRepository;
+
+    let order = db
+        .find_order(order_id)
+        .map_err(unavailable)?
+        .ok_or_else(|| fail(ReturnError::NotFound))?;
+    let email = email.trim();
+    let by_email = !email.is_empty() && order.email.eq_ignore_ascii_case(email);
+    let by_account = match order.customer_id {
+        Some(id) => crate::account::auth::current_customer(db)
+            .map_err(unavailable)?
+            .is_some_and(|customer| customer.id == id),
+        None => false,
+    };
+    if by_email || by_account {
+        Ok(order)
+    } else {
+        Err(fail(ReturnError::NotFound))
+    }
+}
+
+/// What of the order can still be returned, or why nothing can.
+#[cfg(feature = "ssr")]
+fn returnable(
+    db: &crate::db::Db,
+    order: &crate::db::Order,
+    now: DateTime<Utc>,
+) -> Result<ReturnableOrder, ServerFnError<ReturnError>> {
+    use crate::db::ReturnRepository;
+    use crate::orders::OrderStatus;
+
+    match order.status {
+        OrderStatus::Delivered => {}
+        OrderStatus::PendingPayment
+        | OrderStatus::Paid
+        | OrderStatus::Packed
+        | OrderStatus::Shipped => return Err(fail(ReturnError::NotDelivered)),
+        OrderStatus::Cancelled | OrderStatus::Returned | OrderStatus::Refunded => {
+            return Err(fail(ReturnError::NothingToReturn));
+        }
+    }
+    let window_ends_at = order
+        .return_window_ends_at
+        .filter(|&end| now <= end)
+        .ok_or_else(|| fail(ReturnError::WindowClosed))?;
+    let returns = db.returns_for_order(order.id).map_err(unavailable)?;
+    let lines: Vec<ReturnLine> = order
+        .lines
+        .iter()
+        .filter(|line| {
+            !returns
+                .iter()
+                .flat_map(|request| &request.lines)
+                .any(|returned| returned.product_id == line.product_id)
+        })
+        .map(|line| ReturnLine {
+            product_id: line.product_id,
+            title: line.title.clone(),
+            price: line.price,
+        })
+        .collect();
+    Ok(ReturnableOrder {
+        order_id: order.id,
+        created_at: order.created_at,
+        return_window_ends_at: window_ends_at,
+        lines,
+        returns,
+    })
+}
+
+/// The order's items that can still be returned. The signed-in customer's own orders do
+/// not need the e-mail address.
+#[server]
+pub async fn get_returnable_order(
+    order_id: i64,
+    #[server(default)] email: String,
+) -> Result<ReturnableOrder, ServerFnError<ReturnError>> {
+    use crate::db::use_db;
+
+    let db = use_db().map_err(unavailable)?;
+    let order = find_own_order(&db, order_id, &email)?;
+    returnable(&db, &order, Utc::now())
+}
+
+/// The signed-in customer's orders that are within the return window.
+#[server]
+pub async fn my_returnable_orders() -> Result<Vec<ReturnableOrder>, ServerFnError> {
+    use crate::db::{OrderRepository, use_db};
+
+    let db = use_db()?;
+    let Some(customer) = crate::account::auth::current_customer(&db)? else {
+        return Ok(Vec::new());
+    };
+    let now = Utc::now();
+    let mut orders = Vec::new();
+    for order in db.orders_for_customer(customer.id)? {
+        match returnable(&db, &order, now) {
+            Ok(returnable) => orders.push(returnable),
+            Err(ServerFnError::WrappedServerError(ReturnError::Unavailable)) => {
+                return Err(ServerFnError::new("nie udało się wczytać zamówień"));
+            }
+            Err(_) => {}
+        }
+    }
+    Ok(orders)
+}
+
+/// Records the return of the chosen items and takes the customer to the withdrawal form.
+#[server]
+pub async fn request_return(
+    order_id: i64,
+    #[server(default)] email: String,
+    #[server(default)] product_ids: Vec<u32>,
+    #[server(default)] reason: String,
+) -> Result<(), ServerFnError<ReturnError>> {
+    use crate::db::{ReturnRepository, use_db};
+    use crate::mail::{self, Email};
+
+    let reason = reason.trim();
+    let mut errors = Vec::new();
+    if product_ids.is_empty() {
+        errors.push(FieldError::new(
+            "product_ids",
+            "Zaznacz rzeczy, które chcesz zwrócić.",
+        ));
+    }
+    if reason.chars().count() > MAX_REASON_LEN {
+        errors.push(FieldError::new(
+            "reason",
+            "Powód zwrotu może mieć najwyżej 1000 znaków.",
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(fail(ReturnError::Invalid(errors)));
+    }
+
+    let db = use_db().map_err(unavailable)?;
+    let order = find_own_order(&db, order_id, &email)?;
+    let returnable = returnable(&db, &order, Utc::now())?;
+    let lines: Vec<ReturnLine> = returnable
+        .lines
+        .into_iter()
+        .filter(|line| product_ids.contains(&line.product_id))
+        .collect();
+    if lines.len() != product_ids.len() {
+        return Err(fail(ReturnError::NothingToReturn));
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let request = db
+        .create_return(order.id, &token, &lines, reason, Utc::now())
+        .map_err(unavailable)?
+        .ok_or_else(|| fail(ReturnError::NothingToReturn))?;
+
+    let link = format!(
+        "{}/returns/{token}",
+        crate::account::auth::public_url().map_err(unavailable)?
+    );
+    let email = Email::ReturnRequested {
+        order: order.clone(),
+        request,
+        link,
+        return_address: return_address(),
+    };
+    // Zwrot już jest zapisany; link do formularza klient i tak zobaczy zaraz na stronie.
+    if let Err(e) = mail::queue(&db, &order.email, &email) {
+        leptos::logging::log!("nie udało się zakolejkować e-maila o zwrocie: {e}");
+    }
+    leptos_axum::redirect(&format!("/returns/{token}"));
+    Ok(())
+}
+
+#[server]
+pub async fn get_return(token: String) -> Result<Option<ReturnDetails>, ServerFnError> {
+    use crate::db::{OrderRepository, ReturnRepository, use_db};
+
+    let db = use_db()?;
+    let Some(request) = db.find_return_by_token(token.trim())? else {
+        return Ok(None);
+    };
+    let Some(order) = db.find_order(request.order_id)? else {
+        return Ok(None);
+    };
+    Ok(Some(ReturnDetails {
+        request,
+        name: order.name,
+        email: order.email,
+        address: order.address,
+        ordered_at: order.created_at,
+        delivered_at: order
+            .return_window_ends_at
+            .map(|end| end - chrono::Duration::days(RETURN_WINDOW_DAYS)),
+        return_address: return_address(),
+    }))
+}
+
+#[server]
+pub async fn get_return_address() -> Result<Vec<String>, ServerFnError> {
+    Ok(return_address())
+}
+
+/// Returns moving on and what that sets off.
+#[cfg(feature = "ssr")]
+pub mod lifecycle {
+    use chrono::Utc;
+    use leptos::logging::log;
+
+    use super::{ReturnRequest, ReturnStatus};
+    use crate::db::{Db, OrderRepository, ReturnRepository};
+    use crate::mail::{self, Email};
+    use crate::orders::{Actor, OrderStatus, lifecycle};
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum ReturnTransitionError {
+        #[error("nie ma takiego zwrotu")]
+        NotFound,
+        #[error("zwrotu ze statusem „{}” nie można oznaczyć jako „{}”", .from.label(), .to.label())]
+        Illegal {
+            from: ReturnStatus,
+            to: ReturnStatus,
+        },
+        #[error(transparent)]
+        Storage(#[from] crate::db::Error),
+    }
+
+    /// Moves the return to its next step, `to`.
+    ///
+    /// Once the parcel arrives its items go back on sale; when every item of the order has
+    /// come back, the order itself becomes returned. The customer hears about the parcel
+    /// arriving. A return becomes refunded only through [`crate::refunds`], which tells
+    /// the customer about the money.
+    pub fn advance(
+        db: &Db,
+        return_id: i64,
+        to: ReturnStatus,
+        actor: &Actor,
+    ) -> Result<ReturnRequest, ReturnTransitionError> {
+        let request = db
+            .find_return(return_id)?
+            .ok_or(ReturnTransitionError::NotFound)?;
+        let from = request.status;
+        if from.next() != Some(to) {
+            return Err(ReturnTransitionError::Illegal { from, to });
+        }
+        let request = db
+            .change_return_status(return_id, from, to, Utc::now())?
+            // Ktoś inny zmienił status w międzyczasie.
+            .ok_or(ReturnTransitionError::Illegal { from, to })?;
+        let Some(order) = db.find_order(request.order_id)? else {
+            return Ok(request);
+        };
+        let note = format!("zwrot nr {}", request.id);
+
+        let email = match to {
+            ReturnStatus::Received => {
+                let back: Vec<u32> = db
+                    .returns_for_order(order.id)?
+                    .iter()
+                    .filter(|other| other.status != ReturnStatus::Requested)
+                    .flat_map(|other| other.lines.iter().map(|line| line.product_id))
+                    .collect();
+                let everything_back = order
+                    .lines
+                    .iter()
+                    .all(|line| back.contains(&line.product_id));
+                if everything_back && order.status == OrderStatus::Delivered {
+                    // Zmiana statusu zamówienia sama wysyła e-mail o otrzymaniu zwrotu.
+                    match lifecycle::transition(
+                        db,
+                        order.id,
+                        OrderStatus::Returned,
+                        actor,
+                        Some(&note),
+                    ) {
+                        Ok(_) => None,
+                        Err(e) => {
+                            log!(
+                                "nie udało się oznaczyć zamówienia {} jako zwróconego: {e}",
+                                order.id
+                            );
+                            Some(Email::ReturnReceived(order.clone()))
+                        }
+                    }
+                } else {
+                    Some(Email::ReturnReceived(order.clone()))
+                }
+            }
+            // O zwrocie pieniędzy klient dowiaduje się z e-maila wysyłanego przy samym
+            // zwrocie (zob. crate::refunds), który też rozlicza zamówienie.
+            ReturnStatus::Requested | ReturnStatus::Inspected | ReturnStatus::Refunded => None,
+        };
+        if let Some(email) = email {
+            // Status zwrotu już się zmienił; brak e-maila nie może tego cofnąć.
+            if let Err(e) = mail::queue(db, &order.email, &email) {
+                log!("nie udało się zakolejkować e-maila do zwrotu {return_id}: {e}");
+            }
+        }
+        Ok(request)
+    }
+}
+
+fn format_date(at: DateTime<Utc>) -> String {
+    at.format("%d.%m.%Y").to_string()
+}
+
+/// What went wrong with the last submission of `action`'s form.
+fn failure<S>(action: ServerAction<S>) -> Option<ReturnError>
+where
+    S: ServerFn<Output = (), Error = ReturnError> + Clone + Send + Sync + 'static,
+{
+    match action.value().get() {
+        Some(Err(ServerFnError::WrappedServerError(error))) => Some(error),
+        Some(Err(_)) => Some(ReturnError::Unavailable),
+        _ => None,
+    }
+}
+
+#[component]
+pub fn ReturnsPage() -> impl IntoView {
+    let query = use_query_map();
+    let order_id = move || {
+        query
+            .read()
+            .get("order")
+            .and_then(|id| id.trim().trim_start_matches('#').parse::<i64>().ok())
+    };
+
+    view! {
+        <Title text="Zwroty - Meg Joni" />
+        <main>
+            <section class="returns">
+                <h2>"Zwroty"</h2>
+                <p>
+                    {format!(
+                        "Rzeczy z doręczonego zamówienia możesz zwrócić bez podania przyczyny w ciągu {RETURN_WINDOW_DAYS} dni od ich otrzymania. ",
+                    )}
+                    "Wybierz, co odsyłasz, a przygotujemy dla Ciebie formularz odstąpienia od umowy do wydrukowania."
+                </p>
+                {move || match order_id() {
+                    Some(order_id) => {
+                        let email = query.read().get("email").unwrap_or_default();
+                        view! { <ReturnForm order_id email /> }.into_any()
+                    }
+                    None => view! { <OrderLookup /> }.into_any(),
+                }}
+            </section>
+        </main>
+    }
+}
+
+/// The signed-in customer's returnable orders, and a form for finding any order by its
+/// number and e-mail address.
+#[component]
+fn OrderLookup() -> impl IntoView {
+    let orders = Resource::new(|| (), |_| my_returnable_orders());
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || Suspend::new(async move {
+                let orders = orders.await.unwrap_or_default();
+                (!orders.is_empty())
+                    .then(|| {
+                        let items = orders
+                            .into_iter()
+                            .map(|order| {
+                                let titles = order
+                                    .lines
+                                    .iter()
+                                    .map(|line| line.title.clone())
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                view! {
+                                    <li>
+                                        <a href=format!("/returns?order={}", order.order_id)>
+                                            {format!("Zamówienie nr {}", order.order_id)}
+                                        </a>
+                                        {format!(
+                                            " z {} – zwrot możliwy do {}",
+                                            format_date(order.created_at),
+                                            format_date(order.return_window_ends_at),
+                                        )}
+                                        <br />
+                                        <small>{titles}</small>
+                                    </li>
+                                }
+                            })
+                            .collect_view();
+                        view! {
+                            <h3>"Twoje zamówienia"</h3>
+                            <ul class="returns-orders">{items}</ul>
+                        }
+                    })
+            })}
+        </Suspense>
+        <h3>"Znajdź zamówienie"</h3>
+        <form method="get" action="/returns" class="account-form">
+            <label for="returns-order">"Numer zamówienia"</label>
+            <input type="text" id="returns-order" name="order" inputmode="numeric" required />
+            <label for="returns-email">"E-mail podany przy zamówieniu"</label>
+            <input type="email" id="returns-email" name="email" autocomplete="email" required />
+            <button type="submit">"Dalej"</button>
+        </form>
+    }
+}
+
+#[component]
+fn ReturnForm(order_id: i64, email: String) -> impl IntoView {
+    let request = ServerAction::<RequestReturn>::new();
+    let order = Resource::new(
+        {
+            let email = email.clone();
+            move || (order_id, email.clone())
+        },
+        |(order_id, email)| get_returnable_order(order_id, email),
+    );
+    let error = move || failure(request);
+
+    view! {
+        <Suspense fallback=|| view! { <p>"Ładowanie zamówienia..."</p> }>
+            {move || {
+                let email = email.clone();
+                Suspend::new(async move {
+                    match order.await {
+                        Ok(order) => {
+                            let previous = order
+                                .returns
+                                .iter()
+                                .map(|previous| {
+                                    view! {
+                                        <li>
+                                            <a href=format!("/returns/{}", previous.token)>
+                                                {format!("Zwrot nr {}", previous.id)}
+                                            </a>
+                                            {format!(" – {}", previous.status.label())}
+                                        </li>
+                                    }
+                                })
+                                .collect_view();
+                            let lines = order
+                                .lines
+                                .iter()
+                                .enumerate()
+                                .map(|(i, line)| {
+                                    view! {
+                                        <label class="account-checkbox">
+                                            <input
+                                                type="checkbox"
+                                                name=format!("product_ids[{i}]")
+                                                value=line.product_id
+                                            />
+                                            {format!("{} – {}", line.title, line.price)}
+                                        </label>
+                                    }
+                                })
+                                .collect_view();
+                            let nothing_left = order.lines.is_empty();
+                            view! {
+                                <h3>{format!("Zamówienie nr {}", order.order_id)}</h3>
+                                <p>
+                                    {format!(
+                                        "Zwrot możesz zgłosić do {}.",
+                                        format_date(order.return_window_ends_at),
+                                    )}
+                                </p>
+                                {(!order.returns.is_empty())
+                                    .then(|| view! {
+                                        <p>"Zgłoszone zwroty:"</p>
+                                        <ul>{previous}</ul>
+                                    })}
+                                {if nothing_left {
+                                    view! { <p>{ReturnError::NothingToReturn.summary()}</p> }.into_any()
+                                } else {
+                                    view! {
+                                        <ActionForm action=request attr:class="account-form">
+                                            <input type="hidden" name="order_id" value=order.order_id />
+                                            <input type="hidden" name="email" value=email.clone() />
+                                            <fieldset class="returns-lines">
+                                                <legend>"Co odsyłasz?"</legend>
+                                                {lines}
+                                            </fieldset>
+                                            {move || {
+                                                error()
+                                                    .and_then(|error| error.field("product_ids"))
+                                                    .map(|message| view! { <p class="field-error">{message}</p> })
+                                            }}
+                                            <label for="return-reason">"Powód zwrotu (opcjonalnie)"</label>
+                                            <textarea
+                                                id="return-reason"
+                                                name="reason"
+                                                rows="4"
+                                                maxlength=MAX_REASON_LEN
+                                            ></textarea>
+                                            {move || {
+                                                error()
+                                                    .and_then(|error| error.field("reason"))
+                                                    .map(|message| view! { <p class="field-error">{message}</p> })
+                                            }}
+                                            {move || {
+                                                error()
+                                                    .map(|error| view! {
+                                                        <p class="account-error" role="alert">{error.summary()}</p>
+                                                    })
+                                            }}
+                                            <button type="submit" disabled=move || request.pending().get()>
+                                                "Zgłoś zwrot"
+                                            </button>
+                                        </ActionForm>
+                                    }
+                                    .into_any()
+                                }}
+                            }
+                            .into_any()
+                        }
+                        Err(ServerFnError::WrappedServerError(error)) => view! {
+                            <p class="account-error" role="alert">{error.summary()}</p>
+                            <p><a href="/returns">"Wróć"</a></p>
+                        }
+                        .into_any(),
+                        Err(_) => view! {
+                            <p class="account-error" role="alert">
+                                {ReturnError::Unavailable.summary()}
+                            </p>
+                        }
+                        .into_any(),
+                    }
+                })
+            }}
+        </Suspense>
+    }
+}
+
+/// The customer's return: its progress, what to do with the parcel and the withdrawal
+/// form to print.
+#[component]
+pub fn ReturnPage() -> impl IntoView {
+    let params = use_params_map();
+    let details = Resource::new(
+        move || params.read().get("token").unwrap_or_default(),
+        get_return,
+    );
+
+    view! {
+        <Title text="Zwrot - Meg Joni" />
+        <main>
+            <section class="returns">
+                <Suspense fallback=|| view! { <p>"Ładowanie zwrotu..."</p> }>
+                    {move || Suspend::new(async move {
+                        match details.await {
+                            Ok(Some(details)) => view! { <ReturnView details /> }.into_any(),
+                            Ok(None) => view! { <p>"Nie znaleźliśmy tego zwrotu."</p> }.into_any(),
+                            Err(_) => view! { <p>{ReturnError::Unavailable.summary()}</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ReturnView(details: ReturnDetails) -> impl IntoView {
+    let request = details.request.clone();
+    let progress = ReturnStatus::ALL
+        .into_iter()
+        .map(|status| {
+            let at = request.reached_at(status);
+            view! {
+                <li class:done=at.is_some()>
+                    {status.label()}
+                    {at.map(|at| format!(" ({})", format_date(at)))}
+                </li>
+            }
+        })
+        .collect_view();
+    let return_address = details.return_address.clone();
+
+    view! {
+        <div class="returns-instructions">
+            <h2>{format!("Zwrot nr {} z zamówienia nr {}", request.id, request.order_id)}</h2>
+            <ol class="returns-progress">{progress}</ol>
+            <h3>"Jak odesłać rzeczy?"</h3>
+            <ol>
+                <li>
+                    "Wydrukuj formularz odstąpienia od umowy poniżej, podpisz go i włóż do paczki. "
+                    <button class="returns-print" onclick="window.print()">"Drukuj formularz"</button>
+                </li>
+                <li>
+                    {format!(
+                        "Zapakuj rzeczy i nadaj paczkę w ciągu {RETURN_WINDOW_DAYS} dni od zgłoszenia zwrotu na adres:"
+                    )}
+                    <address>
+                        {return_address
+                            .into_iter()
+                            .map(|line| view! { {line} <br /> })
+                            .collect_view()}
+                    </address>
+                </li>
+                <li>"Zwrotu dokonujesz na własny koszt. Rzeczy nie mogą nosić śladów użytkowania."</li>
+                <li>
+                    "Po otrzymaniu i sprawdzeniu przesyłki zwrócimy pieniądze w ciągu 14 dni. "
+                    "O każdym kroku poinformujemy Cię e-mailem."
+                </li>
+            </ol>
+        </div>
+        <WithdrawalForm details />
+    }
+}
+
+/// The model withdrawal form from annex 2 to the consumer rights act, filled in with the
+/// order's details.
+#[component]
+fn WithdrawalForm(details: ReturnDetails) -> impl IntoView {
+    let ReturnDetails {
+        request,
+        name,
+        email,
+        address,
+        ordered_at,
+        delivered_at,
+        return_address,
+    } = details;
+    let items = request
+        .lines
+        .iter()
+        .map(|line| {
+            view! { <li>{format!("{} (nr {}) – {}", line.title, line.product_id, line.price)}</li> }
+        })
+        .collect_view();
+
+    view! {
+        <article class="withdrawal-form">
+            <h3>"Formularz odstąpienia od umowy"</h3>
+            <p class="withdrawal-form-note">
+                "(formularz ten należy wypełnić i odesłać tylko w przypadku chęci odstąpienia od umowy)"
+            </p>
+            <p>
+                <strong>"Adresat: "</strong>
+                {return_address.join(", ")}
+                ", kontakt@megjoni.pl"
+            </p>
+            <p>
+                {format!(
+                    "Ja, niżej podpisana/podpisany, niniejszym informuję o moim odstąpieniu od umowy sprzedaży następujących rzeczy (zamówienie nr {}):",
+                    request.order_id,
+                )}
+            </p>
+            <ul>{items}</ul>
+            <dl class="packing-slip-details">
+                <dt>"Data zawarcia umowy"</dt>
+                <dd>{format_date(ordered_at)}</dd>
+                <dt>"Data odbioru"</dt>
+                <dd>{delivered_at.map(format_date).unwrap_or_default()}</dd>
+                <dt>"Imię i nazwisko konsumenta"</dt>
+                <dd>{name}</dd>
+                <dt>"Adres konsumenta"</dt>
+                <dd>
+                    {address.street}
+                    <br />
+                    {format!("{} {}", address.postcode, address.city)}
+                </dd>
+                <dt>"E-mail"</dt>
+                <dd>{email}</dd>
+                {(!request.reason.is_empty())
+                    .then(|| view! {
+                        <dt>"Powód (nieobowiązkowo)"</dt>
+                        <dd>{request.reason.clone()}</dd>
+                    })}
+                <dt>"Data"</dt>
+                <dd>{format_date(request.created_at)}</dd>
+            </dl>
+            <p class="withdrawal-form-signature">
+                "Podpis konsumenta (tylko jeżeli formularz jest przesyłany w wersji papierowej)"
+            </p>
+        </article>
+    }
+}
+
+/// The configured return address, for the shipping and returns policy page.
+#[component]
+pub fn ReturnAddress() -> impl IntoView {
+    let address = Resource::new(|| (), |_| get_return_address());
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || Suspend::new(async move {
+                address.await.ok().map(|lines| {
+                    lines
+                        .into_iter()
+                        .map(|line| view! { <br /> {line} })
+                        .collect_view()
+                })
+            })}
+        </Suspense>
+    }
+}
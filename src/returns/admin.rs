@@ -0,0 +1,177 @@
+//! Returns as staff see them at `/admin/returns`: every return with its items, the
+//! customer's reason and when the money is due, and a button moving it to its next step.
+//! Inspected returns link to the order page, where the money is refunded.
+
+use super::{ReturnRequest, ReturnStatus};
+use crate::staff::Permission;
+use chrono::Utc;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+
+#[server]
+pub async fn list_returns(
+    status: Option<ReturnStatus>,
+) -> Result<Vec<ReturnRequest>, ServerFnError> {
+    use crate::db::{ReturnRepository, use_db};
+
+    crate::staff::require_staff(Permission::HandleOrders)?;
+    Ok(use_db()?.list_returns(status)?)
+}
+
+#[server]
+pub async fn advance_return(return_id: i64, to: ReturnStatus) -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    let staff = crate::staff::require_staff(Permission::HandleOrders)?;
+    if to == ReturnStatus::Refunded {
+        return Err(ServerFnError::new(
+            "pieniądze zwraca się na stronie zamówienia",
+        ));
+    }
+    let db = use_db()?;
+    super::lifecycle::advance(&db, return_id, to, &staff.actor())?;
+    Ok(())
+}
+
+#[component]
+pub fn ReturnsAdminPage() -> impl IntoView {
+    let query = use_query_map();
+    let advance = ServerAction::<AdvanceReturn>::new();
+    let status = move || {
+        query
+            .read()
+            .get("status")
+            .and_then(|status| ReturnStatus::from_param(&status))
+    };
+    let returns = Resource::new(
+        move || (status(), advance.version().get()),
+        |(status, _)| list_returns(status),
+    );
+
+    let filters = move || {
+        let current = status();
+        let all = view! {
+            <a href="/admin/returns" class:active=current.is_none()>
+                "Wszystkie"
+            </a>
+        };
+        let by_status = ReturnStatus::ALL
+            .into_iter()
+            .map(|filter| {
+                let href = format!("/admin/returns?status={}", filter.param());
+                view! {
+                    <a href=href class:active=current == Some(filter)>
+                        {filter.label()}
+                    </a>
+                }
+            })
+            .collect_view();
+        view! { <nav class="inbox-filters">{all} {by_status}</nav> }
+    };
+    let error = move || {
+        matches!(advance.value().get(), Some(Err(_)))
+            .then(|| view! { <p class="inbox-error">"Nie udało się zmienić statusu zwrotu."</p> })
+    };
+
+    view! {
+        <Title text="Zwroty - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Zwroty"</h2>
+                {filters}
+                {error}
+                <Suspense fallback=|| view! { <p>"Ładowanie zwrotów..."</p> }>
+                    {move || Suspend::new(async move {
+                        match returns.await {
+                            Ok(returns) if returns.is_empty() => {
+                                view! { <p>"Brak zwrotów."</p> }.into_any()
+                            }
+                            Ok(returns) => view! { <ReturnList returns advance /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do zwrotów."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn ReturnList(returns: Vec<ReturnRequest>, advance: ServerAction<AdvanceReturn>) -> impl IntoView {
+    let now = Utc::now();
+    view! {
+        <table class="inbox-threads">
+            <thead>
+                <tr>
+                    <th>"Zwrot"</th>
+                    <th>"Rzeczy"</th>
+                    <th>"Powód"</th>
+                    <th>"Zwrot pieniędzy"</th>
+                    <th>"Status"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {returns
+                    .into_iter()
+                    .map(|request| {
+                        let order = format!("/admin/orders/{}/packing-slip", request.order_id);
+                        let items = request
+                            .lines
+                            .iter()
+                            .map(|line| format!("{} ({})", line.title, line.price))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let next = match request.status.next() {
+                            Some(ReturnStatus::Refunded) => {
+                                let href = format!(
+                                    "/admin/orders/{}/packing-slip?return={}",
+                                    request.order_id, request.id
+                                );
+                                Some(view! { <a href=href>"Zwróć pieniądze"</a> }.into_any())
+                            }
+                            Some(to) => Some(
+                                view! {
+                                    <ActionForm action=advance>
+                                        <input type="hidden" name="return_id" value=request.id />
+                                        <button type="submit" name="to" value=to.param()>
+                                            {format!("Oznacz: {}", to.label())}
+                                        </button>
+                                    </ActionForm>
+                                }
+                                .into_any(),
+                            ),
+                            None => None,
+                        };
+                        let overdue = request.is_refund_overdue(now);
+                        let due = match (request.refunded_at, request.refund_due_by()) {
+                            (Some(at), _) => format!("zwrócono {}", at.format("%Y-%m-%d")),
+                            (None, Some(due)) if overdue => {
+                                format!("PO TERMINIE ({})", due.format("%Y-%m-%d"))
+                            }
+                            (None, Some(due)) => format!("do {}", due.format("%Y-%m-%d")),
+                            (None, None) => "po otrzymaniu paczki".to_string(),
+                        };
+                        view! {
+                            <tr
+                                class=format!("returns-{}", request.status.param())
+                                class:returns-overdue=overdue
+                            >
+                                <td>
+                                    {format!("nr {} ", request.id)}
+                                    <a href=order>{format!("(zamówienie nr {})", request.order_id)}</a>
+                                    <br />
+                                    <small>{request.created_at.format("%Y-%m-%d %H:%M").to_string()}</small>
+                                </td>
+                                <td>{items} <br /> <strong>{request.amount().to_string()}</strong></td>
+                                <td>{request.reason.clone()}</td>
+                                <td>{due}</td>
+                                <td>{request.status.label()} {next}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
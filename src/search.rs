@@ -0,0 +1,362 @@
+//! Product search at `/search?query=...`.
+//!
+//! The server keeps an in-memory index over product titles, brands, categories and
+//! descriptions. Text is folded to lowercase ASCII first, so "zolta" finds "żółta", and
+//! query words may differ from indexed words by a typo or two, depending on length.
+
+use crate::catalog::{Product, product_cards};
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::hooks::use_query_map;
+
+/// Lowercases `text` and strips Polish (and common western) diacritics.
+pub fn fold(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'ą' | 'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+            'ć' | 'č' | 'ç' => 'c',
+            'ę' | 'é' | 'è' | 'ê' | 'ë' | 'ě' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ł' => 'l',
+            'ń' | 'ñ' | 'ň' => 'n',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+            'ś' | 'š' => 's',
+            'ú' | 'ù' | 'û' | 'ü' | 'ů' => 'u',
+            'ý' | 'ÿ' => 'y',
+            'ź' | 'ż' | 'ž' => 'z',
+            other => other,
+        })
+        .collect()
+}
+
+/// Splits folded text into words.
+pub fn tokenize(text: &str) -> Vec<String> {
+    fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(feature = "ssr")]
+pub mod index {
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use super::tokenize;
+    use crate::catalog::{Category, Product};
+    use crate::db::{Db, ProductRepository};
+
+    /// How much a match in each field counts towards a product's score.
+    const TITLE_WEIGHT: f32 = 3.0;
+    const BRAND_WEIGHT: f32 = 2.0;
+    const CATEGORY_WEIGHT: f32 = 1.5;
+    const DESCRIPTION_WEIGHT: f32 = 1.0;
+
+    /// Words are matched on every form a customer might type, not only the label.
+    fn category_words(category: Category) -> &'static str {
+        match category {
+            Category::Woman => "damska damskie kobieta kobiece",
+            Category::Man => "męska męskie mężczyzna męskie",
+        }
+    }
+
+    /// Inverted index from folded words to the products (and fields) containing them.
+    #[derive(Default)]
+    pub struct SearchIndex {
+        postings: HashMap<String, Vec<(u32, f32)>>,
+    }
+
+    impl SearchIndex {
+        pub fn build(products: &[Product]) -> Self {
+            let mut index = SearchIndex::default();
+            for product in products {
+                index.add_field(product.id, &product.title, TITLE_WEIGHT);
+                if let Some(brand) = &product.brand {
+                    index.add_field(product.id, brand, BRAND_WEIGHT);
+                }
+                index.add_field(
+                    product.id,
+                    category_words(product.category),
+                    CATEGORY_WEIGHT,
+                );
+                index.add_field(product.id, &product.description, DESCRIPTION_WEIGHT);
+            }
+            index
+        }
+
+        fn add_field(&mut self, product_id: u32, text: &str, weight: f32) {
+            for word in tokenize(text) {
+                let postings = self.postings.entry(word).or_default();
+                match postings.iter_mut().find(|(id, _)| *id == product_id) {
+                    Some((_, best)) => *best = best.max(weight),
+                    None => postings.push((product_id, weight)),
+                }
+            }
+        }
+
+        /// Returns product ids ordered from the best match. Products matching every
+        /// query word come first; the rest are returned only if nothing matches all.
+        pub fn search(&self, query: &str) -> Vec<u32> {
+            let terms = tokenize(query);
+            if terms.is_empty() {
+                return Vec::new();
+            }
+
+            // product id -> (number of query words matched, score)
+            let mut scores: HashMap<u32, (usize, f32)> = HashMap::new();
+            for term in &terms {
+                let mut best: HashMap<u32, f32> = HashMap::new();
+                for (word, postings) in &self.postings {
+                    let Some(closeness) = closeness(term, word) else {
+                        continue;
+                    };
+                    for &(product_id, weight) in postings {
+                        let score = best.entry(product_id).or_default();
+                        *score = score.max(closeness * weight);
+                    }
+                }
+                for (product_id, score) in best {
+                    let entry = scores.entry(product_id).or_default();
+                    entry.0 += 1;
+                    entry.1 += score;
+                }
+            }
+
+            let all_terms = scores.values().any(|(matched, _)| *matched == terms.len());
+            let mut ranked: Vec<_> = scores
+                .into_iter()
+                .filter(|(_, (matched, _))| !all_terms || *matched == terms.len())
+                .collect();
+            ranked.sort_by(
+                |(a_id, (a_matched, a_score)), (b_id, (b_matched, b_score))| {
+                    b_matched
+                        .cmp(a_matched)
+                        .then(b_score.total_cmp(a_score))
+                        .then(a_id.cmp(b_id))
+                },
+            );
+            ranked.into_iter().map(|(id, _)| id).collect()
+        }
+    }
+
+    /// How well an indexed `word` matches a query `term`: 1.0 for the same word, less
+    /// for a prefix ("sukien" → "sukienka") or a word within the allowed typo distance.
+    fn closeness(term: &str, word: &str) -> Option<f32> {
+        if term == word {
+            return Some(1.0);
+        }
+        let term_len = term.chars().count();
+        if term_len >= 3 && word.starts_with(term) {
+            return Some(0.8);
+        }
+        let allowed = match term_len {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+        if allowed == 0 || word.chars().count().abs_diff(term_len) > allowed {
+            return None;
+        }
+        let distance = edit_distance(term, word);
+        (distance <= allowed).then_some(0.7 - 0.15 * distance as f32)
+    }
+
+    /// Optimal string alignment distance: Levenshtein plus swapping two neighbours.
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in rows[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = usize::from(a[i - 1] != b[j - 1]);
+                let mut best = (rows[i - 1][j] + 1)
+                    .min(rows[i][j - 1] + 1)
+                    .min(rows[i - 1][j - 1] + cost);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    best = best.min(rows[i - 2][j - 2] + 1);
+                }
+                rows[i][j] = best;
+            }
+        }
+        rows[a.len()][b.len()]
+    }
+
+    /// Index shared between requests; rebuild it whenever products are added or edited.
+    #[derive(Clone, Default)]
+    pub struct SharedSearchIndex(Arc<RwLock<SearchIndex>>);
+
+    impl SharedSearchIndex {
+        pub fn build(db: &Db) -> crate::db::Result<Self> {
+            let index = SharedSearchIndex::default();
+            index.rebuild(db)?;
+            Ok(index)
+        }
+
+        pub fn rebuild(&self, db: &Db) -> crate::db::Result<()> {
+            let index = SearchIndex::build(&db.list_products()?);
+            *self.0.write().unwrap_or_else(|e| e.into_inner()) = index;
+            Ok(())
+        }
+
+        pub fn search(&self, query: &str) -> Vec<u32> {
+            self.0
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .search(query)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde_json::json;
+
+        use super::*;
+        use crate::search::fold;
+
+        fn item(id: u32, title: &str, description: &str) -> Product {
+            serde_json::from_value(json!({
+                "id": id,
+                "slug": format!("rzecz-{id}"),
+                "title": title,
+                "category": "woman",
+                "price": 5000,
+                "condition": "very_good",
+                "size": "M",
+                "description": description,
+                "listed_on": "2026-01-01",
+            }))
+            .unwrap()
+        }
+
+        #[test]
+        fn text_is_folded_to_plain_lowercase_words() {
+            assert_eq!(fold("Żółta GĘŚ"), "zolta ges");
+            assert_eq!(
+                tokenize("Sukienka, ŻÓŁTA – lniana (rozm. 38)!"),
+                ["sukienka", "zolta", "lniana", "rozm", "38"]
+            );
+            assert!(tokenize(" – !").is_empty());
+        }
+
+        #[test]
+        fn edit_distance_counts_swapped_neighbours_once() {
+            assert_eq!(edit_distance("sukienka", "sukienka"), 0);
+            assert_eq!(edit_distance("kot", "kto"), 1);
+            assert_eq!(edit_distance("bluzka", "bluza"), 1);
+            assert_eq!(edit_distance("", "kot"), 3);
+        }
+
+        #[test]
+        fn prefixes_match_from_three_letters() {
+            assert_eq!(closeness("sukienka", "sukienka"), Some(1.0));
+            assert_eq!(closeness("sukien", "sukienka"), Some(0.8));
+            assert_eq!(closeness("suk", "sukienka"), Some(0.8));
+            assert_eq!(closeness("su", "sukienka"), None);
+        }
+
+        #[test]
+        fn longer_words_allow_more_typos() {
+            // Do trzech liter: tylko dokładnie.
+            assert_eq!(closeness("kot", "kit"), None);
+            // Od czterech do siedmiu: jedna literówka.
+            assert!(closeness("bluzla", "bluzka").is_some());
+            assert_eq!(closeness("blyzla", "bluzka"), None);
+            // Od ośmiu: dwie.
+            assert!(closeness("spudnica", "spodnice").is_some());
+            assert_eq!(closeness("spudnyca", "spodnice"), None);
+        }
+
+        #[test]
+        fn zolta_finds_zolta() {
+            let index = SearchIndex::build(&[item(1, "Żółta sukienka", ""), item(2, "Bluzka", "")]);
+            assert_eq!(index.search("zolta"), [1]);
+            assert_eq!(index.search("ŻÓŁTA"), [1]);
+        }
+
+        #[test]
+        fn products_matching_every_word_come_first() {
+            let index = SearchIndex::build(&[
+                item(1, "Czerwona sukienka", ""),
+                item(2, "Czerwona bluzka", ""),
+                item(3, "Sukienka w kratę", ""),
+                item(4, "Pasek", "Pasuje do każdej sukienki"),
+            ]);
+            assert_eq!(index.search("czerwona sukienka"), [1]);
+            assert_eq!(index.search("sukienka"), [1, 3, 4]);
+            assert_eq!(index.search("sukeinka"), [1, 3, 4]);
+            // Nic nie pasuje do obu słów, więc wystarczy jedno.
+            assert_eq!(index.search("czerwona spodnie"), [1, 2]);
+            assert!(index.search("").is_empty());
+        }
+    }
+}
+
+#[server]
+pub async fn search_products(query: String) -> Result<Vec<Product>, ServerFnError> {
+    use crate::catalog::ListingStage;
+    use crate::db::{ProductRepository, use_db};
+    use index::SharedSearchIndex;
+
+    /// Longest query we bother to search for.
+    const MAX_QUERY_LEN: usize = 200;
+
+    let query: String = query.chars().take(MAX_QUERY_LEN).collect();
+    let index = use_context::<SharedSearchIndex>()
+        .ok_or_else(|| ServerFnError::new("indeks wyszukiwania nie jest dostępny"))?;
+    let db = use_db()?;
+    let mut products = Vec::new();
+    for id in index.search(&query) {
+        match db.find_product(id)? {
+            Some(product) if product.stage == ListingStage::Listed => products.push(product),
+            _ => {}
+        }
+    }
+    Ok(products)
+}
+
+#[component]
+pub fn SearchPage() -> impl IntoView {
+    let query_map = use_query_map();
+    let query = move || query_map.read().get("query").unwrap_or_default();
+    let results = Resource::new(query, search_products);
+
+    view! {
+        <Title text=move || format!("Szukaj: {} - Meg Joni", query()) />
+        <main>
+            <section>
+                <h2>"Wyniki wyszukiwania"</h2>
+                <Show
+                    when=move || !query().trim().is_empty()
+                    fallback=|| view! { <p>"Wpisz, czego szukasz, w polu wyszukiwania."</p> }
+                >
+                    <p>"Szukana fraza: „"{query}"”"</p>
+                    <div class="product-grid">
+                        <Suspense fallback=|| view! { <p>"Szukam..."</p> }>
+                            {move || Suspend::new(async move {
+                                match results.await {
+                                    Ok(products) if products.is_empty() => view! {
+                                        <p>"Nie znaleźliśmy produktów pasujących do zapytania."</p>
+                                    }
+                                    .into_any(),
+                                    Ok(products) => product_cards(products).into_any(),
+                                    Err(_) => view! {
+                                        <p>"Wyszukiwanie jest chwilowo niedostępne."</p>
+                                    }
+                                    .into_any(),
+                                }
+                            })}
+                        </Suspense>
+                    </div>
+                </Show>
+            </section>
+        </main>
+    }
+}
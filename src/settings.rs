@@ -0,0 +1,176 @@
+//! Shop settings that staff change in the panel instead of in the code: the contact
+//! details shown to customers and an announcement above the header, e.g. about a
+//! holiday break in shipping. Owners edit them at `/admin/settings`.
+
+use crate::checkout::{is_email, is_phone};
+use crate::staff::Permission;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
+
+/// Longest announcement that still fits in one line on a phone.
+pub const MAX_ANNOUNCEMENT_LEN: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShopSettings {
+    /// Where customers write to, shown on the contact page.
+    pub contact_email: String,
+    /// Empty when the shop does not take calls.
+    pub contact_phone: String,
+    /// Shown above the header on every page; empty hides the bar.
+    pub announcement: String,
+}
+
+impl Default for ShopSettings {
+    fn default() -> Self {
+        ShopSettings {
+            contact_email: "kontakt@megjoni.pl".to_string(),
+            contact_phone: String::new(),
+            announcement: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum SettingsError {
+    #[error("podaj poprawny adres e-mail")]
+    InvalidEmail,
+    #[error("podaj poprawny numer telefonu albo zostaw pole puste")]
+    InvalidPhone,
+    #[error("ogłoszenie może mieć najwyżej {MAX_ANNOUNCEMENT_LEN} znaków")]
+    AnnouncementTooLong,
+}
+
+impl ShopSettings {
+    pub fn check(&self) -> Result<(), SettingsError> {
+        if !is_email(&self.contact_email) {
+            return Err(SettingsError::InvalidEmail);
+        }
+        if !self.contact_phone.is_empty() && !is_phone(&self.contact_phone) {
+            return Err(SettingsError::InvalidPhone);
+        }
+        if self.announcement.chars().count() > MAX_ANNOUNCEMENT_LEN {
+            return Err(SettingsError::AnnouncementTooLong);
+        }
+        Ok(())
+    }
+}
+
+#[server]
+pub async fn get_shop_settings() -> Result<ShopSettings, ServerFnError> {
+    use crate::db::{SettingsRepository, use_db};
+
+    Ok(use_db()?.load_settings()?)
+}
+
+#[server]
+pub async fn save_shop_settings(
+    contact_email: String,
+    #[server(default)] contact_phone: String,
+    #[server(default)] announcement: String,
+) -> Result<Result<(), SettingsError>, ServerFnError> {
+    use crate::db::{SettingsRepository, use_db};
+    use chrono::Utc;
+
+    let staff = crate::staff::require_staff(Permission::EditSettings)?;
+    let settings = ShopSettings {
+        contact_email: contact_email.trim().to_string(),
+        contact_phone: contact_phone.trim().to_string(),
+        announcement: announcement.trim().to_string(),
+    };
+    if let Err(e) = settings.check() {
+        return Ok(Err(e));
+    }
+    use_db()?.save_settings(&settings, &staff.actor().to_string(), Utc::now())?;
+    Ok(Ok(()))
+}
+
+/// The announcement bar above the header, if staff set one.
+#[component]
+pub fn Announcement() -> impl IntoView {
+    let settings = Resource::new(|| (), |_| get_shop_settings());
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || Suspend::new(async move {
+                settings
+                    .await
+                    .ok()
+                    .filter(|settings| !settings.announcement.is_empty())
+                    .map(|settings| {
+                        view! { <p class="announcement" role="status">{settings.announcement}</p> }
+                    })
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+pub fn SettingsAdminPage() -> impl IntoView {
+    let save = ServerAction::<SaveShopSettings>::new();
+    let settings = Resource::new(move || save.version().get(), |_| get_shop_settings());
+    let message = move || match save.value().get() {
+        Some(Ok(Ok(()))) => Some(
+            view! {
+                <p class="account-success" role="status">"Zapisano ustawienia."</p>
+            }
+            .into_any(),
+        ),
+        Some(Ok(Err(e))) => Some(view! { <p class="inbox-error">{e.to_string()}</p> }.into_any()),
+        Some(Err(_)) => {
+            Some(view! { <p class="inbox-error">"Nie udało się zapisać ustawień."</p> }.into_any())
+        }
+        None => None,
+    };
+
+    view! {
+        <Title text="Ustawienia sklepu - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Ustawienia sklepu"</h2>
+                {message}
+                <Suspense fallback=|| view! { <p>"Ładowanie ustawień..."</p> }>
+                    {move || Suspend::new(async move {
+                        match settings.await {
+                            Ok(settings) => view! {
+                                <ActionForm action=save attr:class="account-form">
+                                    <label for="settings-email">"E-mail kontaktowy"</label>
+                                    <input
+                                        type="email"
+                                        id="settings-email"
+                                        name="contact_email"
+                                        value=settings.contact_email
+                                        required
+                                    />
+                                    <label for="settings-phone">"Telefon kontaktowy (opcjonalnie)"</label>
+                                    <input
+                                        type="tel"
+                                        id="settings-phone"
+                                        name="contact_phone"
+                                        value=settings.contact_phone
+                                    />
+                                    <label for="settings-announcement">
+                                        "Ogłoszenie nad nagłówkiem (puste ukrywa pasek)"
+                                    </label>
+                                    <input
+                                        type="text"
+                                        id="settings-announcement"
+                                        name="announcement"
+                                        maxlength=MAX_ANNOUNCEMENT_LEN
+                                        value=settings.announcement
+                                    />
+                                    <button type="submit" disabled=move || save.pending().get()>
+                                        "Zapisz"
+                                    </button>
+                                </ActionForm>
+                            }
+                            .into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do ustawień."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+            </section>
+        </main>
+    }
+}
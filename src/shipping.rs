@@ -0,0 +1,264 @@
+//! Delivery pricing. The rate card lives in `data/shipping.json` and is the single source
+//! for what the cart, checkout and the shipping policy page show, and for what an order is
+//! charged.
+
+use crate::cart::CartItem;
+use crate::catalog::Price;
+use crate::checkout::DeliveryMethod;
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One price step of a carrier, e.g. "Gabaryt A" up to 1 kg.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateBracket {
+    /// Parcel size or weight class as shown to customers.
+    pub label: String,
+    /// Heaviest parcel this price covers; `None` means no limit.
+    #[serde(default)]
+    pub max_weight_grams: Option<u32>,
+    pub price: Price,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CarrierRates {
+    pub method: DeliveryMethod,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Whether orders above the free-shipping threshold ship free with this carrier.
+    #[serde(default)]
+    pub free_over_threshold: bool,
+    /// From the lightest parcel to the heaviest.
+    pub brackets: Vec<RateBracket>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// The shop's delivery rate card.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShippingRates {
+    /// Orders worth at least this much ship free with carriers that allow it.
+    #[serde(default)]
+    pub free_shipping_threshold: Option<Price>,
+    /// Weight assumed for items without a recorded weight.
+    pub default_item_weight_grams: u32,
+    /// Weight of the box and filling added to every parcel.
+    #[serde(default)]
+    pub packaging_weight_grams: u32,
+    pub carriers: Vec<CarrierRates>,
+}
+
+/// What is being shipped: the value of the goods and the packed weight.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Parcel {
+    pub value: Price,
+    pub weight_grams: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShippingQuote {
+    pub method: DeliveryMethod,
+    pub price: Price,
+    /// Label of the bracket the parcel falls into.
+    pub bracket: String,
+    /// The free-shipping threshold waived the bracket price.
+    pub free: bool,
+}
+
+impl ShippingRates {
+    /// Carriers customers can currently choose.
+    pub fn available(&self) -> impl Iterator<Item = &CarrierRates> {
+        self.carriers.iter().filter(|carrier| carrier.enabled)
+    }
+
+    /// Packs items given as `(price, weight)` pairs into one parcel.
+    pub fn parcel(&self, items: impl IntoIterator<Item = (Price, Option<u32>)>) -> Parcel {
+        let mut parcel = Parcel {
+            value: Price(0),
+            weight_grams: self.packaging_weight_grams,
+        };
+        for (price, weight) in items {
+            parcel.value = Price(parcel.value.grosze() + price.grosze());
+            parcel.weight_grams += weight.unwrap_or(self.default_item_weight_grams);
+        }
+        parcel
+    }
+
+    pub fn cart_parcel(&self, items: &[CartItem]) -> Parcel {
+        self.parcel(items.iter().map(|item| (item.price, item.weight_grams)))
+    }
+
+    pub fn qualifies_for_free_shipping(&self, value: Price) -> bool {
+        self.free_shipping_threshold
+            .is_some_and(|threshold| value >= threshold)
+    }
+
+    /// How much more the customer has to spend to get free shipping.
+    pub fn missing_for_free_shipping(&self, value: Price) -> Option<Price> {
+        self.free_shipping_threshold
+            .filter(|threshold| value < *threshold)
+            .map(|threshold| Price(threshold.grosze() - value.grosze()))
+    }
+
+    /// Price of sending `parcel` with `method`, or `None` if the carrier is switched off
+    /// or the parcel is too heavy for it.
+    pub fn quote(&self, method: DeliveryMethod, parcel: &Parcel) -> Option<ShippingQuote> {
+        let carrier = self.available().find(|carrier| carrier.method == method)?;
+        let bracket = carrier.brackets.iter().find(|bracket| {
+            bracket
+                .max_weight_grams
+                .is_none_or(|max| parcel.weight_grams <= max)
+        })?;
+        let free = carrier.free_over_threshold
+            && bracket.price > Price(0)
+            && self.qualifies_for_free_shipping(parcel.value);
+        Some(ShippingQuote {
+            method,
+            price: if free { Price(0) } else { bracket.price },
+            bracket: bracket.label.clone(),
+            free,
+        })
+    }
+
+    /// Quotes for every carrier that can take `parcel`, in rate card order.
+    pub fn quotes(&self, parcel: &Parcel) -> Vec<ShippingQuote> {
+        self.available()
+            .filter_map(|carrier| self.quote(carrier.method, parcel))
+            .collect()
+    }
+
+    /// Cheapest delivery that actually ships the parcel, ignoring personal pickup.
+    pub fn cheapest_delivery(&self, parcel: &Parcel) -> Option<ShippingQuote> {
+        self.quotes(parcel)
+            .into_iter()
+            .filter(|quote| quote.method != DeliveryMethod::PersonalPickup)
+            .min_by_key(|quote| quote.price)
+    }
+}
+
+/// Weight as shown on the shipping page, e.g. "1 kg" or "0,5 kg".
+pub fn format_weight(grams: u32) -> String {
+    match (grams / 1000, grams % 1000) {
+        (kg, 0) => format!("{kg} kg"),
+        (kg, rest) => format!("{kg},{} kg", format!("{rest:03}").trim_end_matches('0')),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod source {
+    use super::ShippingRates;
+
+    /// Location of the rate card, relative to the directory the server is started from.
+    pub const DEFAULT_SHIPPING_FILE: &str = "data/shipping.json";
+
+    /// Reads the rate card from disk.
+    pub fn load_shipping_rates() -> Result<ShippingRates, String> {
+        let path = std::env::var("MEGJONI_SHIPPING_FILE")
+            .unwrap_or_else(|_| DEFAULT_SHIPPING_FILE.to_string());
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("nie można odczytać {path}: {e}"))?;
+        serde_json::from_str(&contents).map_err(|e| format!("błędny format {path}: {e}"))
+    }
+}
+
+/// Returns the rate card provided to the current request.
+#[cfg(feature = "ssr")]
+pub fn use_shipping_rates() -> Result<ShippingRates, ServerFnError> {
+    use_context::<ShippingRates>()
+        .ok_or_else(|| ServerFnError::new("cennik dostaw nie jest dostępny"))
+}
+
+#[server]
+pub async fn get_shipping_rates() -> Result<ShippingRates, ServerFnError> {
+    use_shipping_rates()
+}
+
+/// Delivery prices as listed on the shipping policy page, straight from the rate card.
+#[component]
+pub fn ShippingPriceList() -> impl IntoView {
+    let rates = Resource::new(|| (), |_| get_shipping_rates());
+
+    view! {
+        <Suspense fallback=|| view! { <li>"Wczytywanie cennika dostaw..."</li> }>
+            {move || Suspend::new(async move {
+                match rates.await {
+                    Ok(rates) => shipping_price_items(rates).into_any(),
+                    Err(_) => view! {
+                        <li>"Aktualny koszt dostawy podajemy w koszyku i przy składaniu zamówienia."</li>
+                    }
+                    .into_any(),
+                }
+            })}
+        </Suspense>
+    }
+}
+
+fn shipping_price_items(rates: ShippingRates) -> impl IntoView {
+    let carriers = rates
+        .available()
+        .map(|carrier| {
+            let prices = carrier
+                .brackets
+                .iter()
+                .map(|bracket| {
+                    let price = match bracket.price {
+                        Price(0) => "bezpłatnie".to_string(),
+                        price => price.to_string(),
+                    };
+                    match bracket.max_weight_grams {
+                        Some(max) => {
+                            format!("{} do {}: {price}", bracket.label, format_weight(max))
+                        }
+                        None if carrier.brackets.len() == 1 => price,
+                        None => format!("{}: {price}", bracket.label),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            view! { <li>{format!("{}: {prices}", carrier.method.label())}</li> }
+        })
+        .collect_view();
+    let free_carriers: Vec<&str> = rates
+        .available()
+        .filter(|carrier| carrier.free_over_threshold)
+        .map(|carrier| carrier.method.label())
+        .collect();
+    let free_shipping = rates
+        .free_shipping_threshold
+        .filter(|_| !free_carriers.is_empty())
+        .map(|threshold| {
+            view! {
+                <li>
+                    {format!(
+                        "Darmowa dostawa dla zamówień od {threshold} (dotyczy: {})",
+                        free_carriers.join(", "),
+                    )}
+                </li>
+            }
+        });
+
+    view! {
+        {carriers}
+        {free_shipping}
+    }
+}
+
+/// Delivery methods currently offered, for the shipping policy page.
+#[component]
+pub fn DeliveryMethodList() -> impl IntoView {
+    let rates = Resource::new(|| (), |_| get_shipping_rates());
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || Suspend::new(async move {
+                rates.await.ok().map(|rates| {
+                    rates
+                        .available()
+                        .map(|carrier| view! { <li>{carrier.method.label()}</li> })
+                        .collect_view()
+                })
+            })}
+        </Suspense>
+    }
+}
@@ -0,0 +1,256 @@
+//! Staff accounts for the admin panel at `/admin` (see [`crate::admin`]). Everyone signs
+//! in with their own e-mail and password and has one [`Role`], which decides what they
+//! may do ([`Role::can`]). Every staff server function checks the permission itself with
+//! [`require_staff`]; the panel only hides what the role cannot use.
+//!
+//! The first owner is created at startup from `MEGJONI_OWNER_EMAIL` and
+//! `MEGJONI_OWNER_PASSWORD` while there are no staff accounts yet; owners add the rest
+//! at `/admin/staff` (see [`admin`]).
+
+use crate::orders::Actor;
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod admin;
+#[cfg(feature = "ssr")]
+pub mod auth;
+
+/// Staff passwords guard the whole shop, so they are longer than customers'.
+pub const MIN_PASSWORD_LEN: usize = 12;
+#[cfg(feature = "ssr")]
+const MAX_PASSWORD_LEN: usize = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Runs the shop: may do everything, including refunds, settings and staff accounts.
+    Owner,
+    /// Packs and ships orders and takes in returns.
+    Packer,
+    /// Lists products and answers customers.
+    Editor,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Packer, Role::Editor];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Owner => "Właściciel",
+            Role::Packer => "Pakowanie",
+            Role::Editor => "Redakcja",
+        }
+    }
+
+    /// The value used in forms, e.g. `packer`.
+    pub fn param(self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Packer => "packer",
+            Role::Editor => "editor",
+        }
+    }
+
+    pub fn can(self, permission: Permission) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Packer => matches!(permission, Permission::HandleOrders),
+            Role::Editor => matches!(
+                permission,
+                Permission::EditProducts | Permission::AnswerCustomers
+            ),
+        }
+    }
+}
+
+/// Something only some roles may do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    /// Adding, editing and removing products.
+    EditProducts,
+    /// Orders, their packing slips and statuses, and returned parcels.
+    HandleOrders,
+    /// The message inbox and complaints.
+    AnswerCustomers,
+    /// Giving money back.
+    IssueRefunds,
+    EditSettings,
+    /// Adding staff accounts, changing their roles and passwords.
+    ManageStaff,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StaffMember {
+    pub id: i64,
+    pub email: String,
+    pub name: String,
+    pub role: Role,
+    /// Deactivated accounts cannot sign in but stay in the history of their changes.
+    pub active: bool,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StaffMember {
+    /// How changes made by this person are recorded, e.g. `staff:ania@megjoni.pl`.
+    pub fn actor(&self) -> Actor {
+        Actor::Staff(self.email.clone())
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        self.active && self.role.can(permission)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum StaffError {
+    #[error("nieprawidłowy e-mail lub hasło")]
+    WrongCredentials,
+    #[error("zbyt wiele nieudanych prób logowania, spróbuj ponownie za kwadrans")]
+    TooManyAttempts,
+    #[error("podaj poprawny adres e-mail")]
+    InvalidEmail,
+    #[error("podaj imię")]
+    MissingName,
+    #[error("hasło musi mieć od {MIN_PASSWORD_LEN} do 128 znaków")]
+    WeakPassword,
+    #[error("konto z tym adresem e-mail już istnieje")]
+    EmailTaken,
+    #[error("nie ma takiego konta")]
+    NotFound,
+    #[error("sklep musi mieć co najmniej jednego aktywnego właściciela")]
+    LastOwner,
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) fn check_password(password: &str) -> Result<(), StaffError> {
+    let length = password.chars().count();
+    if (MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&length) {
+        Ok(())
+    } else {
+        Err(StaffError::WeakPassword)
+    }
+}
+
+/// The staff member signed in with the current request, if their role allows
+/// `permission`; otherwise an error that tells the panel to show no data.
+#[cfg(feature = "ssr")]
+pub fn require_staff(permission: Permission) -> Result<StaffMember, ServerFnError> {
+    let db = crate::db::use_db()?;
+    permitted(auth::current_staff(&db)?, permission)
+}
+
+#[cfg(feature = "ssr")]
+fn permitted(
+    staff: Option<StaffMember>,
+    permission: Permission,
+) -> Result<StaffMember, ServerFnError> {
+    match staff {
+        Some(staff) if staff.can(permission) => Ok(staff),
+        Some(_) => Err(ServerFnError::new("brak uprawnień")),
+        None => Err(ServerFnError::new("zaloguj się do panelu")),
+    }
+}
+
+#[server]
+pub async fn get_current_staff() -> Result<Option<StaffMember>, ServerFnError> {
+    let db = crate::db::use_db()?;
+    Ok(auth::current_staff(&db)?)
+}
+
+/// Signs the staff member in on this browser. The outer error means a storage failure;
+/// the inner one wrong credentials or too many failed attempts.
+#[server]
+pub async fn staff_log_in(
+    email: String,
+    password: String,
+) -> Result<Result<(), StaffError>, ServerFnError> {
+    use crate::account::auth::{
+        attempt_allowed, check_account_password, clear_attempts, record_attempt,
+    };
+    use crate::db::{AttemptKind, StaffRepository};
+
+    let db = crate::db::use_db()?;
+    let email = email.trim();
+    if !attempt_allowed(&db, AttemptKind::StaffLogIn, email)? {
+        return Ok(Err(StaffError::TooManyAttempts));
+    }
+    let staff = check_account_password(db.find_staff_by_email(email)?, &password, |staff| {
+        db.staff_password_hash(staff.id)
+    })?;
+    match staff.filter(|staff| staff.active) {
+        Some(staff) => {
+            clear_attempts(&db, AttemptKind::StaffLogIn, email)?;
+            auth::start_session(&db, staff.id)?;
+            Ok(Ok(()))
+        }
+        None => {
+            record_attempt(&db, AttemptKind::StaffLogIn, email)?;
+            Ok(Err(StaffError::WrongCredentials))
+        }
+    }
+}
+
+#[server]
+pub async fn staff_log_out() -> Result<(), ServerFnError> {
+    let db = crate::db::use_db()?;
+    auth::end_session(&db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(role: Role, active: bool) -> StaffMember {
+        StaffMember {
+            id: 1,
+            email: "ania@megjoni.pl".into(),
+            name: "Ania".into(),
+            role,
+            active,
+            last_login_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn roles_only_get_their_own_permissions() {
+        use Permission::*;
+        let all = [
+            EditProducts,
+            HandleOrders,
+            AnswerCustomers,
+            IssueRefunds,
+            EditSettings,
+            ManageStaff,
+        ];
+        let allowed = |role: Role| all.into_iter().filter(|p| role.can(*p)).collect::<Vec<_>>();
+        assert_eq!(allowed(Role::Owner), all);
+        assert_eq!(allowed(Role::Packer), [HandleOrders]);
+        assert_eq!(allowed(Role::Editor), [EditProducts, AnswerCustomers]);
+    }
+
+    #[test]
+    fn staff_without_the_permission_are_refused() {
+        let packer = member(Role::Packer, true);
+        assert!(permitted(Some(packer.clone()), Permission::HandleOrders).is_ok());
+        for permission in [
+            Permission::IssueRefunds,
+            Permission::EditSettings,
+            Permission::ManageStaff,
+            Permission::EditProducts,
+        ] {
+            assert!(permitted(Some(packer.clone()), permission).is_err());
+        }
+        assert!(permitted(Some(member(Role::Editor, true)), Permission::HandleOrders).is_err());
+        assert!(permitted(None, Permission::HandleOrders).is_err());
+    }
+
+    #[test]
+    fn deactivated_staff_may_do_nothing() {
+        let owner = member(Role::Owner, false);
+        assert!(!owner.can(Permission::EditProducts));
+        assert!(permitted(Some(owner), Permission::ManageStaff).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Staff accounts at `/admin/staff`, for owners: adding people, changing their roles,
+//! deactivating accounts and setting new passwords. The shop always keeps at least one
+//! active owner, so nobody can lock everyone out of the panel.
+
+use super::{MIN_PASSWORD_LEN, Permission, Role, StaffError, StaffMember};
+use leptos::prelude::*;
+use leptos_meta::Title;
+
+#[server]
+pub async fn list_staff_accounts() -> Result<Vec<StaffMember>, ServerFnError> {
+    use crate::db::{StaffRepository, use_db};
+
+    super::require_staff(Permission::ManageStaff)?;
+    Ok(use_db()?.list_staff()?)
+}
+
+#[server]
+pub async fn add_staff_account(
+    name: String,
+    email: String,
+    role: Role,
+    password: String,
+) -> Result<Result<StaffMember, StaffError>, ServerFnError> {
+    use crate::account::auth::hash_password;
+    use crate::db::{StaffRepository, use_db};
+    use chrono::Utc;
+
+    super::require_staff(Permission::ManageStaff)?;
+    let (name, email) = (name.trim(), email.trim());
+    if name.is_empty() {
+        return Ok(Err(StaffError::MissingName));
+    }
+    if !crate::checkout::is_email(email) {
+        return Ok(Err(StaffError::InvalidEmail));
+    }
+    if let Err(e) = super::check_password(&password) {
+        return Ok(Err(e));
+    }
+    let hash = hash_password(&password).map_err(|e| ServerFnError::new(e.to_string()))?;
+    match use_db()?.create_staff(email, name, role, &hash, Utc::now())? {
+        Some(staff) => Ok(Ok(staff)),
+        None => Ok(Err(StaffError::EmailTaken)),
+    }
+}
+
+/// Changes the role and whether the account can sign in. An unchecked `active` box is
+/// not sent at all, hence the default.
+#[server]
+pub async fn update_staff_account(
+    staff_id: i64,
+    role: Role,
+    #[server(default)] active: bool,
+) -> Result<Result<(), StaffError>, ServerFnError> {
+    use crate::db::{StaffRepository, use_db};
+
+    super::require_staff(Permission::ManageStaff)?;
+    let db = use_db()?;
+    let staff = db.list_staff()?;
+    let Some(member) = staff.iter().find(|member| member.id == staff_id) else {
+        return Ok(Err(StaffError::NotFound));
+    };
+    let is_owner = |member: &StaffMember| member.active && member.role == Role::Owner;
+    let stays_owner = active && role == Role::Owner;
+    if is_owner(member) && !stays_owner && staff.iter().filter(|m| is_owner(m)).count() == 1 {
+        return Ok(Err(StaffError::LastOwner));
+    }
+    db.update_staff(staff_id, role, active)?;
+    Ok(Ok(()))
+}
+
+#[server]
+pub async fn set_staff_password(
+    staff_id: i64,
+    password: String,
+) -> Result<Result<(), StaffError>, ServerFnError> {
+    use crate::account::auth::hash_password;
+    use crate::db::{StaffRepository, use_db};
+
+    super::require_staff(Permission::ManageStaff)?;
+    if let Err(e) = super::check_password(&password) {
+        return Ok(Err(e));
+    }
+    let db = use_db()?;
+    if db.find_staff(staff_id)?.is_none() {
+        return Ok(Err(StaffError::NotFound));
+    }
+    let hash = hash_password(&password).map_err(|e| ServerFnError::new(e.to_string()))?;
+    db.set_staff_password_hash(staff_id, &hash)?;
+    Ok(Ok(()))
+}
+
+/// The message for the last submission of a staff form, if it failed.
+fn failure<T>(value: Option<Result<Result<T, StaffError>, ServerFnError>>) -> Option<String> {
+    match value {
+        Some(Ok(Err(e))) => Some(e.to_string()),
+        Some(Err(_)) => Some("Nie udało się zapisać zmian.".to_string()),
+        _ => None,
+    }
+}
+
+#[component]
+pub fn StaffAdminPage() -> impl IntoView {
+    let add = ServerAction::<AddStaffAccount>::new();
+    let update = ServerAction::<UpdateStaffAccount>::new();
+    let password = ServerAction::<SetStaffPassword>::new();
+    let staff = Resource::new(
+        move || {
+            (
+                add.version().get(),
+                update.version().get(),
+                password.version().get(),
+            )
+        },
+        |_| list_staff_accounts(),
+    );
+    let error = move || {
+        failure(add.value().get())
+            .or_else(|| failure(update.value().get()))
+            .or_else(|| failure(password.value().get()))
+    };
+    let password_set = move || matches!(password.value().get(), Some(Ok(Ok(()))));
+
+    view! {
+        <Title text="Pracownicy - Meg Joni" />
+        <main>
+            <section class="inbox">
+                <h2>"Pracownicy"</h2>
+                <ul class="staff-roles">
+                    <li>"Właściciel: wszystko, także zwroty pieniędzy, ustawienia i konta pracowników."</li>
+                    <li>"Pakowanie: zamówienia, listy przewozowe i przyjmowanie zwrotów."</li>
+                    <li>"Redakcja: produkty, wiadomości od klientów i reklamacje."</li>
+                </ul>
+                {move || error().map(|e| view! { <p class="inbox-error">{e}</p> })}
+                <Show when=password_set>
+                    <p class="account-success" role="status">"Nowe hasło zostało zapisane."</p>
+                </Show>
+                <Suspense fallback=|| view! { <p>"Ładowanie kont..."</p> }>
+                    {move || Suspend::new(async move {
+                        match staff.await {
+                            Ok(staff) => view! { <StaffList staff update password /> }.into_any(),
+                            Err(_) => view! { <p>"Brak dostępu do kont pracowników."</p> }.into_any(),
+                        }
+                    })}
+                </Suspense>
+                <h3>"Dodaj pracownika"</h3>
+                <ActionForm action=add attr:class="account-form">
+                    <label for="staff-name">"Imię"</label>
+                    <input type="text" id="staff-name" name="name" required />
+                    <label for="staff-email">"E-mail"</label>
+                    <input type="email" id="staff-email" name="email" autocomplete="off" required />
+                    <label for="staff-role">"Rola"</label>
+                    <RoleSelect id="staff-role" selected=Role::Packer />
+                    <label for="staff-password">
+                        {format!("Hasło startowe (co najmniej {MIN_PASSWORD_LEN} znaków)")}
+                    </label>
+                    <input
+                        type="password"
+                        id="staff-password"
+                        name="password"
+                        autocomplete="new-password"
+                        required
+                    />
+                    <button type="submit" disabled=move || add.pending().get()>"Dodaj"</button>
+                </ActionForm>
+            </section>
+        </main>
+    }
+}
+
+#[component]
+fn RoleSelect(#[prop(into)] id: String, selected: Role) -> impl IntoView {
+    view! {
+        <select id=id name="role">
+            {Role::ALL
+                .into_iter()
+                .map(|role| {
+                    view! {
+                        <option value=role.param() selected=(role == selected)>
+                            {role.label()}
+                        </option>
+                    }
+                })
+                .collect_view()}
+        </select>
+    }
+}
+
+#[component]
+fn StaffList(
+    staff: Vec<StaffMember>,
+    update: ServerAction<UpdateStaffAccount>,
+    password: ServerAction<SetStaffPassword>,
+) -> impl IntoView {
+    view! {
+        <table class="inbox-threads staff-list">
+            <thead>
+                <tr>
+                    <th>"Pracownik"</th>
+                    <th>"Rola i dostęp"</th>
+                    <th>"Nowe hasło"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {staff
+                    .into_iter()
+                    .map(|member| {
+                        let last_login = match member.last_login_at {
+                            Some(at) => format!("ostatnio {}", at.format("%Y-%m-%d %H:%M")),
+                            None => "jeszcze się nie logował(a)".to_string(),
+                        };
+                        view! {
+                            <tr class:staff-inactive=!member.active>
+                                <td>
+                                    {member.name.clone()}
+                                    <br />
+                                    <small>{member.email.clone()}</small>
+                                    <br />
+                                    <small>{last_login}</small>
+                                </td>
+                                <td>
+                                    <ActionForm action=update>
+                                        <input type="hidden" name="staff_id" value=member.id />
+                                        <RoleSelect
+                                            id=format!("staff-role-{}", member.id)
+                                            selected=member.role
+                                        />
+                                        <label>
+                                            <input
+                                                type="checkbox"
+                                                name="active"
+                                                value="true"
+                                                checked=member.active
+                                            />
+                                            " aktywne"
+                                        </label>
+                                        <button type="submit">"Zapisz"</button>
+                                    </ActionForm>
+                                </td>
+                                <td>
+                                    <ActionForm action=password>
+                                        <input type="hidden" name="staff_id" value=member.id />
+                                        <input
+                                            type="password"
+                                            name="password"
+                                            autocomplete="new-password"
+                                            required
+                                        />
+                                        <button type="submit">"Ustaw"</button>
+                                    </ActionForm>
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
@@ -0,0 +1,79 @@
+//! Staff sign-in sessions. They work like customer sessions (see
+//! [`crate::account::auth`]) but use their own cookie and table and last one working
+//! day, so a customer session never opens the panel and a forgotten browser does not
+//! stay signed in for long.
+
+use chrono::{Duration, Utc};
+use leptos::prelude::*;
+
+use super::{Role, StaffMember};
+use crate::account::auth::{cookie_in, hash_password, new_token, set_cookie, token_hash};
+use crate::db::{self, Db, StaffRepository};
+
+pub const STAFF_COOKIE: &str = "megjoni_staff";
+/// How long a sign-in to the panel lasts.
+pub const SESSION_HOURS: i64 = 12;
+
+fn session_token() -> Option<String> {
+    let parts = use_context::<axum::http::request::Parts>()?;
+    cookie_in(&parts.headers, STAFF_COOKIE)
+}
+
+/// The staff member signed in with the current request, if any.
+pub fn current_staff(db: &Db) -> db::Result<Option<StaffMember>> {
+    match session_token() {
+        Some(token) => db.session_staff(&token_hash(&token), Utc::now()),
+        None => Ok(None),
+    }
+}
+
+pub fn start_session(db: &Db, staff_id: i64) -> Result<(), ServerFnError> {
+    let token = new_token();
+    let now = Utc::now();
+    db.create_staff_session(
+        &token_hash(&token),
+        staff_id,
+        now,
+        now + Duration::hours(SESSION_HOURS),
+    )?;
+    db.record_staff_login(staff_id, now)?;
+    set_cookie(STAFF_COOKIE, &token, SESSION_HOURS * 60 * 60)
+}
+
+pub fn end_session(db: &Db) -> Result<(), ServerFnError> {
+    if let Some(token) = session_token() {
+        db.delete_staff_session(&token_hash(&token))?;
+    }
+    set_cookie(STAFF_COOKIE, "", 0)
+}
+
+/// Creates the first owner from `MEGJONI_OWNER_EMAIL`, `MEGJONI_OWNER_PASSWORD` and
+/// optionally `MEGJONI_OWNER_NAME`, while there are no staff accounts at all. Returns the
+/// new account, or `None` if staff accounts already exist.
+pub fn bootstrap_owner(db: &Db) -> Result<Option<StaffMember>, String> {
+    if db.count_staff().map_err(|e| e.to_string())? > 0 {
+        return Ok(None);
+    }
+    let (Ok(email), Ok(password)) = (
+        std::env::var("MEGJONI_OWNER_EMAIL"),
+        std::env::var("MEGJONI_OWNER_PASSWORD"),
+    ) else {
+        return Err(
+            "brak kont pracowników; ustaw MEGJONI_OWNER_EMAIL i MEGJONI_OWNER_PASSWORD".to_string(),
+        );
+    };
+    if !crate::checkout::is_email(&email) {
+        return Err(format!(
+            "MEGJONI_OWNER_EMAIL: {}",
+            super::StaffError::InvalidEmail
+        ));
+    }
+    super::check_password(&password).map_err(|e| format!("MEGJONI_OWNER_PASSWORD: {e}"))?;
+    let name = std::env::var("MEGJONI_OWNER_NAME")
+        .ok()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| Role::Owner.label().to_string());
+    let hash = hash_password(&password).map_err(|e| e.to_string())?;
+    db.create_staff(email.trim(), name.trim(), Role::Owner, &hash, Utc::now())
+        .map_err(|e| e.to_string())
+}